@@ -0,0 +1,693 @@
+//! The `dependency-cascade` command line: its arguments and the dispatch of each command to
+//! the library. The binary only parses the arguments, runs them and reports the error.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::Display;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use clap::Parser;
+use serde::Serialize;
+
+use crate::commands::{
+    self, AdviseCommands, ArtifactCommands, CodeownersCommands, Commands, ExportCommands, FilterArgs,
+    GenerateCommands, GraphCommands, QueryDirection, QueryEmit, QueryFormat, ReleaseCommands, RunArgs, Scan,
+};
+use crate::types::{ArtifactInfo, Config, DependencyGraph, GlobGuardConfig, Severity};
+use crate::{parallel, plugin, runner, testkit};
+
+#[derive(Parser)]
+#[command(
+    version,
+    about,
+    long_about = None,
+    color = clap::ColorChoice::Auto,
+    styles = get_styles()
+)]
+pub struct Cli {
+    /// Optional name to operate on
+    #[arg(help_heading = "OPTIONS")]
+    pub name: Option<String>,
+
+    /// Sets a custom config file
+    #[arg(
+        short,
+        long,
+        value_name = "FILE",
+        help_heading = "OPTIONS"
+    )]
+    pub config: Option<PathBuf>,
+
+    /// Write the command's output to FILE instead of printing it. The file is replaced
+    /// atomically, so readers never see it half-written.
+    #[arg(
+        short,
+        long,
+        global = true,
+        value_name = "FILE",
+        help_heading = "OPTIONS"
+    )]
+    pub output: Option<PathBuf>,
+
+    /// Wait for an artifact another process is writing, instead of failing. Artifacts written
+    /// with --output are locked meanwhile, through a FILE.lock next to them.
+    #[arg(
+        long,
+        global = true,
+        help_heading = "OPTIONS"
+    )]
+    pub wait: bool,
+
+    /// Turn debugging information on
+    #[arg(
+        short,
+        long,
+        action = clap::ArgAction::Count,
+        help_heading = "GLOBAL FLAGS"
+    )]
+    pub debug: u8,
+
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+}
+
+fn get_styles() -> clap::builder::Styles {
+    use clap::builder::styling::{Style, Color, AnsiColor};
+    clap::builder::Styles::styled()
+        .header(Style::new().bold().fg_color(Some(Color::Ansi(AnsiColor::Green))))
+        .usage(Style::new().fg_color(Some(Color::Ansi(AnsiColor::Cyan))))
+        .literal(Style::new().fg_color(Some(Color::Ansi(AnsiColor::Cyan))))
+        .placeholder(Style::new().fg_color(Some(Color::Ansi(AnsiColor::Yellow))))
+        .error(Style::new().fg_color(Some(Color::Ansi(AnsiColor::Red))))
+}
+
+/// Prints the error a command failed with to stderr and exits with status 1.
+pub fn exit_with_error(error: impl Display) -> ! {
+    eprintln!("Error: {}", error);
+    std::process::exit(1);
+}
+
+/// Runs the parsed command.
+///
+/// ### Arguments
+/// * `cli` - The parsed command line
+///
+/// ### Returns
+/// * `ExitCode` - Failure when the command ran but its result fails the check it stands
+///   for, e.g. `lint` finding errors or `run` having a node fail
+pub fn run(cli: Cli) -> Result<ExitCode, Box<dyn Error>> {
+    // Plugins extend every command, so they're installed before any runs. An unreadable config
+    // is reported by the commands reading it.
+    if let Ok(config) = Config::load(cli.config.as_deref(), Path::new(".")) {
+        let config_dir = cli.config.as_deref().and_then(Path::parent).unwrap_or(Path::new("."));
+        plugin::install(plugin::load(&config.plugins, config_dir)?);
+    }
+
+    commands::wait_for_locks(cli.wait);
+    if let Some(Commands::Prepare { jobs, .. } | Commands::Query { jobs, .. } | Commands::Affected { jobs, .. }) = &cli.command {
+        parallel::set_jobs(*jobs)?;
+    }
+    let output = cli.output.as_deref();
+    match cli.command {
+        Some(Commands::Prepare { dir, root, skip, max_depth, max_files, manifest_list, archive, dependency_toml_name, allow_cyclical, shard_dir: Some(shard_dir), previous, codeowners, detect_languages, repo, max_node_files, .. }) => {
+            let mut scan = Scan { dirs: dir, root, dependency_toml_name, skip, max_depth, max_files, manifest_list, archive, ..Scan::default() };
+            let previous = previous.map(|path| commands::load_graph(&path)).transpose()?;
+            let rules = codeowners.map(|path| read_codeowners(&path)).transpose()?;
+            let glob_guard = load_prepare_config(cli.config.as_deref(), &mut scan, max_node_files)?;
+
+            let mut graph = commands::prepare(&scan, allow_cyclical, previous.as_ref())?;
+            if let Some(rules) = &rules {
+                commands::codeowners::import(&mut graph, rules);
+            }
+            if detect_languages {
+                commands::tag_languages(&mut graph);
+            }
+            if repo.is_some() {
+                graph.set_info(ArtifactInfo { repo, ..graph.info().clone() });
+            }
+            report_globs(&graph, &glob_guard)?;
+            print_json(output, &commands::write_shards(&graph, &shard_dir)?)?;
+        }
+        Some(Commands::Prepare { dir, root, skip, max_depth, max_files, manifest_list, archive, dependency_toml_name, allow_cyclical, shard_dir: None, metadata_file, previous, amend, index_files, reachability, codeowners, detect_languages, repo, max_node_files, artifact_format, .. }) => {
+            let mut scan = Scan { dirs: dir, root, dependency_toml_name, skip, max_depth, max_files, manifest_list, archive, ..Scan::default() };
+            let previous = previous.map(|path| commands::load_graph(&path)).transpose()?;
+            let amended = amend.map(|path| commands::load_graph(&path)).transpose()?;
+            let rules = codeowners.map(|path| read_codeowners(&path)).transpose()?;
+            let glob_guard = load_prepare_config(cli.config.as_deref(), &mut scan, max_node_files)?;
+
+            // Prepare the graph object, or add to the amended one, importing owners, listing its
+            // files and moving the metadata out if asked to
+            let mut graph = match &amended {
+                Some(amended) => commands::amend(amended, &scan, allow_cyclical)?,
+                None => commands::prepare(&scan, allow_cyclical, previous.as_ref())?,
+            };
+            if let Some(rules) = &rules {
+                commands::codeowners::import(&mut graph, rules);
+            }
+            if detect_languages {
+                commands::tag_languages(&mut graph);
+            }
+            if repo.is_some() {
+                graph.set_info(ArtifactInfo { repo, ..graph.info().clone() });
+            }
+            report_globs(&graph, &glob_guard)?;
+            if index_files {
+                commands::index_files(&mut graph)?;
+            }
+            if reachability {
+                graph.set_closure();
+            }
+            if let Some(metadata_file) = &metadata_file {
+                commands::split_metadata(&mut graph, metadata_file)?;
+            }
+
+            // Serialize the graph object to JSON, or to the binary format
+            write_output_bytes(output, &commands::encode_graph(&graph, artifact_format)?)?;
+        }
+        Some(Commands::Query { graph_artifact_path, at, store, mut files, since, emit, format, toposort, direction, max_depth, filters, budget, with_metadata, github_output, stream, hash_compare, cache_dir, .. }) => {
+            // Clap requires either the artifact or a commit of the store. A fetched artifact
+            // is deleted once the query is done.
+            let fetched;
+            let graph_artifact_path = match (graph_artifact_path, at, store) {
+                (Some(path), _, _) => path,
+                (None, Some(at), Some(store)) => {
+                    fetched = commands::fetch_artifact(&store, &at)?;
+                    fetched.to_path_buf()
+                }
+                _ => unreachable!(),
+            };
+
+            if let Some(since) = since {
+                files.extend(commands::changed_files(&since)?);
+            }
+
+            // Drop the files whose content didn't change since the older artifact
+            let files = match hash_compare {
+                Some(previous) => commands::content_changes(&graph_artifact_path, &previous, &files)?,
+                None => files,
+            };
+
+            // Read the graph artifact, only the shards the files can affect, or only the
+            // affected nodes when streaming. Dependencies can be in any shard.
+            let (mut graph, total_nodes) = if stream {
+                let streamed = commands::stream_affected(&graph_artifact_path, &files)?;
+                (streamed.graph, streamed.total_nodes)
+            } else {
+                let graph = match direction {
+                    QueryDirection::Down => commands::load_graph_for(&graph_artifact_path, &files)?,
+                    QueryDirection::Up | QueryDirection::Both => commands::load_graph(&graph_artifact_path)?,
+                };
+                let total = graph.get_all_nodes().len();
+                (graph, total)
+            };
+
+            // Every emit reads the affected nodes from the graph, which now answers with the
+            // nodes in the requested direction and depth, reusing the match of an earlier query
+            // of the same change
+            if let Some(cache_dir) = &cache_dir {
+                let reasons = commands::cached_affected_reasons(&graph, &files, direction, max_depth, cache_dir)?;
+                graph.remember_affected(files.clone(), reasons);
+            } else if direction != QueryDirection::Down || max_depth.is_some() {
+                let reasons = direction.reasons(&graph, &files, max_depth);
+                graph.remember_affected(files.clone(), reasons);
+            }
+
+            // Metadata kept in a sidecar is only read for the affected nodes, and only when needed
+            if with_metadata || github_output || matches!(emit, QueryEmit::Tests | QueryEmit::Ci | QueryEmit::Security | QueryEmit::Rollout) || !filters.selectors.is_empty() {
+                let affected = graph.get_affected_nodes(&files);
+                commands::attach_metadata(&mut graph, &graph_artifact_path, Some(&affected))?;
+            }
+
+            // Query the graph for the given files and print the result in the requested format
+            print_query(output, &graph, &files, emit, &filters, format, toposort)?;
+
+            // Step outputs for GitHub Actions, next to the ones of the other steps
+            if github_output {
+                let path = std::env::var_os("GITHUB_OUTPUT")
+                    .ok_or("--github-output needs the GITHUB_OUTPUT variable, which GitHub Actions sets")?;
+                commands::append(Path::new(&path), commands::github_output(&graph, &files, &filters)?)?;
+            }
+
+            let affected = filters.apply(&graph, graph.get_affected_nodes(&files));
+            if let Some(exceeded) = budget.check(total_nodes, &affected, &graph.get_affected_reasons(&files)) {
+                eprint!("{}", exceeded);
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Some(Commands::Why { graph_artifact_path, node, mut files, since, json }) => {
+            if let Some(since) = since {
+                files.extend(commands::changed_files(&since)?);
+            }
+            let graph = commands::load_graph(&graph_artifact_path)?;
+            if graph.get_node(&node).is_none() {
+                return Err(format!("No node named {}", node).into());
+            }
+
+            let chains = graph.affected_chains(&files, &node);
+            if json {
+                print_json(output, &chains)?;
+            } else if chains.is_empty() {
+                write_output(output, &format!("{} is not affected by the changed files\n", node))?;
+            } else {
+                write_output(output, &chains.iter().map(|chain| format!("{}\n", chain)).collect::<String>())?;
+            }
+            if chains.is_empty() {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Some(Commands::Affected { dir, dependency_toml_name, allow_cyclical, since, mut files, emit, format, toposort, direction, max_depth, filters, .. }) => {
+            if let Some(since) = since {
+                files.extend(commands::changed_files(&since)?);
+            }
+            let scan = Scan { dirs: dir, dependency_toml_name, ..Scan::default() };
+            let mut graph = commands::prepare(&scan, allow_cyclical, None)?;
+            if direction != QueryDirection::Down || max_depth.is_some() {
+                let reasons = direction.reasons(&graph, &files, max_depth);
+                graph.remember_affected(files.clone(), reasons);
+            }
+            print_query(output, &graph, &files, emit, &filters, format, toposort)?;
+        }
+        Some(Commands::Watch { dir, dependency_toml_name, skip, allow_cyclical, debounce, webhook, filters }) => {
+            let mut scan = Scan { dirs: dir, dependency_toml_name, skip, ..Scan::default() };
+            load_prepare_config(cli.config.as_deref(), &mut scan, None)?;
+            commands::watch(&scan, allow_cyclical, &filters, debounce.into(), webhook.as_deref())?;
+        }
+        #[cfg(feature = "serve")]
+        Some(Commands::Serve { graph_artifact_path, listen, threads }) => {
+            let graph = commands::load_graph(&graph_artifact_path)?;
+            commands::serve(&graph, &listen, threads)?;
+        }
+        Some(Commands::Profile { dir, dependency_toml_name, skip, allow_cyclical, files, top }) => {
+            let mut scan = Scan { dirs: dir, dependency_toml_name, skip, ..Scan::default() };
+            load_prepare_config(cli.config.as_deref(), &mut scan, None)?;
+            let files = (!files.is_empty()).then_some(files);
+            print_json(output, &commands::profile(&scan, allow_cyclical, files.as_deref(), top)?)?;
+        }
+        Some(Commands::GenerateFixture { out, nodes, shape, width, fan_out, files_per_node, depth, seed, dependency_toml_name }) => {
+            let spec = testkit::FixtureSpec { nodes, shape, width, fan_out, files_per_node, depth, seed, dependency_toml_name };
+            print_json(output, &testkit::generate_fixture(&out, &spec)?)?;
+        }
+        Some(Commands::List { graph_artifact_path, list_filters, filters, format }) => {
+            let graph = commands::load_graph(&graph_artifact_path)?;
+            write_output(output, &commands::render_nodes(&commands::list(&graph, &list_filters, &filters), format)?)?;
+        }
+        Some(Commands::Migrate { from, dir, dependency_toml_name, dry_run, force }) => {
+            print_json(output, &commands::migrate(dir, from, dependency_toml_name, dry_run, force)?)?;
+        }
+        Some(Commands::Lint { dir, dependency_toml_name, json }) => {
+            let config = Config::load(cli.config.as_deref(), &dir)?;
+            let findings = commands::lint(dir, dependency_toml_name, &config)?;
+            if json {
+                print_json(output, &findings)?;
+            } else {
+                write_output(output, &findings.iter().map(|finding| format!("{}\n\n", finding)).collect::<String>())?;
+            }
+            if findings.iter().any(|f| f.severity == Severity::Error) {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Some(Commands::Validate { dir, dependency_toml_name, skip, json }) => {
+            let mut scan = Scan { dirs: dir, dependency_toml_name, skip, ..Scan::default() };
+            load_prepare_config(cli.config.as_deref(), &mut scan, None)?;
+
+            let findings = commands::validate(&scan)?;
+            if json {
+                print_json(output, &findings)?;
+            } else {
+                write_output(output, &findings.iter().map(|finding| format!("{}\n\n", finding)).collect::<String>())?;
+            }
+            if !findings.is_empty() {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Some(Commands::Fix { dir, dependency_toml_name, rules, all, dry_run }) => {
+            let rules = if all { commands::FixRule::ALL.to_vec() } else { rules };
+
+            let fixes = commands::fix(dir, dependency_toml_name, &rules, dry_run)?;
+            if dry_run {
+                write_output(output, &fixes.iter().map(|fix| fix.diff()).collect::<String>())?;
+            } else {
+                print_json(output, &fixes)?;
+            }
+        }
+        Some(Commands::Artifact { command: ArtifactCommands::Compact { graph_artifact_path, keep } }) => {
+            let graph = commands::load_graph(&graph_artifact_path)?;
+            print_json(output, &commands::artifact::compact(&graph, &keep)?)?;
+        }
+        Some(Commands::Artifact { command: ArtifactCommands::Inspect { graph_artifact_path } }) => {
+            print_json(output, &commands::artifact::inspect(&graph_artifact_path)?)?;
+        }
+        Some(Commands::Artifact { command: ArtifactCommands::Moved { graph_artifact_path, previous } }) => {
+            let graph = commands::load_graph(&graph_artifact_path)?;
+            let previous = commands::load_graph(&previous)?;
+            print_json(output, &commands::moved_files(&previous, &graph))?;
+        }
+        Some(Commands::Artifact { command: ArtifactCommands::Store { graph_artifact_path, store, commit } }) => {
+            let commit = commands::store_artifact(&graph_artifact_path, &store, commit.as_deref())?;
+            write_output(output, &format!("{}\n", commit))?;
+        }
+        Some(Commands::Artifact { command: ArtifactCommands::Equal { a, b } }) => {
+            let differences = commands::artifact::compare(&commands::load_graph(&a)?, &commands::load_graph(&b)?);
+            print_json(output, &differences)?;
+            if !differences.is_empty() {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Some(Commands::Artifact { command: ArtifactCommands::Merge { graph_artifact_paths, strict } }) => {
+            let artifacts = graph_artifact_paths.into_iter()
+                .map(|path| commands::load_graph(&path).map(|graph| (path, graph)))
+                .collect::<Result<Vec<_>, _>>()?;
+            let (merged, unresolved) = commands::artifact::merge(&artifacts)?;
+            for dep in &unresolved {
+                eprintln!("Unresolved dependency of {} on {}:{}", dep.node, dep.repo, dep.name);
+            }
+            if strict && !unresolved.is_empty() {
+                return Ok(ExitCode::FAILURE);
+            }
+            print_json(output, &merged)?;
+        }
+        Some(Commands::Generate { command: GenerateCommands::GitlabCi { graph_artifact_path, mut files, since, template, needs, filters } }) => {
+            if let Some(since) = since {
+                files.extend(commands::changed_files(&since)?);
+            }
+            let template = std::fs::read_to_string(&template).map_err(Box::<dyn Error>::from)
+                .and_then(|content| Ok(serde_norway::from_str::<serde_norway::Value>(&content)?))
+                .map_err(|e| format!("Failed to read {}: {}", template.display(), e))?;
+            let graph = commands::load_graph(&graph_artifact_path)?;
+            write_output(output, &commands::generate::gitlab_ci(&graph, &files, &filters, &template, needs)?)?;
+        }
+        Some(Commands::Export { command: ExportCommands::Backstage { graph_artifact_path, lifecycle, system } }) => {
+            let graph = commands::load_graph(&graph_artifact_path)?;
+            write_output(output, &commands::export::backstage(&graph, &lifecycle, system.as_deref())?)?;
+        }
+        Some(Commands::Export { command: ExportCommands::Sbom { graph_artifact_path, format, name } }) => {
+            let graph = commands::load_graph(&graph_artifact_path)?;
+            let sbom = serde_json::to_string_pretty(&commands::export::sbom(&graph, format, &name))?;
+            write_output(output, &format!("{}\n", sbom))?;
+        }
+        Some(Commands::Graph { command: GraphCommands::Render { graph_artifact_path, out, files } }) => {
+            let graph = commands::load_graph(&graph_artifact_path)?;
+            commands::graph::render(&graph, &out, &files)?;
+        }
+        Some(Commands::Graph { command: GraphCommands::Edges { graph_artifact_path, node } }) => {
+            let graph = commands::load_graph(&graph_artifact_path)?;
+            print_json(output, &commands::graph::edges(&graph, node.as_deref()))?;
+        }
+        Some(Commands::Graph { command: GraphCommands::Reaches { graph_artifact_path, dependent, dependency } }) => {
+            let graph = commands::load_graph(&graph_artifact_path)?;
+            // An unknown node exits with 2, to tell it apart from a node that isn't reached
+            if let Some(unknown) = [&dependent, &dependency].into_iter().find(|name| graph.get_node(name).is_none()) {
+                eprintln!("Error: Node '{}' not found in the graph", unknown);
+                return Ok(ExitCode::from(2));
+            }
+            let reaches = graph.depends_on(&dependent, &dependency);
+            write_output(output, &format!("{}\n", reaches))?;
+            if !reaches {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Some(Commands::Advise { command: AdviseCommands::Dominators { graph_artifact_path, limit } }) => {
+            let graph = commands::load_graph(&graph_artifact_path)?;
+            print_json(output, &commands::advise::dominators(&graph, limit))?;
+        }
+        Some(Commands::Advise { command: AdviseCommands::Partition { graph_artifact_path, parts } }) => {
+            let graph = commands::load_graph(&graph_artifact_path)?;
+            print_json(output, &commands::advise::partition(&graph, parts))?;
+        }
+        Some(Commands::Export { command: ExportCommands::Plantuml { graph_artifact_path, focus, depth } }) => {
+            let graph = commands::load_graph(&graph_artifact_path)?;
+            write_output(output, &commands::export::plantuml(&graph, focus.as_deref(), depth)?)?;
+        }
+        Some(Commands::Export { command: ExportCommands::Diagram { graph_artifact_path, format, files, affected_only, focus, depth, collapse } }) => {
+            let graph = commands::load_graph(&graph_artifact_path)?;
+            let reasons = graph.get_affected_reasons(&files);
+            let scope = commands::export::DiagramScope { focus: focus.as_deref(), depth, collapse, affected_only };
+            write_output(output, &commands::export::diagram(&graph, &reasons, format, &scope)?)?;
+        }
+        Some(Commands::Export { command: ExportCommands::Buck2 { graph_artifact_path, write, mapping } }) => {
+            let graph = commands::load_graph(&graph_artifact_path)?;
+            if mapping {
+                let targets: BTreeMap<&str, String> = graph.get_all_nodes().into_iter()
+                    .map(|node| (node.name.as_str(), commands::export::buck2_target(node)))
+                    .collect();
+                print_json(output, &targets)?;
+            } else if let Some(root) = write {
+                print_json(output, &commands::export::write_buck2(&commands::export::buck2(&graph), &root)?)?;
+            } else {
+                let stubs: Vec<String> = commands::export::buck2(&graph).iter()
+                    .map(|(path, content)| format!("# {}\n{}", path.display(), content))
+                    .collect();
+                write_output(output, &stubs.join("\n"))?;
+            }
+        }
+        Some(Commands::Badges { graph_artifact_path, out_dir }) => {
+            let graph = commands::load_graph(&graph_artifact_path)?;
+            let max_dependents = Config::load(cli.config.as_deref(), Path::new("."))?.blast_radius.max_dependents;
+
+            let badges = commands::badges(&graph, max_dependents);
+            if let Some(dir) = out_dir {
+                commands::write_badges(&badges, &dir)?;
+            }
+            print_json(output, &badges)?;
+        }
+        Some(Commands::Classify { graph_artifact_path, files }) => {
+            let graph = commands::load_graph(&graph_artifact_path)?;
+            let categories = Config::load(cli.config.as_deref(), Path::new("."))?.classify.categories;
+            let classified = commands::classify(&graph, &files, &categories)
+                .map_err(|e| format!("invalid [classify] pattern: {}", e))?;
+            print_json(output, &classified)?;
+        }
+        Some(Commands::DeployOrder { graph_artifact_path, files, tag, with_dependencies, by_group }) => {
+            let graph = commands::load_graph(&graph_artifact_path)?;
+            let waves = commands::deploy_order(&graph, &files, &tag, with_dependencies);
+            if by_group {
+                print_json(output, &commands::rollout_groups(waves))?;
+            } else {
+                print_json(output, &waves)?;
+            }
+        }
+        Some(Commands::Reviewers { graph_artifact_path, files, direct }) => {
+            let graph = commands::load_graph(&graph_artifact_path)?;
+            print_json(output, &commands::reviewers(&graph, &files, direct))?;
+        }
+        Some(Commands::Codeowners { command: CodeownersCommands::Generate { graph_artifact_path, check } }) => {
+            let graph = commands::load_graph(&graph_artifact_path)?;
+            let generated = commands::codeowners::generate(&graph);
+
+            match check {
+                None => write_output(output, &generated)?,
+                Some(path) => {
+                    // A missing file is out of date like any other, but one we can't read is an error
+                    let committed = match std::fs::read_to_string(&path) {
+                        Ok(committed) => committed,
+                        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+                        Err(e) => return Err(format!("Unable to read {}: {}", path.display(), e).into()),
+                    };
+                    if committed != generated {
+                        print!("{}", similar::TextDiff::from_lines(&committed, &generated)
+                            .unified_diff()
+                            .header(&path.to_string_lossy(), "generated"));
+                        println!("{} is out of date, re-run `dependency-cascade codeowners generate`", path.display());
+                        return Ok(ExitCode::FAILURE);
+                    }
+                }
+            }
+        }
+        Some(Commands::SuggestDeps { dir, dependency_toml_name, languages, node }) => {
+            print_json(output, &commands::suggest_deps(dir, dependency_toml_name, &languages, node)?)?;
+        }
+        Some(Commands::History { graph_artifact_path, since, until, period }) => {
+            let graph = commands::load_graph(&graph_artifact_path)?;
+            print_json(output, &commands::history(&graph, &since, &until, period)?)?;
+        }
+        Some(Commands::Release { command: ReleaseCommands::Plan { graph_artifact_path, since, until } }) => {
+            let graph = commands::load_graph(&graph_artifact_path)?;
+            print_json(output, &commands::release::plan(&graph, &since, &until)?)?;
+        }
+        Some(Commands::Release { command: ReleaseCommands::Changelog { graph_artifact_path, since, until, out_dir } }) => {
+            let graph = commands::load_graph(&graph_artifact_path)?;
+            let fragments = commands::changelog(&graph, &since, &until)?;
+            match out_dir {
+                Some(dir) => print_json(output, &commands::write_fragments(&fragments, &dir)?)?,
+                None => write_output(output, &fragments.into_values().collect::<Vec<_>>().join("\n"))?,
+            }
+        }
+        Some(Commands::Hotspots { graph_artifact_path, since, until, limit, markdown }) => {
+            let graph = commands::load_graph(&graph_artifact_path)?;
+            let hotspots = commands::hotspots(&graph, &since, &until, limit)?;
+            if markdown {
+                write_output(output, &commands::hotspots_markdown(&hotspots))?;
+            } else {
+                print_json(output, &hotspots)?;
+            }
+        }
+        Some(Commands::BlastRadius { graph_artifact_path, max_dependents }) => {
+            let graph = commands::load_graph(&graph_artifact_path)?;
+            let max_dependents = max_dependents.or(Config::load(cli.config.as_deref(), Path::new("."))?.blast_radius.max_dependents);
+
+            let radii = commands::blast_radius(&graph, max_dependents);
+            print_json(output, &radii)?;
+            if radii.iter().any(|radius| radius.exceeds_threshold) {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Some(Commands::Triggers { graph_artifact_path, node, list_files }) => {
+            let graph = commands::load_graph(&graph_artifact_path)?;
+            print_json(output, &commands::triggers(&graph, &node, list_files)?)?;
+        }
+        Some(Commands::Info { graph_artifact_path, node, json }) => {
+            let graph = commands::load_graph(&graph_artifact_path)?;
+            let info = commands::info(&graph, &node)?;
+            if json {
+                print_json(output, &info)?;
+            } else {
+                write_output(output, &info.to_string())?;
+            }
+        }
+        Some(Commands::Coverage { graph_artifact_path }) => {
+            let graph = commands::load_graph(&graph_artifact_path)?;
+            print_json(output, &commands::coverage(&graph)?)?;
+        }
+        Some(Commands::Hash { graph_artifact_path, nodes }) => {
+            let graph = commands::load_graph(&graph_artifact_path)?;
+            print_json(output, &commands::hash(&graph, &nodes)?)?;
+        }
+        Some(Commands::Run { task, args, cache }) => {
+            let graph = commands::load_graph(&args.graph_artifact_path)?;
+            let cache = match cache {
+                Some(location) => Some(Arc::new(runner::Cache {
+                    backend: runner::cache_backend(&location),
+                    node_hashes: commands::hash(&graph, &[])?.into_iter().map(|(name, hash)| (name, hash.hash)).collect(),
+                })),
+                None => None,
+            };
+
+            return execute(&graph, &args, output, cache, |names, jobs| runner::Plan::new(&graph, &task, names, jobs));
+        }
+        Some(Commands::Exec { args, command }) => {
+            let graph = commands::load_graph(&args.graph_artifact_path)?;
+            let command = command.join(" ");
+
+            return execute(&graph, &args, output, None, |names, jobs| runner::Plan::for_command(&graph, &command, names, jobs));
+        }
+        Some(Commands::SplitNode { dir, dependency_toml_name, node, mapping, interactive, dry_run }) => {
+            let changes = commands::split_node(dir, dependency_toml_name, &node, &mapping, interactive, dry_run)?;
+            if dry_run {
+                write_output(output, &changes.iter().map(|change| change.diff()).collect::<String>())?;
+            } else {
+                print_json(output, &changes)?;
+            }
+        }
+        None => println!("No command provided. Use --help for more information."),
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Queries the graph for the given files and prints what `emit` asks for in `format`.
+fn print_query(output: Option<&Path>, graph: &DependencyGraph, files: &[PathBuf], emit: QueryEmit, filters: &FilterArgs, format: QueryFormat, toposort: bool) -> Result<(), Box<dyn Error>> {
+    write_output(output, &commands::render_query(graph, files, emit, filters, format, toposort)?)?;
+    Ok(())
+}
+
+/// Loads the root config for `prepare`: its `[default_metadata]` and `[dotfiles]` sections
+/// into the scan, and its `[glob_guard]` section, `max_node_files` overriding its
+/// `max_files`. The files of an archive can't be counted.
+fn load_prepare_config(config: Option<&Path>, scan: &mut Scan, max_node_files: Option<usize>) -> Result<GlobGuardConfig, Box<dyn Error>> {
+    let config = Config::load(config, scan.base())?;
+    scan.dotfiles = config.dotfiles;
+    scan.default_metadata = Some(serde_json::to_value(config.default_metadata).unwrap_or_default())
+        .filter(|defaults| defaults.as_object().is_some_and(|defaults| !defaults.is_empty()));
+    Ok(GlobGuardConfig {
+        max_files: max_node_files.or(config.glob_guard.max_files).filter(|_| scan.archive.is_none()),
+        ..config.glob_guard
+    })
+}
+
+/// Prints the glob guard's warnings, failing when they're configured as errors.
+fn report_globs(graph: &DependencyGraph, config: &GlobGuardConfig) -> Result<(), Box<dyn Error>> {
+    let warnings = commands::check_globs(graph, config)?;
+    for warning in &warnings {
+        eprintln!("{}", warning);
+    }
+    if config.severity == Severity::Error && !warnings.is_empty() {
+        return Err(format!("{} include patterns flagged by the glob guard", warnings.len()).into());
+    }
+    Ok(())
+}
+
+fn read_codeowners(path: &Path) -> io::Result<Vec<commands::codeowners::Rule>> {
+    let content = std::fs::read_to_string(path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+    Ok(commands::codeowners::parse(&content))
+}
+
+/// Prints the value as a line of JSON, or writes it atomically to the `--output` file.
+fn print_json(output: Option<&Path>, value: &impl Serialize) -> Result<(), Box<dyn Error>> {
+    write_output(output, &format!("{}\n", serde_json::to_string(value)?))?;
+    Ok(())
+}
+
+/// Prints the result of a command, or writes it atomically to the `--output` file.
+fn write_output(output: Option<&Path>, content: &str) -> io::Result<()> {
+    write_output_bytes(output, content.as_bytes())
+}
+
+/// Like `write_output`, for output that may not be text, such as binary artifacts.
+fn write_output_bytes(output: Option<&Path>, content: &[u8]) -> io::Result<()> {
+    match output {
+        Some(path) => commands::write_artifact(path, content),
+        None => io::stdout().write_all(content),
+    }
+}
+
+/// Plans the nodes selected by `args` with `make_plan`, then prints the plan or runs it.
+/// Fails if any node fails.
+fn execute(
+    graph: &DependencyGraph,
+    args: &RunArgs,
+    output: Option<&Path>,
+    cache: Option<Arc<runner::Cache>>,
+    make_plan: impl FnOnce(&[String], usize) -> Result<runner::Plan, runner::PlanError>,
+) -> Result<ExitCode, Box<dyn Error>> {
+    let reasons = graph.get_affected_reasons(&args.files);
+    let names: Vec<String> = if args.all {
+        graph.get_all_nodes().into_iter().map(|node| node.name.clone()).collect()
+    } else {
+        reasons.keys().cloned().collect()
+    };
+    let names = args.filters.apply(graph, names);
+    let jobs = args.jobs.unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()));
+
+    let mut plan = make_plan(&names, jobs)?;
+    plan.set_affected_reasons(&reasons);
+    if args.plan {
+        if args.json {
+            print_json(output, &plan)?;
+        } else {
+            write_output(output, &plan.to_string())?;
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if let Some(dir) = &args.log_dir {
+        std::fs::create_dir_all(dir)?;
+    }
+    let options = runner::RunOptions {
+        keep_going: args.keep_going,
+        retries: args.retries,
+        cache,
+        log_dir: args.log_dir.clone(),
+    };
+    let summary = runner::run(&plan, &options);
+    println!("{}", summary);
+
+    if let Some(path) = &args.report {
+        commands::write_atomic(path, serde_json::to_string_pretty(&runner::Report::from(&summary))?)?;
+    }
+    if let Some(path) = &args.junit {
+        commands::write_atomic(path, runner::junit(&summary))?;
+    }
+    Ok(if summary.success() { ExitCode::SUCCESS } else { ExitCode::FAILURE })
+}
@@ -0,0 +1,312 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::PathBuf;
+
+use clap::Subcommand;
+use petgraph::algo::dominators;
+use petgraph::graph::{DiGraph, NodeIndex};
+use serde::Serialize;
+
+use crate::types::DependencyGraph;
+
+/// The `advise` subcommands.
+#[derive(Subcommand)]
+pub enum AdviseCommands {
+    /// Suggests how to split the repository: groups of nodes with many edges between them and
+    /// few to the other groups, found by greedy modularity clustering, printed as JSON with the
+    /// dependencies each split would have to break.
+    Partition {
+        /// The JSON artifact file path containing the previously prepared dependency graph
+        /// from the `prepare` command
+        #[arg(short, long, value_name = "FILE")]
+        graph_artifact_path: PathBuf,
+        /// Keep merging groups until there are at most N, even past the best modularity.
+        /// Groups that no dependency connects are never merged.
+        #[arg(long, value_name = "N")]
+        parts: Option<usize>,
+    },
+    /// Ranks the nodes by how many nodes they dominate, printed as JSON: a node dominates the
+    /// dependents that every dependency chain reaches through it, so a change to it gates all
+    /// of them. Good candidates for stabilization and caching.
+    Dominators {
+        /// The JSON artifact file path containing the previously prepared dependency graph
+        /// from the `prepare` command
+        #[arg(short, long, value_name = "FILE")]
+        graph_artifact_path: PathBuf,
+        /// Only print the N nodes dominating the most.
+        #[arg(long, value_name = "N")]
+        limit: Option<usize>,
+    },
+}
+
+/// A candidate split of the graph.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PartitionAdvice {
+    /// How much denser the groups are than they would be with random edges, from -0.5 to 1.
+    /// Above 0.3 the groups are usually worth considering.
+    pub modularity: f64,
+    /// Largest first.
+    pub partitions: Vec<Partition>,
+    /// The dependencies between nodes of different groups, which a split would turn into
+    /// cross-repository ones or remove.
+    pub crossing_edges: Vec<CrossingEdge>,
+}
+
+/// A group of nodes to keep together.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Partition {
+    pub id: usize,
+    /// Sorted by name.
+    pub nodes: Vec<String>,
+    /// The number of dependencies between nodes of the group.
+    pub internal_edges: usize,
+    /// The number of dependencies between the group and the others.
+    pub crossing_edges: usize,
+}
+
+/// A dependency of a node on a node of another group.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct CrossingEdge {
+    pub dependent: String,
+    pub dependency: String,
+    /// The group of the dependent.
+    pub from: usize,
+    /// The group of the dependency.
+    pub to: usize,
+}
+
+/// Groups the nodes by greedily merging the two connected groups whose merge increases
+/// modularity the most, edges counted regardless of their direction, until no merge does.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+/// * `parts` - Keep merging until there are at most this many groups
+///
+/// ### Returns
+/// * `PartitionAdvice` - The groups and the edges between them
+pub fn partition(graph: &DependencyGraph, parts: Option<usize>) -> PartitionAdvice {
+    let mut names: Vec<String> = graph.get_all_nodes().into_iter().map(|node| node.name.clone()).collect();
+    names.sort();
+    let index: HashMap<&str, usize> = names.iter().enumerate().map(|(i, name)| (name.as_str(), i)).collect();
+    let edges: Vec<(usize, usize)> = graph.get_all_nodes().into_iter()
+        .flat_map(|node| {
+            let dependent = index[node.name.as_str()];
+            node.dependencies.iter().filter_map(|dep| index.get(dep.as_str())).map(move |&dependency| (dependent, dependency)).collect::<Vec<_>>()
+        })
+        .filter(|(dependent, dependency)| dependent != dependency)
+        .collect();
+    let m = edges.len() as f64;
+
+    // Every node starts in its own group, merged groups keep the lower id
+    let mut members: Vec<BTreeSet<usize>> = (0..names.len()).map(|i| BTreeSet::from([i])).collect();
+    let mut degree: Vec<usize> = vec![0; names.len()];
+    let mut internal: Vec<usize> = vec![0; names.len()];
+    let mut links: BTreeMap<(usize, usize), usize> = BTreeMap::new();
+    for &(a, b) in &edges {
+        degree[a] += 1;
+        degree[b] += 1;
+        *links.entry((a.min(b), a.max(b))).or_default() += 1;
+    }
+    let gain = |l: usize, da: usize, db: usize| l as f64 / m - (da * db) as f64 / (2.0 * m * m);
+
+    let mut count = names.len();
+    loop {
+        let best = links.iter()
+            .map(|(&(a, b), &l)| ((a, b), gain(l, degree[a], degree[b])))
+            .fold(None, |best: Option<((usize, usize), f64)>, candidate| match best {
+                Some((_, gain)) if gain >= candidate.1 => best,
+                _ => Some(candidate),
+            });
+        let Some(((a, b), gain)) = best else { break };
+        if gain <= 0.0 && parts.is_none_or(|parts| count <= parts) {
+            break;
+        }
+
+        // Merge b into a
+        let moved = std::mem::take(&mut members[b]);
+        members[a].extend(moved);
+        degree[a] += std::mem::take(&mut degree[b]);
+        internal[a] += std::mem::take(&mut internal[b]) + links.remove(&(a, b)).unwrap_or(0);
+        let touching: Vec<((usize, usize), usize)> = links.iter()
+            .filter(|((x, y), _)| *x == b || *y == b)
+            .map(|(&key, &l)| (key, l))
+            .collect();
+        for ((x, y), l) in touching {
+            links.remove(&(x, y));
+            let other = if x == b { y } else { x };
+            *links.entry((a.min(other), a.max(other))).or_default() += l;
+        }
+        count -= 1;
+    }
+
+    let mut groups: Vec<(BTreeSet<usize>, usize, usize)> = members.into_iter().zip(internal).zip(degree)
+        .filter(|((members, _), _)| !members.is_empty())
+        .map(|((members, internal), degree)| (members, internal, degree))
+        .collect();
+    groups.sort_by(|a, b| b.0.len().cmp(&a.0.len()).then_with(|| a.0.first().cmp(&b.0.first())));
+    let mut group_of = vec![0; names.len()];
+    for (id, (members, _, _)) in groups.iter().enumerate() {
+        for &member in members {
+            group_of[member] = id;
+        }
+    }
+
+    let mut crossing_edges: Vec<CrossingEdge> = edges.iter()
+        .filter(|(a, b)| group_of[*a] != group_of[*b])
+        .map(|&(a, b)| CrossingEdge {
+            dependent: names[a].clone(),
+            dependency: names[b].clone(),
+            from: group_of[a],
+            to: group_of[b],
+        })
+        .collect();
+    crossing_edges.sort();
+    let modularity = if edges.is_empty() {
+        0.0
+    } else {
+        groups.iter()
+            .map(|(_, internal, degree)| *internal as f64 / m - (*degree as f64 / (2.0 * m)).powi(2))
+            .sum()
+    };
+    let partitions = groups.into_iter().enumerate()
+        .map(|(id, (members, internal_edges, degree))| Partition {
+            id,
+            nodes: members.into_iter().map(|member| names[member].clone()).collect(),
+            internal_edges,
+            crossing_edges: degree - 2 * internal_edges,
+        })
+        .collect();
+    PartitionAdvice { modularity, partitions, crossing_edges }
+}
+
+/// A node and the dependents it gates.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Dominator {
+    pub node: String,
+    /// The share of the graph's nodes it dominates, from 0 to 1.
+    pub share: f64,
+    /// The nodes it dominates, sorted by name.
+    pub dominated: Vec<String>,
+}
+
+/// Computes, for every node, the dependents whose every chain of dependencies from a node
+/// without dependencies goes through it. Nodes only reachable through a cycle are left out.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+/// * `limit` - Only keep the first `limit` nodes
+///
+/// ### Returns
+/// * `Vec<Dominator>` - The nodes dominating at least one other, by decreasing number of
+///   dominated nodes, then by name
+pub fn dominators(graph: &DependencyGraph, limit: Option<usize>) -> Vec<Dominator> {
+    let nodes = graph.get_all_nodes();
+    // A virtual root depends on nothing and is a dependency of every node without any
+    let mut dag: DiGraph<(), ()> = DiGraph::new();
+    let root = dag.add_node(());
+    let index: HashMap<&str, NodeIndex> = nodes.iter().map(|node| (node.name.as_str(), dag.add_node(()))).collect();
+    for node in &nodes {
+        let idx = index[node.name.as_str()];
+        if node.dependencies.is_empty() {
+            dag.add_edge(root, idx, ());
+        }
+        for dep in node.dependencies.iter().filter_map(|dep| index.get(dep.as_str())) {
+            dag.add_edge(*dep, idx, ());
+        }
+    }
+    let tree = dominators::simple_fast(&dag, root);
+
+    let mut ranked: Vec<Dominator> = nodes.iter()
+        .map(|node| {
+            let mut dominated = Vec::new();
+            let mut stack: Vec<NodeIndex> = vec![index[node.name.as_str()]];
+            while let Some(idx) = stack.pop() {
+                for child in tree.immediately_dominated_by(idx).filter(|child| *child != idx) {
+                    dominated.push(nodes[child.index() - 1].name.clone());
+                    stack.push(child);
+                }
+            }
+            dominated.sort();
+            Dominator { node: node.name.clone(), share: dominated.len() as f64 / nodes.len() as f64, dominated }
+        })
+        .filter(|dominator| !dominator.dominated.is_empty())
+        .collect();
+    ranked.sort_by(|a, b| b.dominated.len().cmp(&a.dominated.len()).then_with(|| a.node.cmp(&b.node)));
+    if let Some(limit) = limit {
+        ranked.truncate(limit);
+    }
+    ranked
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::Node;
+
+    use super::*;
+
+    fn node(name: &str, deps: Vec<&str>) -> Node {
+        Node::new(
+            name.to_string(),
+            PathBuf::from(name),
+            vec![PathBuf::from("**")],
+            vec![],
+            deps.into_iter().map(String::from).collect(),
+            None,
+        ).unwrap()
+    }
+
+    #[test]
+    fn test_partition() {
+        // Two tight clusters, the web one depending on the data one through a single edge
+        let graph = DependencyGraph::new(vec![
+            node("db", vec![]),
+            node("models", vec!["db"]),
+            node("cache", vec!["db", "models"]),
+            node("api", vec!["models"]),
+            node("web", vec!["api"]),
+            node("ui", vec!["api", "web"]),
+        ], false).unwrap();
+
+        let advice = partition(&graph, None);
+        let nodes: Vec<Vec<&str>> = advice.partitions.iter()
+            .map(|p| p.nodes.iter().map(String::as_str).collect())
+            .collect();
+        assert_eq!(nodes, vec![vec!["api", "ui", "web"], vec!["cache", "db", "models"]]);
+        assert_eq!(advice.crossing_edges, vec![CrossingEdge {
+            dependent: "api".to_string(),
+            dependency: "models".to_string(),
+            from: 0,
+            to: 1,
+        }]);
+        assert_eq!((advice.partitions[0].internal_edges, advice.partitions[0].crossing_edges), (3, 1));
+        assert!(advice.modularity > 0.3);
+
+        let advice = partition(&graph, Some(1));
+        assert_eq!(advice.partitions.len(), 1);
+        assert!(advice.crossing_edges.is_empty());
+        assert_eq!(advice.modularity, 0.0);
+    }
+
+    #[test]
+    fn test_dominators() {
+        // web reaches core through both api and auth, so only core dominates it
+        let graph = DependencyGraph::new(vec![
+            node("core", vec![]),
+            node("api", vec!["core"]),
+            node("auth", vec!["core"]),
+            node("web", vec!["api", "auth"]),
+            node("admin", vec!["api"]),
+            node("docs", vec![]),
+        ], false).unwrap();
+
+        let ranked = dominators(&graph, None);
+        let summary: Vec<(&str, Vec<&str>)> = ranked.iter()
+            .map(|d| (d.node.as_str(), d.dominated.iter().map(String::as_str).collect()))
+            .collect();
+        assert_eq!(summary, vec![
+            ("core", vec!["admin", "api", "auth", "web"]),
+            ("api", vec!["admin"]),
+        ]);
+        assert!((ranked[0].share - 4.0 / 6.0).abs() < 1e-9);
+        assert_eq!(dominators(&graph, Some(1)).len(), 1);
+    }
+}
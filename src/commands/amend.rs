@@ -0,0 +1,127 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::{PrepareError, Scan, ScannedManifest};
+use crate::types::{ArtifactInfo, DependencyGraph, DependencyGraphCreationError, Node};
+
+#[derive(Debug, thiserror::Error)]
+pub enum AmendError {
+    #[error("The manifest of {0} is already in the artifact, re-run prepare to update it")]
+    AlreadyPrepared(PathBuf),
+    #[error(transparent)]
+    Graph(#[from] DependencyGraphCreationError),
+}
+
+/// Adds the nodes of the manifests found by `scan` to a prepared graph, without scanning the
+/// rest of the repository again. Must run from the directory `prepare` ran in, with the
+/// scanned directories inside it, or be given the same root.
+///
+/// Only the new nodes are checked: the nodes already in the graph can't depend on them, so a
+/// new cycle can only go through new nodes.
+///
+/// ### Arguments
+/// * `graph` - The artifact to amend
+/// * `scan` - The directories to scan for new manifests
+/// * `allow_cyclical` - Whether the new nodes may form cycles
+///
+/// ### Returns
+/// * `DependencyGraph` - The graph with the new nodes
+pub fn amend(
+    graph: &DependencyGraph,
+    scan: &Scan,
+    allow_cyclical: bool,
+) -> Result<DependencyGraph, PrepareError> {
+    let mut hashes = graph.manifest_hashes().clone();
+    let existing: HashSet<&Path> = graph.get_all_nodes().into_iter().map(|node| node.path.as_path()).collect();
+    let mut added: Vec<Node> = Vec::new();
+    for ScannedManifest { file, path, content } in scan.read()?.manifests {
+        if existing.contains(path.as_path()) {
+            return Err(AmendError::AlreadyPrepared(path).into());
+        }
+        let mut node = Node::from_toml_str(&content, path.clone())?;
+        node.manifest = file.file_name().map(|name| path.join(name));
+        node.hide_dotfiles = !scan.dotfiles.globs;
+        if let Some(defaults) = &scan.default_metadata {
+            node.merge_default_metadata(defaults)?;
+        }
+        hashes.insert(path, super::manifest_hash(&content, scan.default_metadata.as_ref()));
+        added.push(node);
+    }
+    log::info!("Adding {} nodes to the artifact", added.len());
+    check_added(graph, &added, allow_cyclical)?;
+
+    // The existing nodes were checked by the prepare that wrote the artifact
+    let nodes: Vec<Node> = graph.get_all_nodes().into_iter().cloned().chain(added).collect();
+    let mut amended = DependencyGraph::new(nodes, true)?;
+    amended.set_manifest_hashes(hashes);
+    amended.set_generations();
+    amended.set_file_index(graph.file_index().clone());
+    amended.set_info(ArtifactInfo {
+        created_at: Some(humantime::format_rfc3339_seconds(SystemTime::now()).to_string()),
+        ..graph.info().clone()
+    });
+    Ok(amended)
+}
+
+/// Checks the names and dependencies of the added nodes, and the cycles among them.
+fn check_added(graph: &DependencyGraph, added: &[Node], allow_cyclical: bool) -> Result<(), AmendError> {
+    let added_names: HashSet<&str> = added.iter().map(|node| node.name.as_str()).collect();
+    for node in added {
+        if graph.get_node(&node.name).is_some() {
+            return Err(DependencyGraphCreationError::DuplicateNodeName(node.name.clone()).into());
+        }
+        if let Some(dep) = node.dependencies.iter().find(|dep| !added_names.contains(dep.as_str()) && graph.get_node(dep).is_none()) {
+            let mut names: Vec<&str> = graph.get_all_nodes().into_iter().map(|node| node.name.as_str()).chain(added_names.iter().copied()).collect();
+            names.sort();
+            return Err(DependencyGraphCreationError::MissingDependency(dep.clone(), node.name.clone(), names.join(", ")).into());
+        }
+    }
+
+    // Duplicates among the added nodes and their cycles, the edges to existing nodes left out
+    let subgraph: Vec<Node> = added.iter()
+        .map(|node| Node {
+            dependencies: node.dependencies.iter().filter(|dep| added_names.contains(dep.as_str())).cloned().collect(),
+            ..node.clone()
+        })
+        .collect();
+    DependencyGraph::new(subgraph, allow_cyclical)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(name: &str, deps: Vec<&str>) -> Node {
+        Node::new(
+            name.to_string(),
+            PathBuf::from(name),
+            vec![PathBuf::from("**")],
+            vec![],
+            deps.into_iter().map(String::from).collect(),
+            None,
+        ).unwrap()
+    }
+
+    #[test]
+    fn test_check_added() {
+        let graph = DependencyGraph::new(vec![node("core", vec![]), node("api", vec!["core"])], false).unwrap();
+
+        assert!(check_added(&graph, &[node("gen", vec!["core"]), node("gen-client", vec!["gen", "api"])], false).is_ok());
+        assert!(matches!(
+            check_added(&graph, &[node("api", vec![])], false),
+            Err(AmendError::Graph(DependencyGraphCreationError::DuplicateNodeName(name))) if name == "api",
+        ));
+        assert!(matches!(
+            check_added(&graph, &[node("gen", vec!["proto"])], false),
+            Err(AmendError::Graph(DependencyGraphCreationError::MissingDependency(dep, _, _))) if dep == "proto",
+        ));
+        let cycle = [node("gen", vec!["gen-client", "core"]), node("gen-client", vec!["gen"])];
+        assert!(matches!(
+            check_added(&graph, &cycle, false),
+            Err(AmendError::Graph(DependencyGraphCreationError::CircularDependency(..))),
+        ));
+        assert!(check_added(&graph, &cycle, true).is_ok());
+    }
+}
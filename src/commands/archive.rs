@@ -0,0 +1,93 @@
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use flate2::read::GzDecoder;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ArchiveError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Zip(#[from] zip::result::ZipError),
+}
+
+/// The files of an archive `prepare` reads manifests from.
+pub struct ArchivedFiles {
+    /// The paths and contents of the wanted files, in the archive's order.
+    pub files: Vec<(PathBuf, String)>,
+    /// The commit `git archive` recorded in the archive, if any.
+    pub commit: Option<String>,
+}
+
+/// Reads the files of a tarball, gzipped or not, or of a zip file, recognized by their first
+/// bytes rather than their extension.
+///
+/// ### Arguments
+/// * `path` - The archive
+/// * `wanted` - Whether to read the file at this path of the archive
+///
+/// ### Returns
+/// * `ArchivedFiles` - The wanted files and the archived commit
+pub fn read_archive(path: &Path, wanted: impl Fn(&Path) -> bool) -> Result<ArchivedFiles, ArchiveError> {
+    let mut file = File::open(path)?;
+    let mut magic = [0; 4];
+    let read = file.read(&mut magic)?;
+    file.seek(SeekFrom::Start(0))?;
+
+    match &magic[..read] {
+        [0x50, 0x4b, 0x03, 0x04] => read_zip(file, wanted),
+        [0x1f, 0x8b, ..] => read_tar(GzDecoder::new(BufReader::new(file)), wanted),
+        _ => read_tar(BufReader::new(file), wanted),
+    }
+}
+
+fn read_tar(reader: impl Read, wanted: impl Fn(&Path) -> bool) -> Result<ArchivedFiles, ArchiveError> {
+    let mut archive = tar::Archive::new(reader);
+    let mut archived = ArchivedFiles { files: Vec::new(), commit: None };
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        // `git archive` stores the commit as the comment of a global header
+        if entry.header().entry_type() == tar::EntryType::XGlobalHeader {
+            if let Some(extensions) = entry.pax_extensions()? {
+                for extension in extensions {
+                    let extension = extension?;
+                    if extension.key() == Ok("comment") {
+                        archived.commit = extension.value().ok().map(String::from);
+                    }
+                }
+            }
+            continue;
+        }
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path()?.into_owned();
+        if wanted(&path) {
+            let mut content = String::new();
+            entry.read_to_string(&mut content)?;
+            archived.files.push((path, content));
+        }
+    }
+    Ok(archived)
+}
+
+fn read_zip(file: File, wanted: impl Fn(&Path) -> bool) -> Result<ArchivedFiles, ArchiveError> {
+    let mut archive = zip::ZipArchive::new(BufReader::new(file))?;
+    // `git archive --format=zip` stores the commit as the archive's comment
+    let commit = Some(String::from_utf8_lossy(archive.comment()).trim().to_string()).filter(|comment| !comment.is_empty());
+    let mut files = Vec::new();
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        if entry.is_dir() {
+            continue;
+        }
+        let Some(path) = entry.enclosed_name() else { continue };
+        if wanted(&path) {
+            let mut content = String::new();
+            entry.read_to_string(&mut content)?;
+            files.push((path, content));
+        }
+    }
+    Ok(ArchivedFiles { files, commit })
+}
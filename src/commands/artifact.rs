@@ -0,0 +1,449 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::Subcommand;
+use serde::Serialize;
+
+use crate::types::{ArtifactInfo, DependencyGraph, DependencyGraphCreationError, ExternalDependency, Node};
+
+/// The `artifact` subcommands.
+#[derive(Subcommand)]
+pub enum ArtifactCommands {
+    /// Prints a smaller copy of the artifact for `query`: metadata is stripped except for the
+    /// `--keep` keys. Keep the full artifact for the other commands.
+    Compact {
+        /// The JSON artifact file path containing the previously prepared dependency graph
+        /// from the `prepare` command
+        #[arg(short, long, value_name = "FILE")]
+        graph_artifact_path: PathBuf,
+        /// A top-level metadata key to keep. Can be repeated.
+        #[arg(long = "keep", value_name = "KEY")]
+        keep: Vec<String>,
+    },
+    /// Prints, as JSON, how the artifact was produced (schema and tool versions, creation
+    /// time, source commit), its node and edge counts, and what its size is made of.
+    Inspect {
+        /// The JSON artifact file path containing the previously prepared dependency graph
+        /// from the `prepare` command
+        #[arg(short, long, value_name = "FILE")]
+        graph_artifact_path: PathBuf,
+    },
+    /// Prints, as JSON, the files claimed by different nodes than in a previous artifact.
+    /// Both artifacts must have been prepared with `--index-files`.
+    Moved {
+        /// The JSON artifact file path containing the previously prepared dependency graph
+        /// from the `prepare` command
+        #[arg(short, long, value_name = "FILE")]
+        graph_artifact_path: PathBuf,
+        /// The older artifact to compare with.
+        #[arg(long, value_name = "FILE")]
+        previous: PathBuf,
+    },
+    /// Prints a single artifact federating the artifacts of several repositories, each
+    /// prepared with `--repo`. Nodes are renamed `<repo>:<name>` and their paths prefixed
+    /// with `<repo>/`, so files are queried as `<repo>/<path>`. Dependencies on nodes of
+    /// another repository are resolved into edges, the unresolved ones reported.
+    Merge {
+        /// An artifact to merge. Repeat for every repository.
+        #[arg(short, long = "graph-artifact-path", value_name = "FILE", required = true, num_args = 1)]
+        graph_artifact_paths: Vec<PathBuf>,
+        /// Fail when a dependency on another repository can't be resolved.
+        #[arg(long, default_value_t = false)]
+        strict: bool,
+    },
+    /// Stores the artifact as `<commit>.json` in a store shared by the pipelines, where
+    /// `query --at` finds it. Prints the full hash of the commit.
+    Store {
+        /// The JSON artifact file path containing the previously prepared dependency graph
+        /// from the `prepare` command
+        #[arg(short, long, value_name = "FILE")]
+        graph_artifact_path: PathBuf,
+        /// The store: a local directory, `http(s)://...` (through `curl`) or
+        /// `s3://bucket/prefix` (through the `aws` CLI).
+        #[arg(long, value_name = "LOCATION")]
+        store: String,
+        /// The commit to store the artifact for, by default the one it was prepared at.
+        #[arg(long, value_name = "COMMIT")]
+        commit: Option<String>,
+    },
+    /// Compares the graphs of two artifacts, ignoring how they were produced and the order of
+    /// nodes, patterns, dependencies and tags. Prints the differing nodes as JSON and exits
+    /// with a non-zero status when there are any.
+    Equal {
+        /// The first artifact.
+        #[arg(value_name = "FILE")]
+        a: PathBuf,
+        /// The second artifact.
+        #[arg(value_name = "FILE")]
+        b: PathBuf,
+    },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum MergeError {
+    #[error("The artifact {0} has no repository name, prepare it with `--repo`")]
+    UnnamedRepository(PathBuf),
+    #[error("Repository '{0}' appears in more than one artifact")]
+    DuplicateRepository(String),
+    #[error(transparent)]
+    Graph(#[from] DependencyGraphCreationError),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum InspectError {
+    #[error(transparent)]
+    Load(#[from] super::LoadError),
+    #[error("Unable to read {0}: {1}")]
+    Read(PathBuf, std::io::Error),
+    #[error("Error serializing: {0}")]
+    Serialize(#[from] serde_json::Error),
+}
+
+/// A dependency on another repository that the merged artifacts don't provide.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UnresolvedDependency {
+    /// The depending node, by its merged name.
+    pub node: String,
+    pub repo: String,
+    pub name: String,
+}
+
+/// What `artifact inspect` reports.
+#[derive(Debug, Clone, Serialize)]
+pub struct ArtifactReport {
+    #[serde(flatten)]
+    pub info: ArtifactInfo,
+    pub nodes: usize,
+    pub edges: usize,
+    /// The size of the artifact file, which is only the index for sharded artifacts.
+    pub file_bytes: u64,
+    pub size: SizeBreakdown,
+}
+
+/// The serialized size of the graph, split by what it describes.
+#[derive(Debug, Clone, Serialize)]
+pub struct SizeBreakdown {
+    pub total_bytes: usize,
+    /// Include, exclude and cascade-exclude patterns.
+    pub pattern_bytes: usize,
+    pub metadata_bytes: usize,
+    /// Names, paths, dependencies, tags and the graph structure.
+    pub other_bytes: usize,
+}
+
+/// Strips everything `query` doesn't need to match files and filter nodes from the graph.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+/// * `keep` - The top-level metadata keys to keep
+///
+/// ### Returns
+/// * `DependencyGraph` - The same nodes and edges, with less metadata
+pub fn compact(graph: &DependencyGraph, keep: &[String]) -> Result<DependencyGraph, DependencyGraphCreationError> {
+    let nodes: Vec<Node> = graph.get_all_nodes().into_iter()
+        .map(|node| Node {
+            metadata: compact_metadata(node.metadata.as_ref(), keep),
+            ..node.clone()
+        })
+        .collect();
+    // The original graph was already checked for cycles
+    let mut compacted = DependencyGraph::new(nodes, true)?;
+    compacted.set_info(graph.info().clone());
+    Ok(compacted)
+}
+
+/// Describes the artifact at `path`.
+pub fn inspect(path: &Path) -> Result<ArtifactReport, InspectError> {
+    let graph = super::load_graph(path)?;
+    let mut pattern_bytes = 0;
+    let mut metadata_bytes = 0;
+    for node in graph.get_all_nodes() {
+        for patterns in [&node.included_paths, &node.excluded_paths, &node.cascade_excluded_paths] {
+            pattern_bytes += json_len(patterns)?;
+        }
+        metadata_bytes += json_len(&node.metadata)?;
+    }
+    let total_bytes = json_len(&graph)?;
+
+    Ok(ArtifactReport {
+        info: graph.info().clone(),
+        nodes: graph.get_all_nodes().len(),
+        edges: graph.edge_count(),
+        file_bytes: if super::output::is_stdin(path) { total_bytes as u64 } else { fs::metadata(path).map_err(|e| InspectError::Read(path.to_path_buf(), e))?.len() },
+        size: SizeBreakdown {
+            total_bytes,
+            pattern_bytes,
+            metadata_bytes,
+            other_bytes: total_bytes.saturating_sub(pattern_bytes + metadata_bytes),
+        },
+    })
+}
+
+/// Federates the artifacts of several repositories into one graph.
+///
+/// ### Arguments
+/// * `artifacts` - Each artifact, with the path it was read from
+///
+/// ### Returns
+/// * `(DependencyGraph, Vec<UnresolvedDependency>)` - The merged graph, and the dependencies
+///   on repositories or nodes missing from it, which stay recorded on their node
+pub fn merge(artifacts: &[(PathBuf, DependencyGraph)]) -> Result<(DependencyGraph, Vec<UnresolvedDependency>), MergeError> {
+    let mut repos: Vec<&str> = Vec::new();
+    for (path, graph) in artifacts {
+        let repo = graph.info().repo.as_deref().ok_or_else(|| MergeError::UnnamedRepository(path.clone()))?;
+        if repos.contains(&repo) {
+            return Err(MergeError::DuplicateRepository(repo.to_string()));
+        }
+        repos.push(repo);
+    }
+    let qualified = |repo: &str, name: &str| format!("{}:{}", repo, name);
+    let available: HashSet<String> = artifacts.iter().zip(&repos)
+        .flat_map(|((_, graph), repo)| graph.get_all_nodes().into_iter().map(|node| qualified(repo, &node.name)))
+        .collect();
+
+    let mut nodes = Vec::new();
+    let mut unresolved = Vec::new();
+    for ((_, graph), repo) in artifacts.iter().zip(&repos) {
+        for node in graph.get_all_nodes() {
+            let name = qualified(repo, &node.name);
+            let mut dependencies: Vec<String> = node.dependencies.iter().map(|dep| qualified(repo, dep)).collect();
+            let mut external_dependencies: Vec<ExternalDependency> = Vec::new();
+            for dep in &node.external_dependencies {
+                let target = qualified(&dep.repo, &dep.name);
+                if available.contains(&target) {
+                    dependencies.push(target);
+                } else {
+                    unresolved.push(UnresolvedDependency { node: name.clone(), repo: dep.repo.clone(), name: dep.name.clone() });
+                    external_dependencies.push(dep.clone());
+                }
+            }
+            nodes.push(Node {
+                name,
+                path: Path::new(repo).join(&node.path),
+                dependencies,
+                external_dependencies,
+                dependency_edges: node.dependency_edges.iter().map(|(dep, edge)| (qualified(repo, dep), edge.clone())).collect(),
+                manifest: node.manifest.as_ref().map(|manifest| Path::new(repo).join(manifest)),
+                ..node.clone()
+            });
+        }
+    }
+
+    let mut merged = DependencyGraph::new(nodes, false)?;
+    // Cross-repository edges push nodes to later generations
+    merged.set_generations();
+    merged.set_info(ArtifactInfo {
+        created_at: Some(humantime::format_rfc3339_seconds(std::time::SystemTime::now()).to_string()),
+        ..ArtifactInfo::current()
+    });
+    Ok((merged, unresolved))
+}
+
+/// How a node differs between two artifacts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeChange {
+    /// Only in the second artifact.
+    Added,
+    /// Only in the first artifact.
+    Removed,
+    Changed,
+}
+
+/// A node of either artifact that the other doesn't have the same.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NodeDifference {
+    pub node: String,
+    pub change: NodeChange,
+    /// The fields that differ, for changed nodes.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<&'static str>,
+}
+
+/// Compares the nodes and edges of two graphs. The artifact info, manifest hashes, file
+/// index and where nodes and edges were declared don't count.
+///
+/// ### Returns
+/// * `Vec<NodeDifference>` - The differing nodes, sorted by name. Empty when the graphs are equal
+pub fn compare(a: &DependencyGraph, b: &DependencyGraph) -> Vec<NodeDifference> {
+    let nodes = |graph: &DependencyGraph| -> BTreeMap<String, Node> {
+        graph.get_all_nodes().into_iter().map(|node| (node.name.clone(), node.clone())).collect()
+    };
+    let (a, b) = (nodes(a), nodes(b));
+    let names: BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+    names.into_iter()
+        .filter_map(|name| {
+            let (change, fields) = match (a.get(name), b.get(name)) {
+                (Some(a), Some(b)) => (NodeChange::Changed, changed_fields(a, b)),
+                (Some(_), None) => (NodeChange::Removed, Vec::new()),
+                (None, Some(_)) => (NodeChange::Added, Vec::new()),
+                (None, None) => unreachable!("the name comes from one of the graphs"),
+            };
+            (change != NodeChange::Changed || !fields.is_empty()).then(|| NodeDifference { node: name.clone(), change, fields })
+        })
+        .collect()
+}
+
+fn changed_fields(a: &Node, b: &Node) -> Vec<&'static str> {
+    fn sorted<T: Ord + Clone>(items: &[T]) -> Vec<T> {
+        let mut items = items.to_vec();
+        items.sort();
+        items.dedup();
+        items
+    }
+    // The order of patterns only matters once one of them is negated
+    fn patterns(patterns: &[PathBuf]) -> Vec<PathBuf> {
+        match patterns.iter().any(|pattern| crate::types::negation(pattern).0) {
+            true => patterns.to_vec(),
+            false => sorted(patterns),
+        }
+    }
+    let edges = |node: &Node| -> BTreeMap<String, (crate::types::EdgeKind, Vec<PathBuf>)> {
+        node.dependency_edges.iter().map(|(dep, edge)| (dep.clone(), (edge.kind, sorted(&edge.paths)))).collect()
+    };
+    [
+        ("path", a.path == b.path),
+        ("included_paths", patterns(&a.included_paths) == patterns(&b.included_paths)),
+        ("excluded_paths", patterns(&a.excluded_paths) == patterns(&b.excluded_paths)),
+        ("cascade_excluded_paths", patterns(&a.cascade_excluded_paths) == patterns(&b.cascade_excluded_paths)),
+        ("dependencies", sorted(&a.dependencies) == sorted(&b.dependencies)),
+        ("dependency_edges", edges(a) == edges(b)),
+        ("external_dependencies", sorted(&a.external_dependencies) == sorted(&b.external_dependencies)),
+        ("tags", sorted(&a.tags) == sorted(&b.tags)),
+        ("metadata", a.metadata == b.metadata),
+    ]
+        .into_iter()
+        .filter(|(_, equal)| !equal)
+        .map(|(field, _)| field)
+        .collect()
+}
+
+fn json_len<T: Serialize + ?Sized>(value: &T) -> serde_json::Result<usize> {
+    serde_json::to_string(value).map(|json| json.len())
+}
+
+fn compact_metadata(metadata: Option<&serde_json::Value>, keep: &[String]) -> Option<serde_json::Value> {
+    let kept: serde_json::Map<String, serde_json::Value> = metadata?.as_object()?.iter()
+        .filter(|(key, _)| keep.contains(key))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    (!kept.is_empty()).then_some(serde_json::Value::Object(kept))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testkit::FixtureDir;
+
+    use super::*;
+
+    #[test]
+    fn test_compact() {
+        let mut node = Node::new(
+            "api".to_string(),
+            PathBuf::from("api"),
+            vec![PathBuf::from("src/**")],
+            vec![PathBuf::from("src/**/*.md")],
+            vec![],
+            Some(serde_json::json!({"team": "payments", "tasks": {"build": "cargo build"}})),
+        ).unwrap();
+        node.tags = vec!["backend".to_string()];
+        let graph = DependencyGraph::new(vec![node], false).unwrap();
+
+        let compacted = compact(&graph, &["team".to_string()]).unwrap();
+        let node = compacted.get_node("api").unwrap();
+        assert_eq!(node.metadata, Some(serde_json::json!({"team": "payments"})));
+        assert_eq!(node.tags, vec!["backend"]);
+        assert!(node.includes_path(&PathBuf::from("api/src/main.rs")));
+        assert!(!node.includes_path(&PathBuf::from("api/src/README.md")));
+
+        let compacted = compact(&graph, &[]).unwrap();
+        assert_eq!(compacted.get_node("api").unwrap().metadata, None);
+        assert_eq!(compacted.info().created_at, graph.info().created_at);
+        // Tags are kept for --filter-tag and --exclude-tag
+        assert_eq!(compacted.info().features, vec!["tags"]);
+    }
+
+    #[test]
+    fn test_inspect() {
+        let graph = DependencyGraph::new(vec![
+            Node::new("a".to_string(), PathBuf::from("a"), vec![PathBuf::from("**")], vec![], vec![], None).unwrap(),
+            Node::new("b".to_string(), PathBuf::from("b"), vec![PathBuf::from("**")], vec![], vec!["a".to_string()], None).unwrap(),
+        ], false).unwrap();
+        let dir = FixtureDir::new().file("graph.json", serde_json::to_string(&graph).unwrap());
+
+        let report = inspect(&dir.join("graph.json")).unwrap();
+        assert_eq!((report.nodes, report.edges), (2, 1));
+        assert_eq!(report.info.tool_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(report.size.total_bytes as u64, report.file_bytes);
+        // `["**"]` and `[]`, twice
+        assert_eq!(report.size.pattern_bytes, 2 * (6 + 2 + 2));
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut web = Node::new("web".to_string(), PathBuf::from("apps/web"), vec![PathBuf::from("**")], vec![], vec![], None).unwrap();
+        web.external_dependencies = vec![
+            ExternalDependency { repo: "platform".to_string(), name: "auth-sdk".to_string() },
+            ExternalDependency { repo: "billing".to_string(), name: "client".to_string() },
+        ];
+        let artifact = |repo: &str, nodes: Vec<Node>| {
+            let mut graph = DependencyGraph::new(nodes, false).unwrap();
+            graph.set_info(ArtifactInfo { repo: Some(repo.to_string()), ..ArtifactInfo::current() });
+            (PathBuf::from(format!("{}.json", repo)), graph)
+        };
+        let sdk = Node::new("auth-sdk".to_string(), PathBuf::from("sdk"), vec![PathBuf::from("**")], vec![], vec![], None).unwrap();
+        let artifacts = [artifact("shop", vec![web]), artifact("platform", vec![sdk])];
+
+        let (merged, unresolved) = merge(&artifacts).unwrap();
+        assert_eq!(merged.get_affected_nodes(&[PathBuf::from("platform/sdk/src/lib.rs")]), vec!["platform:auth-sdk", "shop:web"]);
+        assert_eq!(unresolved, vec![UnresolvedDependency { node: "shop:web".to_string(), repo: "billing".to_string(), name: "client".to_string() }]);
+        assert_eq!(merged.get_node("shop:web").unwrap().external_dependencies.len(), 1);
+        assert_eq!(merged.info().optional_features, vec!["generations", "external_dependencies"]);
+        assert_eq!(merged.get_node("shop:web").unwrap().generation, Some(1));
+
+        let (path, mut unnamed) = artifact("shop", vec![]);
+        unnamed.set_info(ArtifactInfo::current());
+        assert!(matches!(merge(&[(path, unnamed)]), Err(MergeError::UnnamedRepository(_))));
+    }
+
+    #[test]
+    fn test_compare() {
+        let node = |name: &str, deps: Vec<&str>, include: Vec<&str>| {
+            Node::new(
+                name.to_string(),
+                PathBuf::from(name),
+                include.into_iter().map(PathBuf::from).collect(),
+                vec![],
+                deps.into_iter().map(String::from).collect(),
+                None,
+            ).unwrap()
+        };
+        let a = DependencyGraph::new(vec![
+            node("core", vec![], vec!["src/**", "Cargo.toml"]),
+            node("api", vec!["core"], vec!["**"]),
+            node("cli", vec!["core"], vec!["**"]),
+        ], false).unwrap();
+        let mut reordered = node("api", vec!["core"], vec!["**"]);
+        reordered.manifest = Some(PathBuf::from("api/dependencies.toml"));
+        let b = DependencyGraph::new(vec![
+            node("cli", vec!["core"], vec!["**"]),
+            node("core", vec![], vec!["Cargo.toml", "src/**"]),
+            reordered,
+        ], false).unwrap();
+        assert!(compare(&a, &b).is_empty());
+
+        let mut tagged = node("api", vec![], vec!["**"]);
+        tagged.tags = vec!["backend".to_string()];
+        let c = DependencyGraph::new(vec![
+            node("core", vec![], vec!["src/**", "Cargo.toml"]),
+            tagged,
+            node("web", vec![], vec!["**"]),
+        ], false).unwrap();
+        assert_eq!(compare(&a, &c), vec![
+            NodeDifference { node: "api".to_string(), change: NodeChange::Changed, fields: vec!["dependencies", "tags"] },
+            NodeDifference { node: "cli".to_string(), change: NodeChange::Removed, fields: vec![] },
+            NodeDifference { node: "web".to_string(), change: NodeChange::Added, fields: vec![] },
+        ]);
+    }
+}
@@ -0,0 +1,105 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::types::DependencyGraph;
+
+#[derive(Debug, thiserror::Error)]
+pub enum BadgeError {
+    #[error("Unable to write {0}: {1}")]
+    Write(PathBuf, std::io::Error),
+    #[error("Error serializing: {0}")]
+    Serialize(#[from] serde_json::Error),
+}
+
+/// A shields.io endpoint badge, see <https://shields.io/badges/endpoint-badge>.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Badge {
+    pub schema_version: u8,
+    pub label: String,
+    pub message: String,
+    pub color: String,
+}
+
+impl Badge {
+    fn new(label: &str, message: String, color: &str) -> Self {
+        Badge { schema_version: 1, label: label.to_string(), message, color: color.to_string() }
+    }
+}
+
+/// Computes the graph health badges, by file stem: the number of nodes, the largest number
+/// of dependents of a node, and the share of nodes with `metadata.owners`.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+/// * `max_dependents` - The configured blast radius threshold, which colors its badge
+///
+/// ### Returns
+/// * `BTreeMap<&str, Badge>` - The badges of `nodes`, `blast-radius` and `ownership`
+pub fn badges(graph: &DependencyGraph, max_dependents: Option<usize>) -> BTreeMap<&'static str, Badge> {
+    let nodes = graph.get_all_nodes();
+    let max_radius = super::blast_radius(graph, None).first().map_or(0, |radius| radius.dependent_count);
+    let owned = nodes.iter().filter(|node| !node.owners().is_empty()).count();
+    let ownership = if nodes.is_empty() { 0.0 } else { owned as f64 * 100.0 / nodes.len() as f64 };
+
+    let radius_color = match max_dependents {
+        Some(max) if max_radius > max => "red",
+        Some(_) => "brightgreen",
+        None => "blue",
+    };
+    let ownership_color = match ownership {
+        o if o >= 90.0 => "brightgreen",
+        o if o >= 50.0 => "yellow",
+        _ => "red",
+    };
+
+    BTreeMap::from([
+        ("nodes", Badge::new("nodes", nodes.len().to_string(), "blue")),
+        ("blast-radius", Badge::new("max blast radius", max_radius.to_string(), radius_color)),
+        ("ownership", Badge::new("ownership", format!("{:.0}%", ownership.floor()), ownership_color)),
+    ])
+}
+
+/// Writes each badge to `<dir>/<name>.json`, replacing the previous ones atomically.
+pub fn write_badges(badges: &BTreeMap<&str, Badge>, dir: &Path) -> Result<(), BadgeError> {
+    fs::create_dir_all(dir).map_err(|e| BadgeError::Write(dir.to_path_buf(), e))?;
+    for (name, badge) in badges {
+        let path = dir.join(format!("{}.json", name));
+        super::write_atomic(&path, serde_json::to_string(badge)?).map_err(|e| BadgeError::Write(path, e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::types::Node;
+
+    use super::*;
+
+    #[test]
+    fn test_badges() {
+        let node = |name: &str, deps: Vec<&str>, owners: Option<serde_json::Value>| Node::new(
+            name.to_string(), PathBuf::from(name), vec![PathBuf::from("**")], vec![],
+            deps.into_iter().map(String::from).collect(), owners.map(|o| serde_json::json!({ "owners": o })),
+        ).unwrap();
+        let graph = DependencyGraph::new(vec![
+            node("core", vec![], Some(serde_json::json!("@org/platform"))),
+            node("api", vec!["core"], None),
+            node("web", vec!["api"], None),
+        ], false).unwrap();
+
+        let badges = badges(&graph, Some(1));
+        assert_eq!(badges["nodes"].message, "3");
+        assert_eq!(badges["blast-radius"], Badge::new("max blast radius", "2".to_string(), "red"));
+        assert_eq!(badges["ownership"], Badge::new("ownership", "33%".to_string(), "red"));
+        assert_eq!(
+            serde_json::to_string(&badges["nodes"]).unwrap(),
+            r#"{"schemaVersion":1,"label":"nodes","message":"3","color":"blue"}"#,
+        );
+    }
+}
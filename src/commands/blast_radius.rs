@@ -0,0 +1,89 @@
+use serde::Serialize;
+
+use crate::types::DependencyGraph;
+
+/// Everything a change to a single node would affect.
+#[derive(Debug, Clone, Serialize)]
+pub struct BlastRadius {
+    pub node: String,
+    /// The number of direct and indirect dependents.
+    pub dependent_count: usize,
+    /// The direct and indirect dependents, sorted by name.
+    pub dependents: Vec<String>,
+    /// The number of include patterns of the node and its dependents, i.e. how much of the
+    /// repository a change to the node reaches.
+    pub surface: usize,
+    /// True when the node has more dependents than the configured maximum.
+    pub exceeds_threshold: bool,
+}
+
+/// Computes the blast radius of every node, largest first.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+/// * `max_dependents` - The most dependents a node may have before it's flagged
+///
+/// ### Returns
+/// * `Vec<BlastRadius>` - One entry per node, by decreasing number of dependents
+pub fn blast_radius(graph: &DependencyGraph, max_dependents: Option<usize>) -> Vec<BlastRadius> {
+    let mut radii: Vec<BlastRadius> = graph.get_all_nodes().into_iter()
+        .map(|node| {
+            let dependents = graph.get_dependents(&node.name);
+            let surface = node.included_paths.len()
+                + dependents.iter().map(|dependent| dependent.included_paths.len()).sum::<usize>();
+            let mut dependents: Vec<String> = dependents.into_iter().map(|dependent| dependent.name).collect();
+            dependents.sort();
+
+            BlastRadius {
+                node: node.name.clone(),
+                dependent_count: dependents.len(),
+                exceeds_threshold: max_dependents.is_some_and(|max| dependents.len() > max),
+                dependents,
+                surface,
+            }
+        })
+        .collect();
+    radii.sort_by(|a, b| b.dependent_count.cmp(&a.dependent_count).then_with(|| a.node.cmp(&b.node)));
+    radii
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::types::Node;
+
+    use super::*;
+
+    fn node(name: &str, deps: Vec<&str>) -> Node {
+        Node::new(
+            name.to_string(),
+            PathBuf::from(name),
+            vec![PathBuf::from("src/**"), PathBuf::from("Cargo.toml")],
+            vec![],
+            deps.into_iter().map(String::from).collect(),
+            None,
+        ).unwrap()
+    }
+
+    #[test]
+    fn test_blast_radius() {
+        let graph = DependencyGraph::new(vec![
+            node("core", vec![]),
+            node("api", vec!["core"]),
+            node("web", vec!["api"]),
+            node("cli", vec!["core"]),
+        ], false).unwrap();
+
+        let radii = blast_radius(&graph, Some(1));
+        let summary: Vec<_> = radii.iter().map(|r| (r.node.as_str(), r.dependent_count, r.surface, r.exceeds_threshold)).collect();
+        assert_eq!(summary, vec![
+            ("core", 3, 8, true),
+            ("api", 1, 4, false),
+            ("cli", 0, 2, false),
+            ("web", 0, 2, false),
+        ]);
+        assert_eq!(radii[0].dependents, vec!["api", "cli", "web"]);
+        assert!(blast_radius(&graph, None).iter().all(|r| !r.exceeds_threshold));
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use clap::Args;
+
+use crate::types::AffectedReason;
+
+/// Limits on how many nodes a change may affect.
+#[derive(Args)]
+pub struct BudgetArgs {
+    /// Exit with a non-zero status when more than N nodes are affected.
+    #[arg(long, value_name = "N")]
+    pub max_affected: Option<usize>,
+    /// Exit with a non-zero status when more than PERCENT of the nodes are affected.
+    #[arg(long, value_name = "PERCENT")]
+    pub max_affected_percent: Option<f64>,
+}
+
+/// A change affecting more nodes than the budget allows.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BudgetExceeded {
+    pub affected: usize,
+    pub total: usize,
+    /// The smallest of the budgets, in nodes.
+    pub budget: usize,
+    /// The changed nodes with how many affected nodes they account for (themselves included),
+    /// largest first.
+    pub causes: Vec<(String, usize)>,
+}
+
+impl BudgetArgs {
+    /// Checks the affected nodes against the budgets.
+    ///
+    /// ### Arguments
+    /// * `total` - The number of nodes in the whole graph
+    /// * `affected` - The affected nodes, after filtering
+    /// * `reasons` - Why each node is affected, as computed by `get_affected_reasons`
+    ///
+    /// ### Returns
+    /// * `Option<BudgetExceeded>` - The report when a budget is exceeded
+    pub fn check(&self, total: usize, affected: &[String], reasons: &BTreeMap<String, AffectedReason>) -> Option<BudgetExceeded> {
+        let from_percent = self.max_affected_percent.map(|percent| (total as f64 * percent / 100.0).floor() as usize);
+        let budget = [self.max_affected, from_percent].into_iter().flatten().min()?;
+        if affected.len() <= budget {
+            return None;
+        }
+
+        let mut causes: BTreeMap<String, usize> = BTreeMap::new();
+        for name in affected {
+            let cause = match reasons.get(name) {
+                Some(AffectedReason::Dependency { via: cause } | AffectedReason::Upstream { of: cause }) => cause.clone(),
+                _ => name.clone(),
+            };
+            *causes.entry(cause).or_default() += 1;
+        }
+        let mut causes: Vec<(String, usize)> = causes.into_iter().collect();
+        causes.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        Some(BudgetExceeded { affected: affected.len(), total, budget, causes })
+    }
+}
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} of {} nodes are affected ({:.0}%), over the budget of {}. Consider splitting the change.",
+            self.affected,
+            self.total,
+            self.affected as f64 * 100.0 / self.total.max(1) as f64,
+            self.budget,
+        )?;
+        writeln!(f, "Affected nodes per changed node:")?;
+        for (node, count) in &self.causes {
+            writeln!(f, "  {}: {}", node, count)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::types::{DependencyGraph, Node};
+
+    use super::*;
+
+    fn node(name: &str, deps: Vec<&str>) -> Node {
+        Node::new(
+            name.to_string(),
+            PathBuf::from(name),
+            vec![PathBuf::from("**")],
+            vec![],
+            deps.into_iter().map(String::from).collect(),
+            None,
+        ).unwrap()
+    }
+
+    #[test]
+    fn test_check() {
+        let graph = DependencyGraph::new(vec![
+            node("core", vec![]),
+            node("api", vec!["core"]),
+            node("web", vec!["api"]),
+            node("docs", vec![]),
+        ], false).unwrap();
+        let reasons = graph.get_affected_reasons(&[PathBuf::from("core/lib.rs"), PathBuf::from("docs/a.md")]);
+        let affected: Vec<String> = reasons.keys().cloned().collect();
+
+        let budget = |max_affected, max_affected_percent| BudgetArgs { max_affected, max_affected_percent };
+        assert_eq!(budget(None, None).check(4, &affected, &reasons), None);
+        assert_eq!(budget(Some(4), None).check(4, &affected, &reasons), None);
+
+        let exceeded = budget(Some(3), Some(50.0)).check(4, &affected, &reasons).unwrap();
+        assert_eq!(exceeded.budget, 2);
+        assert_eq!(exceeded.causes, vec![("core".to_string(), 3), ("docs".to_string(), 1)]);
+        assert!(exceeded.to_string().starts_with("4 of 4 nodes are affected (100%), over the budget of 2."));
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::types::{AffectedReason, DependencyGraph};
+
+use super::git::{self, Commit, GitError};
+use super::release::{commit_bump, conventional_type, Bump};
+
+/// The sections of a fragment, in order, with the commit types they list. Commits of other
+/// conventional types (`chore`, `docs`, `test`...) are left out, commits that aren't
+/// conventional go to the last one.
+const SECTIONS: [(&str, &[&str]); 5] = [
+    ("Breaking changes", &[]),
+    ("Features", &["feat"]),
+    ("Fixes", &["fix", "revert"]),
+    ("Performance", &["perf"]),
+    ("Other changes", &[]),
+];
+
+/// Renders a markdown changelog fragment for every node whose own files the commits of
+/// `since..until` changed. Must run from the directory `prepare` ran in.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+/// * `since` - The commit to start after, usually the last release tag
+/// * `until` - The last commit of the range
+///
+/// ### Returns
+/// * `BTreeMap<String, String>` - The fragment of each changed node
+pub fn changelog(graph: &DependencyGraph, since: &str, until: &str) -> Result<BTreeMap<String, String>, GitError> {
+    Ok(fragments(graph, &git::commits(since, until)?))
+}
+
+fn fragments(graph: &DependencyGraph, commits: &[Commit]) -> BTreeMap<String, String> {
+    let mut entries: BTreeMap<String, [Vec<String>; SECTIONS.len()]> = BTreeMap::new();
+    for commit in commits {
+        let Some(section) = section(&commit.message) else { continue };
+        let subject = commit.message.lines().next().unwrap_or_default();
+        let description = match conventional_type(subject) {
+            Some(_) => subject.split_once(": ").map_or(subject, |(_, description)| description),
+            None => subject,
+        };
+        let entry = format!("- {} ({})", description.trim(), &commit.hash[..commit.hash.len().min(7)]);
+        for (node, reason) in graph.get_affected_reasons(&commit.files) {
+            if matches!(reason, AffectedReason::Changed { .. }) {
+                entries.entry(node).or_default()[section].push(entry.clone());
+            }
+        }
+    }
+
+    entries.into_iter()
+        .map(|(node, sections)| {
+            let mut fragment = format!("## {}\n", node);
+            for ((title, _), entries) in SECTIONS.iter().zip(sections) {
+                if !entries.is_empty() {
+                    fragment.push_str(&format!("\n### {}\n{}\n", title, entries.join("\n")));
+                }
+            }
+            (node, fragment)
+        })
+        .collect()
+}
+
+/// The index in [`SECTIONS`] of a commit, or `None` for the types left out of changelogs.
+fn section(message: &str) -> Option<usize> {
+    let Some((kind, _)) = conventional_type(message) else { return Some(SECTIONS.len() - 1) };
+    if commit_bump(message) == Some(Bump::Major) {
+        return Some(0);
+    }
+    let kind = kind.to_lowercase();
+    SECTIONS.iter().position(|(_, kinds)| kinds.contains(&kind.as_str()))
+}
+
+/// Writes each fragment to `<dir>/<node>.md`, characters other than letters, digits, `-`, `_`
+/// and `.` in the node name replaced by `-`.
+///
+/// ### Returns
+/// * `Vec<PathBuf>` - The files written
+pub fn write_fragments(fragments: &BTreeMap<String, String>, dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    fs::create_dir_all(dir)?;
+    let mut written = Vec::new();
+    for (node, fragment) in fragments {
+        let name: String = node.chars()
+            .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') { c } else { '-' })
+            .collect();
+        let path = dir.join(format!("{}.md", name.trim_start_matches(['-', '.'])));
+        super::write_atomic(&path, fragment)?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::Node;
+
+    use super::*;
+
+    fn node(name: &str, deps: Vec<&str>) -> Node {
+        Node::new(
+            name.to_string(),
+            PathBuf::from(name),
+            vec![PathBuf::from("**")],
+            vec![],
+            deps.into_iter().map(String::from).collect(),
+            None,
+        ).unwrap()
+    }
+
+    #[test]
+    fn test_fragments() {
+        let graph = DependencyGraph::new(vec![node("core", vec![]), node("api", vec!["core"])], false).unwrap();
+        let commit = |hash: &str, message: &str, files: &[&str]| Commit {
+            hash: hash.to_string(),
+            timestamp: 0,
+            message: message.to_string(),
+            files: files.iter().map(PathBuf::from).collect(),
+        };
+        let commits = [
+            commit("1111111aaa", "feat(api): pagination", &["api/main.rs"]),
+            commit("2222222bbb", "fix: null check\n\nCloses #3", &["core/lib.rs", "api/main.rs"]),
+            commit("3333333ccc", "chore: bump deps", &["core/Cargo.toml"]),
+            commit("4444444ddd", "Tidy up", &["core/lib.rs"]),
+            commit("5555555eee", "refactor(core)!: rename Client", &["core/lib.rs"]),
+        ];
+
+        let fragments = fragments(&graph, &commits);
+        assert_eq!(fragments.len(), 2);
+        assert_eq!(fragments["api"], "## api\n\n### Features\n- pagination (1111111)\n\n### Fixes\n- null check (2222222)\n");
+        assert_eq!(
+            fragments["core"],
+            "## core\n\n### Breaking changes\n- rename Client (5555555)\n\n### Fixes\n- null check (2222222)\n\n### Other changes\n- Tidy up (4444444)\n",
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::types::{AffectedReason, DependencyGraph};
+
+/// Why an affected node is affected, by kind of change.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Classification {
+    pub node: String,
+    /// Whether every file triggering the node falls in a soft category, so lighter jobs do.
+    pub soft: bool,
+    /// The soft categories of the triggering files.
+    pub categories: BTreeSet<String>,
+    /// The triggering files in no soft category.
+    pub hard_files: Vec<PathBuf>,
+}
+
+/// Classifies the changes affecting each node. The files triggering a node are its own changed
+/// files and the changed files of its dependencies that cascade to it.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+/// * `changed_files` - The list of files that have changed
+/// * `categories` - The glob patterns of each soft category, from `[classify]` of the config
+///
+/// ### Returns
+/// * `Vec<Classification>` - The affected nodes, sorted by name
+pub fn classify(
+    graph: &DependencyGraph,
+    changed_files: &[PathBuf],
+    categories: &BTreeMap<String, Vec<String>>,
+) -> Result<Vec<Classification>, glob::PatternError> {
+    let categories: Vec<(&String, Vec<glob::Pattern>)> = categories.iter()
+        .map(|(name, patterns)| Ok((name, patterns.iter().map(|p| glob::Pattern::new(p)).collect::<Result<_, _>>()?)))
+        .collect::<Result<_, glob::PatternError>>()?;
+    let category_of = |file: &PathBuf| {
+        categories.iter()
+            .find(|(_, patterns)| patterns.iter().any(|pattern| pattern.matches_path(file)))
+            .map(|(name, _)| (*name).clone())
+    };
+
+    let reasons = graph.get_affected_reasons(changed_files);
+    let mut triggering: BTreeMap<&str, BTreeSet<&PathBuf>> = reasons.keys().map(|name| (name.as_str(), BTreeSet::new())).collect();
+    for (name, reason) in &reasons {
+        let AffectedReason::Changed { files } = reason else { continue };
+        let node = graph.get_node(name).expect("affected nodes come from the graph");
+        triggering.entry(name).or_default().extend(files);
+        let cascading: Vec<&PathBuf> = files.iter().filter(|file| node.cascades_path(file)).collect();
+        if cascading.is_empty() {
+            continue;
+        }
+        for dependent in graph.get_dependents(name) {
+            if let Some(files) = triggering.get_mut(dependent.name.as_str()) {
+                files.extend(&cascading);
+            }
+        }
+    }
+
+    Ok(triggering.into_iter()
+        .map(|(node, files)| {
+            let mut categories = BTreeSet::new();
+            let mut hard_files = Vec::new();
+            for file in files {
+                match category_of(file) {
+                    Some(category) => {
+                        categories.insert(category);
+                    }
+                    None => hard_files.push(file.clone()),
+                }
+            }
+            Classification { node: node.to_string(), soft: hard_files.is_empty(), categories, hard_files }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::{Config, Node};
+
+    use super::*;
+
+    fn node(name: &str, deps: Vec<&str>) -> Node {
+        Node::new(
+            name.to_string(),
+            PathBuf::from(name),
+            vec![PathBuf::from("**")],
+            vec![],
+            deps.into_iter().map(String::from).collect(),
+            None,
+        ).unwrap()
+    }
+
+    #[test]
+    fn test_classify() {
+        let graph = DependencyGraph::new(vec![
+            node("core", vec![]),
+            node("api", vec!["core"]),
+            node("web", vec!["api"]),
+        ], false).unwrap();
+        let categories = Config::default().classify.categories;
+
+        let files = [PathBuf::from("core/README.md"), PathBuf::from("api/src/main.rs")];
+        let classified = classify(&graph, &files, &categories).unwrap();
+        let summary: Vec<_> = classified.iter().map(|c| (c.node.as_str(), c.soft)).collect();
+        assert_eq!(summary, vec![("api", false), ("core", true), ("web", false)]);
+        assert_eq!(classified[1].categories, BTreeSet::from(["docs".to_string()]));
+        assert_eq!(classified[2].hard_files, vec![PathBuf::from("api/src/main.rs")]);
+
+        let files = [PathBuf::from("core/docs/guide.rst"), PathBuf::from(".github/workflows/ci.yml")];
+        let classified = classify(&graph, &files, &categories).unwrap();
+        assert!(classified.iter().all(|c| c.soft));
+        assert_eq!(classified.len(), 3);
+    }
+}
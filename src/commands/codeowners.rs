@@ -0,0 +1,226 @@
+use std::path::{Path, PathBuf};
+
+use clap::Subcommand;
+
+use crate::types::{negation, DependencyGraph, Node};
+
+/// The `codeowners` subcommands.
+#[derive(Subcommand)]
+pub enum CodeownersCommands {
+    /// Prints a CODEOWNERS file with one rule per include pattern of every node that
+    /// has `metadata.owners`. Exclude patterns are emitted as rules without owners.
+    Generate {
+        /// The JSON artifact file path containing the previously prepared dependency graph
+        /// from the `prepare` command
+        #[arg(short, long, value_name = "FILE")]
+        graph_artifact_path: PathBuf,
+        /// Instead of printing the file, verify that the given CODEOWNERS file is up to
+        /// date. Exits with a non-zero status and prints a diff if it isn't.
+        #[arg(long, value_name = "FILE")]
+        check: Option<PathBuf>,
+    },
+}
+
+const HEADER: &str = "# Generated by `dependency-cascade codeowners generate`. Do not edit by hand,\n\
+                      # change the `owners` metadata of the dependency toml files instead.\n";
+
+/// Renders the CODEOWNERS file for the given graph.
+///
+/// Nodes are ordered from the shallowest to the deepest path so that rules of nested
+/// nodes come later and, following CODEOWNERS' last-match-wins semantics, take precedence.
+pub fn generate(graph: &DependencyGraph) -> String {
+    let mut nodes: Vec<&Node> = graph.get_all_nodes()
+        .into_iter()
+        .filter(|node| !node.owners().is_empty())
+        .collect();
+    nodes.sort_by(|a, b| {
+        a.path.components().count().cmp(&b.path.components().count())
+            .then_with(|| a.path.cmp(&b.path))
+    });
+
+    let mut out = String::from(HEADER);
+    for node in nodes {
+        out.push_str(&format!("\n# {}\n", node.name));
+        let owners = node.owners().join(" ");
+        // CODEOWNERS rules also let the last match win: a negated include disowns its files
+        // like an exclude, a negated exclude gives them back
+        for (pattern, owned) in node.included_paths.iter().map(|p| (p, true)).chain(node.excluded_paths.iter().map(|p| (p, false))) {
+            let (negated, pattern) = negation(pattern);
+            match owned != negated {
+                true => out.push_str(&format!("{} {}\n", codeowners_pattern(&node.path, pattern), owners)),
+                false => out.push_str(&format!("{}\n", codeowners_pattern(&node.path, pattern))),
+            }
+        }
+    }
+    out
+}
+
+/// Anchors a node pattern to the repository root, as CODEOWNERS patterns are otherwise
+/// matched at any depth.
+fn codeowners_pattern(node_path: &Path, pattern: &Path) -> String {
+    let full = node_path.join(pattern).to_string_lossy().replace('\\', "/");
+    let escaped = full.replace(' ', "\\ ");
+    format!("/{}", escaped.trim_start_matches('/'))
+}
+
+/// A CODEOWNERS rule: files matching the pattern are owned by the owners.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub pattern: String,
+    pub owners: Vec<String>,
+}
+
+/// Parses a CODEOWNERS file, skipping comments and blank lines. Spaces in patterns are
+/// escaped with a backslash.
+pub fn parse(content: &str) -> Vec<Rule> {
+    content.lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                return None;
+            }
+            // The pattern ends at the first unescaped whitespace
+            let mut end = line.len();
+            let mut escaped = false;
+            for (i, c) in line.char_indices() {
+                match c {
+                    '\\' if !escaped => escaped = true,
+                    c if c.is_whitespace() && !escaped => {
+                        end = i;
+                        break;
+                    }
+                    _ => escaped = false,
+                }
+            }
+            let (pattern, owners) = line.split_at(end);
+            Some(Rule {
+                pattern: pattern.replace("\\ ", " "),
+                owners: owners.split_whitespace().take_while(|o| !o.starts_with('#')).map(String::from).collect(),
+            })
+        })
+        .collect()
+}
+
+/// Sets `metadata.owners` on the nodes that don't have owners yet, from the CODEOWNERS
+/// rules overlapping their directory: the last rule matching the directory itself, as it
+/// owns the node's files, followed by the rules for paths inside the directory.
+///
+/// ### Returns
+/// * `usize` - The number of nodes that got owners
+pub fn import(graph: &mut DependencyGraph, rules: &[Rule]) -> usize {
+    let resolved: Vec<(String, Vec<String>, Option<serde_json::Value>)> = graph.get_all_nodes().into_iter()
+        .filter(|node| node.owners().is_empty())
+        .map(|node| (node.name.clone(), resolve_owners(&node.path, rules), node.metadata.clone()))
+        .filter(|(_, owners, _)| !owners.is_empty())
+        .collect();
+
+    let mut imported = 0;
+    for (name, owners, metadata) in resolved {
+        let mut metadata = metadata.unwrap_or_else(|| serde_json::json!({}));
+        let Some(object) = metadata.as_object_mut() else { continue };
+        object.insert("owners".to_string(), serde_json::json!(owners));
+        graph.set_node_metadata(&name, Some(metadata));
+        imported += 1;
+    }
+    log::info!("Imported owners from CODEOWNERS for {} nodes", imported);
+    imported
+}
+
+fn resolve_owners(node_path: &Path, rules: &[Rule]) -> Vec<String> {
+    let dir = node_path.to_string_lossy().replace('\\', "/");
+    let dir = dir.trim_matches('/');
+    let mut owners: Vec<String> = rules.iter().rev()
+        .find(|rule| covers(&rule.pattern, dir))
+        .map(|rule| rule.owners.clone())
+        .unwrap_or_default();
+    for rule in rules.iter().filter(|rule| !dir.is_empty() && rule.pattern.trim_start_matches('/').starts_with(&format!("{}/", dir))) {
+        for owner in &rule.owners {
+            if !owners.contains(owner) {
+                owners.push(owner.clone());
+            }
+        }
+    }
+    owners
+}
+
+/// Whether the pattern matches the directory or one of its parents, following CODEOWNERS'
+/// gitignore-like rules: patterns with a leading or inner slash are anchored to the root,
+/// others match at any depth.
+fn covers(pattern: &str, dir: &str) -> bool {
+    let trimmed = pattern.trim_end_matches('/');
+    let anchored = trimmed.contains('/');
+    let glob = match (anchored, trimmed.trim_start_matches('/')) {
+        (_, "") => return true,
+        (true, rest) => rest.to_string(),
+        (false, rest) => format!("**/{}", rest),
+    };
+    let Ok(glob) = glob::Pattern::new(&glob) else { return false };
+    let options = glob::MatchOptions { require_literal_separator: true, ..Default::default() };
+
+    let mut candidate = Path::new(dir);
+    while !candidate.as_os_str().is_empty() {
+        if glob.matches_path_with(candidate, options) {
+            return true;
+        }
+        candidate = candidate.parent().unwrap_or(Path::new(""));
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(name: &str, path: &str, owners: serde_json::Value) -> Node {
+        Node::new(
+            name.to_string(),
+            PathBuf::from(path),
+            vec![PathBuf::from("src/**")],
+            vec![PathBuf::from("src/generated/**")],
+            vec![],
+            Some(serde_json::json!({ "owners": owners })),
+        ).unwrap()
+    }
+
+    #[test]
+    fn test_generate() {
+        let graph = DependencyGraph::new(vec![
+            node("nested", "services/api/nested", serde_json::json!("@org/nested")),
+            node("api", "services/api", serde_json::json!(["@org/api", "@alice"])),
+            node("orphan", "orphan", serde_json::json!([])),
+        ], false).unwrap();
+
+        let codeowners = generate(&graph);
+
+        assert!(codeowners.contains("/services/api/src/** @org/api @alice\n/services/api/src/generated/**\n"));
+        assert!(codeowners.contains("/services/api/nested/src/** @org/nested\n"));
+        assert!(codeowners.find("@org/api").unwrap() < codeowners.find("@org/nested").unwrap());
+        assert!(!codeowners.contains("orphan"));
+    }
+
+    #[test]
+    fn test_import() {
+        let rules = parse("# Owners\n\
+            * @org/everyone\n\
+            /services/ @org/backend\n\
+            /services/api/src/payments/ @org/payments # billing\n\
+            docs/ @org/writers\n\
+            /my\\ dir/ @org/spaces\n");
+        assert_eq!(rules[4].pattern, "/my dir/");
+        assert_eq!(rules[2].owners, vec!["@org/payments"]);
+
+        let bare = |name: &str, path: &str| Node::new(name.to_string(), PathBuf::from(path), vec![PathBuf::from("**")], vec![], vec![], None).unwrap();
+        let mut graph = DependencyGraph::new(vec![
+            bare("api", "services/api"),
+            bare("guide", "apps/web/docs"),
+            bare("web", "apps/web"),
+            node("owned", "services/owned", serde_json::json!("@org/owned")),
+        ], false).unwrap();
+
+        assert_eq!(import(&mut graph, &rules), 3);
+        assert_eq!(graph.get_node("api").unwrap().owners(), vec!["@org/backend", "@org/payments"]);
+        assert_eq!(graph.get_node("guide").unwrap().owners(), vec!["@org/writers"]);
+        assert_eq!(graph.get_node("web").unwrap().owners(), vec!["@org/everyone"]);
+        assert_eq!(graph.get_node("owned").unwrap().owners(), vec!["@org/owned"]);
+    }
+}
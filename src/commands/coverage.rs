@@ -0,0 +1,104 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use walkdir::WalkDir;
+
+use crate::types::DependencyGraph;
+
+/// How many files of a directory are matched by at least one node.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DirectoryCoverage {
+    pub directory: String,
+    pub files: usize,
+    pub owned: usize,
+    /// `owned / files`, between 0 and 1.
+    pub ratio: f64,
+}
+
+/// How much of the working tree is owned by the graph.
+#[derive(Debug, Clone, Serialize)]
+pub struct Coverage {
+    pub files: usize,
+    pub owned: usize,
+    pub ratio: f64,
+    /// The coverage per top-level directory, sorted by name. Files at the root are under `.`.
+    pub directories: Vec<DirectoryCoverage>,
+}
+
+/// Walks the working tree and reports the share of files matched by at least one node.
+/// Must run from the directory `prepare` ran in. The `.git` directory is skipped.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+///
+/// ### Returns
+/// * `Coverage` - The overall coverage and its breakdown per top-level directory
+pub fn coverage(graph: &DependencyGraph) -> Result<Coverage, walkdir::Error> {
+    let mut files = Vec::new();
+    let walker = WalkDir::new(".").sort_by_file_name().into_iter()
+        .filter_entry(|entry| entry.file_name() != ".git");
+    for entry in walker {
+        let entry = entry?;
+        if entry.file_type().is_file() {
+            let path = entry.into_path();
+            files.push(path.strip_prefix("./").map(Path::to_path_buf).unwrap_or(path));
+        }
+    }
+    Ok(measure(graph, &files))
+}
+
+fn measure(graph: &DependencyGraph, files: &[PathBuf]) -> Coverage {
+    let nodes = graph.get_all_nodes();
+    let mut directories: BTreeMap<String, DirectoryCoverage> = BTreeMap::new();
+    for file in files {
+        let directory = match file.components().count() {
+            1 => ".".to_string(),
+            _ => file.components().next().map_or(String::new(), |c| c.as_os_str().to_string_lossy().to_string()),
+        };
+        let entry = directories.entry(directory.clone()).or_insert_with(|| DirectoryCoverage { directory, ..Default::default() });
+        entry.files += 1;
+        if nodes.iter().any(|node| node.includes_path(file)) {
+            entry.owned += 1;
+        }
+    }
+
+    let mut directories: Vec<DirectoryCoverage> = directories.into_values().collect();
+    for directory in &mut directories {
+        directory.ratio = ratio(directory.owned, directory.files);
+    }
+    let owned = directories.iter().map(|d| d.owned).sum();
+    Coverage { files: files.len(), owned, ratio: ratio(owned, files.len()), directories }
+}
+
+fn ratio(owned: usize, files: usize) -> f64 {
+    if files == 0 { 0.0 } else { owned as f64 / files as f64 }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::Node;
+
+    use super::*;
+
+    #[test]
+    fn test_measure() {
+        let node = Node::new(
+            "api".to_string(),
+            PathBuf::from("services/api"),
+            vec![PathBuf::from("**/*")],
+            vec![],
+            vec![],
+            None,
+        ).unwrap();
+        let graph = DependencyGraph::new(vec![node], false).unwrap();
+        let files: Vec<PathBuf> = ["README.md", "services/api/main.rs", "services/web/index.ts", "tools/x.sh"]
+            .iter().map(PathBuf::from).collect();
+
+        let coverage = measure(&graph, &files);
+        assert_eq!((coverage.files, coverage.owned, coverage.ratio), (4, 1, 0.25));
+        let directories: Vec<_> = coverage.directories.iter().map(|d| (d.directory.as_str(), d.files, d.owned)).collect();
+        assert_eq!(directories, vec![(".", 1, 0), ("services", 2, 1), ("tools", 1, 0)]);
+        assert_eq!(coverage.directories[1].ratio, 0.5);
+    }
+}
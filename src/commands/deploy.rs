@@ -0,0 +1,165 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::types::DependencyGraph;
+
+/// A node to deploy.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Deployment {
+    pub node: String,
+    /// False for the dependencies added by `--with-dependencies` that the change didn't affect.
+    pub affected: bool,
+    /// The node's `metadata.rollout.group`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+}
+
+/// Deployments that can run in parallel, once the previous waves are done.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DeployWave {
+    /// The position of the wave, starting at 0.
+    pub wave: usize,
+    /// Sorted by name.
+    pub nodes: Vec<Deployment>,
+}
+
+/// Orders the deployable nodes affected by the changed files in waves, every node in a later
+/// wave than the deployables it depends on, directly or through other nodes.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+/// * `changed_files` - The list of files that have changed
+/// * `tag` - The tag marking the deployable nodes
+/// * `with_dependencies` - Whether to also deploy the deployable dependencies of the affected
+///   deployables that the change didn't affect
+///
+/// ### Returns
+/// * `Vec<DeployWave>` - The waves, in deployment order
+pub fn deploy_order(graph: &DependencyGraph, changed_files: &[PathBuf], tag: &str, with_dependencies: bool) -> Vec<DeployWave> {
+    let affected: BTreeSet<String> = graph.get_affected_nodes(changed_files).into_iter()
+        .filter(|name| graph.get_node(name).is_some_and(|node| node.has_tag(tag)))
+        .collect();
+    let mut selected = affected.clone();
+    if with_dependencies {
+        for name in &affected {
+            selected.extend(graph.get_dependencies(name).into_iter().filter(|dep| dep.has_tag(tag)).map(|dep| dep.name));
+        }
+    }
+
+    // Nodes come after their dependencies, so theirs are already placed
+    let names: Vec<String> = selected.into_iter().collect();
+    let mut wave_of: HashMap<&str, usize> = HashMap::new();
+    let mut waves: Vec<DeployWave> = Vec::new();
+    for node in graph.toposorted_subset(&names) {
+        let wave = graph.get_dependencies(&node.name).iter()
+            .filter_map(|dep| wave_of.get(dep.name.as_str()).map(|wave| wave + 1))
+            .max()
+            .unwrap_or(0);
+        wave_of.insert(&node.name, wave);
+        if waves.len() <= wave {
+            waves.extend((waves.len()..=wave).map(|wave| DeployWave { wave, nodes: Vec::new() }));
+        }
+        waves[wave].nodes.push(Deployment {
+            node: node.name.clone(),
+            affected: affected.contains(&node.name),
+            group: node.rollout_group().map(String::from),
+        });
+    }
+    for wave in &mut waves {
+        wave.nodes.sort_by(|a, b| a.node.cmp(&b.node));
+    }
+    waves
+}
+
+/// The deployments of a rollout group, for progressive delivery.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RolloutGroup {
+    /// `None` for the nodes without `metadata.rollout.group`.
+    pub group: Option<String>,
+    /// The group's share of the waves, which keep their overall position.
+    pub waves: Vec<DeployWave>,
+}
+
+/// Splits deployment waves by rollout group. Groups are sorted by name, e.g. `wave-1` before
+/// `wave-2`, with the nodes without a group last.
+pub fn rollout_groups(waves: Vec<DeployWave>) -> Vec<RolloutGroup> {
+    let mut groups: BTreeMap<(bool, Option<String>), Vec<DeployWave>> = BTreeMap::new();
+    for wave in waves {
+        let mut by_group: BTreeMap<Option<String>, Vec<Deployment>> = BTreeMap::new();
+        for deployment in wave.nodes {
+            by_group.entry(deployment.group.clone()).or_default().push(deployment);
+        }
+        for (group, nodes) in by_group {
+            groups.entry((group.is_none(), group)).or_default().push(DeployWave { wave: wave.wave, nodes });
+        }
+    }
+    groups.into_iter().map(|((_, group), waves)| RolloutGroup { group, waves }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::Node;
+
+    use super::*;
+
+    fn node(name: &str, deps: Vec<&str>, deployable: bool) -> Node {
+        let mut node = Node::new(
+            name.to_string(),
+            PathBuf::from(name),
+            vec![PathBuf::from("**")],
+            vec![],
+            deps.into_iter().map(String::from).collect(),
+            None,
+        ).unwrap();
+        if deployable {
+            node.tags = vec!["deployable".to_string()];
+        }
+        node
+    }
+
+    #[test]
+    fn test_deploy_order() {
+        let graph = DependencyGraph::new(vec![
+            node("db", vec![], true),
+            node("models", vec!["db"], false),
+            node("api", vec!["models"], true),
+            node("worker", vec!["models"], true),
+            node("web", vec!["api"], true),
+            node("docs", vec![], false),
+        ], false).unwrap();
+
+        let names = |waves: &[DeployWave]| -> Vec<Vec<String>> {
+            waves.iter().map(|wave| wave.nodes.iter().map(|d| d.node.clone()).collect()).collect()
+        };
+        let waves = deploy_order(&graph, &[PathBuf::from("models/lib.rs")], "deployable", false);
+        assert_eq!(names(&waves), vec![vec!["api", "worker"], vec!["web"]]);
+        assert_eq!(waves[1].wave, 1);
+
+        // The unaffected database is deployed first, through the non-deployable models
+        let waves = deploy_order(&graph, &[PathBuf::from("models/lib.rs")], "deployable", true);
+        assert_eq!(names(&waves), vec![vec!["db"], vec!["api", "worker"], vec!["web"]]);
+        assert!(!waves[0].nodes[0].affected);
+        assert!(waves[2].nodes[0].affected);
+    }
+
+    #[test]
+    fn test_rollout_groups() {
+        let mut api = node("api", vec![], true);
+        api.metadata = Some(serde_json::json!({ "rollout": { "group": "wave-2" } }));
+        let mut web = node("web", vec!["api"], true);
+        web.metadata = Some(serde_json::json!({ "rollout": { "group": "canary" } }));
+        let graph = DependencyGraph::new(vec![api, web, node("worker", vec!["api"], true)], false).unwrap();
+
+        let groups = rollout_groups(deploy_order(&graph, &[PathBuf::from("api/main.rs")], "deployable", false));
+        let summary: Vec<_> = groups.iter()
+            .map(|g| (g.group.as_deref(), g.waves.iter().map(|w| (w.wave, w.nodes[0].node.as_str())).collect::<Vec<_>>()))
+            .collect();
+        assert_eq!(summary, vec![
+            (Some("canary"), vec![(1, "web")]),
+            (Some("wave-2"), vec![(0, "api")]),
+            (None, vec![(1, "worker")]),
+        ]);
+    }
+}
@@ -0,0 +1,88 @@
+use std::io;
+
+use clap::ValueEnum;
+use serde::de::DeserializeOwned;
+
+use crate::types::DependencyGraph;
+
+/// The bytes starting every zstd frame, which a JSON artifact can't start with.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// The compression level of binary artifacts, zstd's default: most of the size reduction of
+/// the higher levels for a fraction of their time.
+const ZSTD_LEVEL: i32 = 3;
+
+/// How `prepare` writes the artifact. Loading tells them apart, so either can be queried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ArtifactFormat {
+    /// A JSON document, readable by other tools.
+    #[default]
+    Json,
+    /// MessagePack compressed with zstd, several times smaller and faster to load.
+    Binary,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum EncodeError {
+    #[error("Error serializing: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Error serializing: {0}")]
+    MessagePack(#[from] rmp_serde::encode::Error),
+    #[error("Error compressing: {0}")]
+    Compress(#[from] io::Error),
+}
+
+/// Serializes the graph in the given format, the JSON ending with a newline.
+pub fn encode_graph(graph: &DependencyGraph, format: ArtifactFormat) -> Result<Vec<u8>, EncodeError> {
+    match format {
+        ArtifactFormat::Json => {
+            let mut json = serde_json::to_vec(graph)?;
+            json.push(b'\n');
+            Ok(json)
+        }
+        ArtifactFormat::Binary => {
+            // With field names, so that fields skipped when empty and added by later versions
+            // are read back like in JSON
+            let packed = rmp_serde::to_vec_named(graph)?;
+            Ok(zstd::encode_all(packed.as_slice(), ZSTD_LEVEL)?)
+        }
+    }
+}
+
+/// Whether an artifact's content is a binary artifact rather than JSON.
+pub(super) fn is_binary(content: &[u8]) -> bool {
+    content.starts_with(&ZSTD_MAGIC)
+}
+
+/// Reads back a binary artifact written by `encode_graph`, or the part of it `T` describes.
+pub(super) fn decode<T: DeserializeOwned>(content: &[u8]) -> Result<T, rmp_serde::decode::Error> {
+    let decoder = zstd::Decoder::new(content).map_err(rmp_serde::decode::Error::InvalidDataRead)?;
+    rmp_serde::from_read(decoder)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::types::Node;
+
+    use super::*;
+
+    #[test]
+    fn test_encode_graph() {
+        let node = |name: &str, deps: Vec<&str>| Node::new(
+            name.to_string(), PathBuf::from(name), vec![PathBuf::from("**")], vec![],
+            deps.into_iter().map(String::from).collect(), Some(serde_json::json!({ "owner": name })),
+        ).unwrap();
+        let graph = DependencyGraph::new(vec![node("lib", vec![]), node("api", vec!["lib"])], false).unwrap();
+
+        let json = encode_graph(&graph, ArtifactFormat::Json).unwrap();
+        let binary = encode_graph(&graph, ArtifactFormat::Binary).unwrap();
+        assert!(!is_binary(&json) && is_binary(&binary));
+        assert!(binary.len() < json.len());
+
+        let decoded: DependencyGraph = decode(&binary).unwrap();
+        assert_eq!(serde_json::to_value(&decoded).unwrap(), serde_json::from_slice::<serde_json::Value>(&json).unwrap());
+        assert!(decode::<DependencyGraph>(&binary[..binary.len() / 2]).is_err());
+    }
+}
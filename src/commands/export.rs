@@ -0,0 +1,795 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+use clap::{Subcommand, ValueEnum};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use serde::Serialize;
+
+use crate::types::{negation, AffectedReason, DependencyGraph, Node};
+
+use super::graph::{AFFECTED_FILL, CHANGED_FILL, DEFAULT_FILL};
+
+/// The `export` subcommands.
+#[derive(Subcommand)]
+pub enum ExportCommands {
+    /// Prints a Backstage `catalog-info.yaml` with one Component entity per node, depending
+    /// on the components of its dependencies. The type and lifecycle come from the node's
+    /// `metadata.type` and `metadata.lifecycle` when set, the owner from `metadata.owners`.
+    Backstage {
+        /// The JSON artifact file path containing the previously prepared dependency graph
+        /// from the `prepare` command
+        #[arg(short, long, value_name = "FILE")]
+        graph_artifact_path: PathBuf,
+        /// The lifecycle of nodes without `metadata.lifecycle`.
+        #[arg(long, value_name = "LIFECYCLE", default_value = "production")]
+        lifecycle: String,
+        /// The Backstage system every component belongs to.
+        #[arg(long, value_name = "SYSTEM")]
+        system: Option<String>,
+    },
+    /// Prints an SBOM describing the nodes as internal components and their dependencies,
+    /// to merge with package-level SBOMs.
+    Sbom {
+        /// The JSON artifact file path containing the previously prepared dependency graph
+        /// from the `prepare` command
+        #[arg(short, long, value_name = "FILE")]
+        graph_artifact_path: PathBuf,
+        #[arg(long, value_enum, default_value_t = SbomFormat::Spdx)]
+        format: SbomFormat,
+        /// The name of the SBOM document.
+        #[arg(long, value_name = "NAME", default_value = "internal-components")]
+        name: String,
+    },
+    /// Prints a PlantUML component diagram with an arrow from each node to its
+    /// dependencies, tags shown as stereotypes.
+    Plantuml {
+        /// The JSON artifact file path containing the previously prepared dependency graph
+        /// from the `prepare` command
+        #[arg(short, long, value_name = "FILE")]
+        graph_artifact_path: PathBuf,
+        /// Only draw this node, its dependencies and its dependents.
+        #[arg(long, value_name = "NODE")]
+        focus: Option<String>,
+        /// How many dependency levels to follow from the focused node, in each direction.
+        /// All of them by default.
+        #[arg(long, value_name = "N", requires = "focus")]
+        depth: Option<usize>,
+    },
+    /// Prints the graph as a Graphviz DOT or Mermaid diagram, with an arrow from each node to
+    /// its dependencies. With `--files`, the changed nodes are filled in red and the nodes
+    /// they affect in pink.
+    #[command(visible_alias = "dot")]
+    Diagram {
+        /// The JSON artifact file path containing the previously prepared dependency graph
+        /// from the `prepare` command
+        #[arg(short, long, value_name = "FILE")]
+        graph_artifact_path: PathBuf,
+        #[arg(long, value_enum, default_value_t = DiagramFormat::Dot)]
+        format: DiagramFormat,
+        /// Changed files whose affected nodes to highlight.
+        #[arg(short, long, value_name = "FILE")]
+        files: Vec<PathBuf>,
+        /// Only draw the nodes the files change or affect.
+        #[arg(long, default_value_t = false, requires = "files")]
+        affected_only: bool,
+        /// Only draw this node, its dependencies and its dependents.
+        #[arg(long, value_name = "NODE")]
+        focus: Option<String>,
+        /// How many dependency levels to follow from the focused node, in each direction.
+        /// All of them by default.
+        #[arg(long, value_name = "N", requires = "focus")]
+        depth: Option<usize>,
+        /// Draw the nodes sharing the first N directories of their path as a single box.
+        #[arg(long, value_name = "N")]
+        collapse: Option<usize>,
+    },
+    /// Prints a Buck2 `BUCK` file stub for every node directory: a `filegroup` per node
+    /// globbing its include patterns, with its dependencies' targets among its sources. The
+    /// manifests stay the source of truth, the stubs are regenerated from them.
+    Buck2 {
+        /// The JSON artifact file path containing the previously prepared dependency graph
+        /// from the `prepare` command
+        #[arg(short, long, value_name = "FILE")]
+        graph_artifact_path: PathBuf,
+        /// Write the stubs into the node directories under this repository root instead of
+        /// printing them. `BUCK` files not generated by dependency-cascade are left alone.
+        #[arg(long, value_name = "DIR", conflicts_with = "mapping")]
+        write: Option<PathBuf>,
+        /// Print, as JSON, the Buck2 target of every node instead of the stubs.
+        #[arg(long, default_value_t = false)]
+        mapping: bool,
+    },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExportError {
+    #[error("Node '{0}' not found in the graph")]
+    UnknownNode(String),
+    #[error("{0} wasn't generated by dependency-cascade, remove it or convert the node by hand")]
+    HandWrittenBuildFile(PathBuf),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// Marks the `BUCK` files `export buck2 --write` may overwrite.
+const BUCK2_GENERATED: &str = "# @generated by dependency-cascade export buck2";
+
+/// The languages `export diagram` writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DiagramFormat {
+    /// Graphviz's DOT, for `dot` and the tools built on it
+    Dot,
+    /// Mermaid flowcharts, rendered by GitHub and GitLab in Markdown
+    Mermaid,
+}
+
+/// Which part of the graph `export diagram` draws, and how.
+#[derive(Debug, Clone, Default)]
+pub struct DiagramScope<'a> {
+    /// The node to center the diagram on.
+    pub focus: Option<&'a str>,
+    /// How many levels of dependencies and dependents of `focus` to include.
+    pub depth: Option<usize>,
+    /// How many leading directories of the node paths to group the nodes by.
+    pub collapse: Option<usize>,
+    /// Only keep the changed and affected nodes.
+    pub affected_only: bool,
+}
+
+/// The SBOM standards `export sbom` writes, both as JSON.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SbomFormat {
+    /// SPDX 2.3
+    Spdx,
+    /// CycloneDX 1.5
+    Cyclonedx,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Entity {
+    api_version: &'static str,
+    kind: &'static str,
+    metadata: EntityMetadata,
+    spec: ComponentSpec,
+}
+
+#[derive(Debug, Serialize)]
+struct EntityMetadata {
+    name: String,
+    /// The node's name, when it isn't a valid entity name as is.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    annotations: BTreeMap<&'static str, String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ComponentSpec {
+    #[serde(rename = "type")]
+    kind: String,
+    lifecycle: String,
+    owner: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    depends_on: Vec<String>,
+}
+
+/// Renders the graph as a multi-document Backstage catalog, one Component per node sorted
+/// by name.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+/// * `lifecycle` - The lifecycle of nodes without `metadata.lifecycle`
+/// * `system` - The system every component belongs to
+///
+/// ### Returns
+/// * `String` - The YAML documents, separated by `---`
+pub fn backstage(graph: &DependencyGraph, lifecycle: &str, system: Option<&str>) -> Result<String, serde_norway::Error> {
+    let mut nodes = graph.get_all_nodes();
+    nodes.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut out = String::new();
+    for node in nodes {
+        out.push_str("---\n");
+        out.push_str(&serde_norway::to_string(&entity(node, lifecycle, system))?);
+    }
+    Ok(out)
+}
+
+fn entity(node: &Node, lifecycle: &str, system: Option<&str>) -> Entity {
+    let name = entity_name(&node.name);
+    let metadata_string = |key: &str| node.metadata_value(key).and_then(|v| v.as_str()).map(String::from);
+    let mut depends_on: Vec<String> = node.dependencies.iter()
+        .map(|dep| format!("component:{}", entity_name(dep)))
+        .collect();
+    depends_on.sort();
+
+    Entity {
+        api_version: "backstage.io/v1alpha1",
+        kind: "Component",
+        metadata: EntityMetadata {
+            title: (name != node.name).then(|| node.name.clone()),
+            name,
+            tags: node.tags.iter().map(|tag| entity_tag(tag)).collect(),
+            annotations: [("dependency-cascade/path", node.path.to_string_lossy().replace('\\', "/"))].into(),
+        },
+        spec: ComponentSpec {
+            kind: metadata_string("type").unwrap_or_else(|| "service".to_string()),
+            lifecycle: metadata_string("lifecycle").unwrap_or_else(|| lifecycle.to_string()),
+            owner: node.owners().first().map_or_else(|| "unknown".to_string(), |owner| owner_ref(owner)),
+            system: system.map(String::from),
+            depends_on,
+        },
+    }
+}
+
+/// Renders the graph as an SBOM where every node is a package (SPDX) or component
+/// (CycloneDX) depending on the ones of its dependencies. The creation time is the
+/// artifact's, so exporting the same artifact twice gives the same document.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+/// * `format` - The SBOM standard
+/// * `name` - The name of the document
+///
+/// ### Returns
+/// * `serde_json::Value` - The SBOM document
+pub fn sbom(graph: &DependencyGraph, format: SbomFormat, name: &str) -> serde_json::Value {
+    let mut nodes = graph.get_all_nodes();
+    nodes.sort_by(|a, b| a.name.cmp(&b.name));
+    let created = graph.info().created_at.clone()
+        .unwrap_or_else(|| humantime::format_rfc3339_seconds(std::time::SystemTime::now()).to_string());
+    let tool = format!("dependency-cascade-{}", env!("CARGO_PKG_VERSION"));
+
+    match format {
+        SbomFormat::Spdx => {
+            let mut relationships: Vec<serde_json::Value> = nodes.iter()
+                .map(|node| json!({
+                    "spdxElementId": "SPDXRef-DOCUMENT",
+                    "relationshipType": "DESCRIBES",
+                    "relatedSpdxElement": spdx_id(&node.name),
+                }))
+                .collect();
+            for node in &nodes {
+                let mut dependencies = node.dependencies.clone();
+                dependencies.sort();
+                relationships.extend(dependencies.iter().map(|dep| json!({
+                    "spdxElementId": spdx_id(&node.name),
+                    "relationshipType": "DEPENDS_ON",
+                    "relatedSpdxElement": spdx_id(dep),
+                })));
+            }
+            json!({
+                "spdxVersion": "SPDX-2.3",
+                "dataLicense": "CC0-1.0",
+                "SPDXID": "SPDXRef-DOCUMENT",
+                "name": name,
+                "documentNamespace": format!("https://spdx.org/spdxdocs/{}-{}", name, graph_digest(&nodes)),
+                "creationInfo": { "created": created, "creators": [format!("Tool: {}", tool)] },
+                "packages": nodes.iter().map(|node| {
+                    let mut package = json!({
+                        "name": node.name,
+                        "SPDXID": spdx_id(&node.name),
+                        "downloadLocation": "NOASSERTION",
+                        "filesAnalyzed": false,
+                        "sourceInfo": format!("Directory {} of the repository", node.path.to_string_lossy().replace('\\', "/")),
+                    });
+                    if let Some(owner) = node.owners().first() {
+                        package["supplier"] = json!(format!("Organization: {}", owner));
+                    }
+                    package
+                }).collect::<Vec<_>>(),
+                "relationships": relationships,
+            })
+        }
+        SbomFormat::Cyclonedx => json!({
+            "bomFormat": "CycloneDX",
+            "specVersion": "1.5",
+            "version": 1,
+            "metadata": {
+                "timestamp": created,
+                "component": { "type": "application", "bom-ref": name, "name": name },
+                "tools": { "components": [{ "type": "application", "name": "dependency-cascade", "version": env!("CARGO_PKG_VERSION") }] },
+            },
+            "components": nodes.iter().map(|node| {
+                let mut properties = vec![json!({ "name": "dependency-cascade:path", "value": node.path.to_string_lossy().replace('\\', "/") })];
+                properties.extend(node.owners().iter().map(|owner| json!({ "name": "dependency-cascade:owner", "value": owner })));
+                properties.extend(node.tags.iter().map(|tag| json!({ "name": "dependency-cascade:tag", "value": tag })));
+                json!({ "type": "library", "bom-ref": node.name, "name": node.name, "properties": properties })
+            }).collect::<Vec<_>>(),
+            "dependencies": nodes.iter().map(|node| {
+                let mut dependencies = node.dependencies.clone();
+                dependencies.sort();
+                json!({ "ref": node.name, "dependsOn": dependencies })
+            }).collect::<Vec<_>>(),
+        }),
+    }
+}
+
+/// Renders the graph, or the neighborhood of the focused node, as a PlantUML component
+/// diagram.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+/// * `focus` - The node to center the diagram on
+/// * `depth` - How many levels of dependencies and dependents of `focus` to include
+///
+/// ### Returns
+/// * `String` - The diagram, from `@startuml` to `@enduml`
+pub fn plantuml(graph: &DependencyGraph, focus: Option<&str>, depth: Option<usize>) -> Result<String, ExportError> {
+    let names = neighborhood(graph, focus, depth)?;
+
+    let alias = |name: &str| -> String {
+        let id: String = name.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+        format!("c_{}", id)
+    };
+    let mut out = String::from("@startuml\n");
+    for name in &names {
+        let node = graph.get_node(name).expect("names come from the graph");
+        let stereotypes: String = node.tags.iter().map(|tag| format!(" <<{}>>", tag)).collect();
+        out.push_str(&format!("component \"{}\" as {}{}\n", name.replace('"', "'"), alias(name), stereotypes));
+    }
+    for name in &names {
+        let mut dependencies: Vec<&String> = graph.get_node(name).expect("names come from the graph").dependencies.iter()
+            .filter(|dep| names.contains(dep.as_str()))
+            .collect();
+        dependencies.sort();
+        for dep in dependencies {
+            out.push_str(&format!("{} --> {}\n", alias(name), alias(dep)));
+        }
+    }
+    out.push_str("@enduml\n");
+    Ok(out)
+}
+
+/// Renders the graph, or the part of it `scope` selects, as a diagram, the nodes filled by
+/// how the changed files affect them. With `collapse`, the nodes whose paths share their
+/// first `collapse` directories are drawn as one box, labeled with those directories and
+/// filled like the most affected of its nodes.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+/// * `reasons` - Why each affected node is affected, from `get_affected_reasons`
+/// * `format` - The diagram language
+/// * `scope` - The nodes to draw, and how to group them
+///
+/// ### Returns
+/// * `String` - The DOT `digraph` or the Mermaid `flowchart`
+pub fn diagram(
+    graph: &DependencyGraph,
+    reasons: &BTreeMap<String, AffectedReason>,
+    format: DiagramFormat,
+    scope: &DiagramScope,
+) -> Result<String, ExportError> {
+    let mut names = neighborhood(graph, scope.focus, scope.depth)?;
+    if scope.affected_only {
+        names.retain(|name| reasons.contains_key(*name));
+    }
+    // The box of each node, by its name
+    let boxes: BTreeMap<&str, String> = names.iter()
+        .map(|&name| {
+            let node = graph.get_node(name).expect("names come from the graph");
+            let label = match scope.collapse {
+                Some(collapse) => format!("{}/", node.path.components().take(collapse).collect::<PathBuf>().display()),
+                None => name.to_string(),
+            };
+            (name, label)
+        })
+        .collect();
+
+    // 0 for untouched, 1 for affected and 2 for changed, each box taking its highest
+    let mut ranks: BTreeMap<&str, usize> = BTreeMap::new();
+    for (name, label) in &boxes {
+        let rank = match reasons.get(*name) {
+            Some(AffectedReason::Changed { .. }) => 2,
+            Some(AffectedReason::Dependency { .. } | AffectedReason::Upstream { .. }) => 1,
+            None => 0,
+        };
+        let highest = ranks.entry(label.as_str()).or_default();
+        *highest = (*highest).max(rank);
+    }
+    let mut arrows: BTreeSet<(&str, &str)> = BTreeSet::new();
+    for (name, label) in &boxes {
+        for dep in &graph.get_node(name).expect("names come from the graph").dependencies {
+            if let Some(dep_label) = boxes.get(dep.as_str()).filter(|dep_label| *dep_label != label) {
+                arrows.insert((label, dep_label));
+            }
+        }
+    }
+
+    let fills = [DEFAULT_FILL, AFFECTED_FILL, CHANGED_FILL];
+    let mut out = String::new();
+    match format {
+        DiagramFormat::Dot => {
+            let quote = |name: &str| format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""));
+            out.push_str("digraph dependencies {\n  node [shape=box, style=\"rounded,filled\", fontname=\"sans-serif\"];\n");
+            for (label, rank) in &ranks {
+                out.push_str(&format!("  {} [fillcolor=\"{}\"];\n", quote(label), fills[*rank]));
+            }
+            for (dependent, dependency) in arrows {
+                out.push_str(&format!("  {} -> {};\n", quote(dependent), quote(dependency)));
+            }
+            out.push_str("}\n");
+        }
+        DiagramFormat::Mermaid => {
+            // Labels can hold any character, so the boxes are identified by their position and
+            // the labels written with Mermaid's entity codes, starting with the `#` they use
+            let ids: HashMap<&str, String> = ranks.keys().enumerate().map(|(i, label)| (*label, format!("n{}", i))).collect();
+            let escape = |label: &str| label.replace('#', "#35;").replace('"', "#quot;").replace('<', "#lt;").replace('>', "#gt;");
+            out.push_str("flowchart TD\n");
+            for label in ranks.keys() {
+                out.push_str(&format!("  {}[\"{}\"]\n", ids[label], escape(label)));
+            }
+            for (dependent, dependency) in arrows {
+                out.push_str(&format!("  {} --> {}\n", ids[dependent], ids[dependency]));
+            }
+            for (class, rank) in [("affected", 1), ("changed", 2)] {
+                let members: Vec<&str> = ranks.iter().filter(|(_, r)| **r == rank).map(|(label, _)| ids[label].as_str()).collect();
+                if !members.is_empty() {
+                    out.push_str(&format!("  classDef {} fill:{}\n  class {} {}\n", class, fills[rank], members.join(","), class));
+                }
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// The names of every node, or of the focused node with its dependencies and dependents up
+/// to `depth` levels away.
+fn neighborhood<'a>(graph: &'a DependencyGraph, focus: Option<&str>, depth: Option<usize>) -> Result<BTreeSet<&'a str>, ExportError> {
+    let Some(focus) = focus else {
+        return Ok(graph.get_all_nodes().into_iter().map(|node| node.name.as_str()).collect());
+    };
+    let node = graph.get_node(focus).ok_or_else(|| ExportError::UnknownNode(focus.to_string()))?;
+    let mut dependents: HashMap<&str, Vec<&str>> = HashMap::new();
+    for node in graph.get_all_nodes() {
+        for dep in &node.dependencies {
+            dependents.entry(dep.as_str()).or_default().push(&node.name);
+        }
+    }
+    let mut names = within(node.name.as_str(), depth, |name| {
+        graph.get_node(name).map(|n| n.dependencies.iter().map(String::as_str).collect()).unwrap_or_default()
+    });
+    names.extend(within(node.name.as_str(), depth, |name| dependents.get(name).cloned().unwrap_or_default()));
+    Ok(names)
+}
+
+/// The Buck2 target of a node: its directory as the package and its name as the target name,
+/// e.g. `//packages/web:@x_web` for `@x/web`.
+pub fn buck2_target(node: &Node) -> String {
+    let package = node.path.to_string_lossy().replace('\\', "/");
+    let package = package.trim_start_matches("./").trim_end_matches('/');
+    let name: String = node.name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || "_-.,=+@~".contains(c) { c } else { '_' })
+        .collect();
+    format!("//{}:{}", if package == "." { "" } else { package }, name)
+}
+
+/// Renders a `BUCK` file per node directory, with a `filegroup` per node sorted by name.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+///
+/// ### Returns
+/// * `BTreeMap<PathBuf, String>` - The contents of each `BUCK` file, by path relative to the
+///   repository root
+pub fn buck2(graph: &DependencyGraph) -> BTreeMap<PathBuf, String> {
+    let mut nodes = graph.get_all_nodes();
+    nodes.sort_by(|a, b| a.name.cmp(&b.name));
+    let quote = |pattern: &Path| format!("\"{}\"", pattern.to_string_lossy().replace('\\', "/").replace('"', "\\\""));
+
+    let mut files: BTreeMap<PathBuf, String> = BTreeMap::new();
+    for node in nodes {
+        let content = files.entry(node.path.join("BUCK"))
+            .or_insert_with(|| format!("{}, do not edit: change the node's manifest instead.\n", BUCK2_GENERATED));
+        // Buck globs have no negation: negated includes become excludes, negated excludes are
+        // left out, so the glob may match a few files less than the node
+        let (negated, includes): (Vec<&PathBuf>, Vec<&PathBuf>) = node.included_paths.iter().partition(|p| negation(p).0);
+        let includes: Vec<String> = includes.into_iter().map(|p| quote(p)).collect();
+        let excludes: Vec<String> = node.excluded_paths.iter()
+            .filter(|p| !negation(p).0)
+            .chain(negated)
+            .map(|p| quote(negation(p).1))
+            .collect();
+        let mut srcs = format!("glob([{}]", includes.join(", "));
+        if !excludes.is_empty() {
+            srcs.push_str(&format!(", exclude = [{}]", excludes.join(", ")));
+        }
+        srcs.push(')');
+        let mut deps: Vec<String> = node.dependencies.iter()
+            .filter_map(|dep| graph.get_node(dep))
+            .map(|dep| format!("        \"{}\",\n", buck2_target(dep)))
+            .collect();
+        deps.sort();
+        if !deps.is_empty() {
+            srcs.push_str(&format!(" + [\n{}    ]", deps.concat()));
+        }
+
+        let target = buck2_target(node);
+        let name = target.rsplit_once(':').map_or(target.as_str(), |(_, name)| name);
+        content.push_str(&format!("\nfilegroup(\n    name = \"{}\",\n    srcs = {},\n", name, srcs));
+        if !node.tags.is_empty() {
+            let labels: Vec<String> = node.tags.iter().map(|tag| format!("\"{}\"", tag.replace('"', "\\\""))).collect();
+            content.push_str(&format!("    labels = [{}],\n", labels.join(", ")));
+        }
+        content.push_str("    visibility = [\"PUBLIC\"],\n)\n");
+    }
+    files
+}
+
+/// Writes the `BUCK` files under `root`, refusing to replace ones that weren't generated.
+///
+/// ### Returns
+/// * `Vec<PathBuf>` - The files written
+pub fn write_buck2(files: &BTreeMap<PathBuf, String>, root: &Path) -> Result<Vec<PathBuf>, ExportError> {
+    let files: Vec<(PathBuf, &String)> = files.iter().map(|(path, content)| (root.join(path), content)).collect();
+    // Check them all first, so a hand-written file doesn't leave the conversion half done
+    for (path, _) in &files {
+        match std::fs::read_to_string(path) {
+            Ok(existing) if !existing.starts_with(BUCK2_GENERATED) => return Err(ExportError::HandWrittenBuildFile(path.clone())),
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    for (path, content) in &files {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        super::write_atomic(path, content)?;
+    }
+    Ok(files.into_iter().map(|(path, _)| path).collect())
+}
+
+/// The nodes reachable from `start` in at most `depth` steps of `next`, `start` included.
+fn within<'a>(start: &'a str, depth: Option<usize>, next: impl Fn(&str) -> Vec<&'a str>) -> BTreeSet<&'a str> {
+    let mut reached = BTreeSet::from([start]);
+    let mut frontier = vec![start];
+    let mut level = 0;
+    while !frontier.is_empty() && depth.is_none_or(|depth| level < depth) {
+        frontier = frontier.into_iter()
+            .flat_map(&next)
+            .filter(|name| reached.insert(name))
+            .collect();
+        level += 1;
+    }
+    reached
+}
+
+/// SPDX identifiers only allow letters, digits, `.` and `-`.
+fn spdx_id(name: &str) -> String {
+    let id: String = name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '-') { c } else { '-' })
+        .collect();
+    format!("SPDXRef-Package-{}", id)
+}
+
+/// Identifies the graph's structure, for a document namespace that only changes with it.
+fn graph_digest(nodes: &[&Node]) -> String {
+    let mut hasher = Sha256::new();
+    for node in nodes {
+        hasher.update(node.name.as_bytes());
+        for dep in &node.dependencies {
+            hasher.update(b"\0");
+            hasher.update(dep.as_bytes());
+        }
+        hasher.update(b"\n");
+    }
+    format!("{:x}", hasher.finalize())[..16].to_string()
+}
+
+/// Backstage names are made of letters, digits, `-`, `_` and `.`, and start and end with a
+/// letter or digit, e.g. `@scope/pkg` becomes `scope-pkg`.
+fn entity_name(name: &str) -> String {
+    let replaced: String = name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') { c } else { '-' })
+        .collect();
+    replaced.trim_matches(|c: char| !c.is_ascii_alphanumeric()).to_string()
+}
+
+/// Backstage tags are lowercase letters, digits, `+`, `#` and `-`.
+fn entity_tag(tag: &str) -> String {
+    tag.to_lowercase().chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '+' | '#' | '-') { c } else { '-' })
+        .collect()
+}
+
+/// Turns a CODEOWNERS-style owner into an entity reference: `@org/team` is the group `team`
+/// and `@user` the user `user`. Other owners, like emails, are kept as is.
+fn owner_ref(owner: &str) -> String {
+    match owner.strip_prefix('@') {
+        Some(handle) => match handle.rsplit_once('/') {
+            Some((_, team)) => format!("group:{}", team),
+            None => format!("user:{}", handle),
+        },
+        None => owner.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testkit::FixtureDir;
+
+    use super::*;
+
+    #[test]
+    fn test_backstage() {
+        let mut web = Node::new(
+            "@acme/web".to_string(),
+            PathBuf::from("apps/web"),
+            vec![PathBuf::from("**")],
+            vec![],
+            vec!["api".to_string()],
+            Some(serde_json::json!({"owners": ["@acme/frontend"], "type": "website"})),
+        ).unwrap();
+        web.tags = vec!["Frontend".to_string()];
+        let api = Node::new("api".to_string(), PathBuf::from("services/api"), vec![PathBuf::from("**")], vec![], vec![], None).unwrap();
+        let graph = DependencyGraph::new(vec![web, api], false).unwrap();
+
+        let catalog = backstage(&graph, "experimental", Some("shop")).unwrap();
+        let documents: Vec<&str> = catalog.split("---\n").filter(|doc| !doc.is_empty()).collect();
+        assert_eq!(documents.len(), 2);
+        assert!(documents[0].contains("name: acme-web\n  title: '@acme/web'\n"));
+        assert!(documents[0].contains("tags:\n  - frontend\n"));
+        assert!(documents[0].contains("type: website\n  lifecycle: experimental\n  owner: group:frontend\n  system: shop\n  dependsOn:\n  - component:api\n"));
+        assert!(documents[1].contains("name: api\n"));
+        assert!(documents[1].contains("owner: unknown\n"));
+    }
+
+    #[test]
+    fn test_sbom() {
+        let node = |name: &str, deps: Vec<&str>| Node::new(
+            name.to_string(), PathBuf::from(name), vec![PathBuf::from("**")], vec![], deps.into_iter().map(String::from).collect(), None,
+        ).unwrap();
+        let graph = DependencyGraph::new(vec![node("@x/web", vec!["core"]), node("core", vec![])], false).unwrap();
+
+        let spdx = sbom(&graph, SbomFormat::Spdx, "shop");
+        assert_eq!(spdx["packages"][0]["SPDXID"], "SPDXRef-Package--x-web");
+        assert_eq!(spdx["relationships"].as_array().unwrap().len(), 3);
+        assert_eq!(spdx["relationships"][2], json!({
+            "spdxElementId": "SPDXRef-Package--x-web",
+            "relationshipType": "DEPENDS_ON",
+            "relatedSpdxElement": "SPDXRef-Package-core",
+        }));
+        assert_eq!(spdx, sbom(&graph, SbomFormat::Spdx, "shop"));
+
+        let cyclonedx = sbom(&graph, SbomFormat::Cyclonedx, "shop");
+        assert_eq!(cyclonedx["components"][1]["bom-ref"], "core");
+        assert_eq!(cyclonedx["dependencies"][0], json!({ "ref": "@x/web", "dependsOn": ["core"] }));
+    }
+
+    #[test]
+    fn test_plantuml() {
+        let node = |name: &str, deps: Vec<&str>| Node::new(
+            name.to_string(), PathBuf::from(name), vec![PathBuf::from("**")], vec![], deps.into_iter().map(String::from).collect(), None,
+        ).unwrap();
+        let graph = DependencyGraph::new(vec![
+            node("base", vec![]),
+            node("core", vec!["base"]),
+            node("api", vec!["core"]),
+            node("web-app", vec!["api"]),
+            node("docs", vec![]),
+        ], false).unwrap();
+
+        let diagram = plantuml(&graph, Some("core"), Some(1)).unwrap();
+        assert_eq!(diagram, "@startuml\n\
+            component \"api\" as c_api\n\
+            component \"base\" as c_base\n\
+            component \"core\" as c_core\n\
+            c_api --> c_core\n\
+            c_core --> c_base\n\
+            @enduml\n");
+
+        let diagram = plantuml(&graph, Some("core"), None).unwrap();
+        assert!(diagram.contains("c_web_app --> c_api\n"));
+        assert!(!diagram.contains("docs"));
+        assert!(plantuml(&graph, None, None).unwrap().contains("component \"docs\" as c_docs\n"));
+        assert!(matches!(plantuml(&graph, Some("nope"), None), Err(ExportError::UnknownNode(_))));
+    }
+
+    #[test]
+    fn test_diagram() {
+        let node = |name: &str, path: &str, deps: Vec<&str>| Node::new(
+            name.to_string(), PathBuf::from(path), vec![PathBuf::from("**")], vec![], deps.into_iter().map(String::from).collect(), None,
+        ).unwrap();
+        let graph = DependencyGraph::new(vec![
+            node("base", "libs/base", vec![]),
+            node("core", "libs/core", vec!["base"]),
+            node("api", "apps/api", vec!["core"]),
+            node("web", "apps/web", vec!["api", "core"]),
+            node("docs", "docs", vec![]),
+        ], false).unwrap();
+        let reasons = graph.get_affected_reasons(&[PathBuf::from("apps/api/main.rs")]);
+
+        let scope = DiagramScope { focus: Some("api"), depth: Some(1), ..Default::default() };
+        let diagram = super::diagram(&graph, &reasons, DiagramFormat::Dot, &scope).unwrap();
+        assert!(diagram.contains(&format!("  \"api\" [fillcolor=\"{}\"];\n", CHANGED_FILL)));
+        assert!(diagram.contains(&format!("  \"web\" [fillcolor=\"{}\"];\n", AFFECTED_FILL)));
+        assert!(diagram.contains("  \"web\" -> \"api\";\n"));
+        assert!(!diagram.contains("base") && !diagram.contains("docs"));
+
+        let collapsed = DiagramScope { collapse: Some(1), ..Default::default() };
+        let diagram = super::diagram(&graph, &reasons, DiagramFormat::Dot, &collapsed).unwrap();
+        assert!(diagram.contains(&format!("  \"apps/\" [fillcolor=\"{}\"];\n", CHANGED_FILL)));
+        assert!(diagram.contains(&format!("  \"libs/\" [fillcolor=\"{}\"];\n", DEFAULT_FILL)));
+        assert!(diagram.contains("  \"apps/\" -> \"libs/\";\n"));
+        assert_eq!(diagram.matches(" -> ").count(), 1);
+
+        let affected = DiagramScope { affected_only: true, ..Default::default() };
+        let diagram = super::diagram(&graph, &reasons, DiagramFormat::Mermaid, &affected).unwrap();
+        assert_eq!(diagram, format!("flowchart TD\n  n0[\"api\"]\n  n1[\"web\"]\n  n1 --> n0\n  \
+            classDef affected fill:{}\n  class n1 affected\n  classDef changed fill:{}\n  class n0 changed\n", AFFECTED_FILL, CHANGED_FILL));
+
+        let unknown = DiagramScope { focus: Some("nope"), ..Default::default() };
+        assert!(matches!(super::diagram(&graph, &reasons, DiagramFormat::Dot, &unknown), Err(ExportError::UnknownNode(_))));
+    }
+
+    #[test]
+    fn test_diagram_mermaid() {
+        let node = |name: &str, path: &str, deps: Vec<&str>| Node::new(
+            name.to_string(), PathBuf::from(path), vec![PathBuf::from("**")], vec![], deps.into_iter().map(String::from).collect(), None,
+        ).unwrap();
+        let graph = DependencyGraph::new(vec![
+            node("lib", "libs/lib", vec![]),
+            node("@scope/web", "apps/web", vec!["lib"]),
+            node("say \"hi\"", "apps/hi", vec!["lib"]),
+            node("a<b>", "libs/ab", vec![]),
+            node("c#", "apps/c", vec!["a<b>"]),
+        ], false).unwrap();
+        let reasons = graph.get_affected_reasons(&[PathBuf::from("libs/lib/src/lib.rs")]);
+
+        let diagram = super::diagram(&graph, &reasons, DiagramFormat::Mermaid, &DiagramScope::default()).unwrap();
+        assert_eq!(diagram, format!("flowchart TD\n  \
+            n0[\"@scope/web\"]\n  n1[\"a#lt;b#gt;\"]\n  n2[\"c#35;\"]\n  n3[\"lib\"]\n  n4[\"say #quot;hi#quot;\"]\n  \
+            n0 --> n3\n  n2 --> n1\n  n4 --> n3\n  \
+            classDef affected fill:{}\n  class n0,n4 affected\n  classDef changed fill:{}\n  class n3 changed\n", AFFECTED_FILL, CHANGED_FILL));
+
+        // The unaffected nodes are dropped, with the arrows between them
+        let affected = DiagramScope { affected_only: true, ..Default::default() };
+        let diagram = super::diagram(&graph, &reasons, DiagramFormat::Mermaid, &affected).unwrap();
+        assert_eq!(diagram, format!("flowchart TD\n  \
+            n0[\"@scope/web\"]\n  n1[\"lib\"]\n  n2[\"say #quot;hi#quot;\"]\n  \
+            n0 --> n1\n  n2 --> n1\n  \
+            classDef affected fill:{}\n  class n0,n2 affected\n  classDef changed fill:{}\n  class n1 changed\n", AFFECTED_FILL, CHANGED_FILL));
+    }
+
+    #[test]
+    fn test_buck2() {
+        let mut web = Node::new(
+            "@x/web".to_string(),
+            PathBuf::from("apps/web"),
+            vec![PathBuf::from("src/**")],
+            vec![PathBuf::from("**/*.md")],
+            vec!["core".to_string()],
+            None,
+        ).unwrap();
+        web.tags = vec!["frontend".to_string()];
+        let core = Node::new("core".to_string(), PathBuf::from("libs/core"), vec![PathBuf::from("**")], vec![], vec![], None).unwrap();
+        let graph = DependencyGraph::new(vec![web, core], false).unwrap();
+
+        assert_eq!(buck2_target(graph.get_node("@x/web").unwrap()), "//apps/web:@x_web");
+        let files = buck2(&graph);
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[&PathBuf::from("apps/web/BUCK")], format!("{}, do not edit: change the node's manifest instead.\n\
+            \n\
+            filegroup(\n\
+            \x20   name = \"@x_web\",\n\
+            \x20   srcs = glob([\"src/**\"], exclude = [\"**/*.md\"]) + [\n\
+            \x20       \"//libs/core:core\",\n\
+            \x20   ],\n\
+            \x20   labels = [\"frontend\"],\n\
+            \x20   visibility = [\"PUBLIC\"],\n\
+            )\n", BUCK2_GENERATED));
+
+        let root = FixtureDir::new().file("libs/core/BUCK", "rust_library(name = \"core\")\n");
+        assert!(matches!(write_buck2(&files, root.path()), Err(ExportError::HandWrittenBuildFile(_))));
+        assert!(!root.join("apps/web/BUCK").exists());
+        std::fs::remove_file(root.join("libs/core/BUCK")).unwrap();
+        assert_eq!(write_buck2(&files, root.path()).unwrap().len(), 2);
+        assert_eq!(write_buck2(&files, root.path()).unwrap().len(), 2);
+    }
+}
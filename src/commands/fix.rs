@@ -0,0 +1,285 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+
+use clap::ValueEnum;
+use serde::Serialize;
+use toml_edit::{Array, DocumentMut, Item, TableLike, Value};
+
+use crate::types::{Node, NodeCreationError};
+
+/// The auto-repairable issues `fix` knows how to rewrite.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FixRule {
+    /// Removes dependency entries pointing at nodes that don't exist.
+    Dangling,
+    /// Removes dependency entries pointing at a node that's already declared.
+    Duplicates,
+    /// Renames dependency keys that alias another name (`foo = { name = "bar" }`)
+    /// to the name they point at.
+    AliasKeys,
+    /// Sorts dependency entries and tags alphabetically. File path lists are left
+    /// alone: their order decides which pattern wins.
+    Sort,
+}
+
+impl FixRule {
+    /// Every rule, in the order they're applied.
+    pub const ALL: [FixRule; 4] = [FixRule::Duplicates, FixRule::Dangling, FixRule::AliasKeys, FixRule::Sort];
+}
+
+/// Why `fix` failed.
+#[derive(Debug, thiserror::Error)]
+pub enum FixError {
+    #[error(transparent)]
+    Walk(#[from] walkdir::Error),
+    #[error("Unable to read {0}: {1}")]
+    Read(PathBuf, std::io::Error),
+    #[error(transparent)]
+    Node(#[from] NodeCreationError),
+    #[error("Failed to parse {0}: {1}")]
+    Parse(PathBuf, toml_edit::TomlError),
+    #[error("Unable to write {0}: {1}")]
+    Write(PathBuf, std::io::Error),
+}
+
+/// The result of fixing a single dependency toml file.
+#[derive(Debug, Clone, Serialize)]
+pub struct ManifestFix {
+    /// The rewritten dependency toml file.
+    pub manifest: PathBuf,
+    /// The rules that changed something in the file.
+    pub applied: Vec<FixRule>,
+    #[serde(skip)]
+    pub original: String,
+    #[serde(skip)]
+    pub fixed: String,
+}
+
+impl ManifestFix {
+    /// A unified diff between the original and the fixed file.
+    pub fn diff(&self) -> String {
+        let path = self.manifest.to_string_lossy();
+        similar::TextDiff::from_lines(&self.original, &self.fixed)
+            .unified_diff()
+            .header(&format!("a/{}", path), &format!("b/{}", path))
+            .to_string()
+    }
+}
+
+/// Rewrites every dependency toml file under `dir` to repair the issues selected in `rules`.
+/// Formatting and comments are preserved wherever the rules don't touch the file.
+///
+/// ### Arguments
+/// * `dir` - The directory to start the recursive scan from
+/// * `dependency_toml_name` - The name of the dependency toml file commmon to all the services. Defaults to `dependencies.toml`
+/// * `rules` - The rules to apply
+/// * `dry_run` - Compute the fixes without writing them
+///
+/// ### Returns
+/// * `Vec<ManifestFix>` - The files that needed fixing
+pub fn fix(dir: PathBuf, dependency_toml_name: Option<String>, rules: &[FixRule], dry_run: bool) -> Result<Vec<ManifestFix>, FixError> {
+    let mut manifests = Vec::new();
+    for manifest in super::find_manifests(&dir, dependency_toml_name.as_deref())? {
+        let content = fs::read_to_string(&manifest).map_err(|e| FixError::Read(manifest.clone(), e))?;
+        let node = Node::from_toml_str(&content, super::node_path(&manifest))?;
+        manifests.push((manifest, content, node.name));
+    }
+    let known_names: HashSet<String> = manifests.iter().map(|(_, _, name)| name.clone()).collect();
+
+    let mut fixes = Vec::new();
+    for (manifest, content, _) in manifests {
+        let (fixed, applied) = fix_manifest(&content, &known_names, rules).map_err(|e| FixError::Parse(manifest.clone(), e))?;
+        if applied.is_empty() {
+            continue;
+        }
+        if !dry_run {
+            fs::write(&manifest, &fixed).map_err(|e| FixError::Write(manifest.clone(), e))?;
+        }
+        fixes.push(ManifestFix { manifest, applied, original: content, fixed });
+    }
+
+    Ok(fixes)
+}
+
+/// Applies the selected rules to the content of a single dependency toml file.
+///
+/// ### Returns
+/// The fixed content and the rules that changed something.
+fn fix_manifest(content: &str, known_names: &HashSet<String>, rules: &[FixRule]) -> Result<(String, Vec<FixRule>), toml_edit::TomlError> {
+    let mut doc: DocumentMut = content.parse()?;
+    let mut applied = Vec::new();
+
+    for rule in FixRule::ALL.iter().filter(|r| rules.contains(r)) {
+        let changed = match rule {
+            FixRule::Duplicates => retain_dependencies(&mut doc, {
+                let mut seen = HashSet::new();
+                move |name| seen.insert(name.to_string())
+            }),
+            FixRule::Dangling => retain_dependencies(&mut doc, |name| known_names.contains(name)),
+            FixRule::AliasKeys => rename_alias_keys(&mut doc),
+            FixRule::Sort => sort_lists(&mut doc),
+        };
+        if changed {
+            applied.push(*rule);
+        }
+    }
+
+    Ok((doc.to_string(), applied))
+}
+
+fn dependencies_mut(doc: &mut DocumentMut) -> Option<&mut dyn TableLike> {
+    doc.get_mut("dependencies").and_then(|d| d.as_table_like_mut())
+}
+
+/// The name of a dependency on a node of this repository. Dependencies on another
+/// repository's nodes (`{ repo = "...", name = "..." }`) are resolved by `artifact merge`,
+/// so the rules leave them alone.
+fn dependency_name(item: &Item) -> Option<&str> {
+    if item.get("repo").is_some() {
+        return None;
+    }
+    item.get("name").and_then(|n| n.as_str())
+}
+
+/// Drops the dependency entries whose name doesn't satisfy `keep`, in declaration order.
+fn retain_dependencies(doc: &mut DocumentMut, mut keep: impl FnMut(&str) -> bool) -> bool {
+    let Some(dependencies) = dependencies_mut(doc) else { return false };
+
+    let to_remove: Vec<String> = dependencies.iter()
+        .filter(|(_, item)| dependency_name(item).is_some_and(|name| !keep(name)))
+        .map(|(key, _)| key.to_string())
+        .collect();
+    for key in &to_remove {
+        dependencies.remove(key);
+    }
+    !to_remove.is_empty()
+}
+
+fn rename_alias_keys(doc: &mut DocumentMut) -> bool {
+    let Some(dependencies) = dependencies_mut(doc) else { return false };
+
+    let needs_rename = dependencies.iter()
+        .any(|(key, item)| dependency_name(item).is_some_and(|name| name != key));
+    if !needs_rename {
+        return false;
+    }
+
+    // Re-insert every entry so the renamed keys keep their original position
+    let entries: Vec<(String, Item)> = dependencies.iter()
+        .map(|(key, item)| (dependency_name(item).unwrap_or(key).to_string(), item.clone()))
+        .collect();
+    dependencies.clear();
+    for (key, item) in entries {
+        if !dependencies.contains_key(&key) {
+            dependencies.insert(&key, item);
+        }
+    }
+    true
+}
+
+fn sort_lists(doc: &mut DocumentMut) -> bool {
+    let mut changed = false;
+
+    if let Some(dependencies) = dependencies_mut(doc) {
+        let keys: Vec<String> = dependencies.iter().map(|(k, _)| k.to_string()).collect();
+        if !keys.is_sorted() {
+            dependencies.sort_values();
+            changed = true;
+        }
+    }
+
+    if let Some(array) = doc.get_mut("module")
+        .and_then(|t| t.get_mut("tags"))
+        .and_then(|a| a.as_array_mut())
+    {
+        let mut values: Vec<String> = array.iter().filter_map(|v| v.as_str().map(String::from)).collect();
+        if values.len() == array.len() && !values.is_sorted() {
+            values.sort();
+            *array = values.into_iter().map(Value::from).collect::<Array>();
+            changed = true;
+        }
+    }
+
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"# Service manifest
+[module]
+name = "web"
+tags = ["frontend", "deployable"]
+
+[dependencies]
+lib = { name = "lib" }
+api = { name = "api" }
+also-lib = { name = "lib" } # duplicate
+gone = { name = "removed-service" }
+
+[file_paths]
+include = ["src/**", "assets/**"]
+"#;
+
+    fn known() -> HashSet<String> {
+        ["web", "lib", "api"].iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_fix_dangling_and_duplicates() {
+        let (fixed, applied) = fix_manifest(MANIFEST, &known(), &[FixRule::Dangling, FixRule::Duplicates]).unwrap();
+
+        assert_eq!(applied, vec![FixRule::Duplicates, FixRule::Dangling]);
+        assert!(!fixed.contains("also-lib"));
+        assert!(!fixed.contains("removed-service"));
+        assert!(fixed.starts_with("# Service manifest"));
+
+        let node = Node::from_toml_str(&fixed, PathBuf::from("web")).unwrap();
+        assert_eq!(node.dependencies.len(), 2);
+    }
+
+    #[test]
+    fn test_fix_alias_keys() {
+        let toml = "[module]\nname = \"a\"\n\n[dependencies]\nfoo = { name = \"bar\" }\nbaz = { name = \"baz\" }\n";
+        let (fixed, applied) = fix_manifest(toml, &known(), &[FixRule::AliasKeys]).unwrap();
+
+        assert_eq!(applied, vec![FixRule::AliasKeys]);
+        assert!(fixed.contains("bar = { name = \"bar\" }"));
+        assert!(fixed.find("bar =").unwrap() < fixed.find("baz =").unwrap());
+    }
+
+    #[test]
+    fn test_fix_sort() {
+        let (fixed, applied) = fix_manifest(MANIFEST, &known(), &[FixRule::Sort]).unwrap();
+
+        assert_eq!(applied, vec![FixRule::Sort]);
+        assert!(fixed.contains(r#"tags = ["deployable", "frontend"]"#));
+        assert!(fixed.contains(r#"include = ["src/**", "assets/**"]"#));
+        assert!(fixed.find("\nalso-lib =").unwrap() < fixed.find("\nlib =").unwrap());
+
+        // Already fixed files are left alone
+        let (_, applied) = fix_manifest(&fixed, &known(), &[FixRule::Sort]).unwrap();
+        assert!(applied.is_empty());
+    }
+
+    #[test]
+    fn test_fix_sort_keeps_pattern_order() {
+        let toml = "[module]\nname = \"a\"\n\n[file_paths]\ninclude = [\"**\", \"!generated/**\"]\nexclude = [\"z/**\", \"a/**\"]\n";
+        let (fixed, applied) = fix_manifest(toml, &known(), &[FixRule::Sort]).unwrap();
+
+        assert!(applied.is_empty());
+        assert_eq!(fixed, toml);
+    }
+
+    #[test]
+    fn test_fix_keeps_cross_repo_dependencies() {
+        let toml = "[module]\nname = \"a\"\n\n[dependencies]\nlib = { name = \"lib\" }\next = { repo = \"other\", name = \"shared\" }\nother-lib = { repo = \"other\", name = \"lib\" }\n";
+        let (fixed, applied) = fix_manifest(toml, &known(), &[FixRule::Dangling, FixRule::Duplicates, FixRule::AliasKeys]).unwrap();
+
+        assert!(applied.is_empty());
+        assert_eq!(fixed, toml);
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use clap::Subcommand;
+use serde_norway::{Mapping, Value};
+
+use crate::types::{DependencyGraph, Node};
+
+use super::FilterArgs;
+
+/// The `generate` subcommands.
+#[derive(Subcommand)]
+pub enum GenerateCommands {
+    /// Prints a GitLab child pipeline with the jobs of a template for every affected node,
+    /// dependencies first. `{{node}}`, `{{path}}` and `{{metadata.KEY}}` (a dotted path) are
+    /// replaced in the job names and values of the template.
+    GitlabCi {
+        /// The JSON artifact file path containing the previously prepared dependency graph
+        /// from the `prepare` command
+        #[arg(short, long, value_name = "FILE")]
+        graph_artifact_path: PathBuf,
+        /// A list of file paths to query.
+        #[arg(short, long, value_name = "FILE")]
+        files: Vec<PathBuf>,
+        /// Also query the files changed since the merge base with this ref, as with `query`.
+        #[arg(long, value_name = "REF")]
+        since: Option<String>,
+        /// The YAML file of the jobs to generate for each node, keyed by job name.
+        #[arg(short, long, value_name = "FILE")]
+        template: PathBuf,
+        /// Make each job `needs` the jobs of the same template of the node's affected direct
+        /// dependencies, so that they run as soon as those are done.
+        #[arg(long, default_value_t = false)]
+        needs: bool,
+        #[command(flatten)]
+        filters: FilterArgs,
+    },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum GenerateError {
+    #[error("The template must be a mapping of job names to jobs")]
+    InvalidTemplate,
+    #[error("Unknown placeholder {{{{{0}}}}}, use node, path or metadata.KEY")]
+    UnknownPlaceholder(String),
+    #[error("Node '{0}' has no value for {{{{{1}}}}}")]
+    MissingValue(String, String),
+    #[error("Job '{0}' is generated twice, add {{{{node}}}} to its name")]
+    DuplicateJob(String),
+    #[error(transparent)]
+    Yaml(#[from] serde_norway::Error),
+}
+
+/// The job of the pipeline when no node is affected, since GitLab rejects a pipeline
+/// without jobs.
+const NO_JOB: &str = "no-affected-nodes";
+
+/// Generates a GitLab child pipeline running the template's jobs for every affected node,
+/// in dependency order. A string made of a single placeholder takes the value as is, so
+/// `script: "{{metadata.ci.test}}"` becomes the node's list of commands.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+/// * `changed_files` - The list of files that have changed
+/// * `filters` - Restrictions on the affected nodes
+/// * `template` - The jobs to generate for each node, keyed by job name
+/// * `needs` - Whether each job needs the same job of the node's affected direct dependencies
+///
+/// ### Returns
+/// * `String` - The pipeline YAML
+pub fn gitlab_ci(
+    graph: &DependencyGraph,
+    changed_files: &[PathBuf],
+    filters: &FilterArgs,
+    template: &Value,
+    needs: bool,
+) -> Result<String, GenerateError> {
+    let template = template.as_mapping().ok_or(GenerateError::InvalidTemplate)?;
+    let affected = filters.apply(graph, graph.get_affected_nodes(changed_files));
+    let nodes = graph.toposorted_subset(&affected);
+
+    // The name of each template job for each node, to resolve `needs`
+    let mut names: BTreeMap<(&str, usize), String> = BTreeMap::new();
+    let mut pipeline = Mapping::new();
+    for node in &nodes {
+        for (i, (name, job)) in template.iter().enumerate() {
+            let name = substitute(name, node)?;
+            let mut job = substitute(job, node)?;
+            if needs {
+                let needed: Vec<Value> = node.dependencies.iter()
+                    .filter_map(|dep| names.get(&(dep.as_str(), i)))
+                    .map(|name| Value::String(name.clone()))
+                    .collect();
+                if let Some(job) = job.as_mapping_mut().filter(|_| !needed.is_empty()) {
+                    match job.get_mut("needs").and_then(Value::as_sequence_mut) {
+                        Some(existing) => existing.extend(needed),
+                        None => {
+                            job.insert(Value::from("needs"), Value::Sequence(needed));
+                        }
+                    }
+                }
+            }
+            let key = name.as_str().map(str::to_string).unwrap_or_else(|| serde_norway::to_string(&name).unwrap_or_default());
+            if pipeline.insert(name, job).is_some() {
+                return Err(GenerateError::DuplicateJob(key));
+            }
+            names.insert((&node.name, i), key);
+        }
+    }
+
+    if pipeline.is_empty() {
+        let mut job = Mapping::new();
+        job.insert(Value::from("script"), Value::Sequence(vec![Value::from("echo No node is affected")]));
+        pipeline.insert(Value::from(NO_JOB), Value::Mapping(job));
+    }
+    Ok(serde_norway::to_string(&pipeline)?)
+}
+
+/// Replaces the placeholders of the template value, keys included, with the node's values.
+fn substitute(value: &Value, node: &Node) -> Result<Value, GenerateError> {
+    match value {
+        Value::String(text) => {
+            let trimmed = text.trim();
+            let whole = trimmed.strip_prefix("{{").and_then(|rest| rest.strip_suffix("}}")).filter(|inner| !inner.contains("{{"));
+            if let Some(placeholder) = whole {
+                return Ok(serde_norway::to_value(resolve(placeholder.trim(), node)?)?);
+            }
+
+            let mut out = String::new();
+            let mut rest = text.as_str();
+            while let Some(start) = rest.find("{{") {
+                let Some(end) = rest[start..].find("}}") else { break };
+                out.push_str(&rest[..start]);
+                out.push_str(&match resolve(rest[start + 2..start + end].trim(), node)? {
+                    serde_json::Value::String(s) => s,
+                    other => other.to_string(),
+                });
+                rest = &rest[start + end + 2..];
+            }
+            out.push_str(rest);
+            Ok(Value::String(out))
+        }
+        Value::Sequence(items) => Ok(Value::Sequence(items.iter().map(|item| substitute(item, node)).collect::<Result<_, _>>()?)),
+        Value::Mapping(mapping) => Ok(Value::Mapping(mapping.iter()
+            .map(|(key, value)| Ok((substitute(key, node)?, substitute(value, node)?)))
+            .collect::<Result<_, GenerateError>>()?)),
+        other => Ok(other.clone()),
+    }
+}
+
+/// The value of a placeholder for the node.
+fn resolve(placeholder: &str, node: &Node) -> Result<serde_json::Value, GenerateError> {
+    match placeholder {
+        "node" => Ok(node.name.clone().into()),
+        "path" => Ok(node.path.to_string_lossy().into()),
+        _ => {
+            let key = placeholder.strip_prefix("metadata.").ok_or_else(|| GenerateError::UnknownPlaceholder(placeholder.to_string()))?;
+            node.metadata_value(key)
+                .filter(|value| !value.is_null())
+                .cloned()
+                .ok_or_else(|| GenerateError::MissingValue(node.name.clone(), placeholder.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gitlab_ci() {
+        let node = |name: &str, deps: Vec<&str>, metadata: serde_json::Value| Node::new(
+            name.to_string(), PathBuf::from(format!("libs/{}", name)), vec![PathBuf::from("**")], vec![],
+            deps.into_iter().map(String::from).collect(), Some(metadata),
+        ).unwrap();
+        let graph = DependencyGraph::new(vec![
+            node("lib", vec![], serde_json::json!({ "ci": { "test": ["cargo test"] }, "image": "rust" })),
+            node("api", vec!["lib"], serde_json::json!({ "ci": { "test": ["npm test", "npm run e2e"] }, "image": "node" })),
+            node("docs", vec![], serde_json::json!({ "image": "python" })),
+        ], false).unwrap();
+        let template: Value = serde_norway::from_str("\
+test:{{node}}:
+  image: '{{metadata.image}}:latest'
+  script: '{{ metadata.ci.test }}'
+  variables:
+    NODE_PATH: '{{path}}'
+").unwrap();
+        let files = [PathBuf::from("libs/lib/src/a.rs")];
+        let filters = FilterArgs::default();
+
+        let pipeline: Value = serde_norway::from_str(&gitlab_ci(&graph, &files, &filters, &template, true).unwrap()).unwrap();
+        let jobs: Vec<&str> = pipeline.as_mapping().unwrap().keys().map(|key| key.as_str().unwrap()).collect();
+        assert_eq!(jobs, ["test:lib", "test:api"]);
+        let api = &pipeline["test:api"];
+        assert_eq!(api["image"], Value::from("node:latest"));
+        assert_eq!(api["script"], serde_norway::from_str::<Value>("[npm test, npm run e2e]").unwrap());
+        assert_eq!(api["variables"]["NODE_PATH"], Value::from("libs/api"));
+        assert_eq!(api["needs"], serde_norway::from_str::<Value>("[test:lib]").unwrap());
+        assert!(pipeline["test:lib"].get("needs").is_none());
+
+        let pipeline = gitlab_ci(&graph, &[], &filters, &template, true).unwrap();
+        assert!(pipeline.starts_with(NO_JOB));
+        assert!(matches!(
+            gitlab_ci(&graph, &[PathBuf::from("libs/docs/a.md")], &filters, &template, false),
+            Err(GenerateError::MissingValue(node, _)) if node == "docs"
+        ));
+        let fixed: Value = serde_norway::from_str("test:\n  script: [make]\n").unwrap();
+        assert!(matches!(gitlab_ci(&graph, &files, &filters, &fixed, false), Err(GenerateError::DuplicateJob(_))));
+    }
+}
@@ -0,0 +1,105 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[derive(Debug, thiserror::Error)]
+pub enum GitError {
+    #[error("Failed to run git: {0}")]
+    Spawn(#[from] std::io::Error),
+    #[error("git log failed: {0}")]
+    Log(String),
+    #[error("git diff failed: {0}")]
+    Diff(String),
+    #[error("Unknown commit '{0}': {1}")]
+    UnknownCommit(String, String),
+}
+
+/// A commit and the files it touched.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Commit {
+    pub hash: String,
+    /// Seconds since the Unix epoch, from the committer date.
+    pub timestamp: u64,
+    /// The full commit message, subject and body.
+    pub message: String,
+    pub files: Vec<PathBuf>,
+}
+
+/// Lists the non-merge commits of `since..until`, oldest first, with the files they touched
+/// relative to the current directory. Files outside of the current directory are left out.
+pub fn commits(since: &str, until: &str) -> Result<Vec<Commit>, GitError> {
+    let output = Command::new("git")
+        .args(["-c", "core.quotepath=off", "log", "--reverse", "--no-merges", "--no-renames", "--relative", "--name-only"])
+        .arg("--format=%x1e%H %ct%n%B%x1f")
+        .arg(format!("{}..{}", since, until))
+        .output()?;
+    if !output.status.success() {
+        return Err(GitError::Log(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+    Ok(parse_log(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Lists the files changed between the merge base of `since` and `HEAD`, and the working
+/// tree, relative to the current directory. Renamed files are listed under both names.
+/// Untracked files are left out.
+pub fn changed_files(since: &str) -> Result<Vec<PathBuf>, GitError> {
+    let output = Command::new("git")
+        .args(["-c", "core.quotepath=off", "diff", "--name-only", "--no-renames", "--relative", "--merge-base", since])
+        .output()?;
+    if !output.status.success() {
+        return Err(GitError::Diff(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).lines().filter(|line| !line.is_empty()).map(PathBuf::from).collect())
+}
+
+/// The commit checked out in `dir`, or `None` if it isn't inside a git repository.
+pub fn head_commit(dir: &Path) -> Option<String> {
+    let output = Command::new("git").arg("-C").arg(dir).args(["rev-parse", "HEAD"]).output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Resolves a revision, like a branch, a tag or an abbreviated hash, to the full hash of its
+/// commit. Outside of a repository, or one without the commit, only full hashes are accepted.
+pub fn resolve_commit(revision: &str) -> Result<String, GitError> {
+    let output = Command::new("git").args(["rev-parse", "--verify"]).arg(format!("{}^{{commit}}", revision)).output()?;
+    if output.status.success() {
+        return Ok(String::from_utf8_lossy(&output.stdout).trim().to_string());
+    }
+    if revision.len() == 40 && revision.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Ok(revision.to_ascii_lowercase());
+    }
+    Err(GitError::UnknownCommit(revision.to_string(), String::from_utf8_lossy(&output.stderr).trim().to_string()))
+}
+
+/// Parses `git log --name-only` output where each commit starts with a record separator,
+/// followed by the hash and the timestamp, then the message ended by a unit separator.
+fn parse_log(log: &str) -> Vec<Commit> {
+    log.split('\x1e')
+        .filter_map(|record| {
+            let (header, rest) = record.split_once('\n')?;
+            let (hash, timestamp) = header.split_once(' ')?;
+            let (message, files) = rest.split_once('\x1f')?;
+            Some(Commit {
+                hash: hash.to_string(),
+                timestamp: timestamp.trim().parse().ok()?,
+                message: message.trim().to_string(),
+                files: files.lines().filter(|line| !line.is_empty()).map(PathBuf::from).collect(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_log() {
+        let log = "\x1eaaa 1700000000\nfeat: add a\n\nSee #1\n\x1f\n\nlib/src/a.rs\napp/main.rs\n\x1ebbb 1700100000\nchore: empty\n\x1f\n";
+        let commits = parse_log(log);
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0].message, "feat: add a\n\nSee #1");
+        assert_eq!(commits[0].files, vec![PathBuf::from("lib/src/a.rs"), PathBuf::from("app/main.rs")]);
+        assert_eq!((commits[1].hash.as_str(), commits[1].timestamp), ("bbb", 1700100000));
+        assert!(commits[1].files.is_empty());
+    }
+}
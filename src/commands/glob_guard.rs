@@ -0,0 +1,128 @@
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+use crate::types::{negation, DependencyGraph, GlobGuardConfig, Node, Severity};
+
+/// A node whose include patterns likely match far more than intended, so that every change
+/// affects it.
+#[derive(Debug, Clone)]
+pub struct GlobWarning {
+    pub severity: Severity,
+    pub node: String,
+    /// The manifest declaring the node, when known.
+    pub manifest: Option<PathBuf>,
+    /// What's wrong, following the node's name.
+    pub message: String,
+}
+
+/// Checks the include patterns of every node: patterns reaching outside the node's directory,
+/// a node at the root of the repository claiming the files of all the others with a leading
+/// `**`, and, when `config.max_files` is set, nodes matching more files than that. Counting
+/// walks the nodes' directories, so it must run from the directory `prepare` ran in.
+///
+/// ### Arguments
+/// * `graph` - The prepared graph
+/// * `config` - The `[glob_guard]` section of the root config
+///
+/// ### Returns
+/// * `Vec<GlobWarning>` - One warning per offending pattern or node, by node name
+pub fn check_globs(graph: &DependencyGraph, config: &GlobGuardConfig) -> Result<Vec<GlobWarning>, walkdir::Error> {
+    let mut nodes = graph.get_all_nodes();
+    nodes.sort_by(|a, b| a.name.cmp(&b.name));
+    let warn = |node: &Node, message: String| GlobWarning {
+        severity: config.severity,
+        node: node.name.clone(),
+        manifest: node.manifest.clone(),
+        message,
+    };
+
+    let mut warnings = Vec::new();
+    for node in &nodes {
+        // Negated patterns only take files away
+        for pattern in node.included_paths.iter().filter(|pattern| !negation(pattern).0) {
+            if escapes(pattern) {
+                warnings.push(warn(node, format!(
+                    "includes {}, which reaches outside its directory",
+                    pattern.display(),
+                )));
+            } else if node.path.as_os_str().is_empty() && starts_with_globstar(pattern) && nodes.len() > 1 {
+                warnings.push(warn(node, format!(
+                    "is at the root of the repository and includes {}, which claims the files of every other node",
+                    pattern.display(),
+                )));
+            }
+        }
+        if let Some(max) = config.max_files {
+            let files = super::node_files(node)?.len();
+            if files > max {
+                warnings.push(warn(node, format!(
+                    "matches {} files, more than the allowed {}",
+                    files, max,
+                )));
+            }
+        }
+    }
+    Ok(warnings)
+}
+
+/// Whether the pattern is absolute or goes up a directory, matching outside the node.
+fn escapes(pattern: &Path) -> bool {
+    pattern.components().any(|component| matches!(component, Component::ParentDir | Component::RootDir | Component::Prefix(_)))
+}
+
+fn starts_with_globstar(pattern: &Path) -> bool {
+    pattern.components().next().is_some_and(|first| first.as_os_str().to_string_lossy().starts_with("**"))
+}
+
+impl fmt::Display for GlobWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}[glob-guard]: {} {}", severity, self.node, self.message)?;
+        if let Some(manifest) = &self.manifest {
+            write!(f, "\n  --> {}", manifest.display())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(name: &str, path: &str, include: Vec<&str>) -> Node {
+        Node::new(
+            name.to_string(),
+            PathBuf::from(path),
+            include.into_iter().map(PathBuf::from).collect(),
+            vec![],
+            vec![],
+            None,
+        ).unwrap()
+    }
+
+    #[test]
+    fn test_check_globs() {
+        let graph = DependencyGraph::new(vec![
+            node("api", "services/api", vec!["src/**", "../shared/**"]),
+            node("web", "web", vec!["/**"]),
+            node("tooling", "", vec!["**/*.yaml", "scripts/**"]),
+            node("docs", "docs", vec!["**"]),
+        ], false).unwrap();
+
+        let warnings = check_globs(&graph, &GlobGuardConfig::default()).unwrap();
+        let flagged: Vec<(&str, Severity)> = warnings.iter().map(|w| (w.node.as_str(), w.severity)).collect();
+        assert_eq!(flagged, vec![
+            ("api", Severity::Warning),
+            ("tooling", Severity::Warning),
+            ("web", Severity::Warning),
+        ]);
+        assert!(warnings[0].message.contains("../shared/**"));
+
+        // A root node alone in the graph owns everything anyway
+        let alone = DependencyGraph::new(vec![node("tooling", "", vec!["**"])], false).unwrap();
+        assert!(check_globs(&alone, &GlobGuardConfig::default()).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,315 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use clap::Subcommand;
+use serde::Serialize;
+
+use crate::types::{AffectedReason, DependencyGraph, Node};
+
+use super::export::{DiagramFormat, DiagramScope};
+
+/// The `graph` subcommands.
+#[derive(Subcommand)]
+pub enum GraphCommands {
+    /// Draws the graph into an image, dependents above their dependencies. With `--files`,
+    /// the changed nodes are filled in red and the nodes they affect in pink.
+    Render {
+        /// The JSON artifact file path containing the previously prepared dependency graph
+        /// from the `prepare` command
+        #[arg(short, long, value_name = "FILE")]
+        graph_artifact_path: PathBuf,
+        /// The image to write. SVG is laid out by dependency-cascade itself, PNG needs
+        /// Graphviz's `dot` on the PATH.
+        #[arg(long, value_name = "FILE")]
+        out: PathBuf,
+        /// Changed files whose affected nodes to highlight.
+        #[arg(short, long, value_name = "FILE")]
+        files: Vec<PathBuf>,
+    },
+    /// Lists the dependency edges with their data, printed as JSON: their `kind`, the manifest
+    /// they're `declared_in` and the `paths` of the dependency that cascade through them.
+    Edges {
+        /// The JSON artifact file path containing the previously prepared dependency graph
+        /// from the `prepare` command
+        #[arg(short, long, value_name = "FILE")]
+        graph_artifact_path: PathBuf,
+        /// Only list the edges from or to this node.
+        #[arg(short, long, value_name = "NAME")]
+        node: Option<String>,
+    },
+    /// Tells whether DEPENDENT depends on DEPENDENCY, directly or not: prints `true` and exits
+    /// with status 0 if it does, prints `false` and exits with status 1 if it doesn't. Unknown
+    /// nodes exit with status 2. Instant on artifacts prepared with `--reachability`.
+    Reaches {
+        /// The JSON artifact file path containing the previously prepared dependency graph
+        /// from the `prepare` command
+        #[arg(short, long, value_name = "FILE")]
+        graph_artifact_path: PathBuf,
+        dependent: String,
+        dependency: String,
+    },
+}
+
+/// A dependency edge, for `graph edges`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EdgeEntry {
+    pub dependent: String,
+    pub dependency: String,
+    pub kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub declared_in: Option<PathBuf>,
+    pub paths: Vec<PathBuf>,
+}
+
+/// Lists the edges of the graph, sorted by dependent then dependency.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+/// * `node` - Only keep the edges from or to this node
+pub fn edges(graph: &DependencyGraph, node: Option<&str>) -> Vec<EdgeEntry> {
+    graph.edges().into_iter()
+        .filter(|(dependent, dependency, _)| node.is_none_or(|node| dependent.name == node || dependency.name == node))
+        .map(|(dependent, dependency, edge)| EdgeEntry {
+            dependent: dependent.name.clone(),
+            dependency: dependency.name.clone(),
+            kind: edge.kind.as_str(),
+            declared_in: edge.declared_in.clone(),
+            paths: edge.paths.clone(),
+        })
+        .collect()
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RenderError {
+    #[error("Unsupported image format '{0}', use .svg or .png")]
+    UnsupportedFormat(String),
+    #[error("Failed to run dot, is Graphviz installed? {0}")]
+    Dot(std::io::Error),
+    #[error("dot failed: {0}")]
+    DotFailed(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+const NODE_HEIGHT: usize = 30;
+const ROW_GAP: usize = 60;
+const COLUMN_GAP: usize = 20;
+const MARGIN: usize = 20;
+/// An approximation of the width of a character of the 12px sans-serif font.
+const CHAR_WIDTH: usize = 7;
+
+pub(super) const CHANGED_FILL: &str = "#f87171";
+pub(super) const AFFECTED_FILL: &str = "#fecaca";
+pub(super) const DEFAULT_FILL: &str = "#f3f4f6";
+
+/// Renders the graph to `out`, as SVG or PNG depending on its extension.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+/// * `out` - The image to write
+/// * `changed_files` - The changed files whose affected nodes to highlight
+pub fn render(graph: &DependencyGraph, out: &Path, changed_files: &[PathBuf]) -> Result<(), RenderError> {
+    let reasons = graph.get_affected_reasons(changed_files);
+    let extension = out.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+    let image = match extension.as_str() {
+        "svg" => svg(graph, &reasons).into_bytes(),
+        "png" => dot_png(&dot(graph, &reasons))?,
+        _ => return Err(RenderError::UnsupportedFormat(extension)),
+    };
+    super::write_atomic(out, image)?;
+    Ok(())
+}
+
+fn fill(reasons: &BTreeMap<String, AffectedReason>, name: &str) -> &'static str {
+    match reasons.get(name) {
+        Some(AffectedReason::Changed { .. }) => CHANGED_FILL,
+        Some(AffectedReason::Dependency { .. } | AffectedReason::Upstream { .. }) => AFFECTED_FILL,
+        None => DEFAULT_FILL,
+    }
+}
+
+/// Assigns every node a layer, one more than its deepest dependency, and orders each layer
+/// by the mean position of the nodes' dependencies in the layers below, then by name.
+fn layers(graph: &DependencyGraph) -> Vec<Vec<&Node>> {
+    let names: Vec<String> = graph.get_all_nodes().into_iter().map(|node| node.name.clone()).collect();
+    let mut layer_of: HashMap<&str, usize> = HashMap::new();
+    let mut layers: Vec<Vec<&Node>> = Vec::new();
+    // Nodes come after their dependencies, except within cycles where missing ones count as 0
+    for node in graph.toposorted_subset(&names) {
+        let layer = node.dependencies.iter()
+            .filter_map(|dep| layer_of.get(dep.as_str()).map(|l| l + 1))
+            .max()
+            .unwrap_or(0);
+        layer_of.insert(&node.name, layer);
+        if layers.len() <= layer {
+            layers.resize(layer + 1, Vec::new());
+        }
+        layers[layer].push(node);
+    }
+
+    let mut position: HashMap<&str, f64> = HashMap::new();
+    for layer in &mut layers {
+        let barycenter = |node: &Node| -> f64 {
+            let placed: Vec<f64> = node.dependencies.iter().filter_map(|dep| position.get(dep.as_str()).copied()).collect();
+            if placed.is_empty() { 0.0 } else { placed.iter().sum::<f64>() / placed.len() as f64 }
+        };
+        layer.sort_by(|a, b| barycenter(a).total_cmp(&barycenter(b)).then_with(|| a.name.cmp(&b.name)));
+        for (i, node) in layer.iter().enumerate() {
+            position.insert(&node.name, i as f64);
+        }
+    }
+    layers
+}
+
+/// Lays out and draws the graph as SVG, without external tools.
+fn svg(graph: &DependencyGraph, reasons: &BTreeMap<String, AffectedReason>) -> String {
+    let layers = layers(graph);
+    let width_of = |node: &Node| node.name.chars().count() * CHAR_WIDTH + 2 * COLUMN_GAP;
+    let row_widths: Vec<usize> = layers.iter()
+        .map(|layer| layer.iter().map(|node| width_of(node) + COLUMN_GAP).sum::<usize>())
+        .collect();
+    let width = row_widths.iter().copied().max().unwrap_or(0) + 2 * MARGIN;
+    let height = (layers.len() * (NODE_HEIGHT + ROW_GAP)).saturating_sub(ROW_GAP) + 2 * MARGIN;
+
+    // Dependencies at the bottom, rows centered
+    let mut boxes: HashMap<&str, (usize, usize, usize)> = HashMap::new();
+    for (i, layer) in layers.iter().enumerate() {
+        let y = MARGIN + (layers.len() - 1 - i) * (NODE_HEIGHT + ROW_GAP);
+        let mut x = (width - row_widths[i]) / 2 + COLUMN_GAP / 2;
+        for node in layer {
+            boxes.insert(&node.name, (x, y, width_of(node)));
+            x += width_of(node) + COLUMN_GAP;
+        }
+    }
+
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\" font-family=\"sans-serif\" font-size=\"12\">\n\
+         <defs><marker id=\"arrow\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" markerWidth=\"6\" markerHeight=\"6\" orient=\"auto\"><path d=\"M0,0 L10,5 L0,10 z\" fill=\"#6b7280\"/></marker></defs>\n"
+    );
+    for layer in &layers {
+        for node in layer {
+            let (x, y, w) = boxes[node.name.as_str()];
+            for dep in &node.dependencies {
+                let Some(&(dx, dy, dw)) = boxes.get(dep.as_str()) else { continue };
+                out.push_str(&format!(
+                    "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"#6b7280\" marker-end=\"url(#arrow)\"/>\n",
+                    x + w / 2, y + NODE_HEIGHT, dx + dw / 2, dy,
+                ));
+            }
+        }
+    }
+    for layer in &layers {
+        for node in layer {
+            let (x, y, w) = boxes[node.name.as_str()];
+            out.push_str(&format!(
+                "<g><title>{name}</title><rect x=\"{x}\" y=\"{y}\" width=\"{w}\" height=\"{NODE_HEIGHT}\" rx=\"4\" fill=\"{fill}\" stroke=\"#374151\"/>\
+                 <text x=\"{cx}\" y=\"{cy}\" text-anchor=\"middle\" dominant-baseline=\"middle\">{name}</text></g>\n",
+                name = escape(&node.name),
+                fill = fill(reasons, &node.name),
+                cx = x + w / 2,
+                cy = y + NODE_HEIGHT / 2,
+            ));
+        }
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+/// Describes the whole graph in Graphviz's DOT language, as `export diagram` does.
+fn dot(graph: &DependencyGraph, reasons: &BTreeMap<String, AffectedReason>) -> String {
+    super::export::diagram(graph, reasons, DiagramFormat::Dot, &DiagramScope::default()).expect("only an unknown focus fails")
+}
+
+fn dot_png(dot: &str) -> Result<Vec<u8>, RenderError> {
+    let mut child = Command::new("dot")
+        .arg("-Tpng")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(RenderError::Dot)?;
+    child.stdin.take().expect("stdin is piped").write_all(dot.as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(RenderError::DotFailed(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+    Ok(output.stdout)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(name: &str, deps: Vec<&str>) -> Node {
+        Node::new(
+            name.to_string(),
+            PathBuf::from(name),
+            vec![PathBuf::from("**")],
+            vec![],
+            deps.into_iter().map(String::from).collect(),
+            None,
+        ).unwrap()
+    }
+
+    #[test]
+    fn test_render_svg() {
+        let graph = DependencyGraph::new(vec![
+            node("core", vec![]),
+            node("api", vec!["core"]),
+            node("<web>", vec!["api", "core"]),
+            node("docs", vec![]),
+        ], false).unwrap();
+
+        let layers: Vec<Vec<&str>> = layers(&graph).iter()
+            .map(|layer| layer.iter().map(|node| node.name.as_str()).collect())
+            .collect();
+        assert_eq!(layers, vec![vec!["core", "docs"], vec!["api"], vec!["<web>"]]);
+
+        let reasons = graph.get_affected_reasons(&[PathBuf::from("api/main.rs")]);
+        let svg = svg(&graph, &reasons);
+        assert_eq!(svg.matches("<rect").count(), 4);
+        assert_eq!(svg.matches("<line").count(), 3);
+        // The middle row, centered
+        assert!(svg.contains(&format!("<title>api</title><rect x=\"77\" y=\"110\" width=\"61\" height=\"30\" rx=\"4\" fill=\"{}\"", CHANGED_FILL)));
+        assert!(svg.contains("&lt;web&gt;"));
+        assert_eq!(svg.matches(AFFECTED_FILL).count(), 1);
+
+        assert!(dot(&graph, &reasons).contains("  \"<web>\" -> \"api\";\n"));
+    }
+
+    #[test]
+    fn test_edges() {
+        let mut web = node("web", vec!["api", "schema"]);
+        web.manifest = Some(PathBuf::from("web/dependencies.toml"));
+        web.dependency_edges.insert("schema".to_string(), crate::types::Edge {
+            kind: crate::types::EdgeKind::Build,
+            declared_in: None,
+            paths: vec![PathBuf::from("proto/**")],
+        });
+        let graph = DependencyGraph::new(vec![node("schema", vec![]), node("api", vec![]), web], false).unwrap();
+        assert_eq!(graph.info().features, vec!["edge_data"]);
+
+        let listed = edges(&graph, Some("web"));
+        assert_eq!(listed.len(), 2);
+        assert_eq!((listed[0].dependency.as_str(), listed[0].kind, listed[0].declared_in.is_some()), ("api", "runtime", true));
+        assert_eq!(listed[1], EdgeEntry {
+            dependent: "web".to_string(),
+            dependency: "schema".to_string(),
+            kind: "build",
+            declared_in: Some(PathBuf::from("web/dependencies.toml")),
+            paths: vec![PathBuf::from("proto/**")],
+        });
+        assert_eq!(edges(&graph, Some("api")).len(), 1);
+
+        // Only the schema's protos cascade to web
+        assert_eq!(graph.get_affected_nodes(&[PathBuf::from("schema/README.md")]), vec!["schema"]);
+        let reasons = graph.get_affected_reasons(&[PathBuf::from("schema/README.md"), PathBuf::from("schema/proto/user.proto")]);
+        assert_eq!(reasons["web"], AffectedReason::Dependency { via: "schema".to_string() });
+    }
+}
@@ -0,0 +1,152 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::types::{DependencyGraph, Node};
+
+/// Why `hash` failed.
+#[derive(Debug, thiserror::Error)]
+pub enum HashError {
+    #[error(transparent)]
+    Walk(#[from] walkdir::Error),
+    #[error("Unable to read {0}: {1}")]
+    Read(PathBuf, std::io::Error),
+}
+
+/// The cache key of a single node.
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeHash {
+    /// Covers the node's own files and definition plus the hashes of its dependencies.
+    pub hash: String,
+    /// Covers only the node's own files and definition.
+    pub content_hash: String,
+    /// The number of files matched by the node.
+    pub files: usize,
+}
+
+/// Hashes every node of the graph, dependencies first, reading the files relative to
+/// the current directory.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+/// * `nodes` - The nodes to report. Defaults to all of them when empty
+///
+/// ### Returns
+/// * `BTreeMap<String, NodeHash>` - The hashes, keyed by node name
+pub fn hash(graph: &DependencyGraph, nodes: &[String]) -> Result<BTreeMap<String, NodeHash>, HashError> {
+    let all: Vec<String> = graph.get_all_nodes().into_iter().map(|n| n.name.clone()).collect();
+    let mut hashes: HashMap<String, NodeHash> = HashMap::new();
+
+    for node in graph.toposorted_subset(&all) {
+        let files = super::node_files(node)?;
+        let mut contents = Vec::with_capacity(files.len());
+        for file in &files {
+            contents.push((relative_to(file, &node.path), fs::read(file).map_err(|e| HashError::Read(file.clone(), e))?));
+        }
+        let content_hash = content_hash(node, &contents);
+
+        // Dependencies caught in a cycle aren't hashed yet and only contribute their name
+        let mut dependencies: Vec<(&str, &str)> = node.dependencies.iter()
+            .map(|dep| (dep.as_str(), hashes.get(dep).map_or("", |h| h.hash.as_str())))
+            .collect();
+        dependencies.sort();
+        let hash = closure_hash(&content_hash, &dependencies);
+
+        hashes.insert(node.name.clone(), NodeHash { hash, content_hash, files: files.len() });
+    }
+
+    Ok(hashes.into_iter()
+        .filter(|(name, _)| nodes.is_empty() || nodes.contains(name))
+        .collect())
+}
+
+fn relative_to(file: &Path, base: &Path) -> PathBuf {
+    file.strip_prefix(base).map(Path::to_path_buf).unwrap_or_else(|_| file.to_path_buf())
+}
+
+/// Hashes the node definition and its files. Paths are relative to the node so the
+/// hash doesn't change when the repository is checked out somewhere else.
+fn content_hash(node: &Node, files: &[(PathBuf, Vec<u8>)]) -> String {
+    let mut hasher = Sha256::new();
+
+    let definition = serde_json::json!({
+        "name": node.name,
+        "include": node.included_paths,
+        "exclude": node.excluded_paths,
+        "dependencies": node.dependencies,
+        "tags": node.tags,
+        "metadata": node.metadata,
+    });
+    hasher.update(definition.to_string().as_bytes());
+
+    // Lengths are included so that no two different file sets hash the same bytes
+    for (path, content) in files {
+        let path = path.to_string_lossy().replace('\\', "/");
+        hasher.update((path.len() as u64).to_le_bytes());
+        hasher.update(path.as_bytes());
+        hasher.update((content.len() as u64).to_le_bytes());
+        hasher.update(content);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Combines a node's content hash with the (sorted) hashes of its dependencies.
+fn closure_hash(content_hash: &str, dependencies: &[(&str, &str)]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(content_hash.as_bytes());
+    for (name, hash) in dependencies {
+        hasher.update(b"\0");
+        hasher.update(name.as_bytes());
+        hasher.update(b"=");
+        hasher.update(hash.as_bytes());
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(deps: Vec<&str>) -> Node {
+        Node::new(
+            "a".to_string(),
+            PathBuf::from("a"),
+            vec![PathBuf::from("src/**")],
+            vec![],
+            deps.into_iter().map(String::from).collect(),
+            None,
+        ).unwrap()
+    }
+
+    fn files(entries: &[(&str, &str)]) -> Vec<(PathBuf, Vec<u8>)> {
+        entries.iter().map(|(p, c)| (PathBuf::from(p), c.as_bytes().to_vec())).collect()
+    }
+
+    #[test]
+    fn test_content_hash() {
+        let base = content_hash(&node(vec![]), &files(&[("src/a.rs", "fn a() {}")]));
+
+        assert_eq!(base, content_hash(&node(vec![]), &files(&[("src/a.rs", "fn a() {}")])));
+        assert_ne!(base, content_hash(&node(vec![]), &files(&[("src/a.rs", "fn b() {}")])));
+        assert_ne!(base, content_hash(&node(vec![]), &files(&[("src/b.rs", "fn a() {}")])));
+        assert_ne!(base, content_hash(&node(vec!["b"]), &files(&[("src/a.rs", "fn a() {}")])));
+        // Moving bytes between the path and the content must change the hash
+        assert_ne!(
+            content_hash(&node(vec![]), &files(&[("src/ab", "c")])),
+            content_hash(&node(vec![]), &files(&[("src/a", "bc")])),
+        );
+    }
+
+    #[test]
+    fn test_closure_hash() {
+        let own = "abc";
+        let base = closure_hash(own, &[("b", "111"), ("c", "222")]);
+
+        assert_eq!(base, closure_hash(own, &[("b", "111"), ("c", "222")]));
+        assert_ne!(base, closure_hash(own, &[("b", "111"), ("c", "333")]));
+        assert_ne!(base, closure_hash(own, &[("b", "111")]));
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, UNIX_EPOCH};
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::types::{AffectedReason, DependencyGraph};
+
+use super::git::{self, Commit, GitError};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// How commits are grouped for the trend of `history`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum HistoryPeriod {
+    Day,
+    /// Weeks start on Monday.
+    Week,
+    Month,
+}
+
+/// How often a node was affected over the commit range.
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeImpact {
+    pub node: String,
+    /// Commits that affected the node, directly or through a dependency.
+    pub affected: usize,
+    /// Commits that changed the node's own files.
+    pub changed: usize,
+    /// The share of the commits that affected the node, between 0 and 1.
+    pub affected_ratio: f64,
+}
+
+/// The commits of a single period.
+#[derive(Debug, Clone, Serialize)]
+pub struct PeriodImpact {
+    /// The first day of the period, as `YYYY-MM-DD`.
+    pub period: String,
+    pub commits: usize,
+    pub average_blast_radius: f64,
+    pub max_blast_radius: usize,
+}
+
+/// The impact of every commit in a range, aggregated.
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryReport {
+    pub since: String,
+    pub until: String,
+    pub commits: usize,
+    /// The average number of nodes affected by a commit.
+    pub average_blast_radius: f64,
+    /// Every node affected at least once, most affected first.
+    pub nodes: Vec<NodeImpact>,
+    /// The blast radius per period, oldest first.
+    pub trend: Vec<PeriodImpact>,
+}
+
+/// Computes the affected nodes of every (non-merge) commit in `since..until` and aggregates
+/// them. Must run from the directory `prepare` ran in, inside the git repository. Every commit
+/// is evaluated against the given graph, not the graph as it was at the time.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+/// * `since` - The commit to start after, e.g. a tag or `HEAD~100`
+/// * `until` - The last commit of the range
+/// * `period` - How commits are grouped for the trend
+///
+/// ### Returns
+/// * `HistoryReport` - The aggregated impact
+pub fn history(graph: &DependencyGraph, since: &str, until: &str, period: HistoryPeriod) -> Result<HistoryReport, GitError> {
+    let commits = git::commits(since, until)?;
+    Ok(aggregate(graph, &commits, since, until, period))
+}
+
+fn aggregate(graph: &DependencyGraph, commits: &[Commit], since: &str, until: &str, period: HistoryPeriod) -> HistoryReport {
+    let mut impacts: HashMap<String, (usize, usize)> = HashMap::new();
+    let mut periods: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    let mut total_affected = 0;
+
+    for commit in commits {
+        let affected = graph.get_affected_reasons(&commit.files);
+        log::debug!("{} affects {} nodes", commit.hash, affected.len());
+        for (node, reason) in &affected {
+            let impact = impacts.entry(node.clone()).or_default();
+            impact.0 += 1;
+            if matches!(reason, AffectedReason::Changed { .. }) {
+                impact.1 += 1;
+            }
+        }
+        total_affected += affected.len();
+        periods.entry(period_start(commit.timestamp, period)).or_default().push(affected.len());
+    }
+
+    let mut nodes: Vec<NodeImpact> = impacts.into_iter()
+        .map(|(node, (affected, changed))| NodeImpact {
+            node,
+            affected,
+            changed,
+            affected_ratio: affected as f64 / commits.len() as f64,
+        })
+        .collect();
+    nodes.sort_by(|a, b| b.affected.cmp(&a.affected).then_with(|| a.node.cmp(&b.node)));
+
+    HistoryReport {
+        since: since.to_string(),
+        until: until.to_string(),
+        commits: commits.len(),
+        average_blast_radius: average(total_affected, commits.len()),
+        nodes,
+        trend: periods.into_iter()
+            .map(|(period, radii)| PeriodImpact {
+                period,
+                commits: radii.len(),
+                average_blast_radius: average(radii.iter().sum(), radii.len()),
+                max_blast_radius: radii.iter().copied().max().unwrap_or(0),
+            })
+            .collect(),
+    }
+}
+
+fn average(total: usize, count: usize) -> f64 {
+    if count == 0 { 0.0 } else { total as f64 / count as f64 }
+}
+
+/// The first day of the period containing `timestamp`, as `YYYY-MM-DD` (in UTC).
+fn period_start(timestamp: u64, period: HistoryPeriod) -> String {
+    let days = timestamp / SECONDS_PER_DAY;
+    let day = |days: u64| {
+        let date = humantime::format_rfc3339(UNIX_EPOCH + Duration::from_secs(days * SECONDS_PER_DAY)).to_string();
+        date[..10].to_string()
+    };
+    match period {
+        HistoryPeriod::Day => day(days),
+        // The epoch was a Thursday, three days after a Monday
+        HistoryPeriod::Week => day(days.saturating_sub((days + 3) % 7)),
+        HistoryPeriod::Month => format!("{}-01", &day(days)[..7]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::types::Node;
+
+    use super::*;
+
+    fn node(name: &str, deps: Vec<&str>) -> Node {
+        Node::new(
+            name.to_string(),
+            PathBuf::from(name),
+            vec![PathBuf::from("**/*")],
+            vec![],
+            deps.into_iter().map(String::from).collect(),
+            None,
+        ).unwrap()
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let graph = DependencyGraph::new(vec![node("lib", vec![]), node("app", vec!["lib"])], false).unwrap();
+        let commit = |timestamp, file: &str| Commit {
+            hash: String::new(),
+            timestamp,
+            message: String::new(),
+            files: vec![PathBuf::from(file)],
+        };
+        // Tuesday 2023-11-14, Wednesday 2023-11-15 and Monday 2023-11-20
+        let commits = [commit(1699963200, "lib/a.rs"), commit(1700049600, "app/a.rs"), commit(1700481600, "docs/a.md")];
+
+        let report = aggregate(&graph, &commits, "v1", "HEAD", HistoryPeriod::Week);
+        assert_eq!(report.commits, 3);
+        assert_eq!(report.average_blast_radius, 1.0);
+        assert_eq!(report.nodes[0].node, "app");
+        assert_eq!((report.nodes[0].affected, report.nodes[0].changed), (2, 1));
+        assert_eq!((report.nodes[1].affected, report.nodes[1].changed), (1, 1));
+
+        let trend: Vec<_> = report.trend.iter().map(|p| (p.period.as_str(), p.commits, p.max_blast_radius)).collect();
+        assert_eq!(trend, vec![("2023-11-13", 2, 2), ("2023-11-20", 1, 0)]);
+        assert_eq!(period_start(1700049600, HistoryPeriod::Month), "2023-11-01");
+    }
+}
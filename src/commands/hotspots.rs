@@ -0,0 +1,119 @@
+use serde::Serialize;
+
+use crate::types::DependencyGraph;
+
+use super::git::{self, Commit, GitError};
+
+/// A node ranked by how often it changes and how much depends on it.
+#[derive(Debug, Clone, Serialize)]
+pub struct Hotspot {
+    pub node: String,
+    /// The number of commits that changed the node's own files.
+    pub churn: usize,
+    /// The number of direct and indirect dependents.
+    pub dependents: usize,
+    /// `churn × dependents`.
+    pub score: usize,
+}
+
+/// Ranks the nodes changed in `since..until` by churn times transitive dependents, highest
+/// first. Must run from the directory `prepare` ran in, inside the git repository.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+/// * `since` - The commit to start after
+/// * `until` - The last commit of the range
+/// * `limit` - Only keep the first `limit` hotspots
+///
+/// ### Returns
+/// * `Vec<Hotspot>` - Every node changed at least once, by decreasing score
+pub fn hotspots(graph: &DependencyGraph, since: &str, until: &str, limit: Option<usize>) -> Result<Vec<Hotspot>, GitError> {
+    let mut hotspots = rank(graph, &git::commits(since, until)?);
+    if let Some(limit) = limit {
+        hotspots.truncate(limit);
+    }
+    Ok(hotspots)
+}
+
+fn rank(graph: &DependencyGraph, commits: &[Commit]) -> Vec<Hotspot> {
+    let mut hotspots: Vec<Hotspot> = graph.get_all_nodes().into_iter()
+        .map(|node| {
+            let churn = commits.iter()
+                .filter(|commit| commit.files.iter().any(|file| node.includes_path(file)))
+                .count();
+            let dependents = graph.get_dependents(&node.name).len();
+            Hotspot { node: node.name.clone(), churn, dependents, score: churn * dependents }
+        })
+        .filter(|hotspot| hotspot.churn > 0)
+        .collect();
+    hotspots.sort_by(|a, b| {
+        b.score.cmp(&a.score)
+            .then_with(|| b.churn.cmp(&a.churn))
+            .then_with(|| a.node.cmp(&b.node))
+    });
+    hotspots
+}
+
+/// Renders the hotspots as a markdown table.
+pub fn hotspots_markdown(hotspots: &[Hotspot]) -> String {
+    let mut out = String::from("| Node | Churn | Dependents | Score |\n|---|---:|---:|---:|\n");
+    for hotspot in hotspots {
+        out.push_str(&format!(
+            "| {} | {} | {} | {} |\n",
+            hotspot.node.replace('|', "\\|"), hotspot.churn, hotspot.dependents, hotspot.score,
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::types::Node;
+
+    use super::*;
+
+    fn node(name: &str, deps: Vec<&str>) -> Node {
+        Node::new(
+            name.to_string(),
+            PathBuf::from(name),
+            vec![PathBuf::from("**/*")],
+            vec![],
+            deps.into_iter().map(String::from).collect(),
+            None,
+        ).unwrap()
+    }
+
+    #[test]
+    fn test_rank() {
+        let graph = DependencyGraph::new(vec![
+            node("core", vec![]),
+            node("api", vec!["core"]),
+            node("web", vec!["api"]),
+            node("docs", vec![]),
+        ], false).unwrap();
+        let commit = |files: &[&str]| Commit {
+            hash: String::new(),
+            timestamp: 0,
+            message: String::new(),
+            files: files.iter().map(PathBuf::from).collect(),
+        };
+        let commits = [
+            commit(&["core/lib.rs"]),
+            commit(&["web/app.ts", "web/index.html"]),
+            commit(&["web/app.ts"]),
+            commit(&["web/app.ts", "api/main.rs"]),
+        ];
+
+        let ranked: Vec<_> = rank(&graph, &commits).into_iter()
+            .map(|h| (h.node, h.churn, h.dependents, h.score))
+            .collect();
+        assert_eq!(ranked, vec![
+            ("core".to_string(), 1, 2, 2),
+            ("api".to_string(), 1, 1, 1),
+            ("web".to_string(), 3, 0, 0),
+        ]);
+        assert!(hotspots_markdown(&rank(&graph, &commits)).contains("| core | 1 | 2 | 2 |"));
+    }
+}
@@ -0,0 +1,124 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::types::{DependencyGraph, Node};
+
+#[derive(Debug, thiserror::Error)]
+pub enum InfoError {
+    #[error("Node '{0}' not found")]
+    UnknownNode(String),
+}
+
+/// A node's profile, as `info` prints it.
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeInfo {
+    pub node: String,
+    pub path: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub manifest: Option<PathBuf>,
+    pub tags: Vec<String>,
+    pub include: Vec<PathBuf>,
+    pub exclude: Vec<PathBuf>,
+    pub cascade_exclude: Vec<PathBuf>,
+    /// The direct dependencies, sorted by name.
+    pub dependencies: Vec<String>,
+    /// The direct dependents, sorted by name.
+    pub dependents: Vec<String>,
+    /// The number of direct and indirect dependencies.
+    pub transitive_dependency_count: usize,
+    /// The number of direct and indirect dependents.
+    pub transitive_dependent_count: usize,
+    pub metadata: Option<serde_json::Value>,
+}
+
+/// Collects the profile of a node.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+/// * `name` - The node to describe
+pub fn info(graph: &DependencyGraph, name: &str) -> Result<NodeInfo, InfoError> {
+    let node = graph.get_node(name).ok_or_else(|| InfoError::UnknownNode(name.to_string()))?;
+    let names = |nodes: Vec<Node>| {
+        let mut names: Vec<String> = nodes.into_iter().map(|node| node.name).collect();
+        names.sort();
+        names
+    };
+    Ok(NodeInfo {
+        node: node.name.clone(),
+        path: node.path.clone(),
+        manifest: node.manifest.clone(),
+        tags: node.tags.clone(),
+        include: node.included_paths.clone(),
+        exclude: node.excluded_paths.clone(),
+        cascade_exclude: node.cascade_excluded_paths.clone(),
+        dependencies: names(graph.get_dependencies_within(name, Some(1))),
+        dependents: names(graph.get_dependents_within(name, Some(1))),
+        transitive_dependency_count: graph.get_dependencies(name).len(),
+        transitive_dependent_count: graph.get_dependents(name).len(),
+        metadata: node.metadata.clone(),
+    })
+}
+
+impl fmt::Display for NodeInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |items: &[String]| if items.is_empty() { "-".to_string() } else { items.join(", ") };
+        let paths = |paths: &[PathBuf]| list(&paths.iter().map(|path| path.display().to_string()).collect::<Vec<_>>());
+
+        writeln!(f, "{}", self.node)?;
+        writeln!(f, "  path:            {}", self.path.display())?;
+        if let Some(manifest) = &self.manifest {
+            writeln!(f, "  manifest:        {}", manifest.display())?;
+        }
+        writeln!(f, "  tags:            {}", list(&self.tags))?;
+        writeln!(f, "  include:         {}", paths(&self.include))?;
+        writeln!(f, "  exclude:         {}", paths(&self.exclude))?;
+        if !self.cascade_exclude.is_empty() {
+            writeln!(f, "  cascade exclude: {}", paths(&self.cascade_exclude))?;
+        }
+        writeln!(f, "  dependencies:    {} ({} in total)", list(&self.dependencies), self.transitive_dependency_count)?;
+        writeln!(f, "  dependents:      {} ({} in total)", list(&self.dependents), self.transitive_dependent_count)?;
+        match &self.metadata {
+            Some(metadata) => {
+                writeln!(f, "  metadata:")?;
+                let pretty = serde_json::to_string_pretty(metadata).map_err(|_| fmt::Error)?;
+                for line in pretty.lines() {
+                    writeln!(f, "    {}", line)?;
+                }
+                Ok(())
+            }
+            None => writeln!(f, "  metadata:        -"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_info() {
+        let node = |name: &str, deps: Vec<&str>| Node::new(
+            name.to_string(), PathBuf::from(name), vec![PathBuf::from("src/**")], vec![PathBuf::from("src/gen/**")],
+            deps.into_iter().map(String::from).collect(), Some(serde_json::json!({ "team": "payments" })),
+        ).unwrap();
+        let graph = DependencyGraph::new(vec![
+            node("crypto", vec![]),
+            node("lib", vec!["crypto"]),
+            node("api", vec!["lib"]),
+            node("web", vec!["api"]),
+            node("admin", vec!["api"]),
+        ], false).unwrap();
+
+        let api = info(&graph, "api").unwrap();
+        assert_eq!(api.dependencies, ["lib"]);
+        assert_eq!(api.dependents, ["admin", "web"]);
+        assert_eq!((api.transitive_dependency_count, api.transitive_dependent_count), (2, 2));
+        let text = api.to_string();
+        assert!(text.contains("  dependents:      admin, web (2 in total)\n"));
+        assert!(text.contains("  exclude:         src/gen/**\n"));
+        assert!(text.contains("      \"team\": \"payments\"\n"));
+        assert!(info(&graph, "ghost").is_err());
+    }
+}
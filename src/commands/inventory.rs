@@ -0,0 +1,191 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::types::{DependencyGraph, IndexedFile};
+
+#[derive(Debug, thiserror::Error)]
+pub enum HashCompareError {
+    #[error("Failed to read {0}: {1}")]
+    Read(PathBuf, std::io::Error),
+    #[error("Failed to parse {0}: {1}")]
+    Parse(PathBuf, serde_json::Error),
+    #[error("Failed to decode {0}: {1}")]
+    Decode(PathBuf, rmp_serde::decode::Error),
+    #[error("{0} has no file index, prepare it with --index-files")]
+    MissingIndex(PathBuf),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum IndexError {
+    #[error(transparent)]
+    Walk(#[from] walkdir::Error),
+    #[error("Unable to read {0}: {1}")]
+    Read(PathBuf, std::io::Error),
+}
+
+/// The file index of an artifact, the rest of it is skipped while parsing.
+#[derive(Deserialize)]
+struct FileIndexOnly {
+    #[serde(default)]
+    file_index: BTreeMap<PathBuf, IndexedFile>,
+}
+
+/// A file claimed by different nodes in two artifacts.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MovedFile {
+    pub file: PathBuf,
+    pub from: Vec<String>,
+    pub to: Vec<String>,
+}
+
+/// Lists and hashes the files of every node into the graph's file index. Must run from the
+/// directory `prepare` ran in.
+///
+/// ### Arguments
+/// * `graph` - The freshly prepared dependency graph
+pub fn index_files(graph: &mut DependencyGraph) -> Result<(), IndexError> {
+    let mut nodes_by_file: BTreeMap<PathBuf, Vec<String>> = BTreeMap::new();
+    for node in graph.get_all_nodes() {
+        for file in super::node_files(node)? {
+            nodes_by_file.entry(file).or_default().push(node.name.clone());
+        }
+    }
+
+    let mut index = BTreeMap::new();
+    for (file, mut nodes) in nodes_by_file {
+        nodes.sort();
+        let hash = format!("{:x}", Sha256::digest(fs::read(&file).map_err(|e| IndexError::Read(file.clone(), e))?));
+        index.insert(file, IndexedFile { hash, nodes });
+    }
+    log::debug!("Indexed {} files", index.len());
+    graph.set_file_index(index);
+    Ok(())
+}
+
+/// Compares the file indexes of two artifacts and lists the files present in both that
+/// changed hands, e.g. after a node's patterns changed or a node was split.
+///
+/// ### Arguments
+/// * `previous` - The older artifact
+/// * `graph` - The newer artifact
+///
+/// ### Returns
+/// * `Vec<MovedFile>` - The files whose nodes differ, sorted by path
+pub fn moved_files(previous: &DependencyGraph, graph: &DependencyGraph) -> Vec<MovedFile> {
+    graph.file_index().iter()
+        .filter_map(|(file, indexed)| {
+            let before = previous.file_index().get(file)?;
+            (before.nodes != indexed.nodes).then(|| MovedFile {
+                file: file.clone(),
+                from: before.nodes.clone(),
+                to: indexed.nodes.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Compares the file indexes of two artifacts by content hash and returns the files whose
+/// content differs, including added and removed files. When `files` isn't empty, only
+/// those are considered: files touched without changing their content, or reverted since,
+/// are left out, and files neither artifact indexes are kept.
+///
+/// ### Arguments
+/// * `graph_artifact_path` - The current artifact, prepared with `--index-files`
+/// * `previous_path` - The artifact to compare with, prepared with `--index-files`
+/// * `files` - The files reported as changed, if any
+///
+/// ### Returns
+/// * `Vec<PathBuf>` - The files with a different content
+pub fn content_changes(graph_artifact_path: &Path, previous_path: &Path, files: &[PathBuf]) -> Result<Vec<PathBuf>, HashCompareError> {
+    let current = read_file_index(graph_artifact_path)?;
+    let previous = read_file_index(previous_path)?;
+    Ok(diff_indexes(&previous, &current, files))
+}
+
+fn read_file_index(path: &Path) -> Result<BTreeMap<PathBuf, IndexedFile>, HashCompareError> {
+    let content = super::output::read_artifact_bytes(path).map_err(|e| HashCompareError::Read(path.to_path_buf(), e))?;
+    let file_index = match super::encoding::is_binary(&content) {
+        true => super::encoding::decode::<FileIndexOnly>(&content).map_err(|e| HashCompareError::Decode(path.to_path_buf(), e))?.file_index,
+        false => serde_json::from_slice::<FileIndexOnly>(&content).map_err(|e| HashCompareError::Parse(path.to_path_buf(), e))?.file_index,
+    };
+    if file_index.is_empty() {
+        return Err(HashCompareError::MissingIndex(path.to_path_buf()));
+    }
+    Ok(file_index)
+}
+
+fn diff_indexes(previous: &BTreeMap<PathBuf, IndexedFile>, current: &BTreeMap<PathBuf, IndexedFile>, files: &[PathBuf]) -> Vec<PathBuf> {
+    let differs = |file: &PathBuf| match (previous.get(file), current.get(file)) {
+        (Some(before), Some(after)) => before.hash != after.hash,
+        _ => true,
+    };
+    if files.is_empty() {
+        let mut all: Vec<&PathBuf> = previous.keys().chain(current.keys()).collect();
+        all.sort();
+        all.dedup();
+        return all.into_iter().filter(|file| differs(file)).cloned().collect();
+    }
+    files.iter().filter(|file| differs(file)).cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::Node;
+
+    use super::*;
+
+    fn indexed(nodes: &[(&str, &str)], files: &[(&str, &[&str])]) -> DependencyGraph {
+        let nodes = nodes.iter()
+            .map(|(name, include)| Node::new(name.to_string(), PathBuf::new(), vec![PathBuf::from(include)], vec![], vec![], None).unwrap())
+            .collect();
+        let mut graph = DependencyGraph::new(nodes, false).unwrap();
+        graph.set_file_index(files.iter()
+            .map(|(file, nodes)| (PathBuf::from(file), IndexedFile {
+                hash: "0".to_string(),
+                nodes: nodes.iter().map(|n| n.to_string()).collect(),
+            }))
+            .collect());
+        graph
+    }
+
+    #[test]
+    fn test_diff_indexes() {
+        let index = |files: &[(&str, &str)]| -> BTreeMap<PathBuf, IndexedFile> {
+            files.iter()
+                .map(|(file, hash)| (PathBuf::from(file), IndexedFile { hash: hash.to_string(), nodes: vec![] }))
+                .collect()
+        };
+        let previous = index(&[("a.rs", "1"), ("b.rs", "2"), ("gone.rs", "3")]);
+        let current = index(&[("a.rs", "1"), ("b.rs", "9"), ("new.rs", "4")]);
+
+        let all = diff_indexes(&previous, &current, &[]);
+        assert_eq!(all, ["b.rs", "gone.rs", "new.rs"].map(PathBuf::from));
+
+        // a.rs was only touched, c.rs isn't indexed by either
+        let files = ["a.rs", "b.rs", "c.rs"].map(PathBuf::from);
+        assert_eq!(diff_indexes(&previous, &current, &files), ["b.rs", "c.rs"].map(PathBuf::from));
+    }
+
+    #[test]
+    fn test_index_lookup_and_moves() {
+        let previous = indexed(&[("api", "api/**")], &[("api/a.rs", &["api"]), ("api/b.rs", &["api"])]);
+        let graph = indexed(
+            &[("api", "api/**"), ("api-client", "api/client/**")],
+            &[("api/a.rs", &["api"]), ("api/b.rs", &["api-client"])],
+        );
+
+        // Indexed files skip pattern matching, other files fall back to it
+        assert_eq!(graph.nodes_including(&PathBuf::from("api/b.rs")), vec!["api-client"]);
+        assert_eq!(graph.nodes_including(&PathBuf::from("api/client/new.rs")), vec!["api", "api-client"]);
+
+        assert_eq!(moved_files(&previous, &graph), vec![MovedFile {
+            file: PathBuf::from("api/b.rs"),
+            from: vec!["api".to_string()],
+            to: vec!["api-client".to_string()],
+        }]);
+    }
+}
@@ -0,0 +1,100 @@
+use std::path::Path;
+
+use clap::ValueEnum;
+
+use crate::types::DependencyGraph;
+
+/// The toolchains `prepare --detect-languages` recognizes, tagged by their lowercase name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum Language {
+    /// A `Cargo.toml`.
+    Rust,
+    /// A `go.mod`.
+    Go,
+    /// A `pyproject.toml`, `setup.py`, `setup.cfg`, `requirements.txt` or `Pipfile`.
+    Python,
+    /// A `package.json` with a `tsconfig.json`.
+    Typescript,
+    /// A `package.json` without a `tsconfig.json`.
+    Javascript,
+}
+
+impl Language {
+    /// The tag of the nodes using the language.
+    pub fn tag(self) -> &'static str {
+        match self {
+            Language::Rust => "rust",
+            Language::Go => "go",
+            Language::Python => "python",
+            Language::Typescript => "typescript",
+            Language::Javascript => "javascript",
+        }
+    }
+}
+
+/// The languages whose manifests are in `dir` itself, sorted.
+pub fn detect(dir: &Path) -> Vec<Language> {
+    let has = |file: &str| dir.join(file).is_file();
+    let mut languages = Vec::new();
+    if has("Cargo.toml") {
+        languages.push(Language::Rust);
+    }
+    if has("go.mod") {
+        languages.push(Language::Go);
+    }
+    if ["pyproject.toml", "setup.py", "setup.cfg", "requirements.txt", "Pipfile"].into_iter().any(has) {
+        languages.push(Language::Python);
+    }
+    if has("package.json") {
+        languages.push(if has("tsconfig.json") { Language::Typescript } else { Language::Javascript });
+    }
+    languages
+}
+
+/// Tags every node with the languages detected in its directory, keeping its own tags. Must
+/// run from the directory `prepare` ran in.
+///
+/// ### Returns
+/// * `usize` - The number of nodes that got a language tag
+pub fn tag_languages(graph: &mut DependencyGraph) -> usize {
+    let tagged: Vec<(String, Vec<String>)> = graph.get_all_nodes().into_iter()
+        .filter_map(|node| {
+            let mut tags = node.tags.clone();
+            for language in detect(&node.path) {
+                if !node.has_tag(language.tag()) {
+                    tags.push(language.tag().to_string());
+                }
+            }
+            (tags.len() > node.tags.len()).then(|| (node.name.clone(), tags))
+        })
+        .collect();
+    for (name, tags) in &tagged {
+        graph.set_node_tags(name, tags.clone());
+    }
+    log::info!("Tagged {} nodes with their languages", tagged.len());
+    tagged.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::testkit::FixtureDir;
+
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        let dir = ["Cargo.toml", "package.json", "tsconfig.json", "requirements.txt"].into_iter()
+            .fold(FixtureDir::new(), |dir, file| dir.file(file, ""));
+        assert_eq!(detect(dir.path()), vec![Language::Rust, Language::Python, Language::Typescript]);
+
+        fs::remove_file(dir.join("tsconfig.json")).unwrap();
+        let node = crate::types::Node::new("web".to_string(), dir.path().to_path_buf(), vec![PathBuf::from("**")], vec![], vec![], None).unwrap();
+        let mut graph = DependencyGraph::new(vec![node], false).unwrap();
+        assert_eq!(tag_languages(&mut graph), 1);
+        assert_eq!(graph.get_node("web").unwrap().tags, vec!["rust", "python", "javascript"]);
+        assert_eq!(graph.info().features, vec!["tags"]);
+    }
+}
@@ -0,0 +1,356 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+
+use regex::Regex;
+use serde::Serialize;
+
+use super::{PrepareError, Scan, ScanError, ScannedManifest};
+use crate::types::{Config, DependencyGraph, LintConfig, Node, Severity};
+
+/// Where in the repository a finding was detected.
+#[derive(Debug, Clone, Serialize)]
+pub struct Location {
+    /// The dependency toml file of the offending node.
+    pub file: PathBuf,
+    /// The 1-based line the finding points at, when it could be located.
+    pub line: Option<usize>,
+}
+
+/// Why `lint` failed.
+#[derive(Debug, thiserror::Error)]
+pub enum LintError {
+    #[error(transparent)]
+    Prepare(#[from] PrepareError),
+    #[error(transparent)]
+    Scan(#[from] ScanError),
+    #[error("Invalid [lint.naming] pattern: {0}")]
+    Pattern(#[from] regex::Error),
+    #[error("The manifest of {0} changed while linting")]
+    Changed(String),
+}
+
+/// A single problem reported by a lint rule.
+#[derive(Debug, Clone, Serialize)]
+pub struct LintFinding {
+    /// The rule that produced the finding (e.g. `forbidden-edge`).
+    pub rule: &'static str,
+    pub severity: Severity,
+    /// The name of the offending node.
+    pub node: String,
+    pub location: Location,
+    pub message: String,
+}
+
+/// A violation as reported by a rule, before it's located in the manifest.
+struct Violation {
+    message: String,
+    /// The TOML table the violation refers to (e.g. `dependencies`).
+    section: &'static str,
+    /// Text searched for inside `section` to find the offending line.
+    needle: String,
+}
+
+/// A lint rule, checked once per node of the graph.
+trait LintRule {
+    fn name(&self) -> &'static str;
+    fn severity(&self) -> Severity;
+    fn check(&self, graph: &DependencyGraph, node: &Node) -> Vec<Violation>;
+}
+
+struct MaxDependencies {
+    max: usize,
+    severity: Severity,
+}
+
+impl LintRule for MaxDependencies {
+    fn name(&self) -> &'static str {
+        "max-dependencies"
+    }
+
+    fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    fn check(&self, _graph: &DependencyGraph, node: &Node) -> Vec<Violation> {
+        if node.dependencies.len() <= self.max {
+            return Vec::new();
+        }
+        vec![Violation {
+            message: format!(
+                "{} declares {} dependencies, more than the allowed {}",
+                node.name, node.dependencies.len(), self.max
+            ),
+            section: "dependencies",
+            needle: "[dependencies]".to_string(),
+        }]
+    }
+}
+
+struct ForbiddenEdge {
+    from: String,
+    to: String,
+    severity: Severity,
+}
+
+impl LintRule for ForbiddenEdge {
+    fn name(&self) -> &'static str {
+        "forbidden-edge"
+    }
+
+    fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    fn check(&self, graph: &DependencyGraph, node: &Node) -> Vec<Violation> {
+        if !node.has_tag(&self.from) {
+            return Vec::new();
+        }
+        node.dependencies.iter()
+            .filter_map(|dep| graph.get_node(dep))
+            .filter(|dep| dep.has_tag(&self.to))
+            .map(|dep| Violation {
+                message: format!(
+                    "{} ({}) must not depend on {} ({})",
+                    node.name, self.from, dep.name, self.to
+                ),
+                section: "dependencies",
+                needle: format!("\"{}\"", dep.name),
+            })
+            .collect()
+    }
+}
+
+struct RequiredTags {
+    one_of: Vec<String>,
+    severity: Severity,
+}
+
+impl LintRule for RequiredTags {
+    fn name(&self) -> &'static str {
+        "required-tags"
+    }
+
+    fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    fn check(&self, _graph: &DependencyGraph, node: &Node) -> Vec<Violation> {
+        if self.one_of.iter().any(|tag| node.has_tag(tag)) {
+            return Vec::new();
+        }
+        vec![Violation {
+            message: format!("{} must be tagged with one of: {}", node.name, self.one_of.join(", ")),
+            section: "module",
+            needle: "name".to_string(),
+        }]
+    }
+}
+
+struct Naming {
+    pattern: Regex,
+    severity: Severity,
+}
+
+impl LintRule for Naming {
+    fn name(&self) -> &'static str {
+        "naming"
+    }
+
+    fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    fn check(&self, _graph: &DependencyGraph, node: &Node) -> Vec<Violation> {
+        if self.pattern.is_match(&node.name) {
+            return Vec::new();
+        }
+        vec![Violation {
+            message: format!("{} doesn't match the naming convention {}", node.name, self.pattern),
+            section: "module",
+            needle: "name".to_string(),
+        }]
+    }
+}
+
+/// Builds the enabled rules out of the `[lint]` section of the root config.
+fn rules(config: &LintConfig) -> Result<Vec<Box<dyn LintRule>>, regex::Error> {
+    let mut rules: Vec<Box<dyn LintRule>> = Vec::new();
+
+    if let Some(rule) = &config.max_dependencies {
+        rules.push(Box::new(MaxDependencies { max: rule.max, severity: rule.severity }));
+    }
+    for rule in &config.forbidden_edges {
+        rules.push(Box::new(ForbiddenEdge {
+            from: rule.from.clone(),
+            to: rule.to.clone(),
+            severity: rule.severity,
+        }));
+    }
+    if let Some(rule) = &config.required_tags {
+        rules.push(Box::new(RequiredTags { one_of: rule.one_of.clone(), severity: rule.severity }));
+    }
+    if let Some(rule) = &config.naming {
+        rules.push(Box::new(Naming { pattern: Regex::new(&rule.pattern)?, severity: rule.severity }));
+    }
+
+    Ok(rules)
+}
+
+/// Finds the first line inside the `[section]` table containing `needle`.
+pub(super) fn find_line(content: &str, section: &str, needle: &str) -> Option<usize> {
+    let header = format!("[{}]", section);
+    let mut in_section = false;
+    for (i, line) in content.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') && !trimmed.starts_with("[[") {
+            in_section = trimmed == header;
+            if in_section && trimmed.contains(needle) {
+                return Some(i + 1);
+            }
+            continue;
+        }
+        if in_section && trimmed.contains(needle) {
+            return Some(i + 1);
+        }
+    }
+    None
+}
+
+/// Runs the lint rules configured in the root config against every node found under `dir`.
+///
+/// ### Arguments
+/// * `dir` - The directory to start the recursive scan from
+/// * `dependency_toml_name` - The name of the dependency toml file commmon to all the services. Defaults to `dependencies.toml`
+/// * `config` - The root config holding the `[lint]` rules
+///
+/// ### Returns
+/// * `Vec<LintFinding>` - All findings, errors first
+pub fn lint(dir: PathBuf, dependency_toml_name: Option<String>, config: &Config) -> Result<Vec<LintFinding>, LintError> {
+    let scan = Scan::new(dir, dependency_toml_name);
+    let graph = super::prepare(&scan, true, None)?;
+    let rules = rules(&config.lint)?;
+
+    // The manifests as walked, by node path, to locate the findings in
+    let manifests: HashMap<PathBuf, ScannedManifest> = scan.read()?.manifests.into_iter()
+        .map(|manifest| (manifest.path.clone(), manifest))
+        .collect();
+
+    let mut findings = Vec::new();
+    for node in graph.get_all_nodes() {
+        let ScannedManifest { file, content, .. } = manifests.get(&node.path)
+            .ok_or_else(|| LintError::Changed(node.name.clone()))?;
+
+        for rule in &rules {
+            for violation in rule.check(&graph, node) {
+                findings.push(LintFinding {
+                    rule: rule.name(),
+                    severity: rule.severity(),
+                    node: node.name.clone(),
+                    location: Location {
+                        file: file.clone(),
+                        line: find_line(content, violation.section, &violation.needle),
+                    },
+                    message: violation.message,
+                });
+            }
+        }
+    }
+
+    findings.sort_by(|a, b| {
+        b.severity.cmp(&a.severity)
+            .then_with(|| a.location.file.cmp(&b.location.file))
+            .then_with(|| a.location.line.cmp(&b.location.line))
+    });
+    Ok(findings)
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}", self.file.display(), line),
+            None => write!(f, "{}", self.file.display()),
+        }
+    }
+}
+
+impl fmt::Display for LintFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}[{}]: {}\n  --> {}", severity, self.rule, self.message, self.location)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testkit::FixtureDir;
+
+    use super::*;
+
+    fn node(name: &str, deps: Vec<&str>, tags: Vec<&str>) -> Node {
+        let mut node = Node::new(
+            name.to_string(),
+            PathBuf::from(name),
+            vec![PathBuf::from("src/**")],
+            vec![],
+            deps.into_iter().map(String::from).collect(),
+            None,
+        ).unwrap();
+        node.tags = tags.into_iter().map(String::from).collect();
+        node
+    }
+
+    #[test]
+    fn test_forbidden_edge() {
+        let graph = DependencyGraph::new(vec![
+            node("api", vec![], vec!["backend"]),
+            node("web", vec!["api"], vec!["frontend"]),
+        ], false).unwrap();
+        let rule = ForbiddenEdge { from: "frontend".into(), to: "backend".into(), severity: Severity::Error };
+
+        assert_eq!(rule.check(&graph, graph.get_node("web").unwrap()).len(), 1);
+        assert!(rule.check(&graph, graph.get_node("api").unwrap()).is_empty());
+    }
+
+    #[test]
+    fn test_max_dependencies_and_naming() {
+        let graph = DependencyGraph::new(vec![
+            node("a", vec![], vec![]),
+            node("b", vec![], vec![]),
+            node("Bad_Name", vec!["a", "b"], vec![]),
+        ], false).unwrap();
+        let bad = graph.get_node("Bad_Name").unwrap();
+
+        let max = MaxDependencies { max: 1, severity: Severity::Warning };
+        assert_eq!(max.check(&graph, bad).len(), 1);
+
+        let naming = Naming { pattern: Regex::new("^[a-z-]+$").unwrap(), severity: Severity::Warning };
+        assert_eq!(naming.check(&graph, bad).len(), 1);
+        assert!(naming.check(&graph, graph.get_node("a").unwrap()).is_empty());
+    }
+
+    #[test]
+    fn test_find_line() {
+        let content = "[module]\nname = \"web\"\n\n[dependencies]\napi = { name = \"api\" }\n";
+        assert_eq!(find_line(content, "module", "name"), Some(2));
+        assert_eq!(find_line(content, "dependencies", "\"api\""), Some(5));
+        assert_eq!(find_line(content, "dependencies", "[dependencies]"), Some(4));
+        assert_eq!(find_line(content, "metadata", "x"), None);
+    }
+
+    #[test]
+    fn test_lint_absolute_dir() {
+        let dir = FixtureDir::new().manifest("Bad_Name", "[module]\nname = \"Bad_Name\"\n\n[file_paths]\ninclude = [\"**\"]\n");
+        let config = Config::from_toml_str("[lint.naming]\npattern = \"^[a-z-]+$\"", std::path::Path::new("cfg.toml")).unwrap();
+
+        let findings = lint(dir.path().to_path_buf(), None, &config).unwrap();
+
+        assert!(dir.path().is_absolute());
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].location.file, dir.join("Bad_Name/dependencies.toml"));
+        assert_eq!(findings[0].location.line, Some(2));
+    }
+}
@@ -0,0 +1,423 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use crate::types::Node;
+
+/// The monorepo tools whose project configuration can be converted by `migrate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MigrateSource {
+    /// Nx workspaces (`project.json` files plus package-based projects).
+    Nx,
+    /// Turborepo workspaces (`workspaces` in the root `package.json` or `pnpm-workspace.yaml`).
+    Turborepo,
+    /// Lerna repositories (`packages` in `lerna.json`).
+    Lerna,
+}
+
+/// What happened to a single generated manifest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationStatus {
+    /// The manifest was written to disk.
+    Written,
+    /// A manifest already existed and `--force` wasn't given.
+    Skipped,
+    /// `--dry-run` was given, nothing was written.
+    DryRun,
+}
+
+/// A single `dependencies.toml` produced by `migrate`.
+#[derive(Debug, Clone, Serialize)]
+pub struct MigratedManifest {
+    /// The name of the generated node.
+    pub name: String,
+    /// The location of the generated manifest.
+    pub manifest: PathBuf,
+    /// The names of the nodes the generated node depends on.
+    pub dependencies: Vec<String>,
+    /// What happened to the manifest.
+    pub status: MigrationStatus,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum MigrateError {
+    #[error("Unable to read {0}: {1}")]
+    ReadError(PathBuf, std::io::Error),
+    #[error("Failed to parse {0}: {1}")]
+    ParseError(PathBuf, serde_json::Error),
+    #[error("Failed to parse {0}: {1}")]
+    YamlError(PathBuf, serde_norway::Error),
+    #[error("Invalid workspace pattern '{0}': {1}")]
+    InvalidPattern(String, glob::PatternError),
+    #[error("No {0:?} projects found under {1}")]
+    NoProjects(MigrateSource, PathBuf),
+}
+
+/// A project as described by the source tool, before it's converted into a node.
+#[derive(Debug)]
+struct Project {
+    name: String,
+    dir: PathBuf,
+    dependencies: Vec<String>,
+    project_type: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct PackageJson {
+    name: Option<String>,
+    #[serde(default)]
+    dependencies: HashMap<String, serde_json::Value>,
+    #[serde(default)]
+    dev_dependencies: HashMap<String, serde_json::Value>,
+    #[serde(default)]
+    peer_dependencies: HashMap<String, serde_json::Value>,
+    #[serde(default)]
+    optional_dependencies: HashMap<String, serde_json::Value>,
+    workspaces: Option<Workspaces>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Workspaces {
+    List(Vec<String>),
+    Object { packages: Vec<String> },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NxProjectJson {
+    name: Option<String>,
+    #[serde(default)]
+    implicit_dependencies: Vec<String>,
+    project_type: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+struct PnpmWorkspace {
+    packages: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LernaJson {
+    packages: Option<Vec<String>>,
+    #[serde(default)]
+    use_workspaces: bool,
+}
+
+impl PackageJson {
+    /// All the package names this package depends on, regardless of dependency kind.
+    fn all_dependencies(&self) -> impl Iterator<Item = &String> {
+        self.dependencies.keys()
+            .chain(self.dev_dependencies.keys())
+            .chain(self.peer_dependencies.keys())
+            .chain(self.optional_dependencies.keys())
+    }
+
+    fn workspace_patterns(&self) -> Vec<String> {
+        match &self.workspaces {
+            Some(Workspaces::List(patterns)) => patterns.clone(),
+            Some(Workspaces::Object { packages }) => packages.clone(),
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Converts an existing monorepo tool's project configuration into `dependencies.toml`
+/// files, one next to each project. Dependencies are only kept when they point at
+/// another project of the same repository.
+///
+/// ### Arguments
+/// * `dir` - The root of the monorepo
+/// * `from` - The tool whose configuration should be read
+/// * `dependency_toml_name` - The name of the manifests to write. Defaults to `dependencies.toml`
+/// * `dry_run` - Only report what would be written
+/// * `force` - Overwrite manifests that already exist
+///
+/// ### Returns
+/// * `Vec<MigratedManifest>` - One entry per project found
+pub fn migrate(
+    dir: PathBuf,
+    from: MigrateSource,
+    dependency_toml_name: Option<String>,
+    dry_run: bool,
+    force: bool,
+) -> Result<Vec<MigratedManifest>, Box<dyn std::error::Error>> {
+    let projects = match from {
+        MigrateSource::Nx => discover_nx(&dir)?,
+        MigrateSource::Turborepo => {
+            let root = read_package_json(&dir.join("package.json"))?.unwrap_or_default();
+            let mut patterns = root.workspace_patterns();
+            if patterns.is_empty() {
+                patterns = read_pnpm_workspace(&dir)?;
+            }
+            discover_workspaces(&dir, &patterns)?
+        }
+        MigrateSource::Lerna => {
+            let lerna_path = dir.join("lerna.json");
+            let lerna: LernaJson = read_json(&lerna_path)?;
+            let patterns = match lerna.packages {
+                Some(packages) if !lerna.use_workspaces => packages,
+                _ => {
+                    let root = read_package_json(&dir.join("package.json"))?.unwrap_or_default();
+                    match root.workspace_patterns() {
+                        patterns if patterns.is_empty() => vec!["packages/*".to_string()],
+                        patterns => patterns,
+                    }
+                }
+            };
+            discover_workspaces(&dir, &patterns)?
+        }
+    };
+
+    if projects.is_empty() {
+        return Err(Box::new(MigrateError::NoProjects(from, dir)));
+    }
+
+    let manifest_name = dependency_toml_name.as_deref().unwrap_or("dependencies.toml");
+    let known_names: HashSet<&str> = projects.iter().map(|p| p.name.as_str()).collect();
+    let mut results = Vec::new();
+
+    for project in &projects {
+        let mut dependencies: Vec<String> = project.dependencies.iter()
+            .filter(|dep| known_names.contains(dep.as_str()) && **dep != project.name)
+            .cloned()
+            .collect();
+        dependencies.sort();
+        dependencies.dedup();
+
+        let metadata = project.project_type.as_ref().map(|t| serde_json::json!({ "type": t }));
+        let node = Node::new(
+            project.name.clone(),
+            project.dir.clone(),
+            vec![PathBuf::from("**/*")],
+            vec![PathBuf::from("node_modules/**")],
+            dependencies.clone(),
+            metadata,
+        )?;
+
+        let manifest = project.dir.join(manifest_name);
+        let status = if manifest.exists() && !force {
+            log::warn!("Skipping {}: manifest already exists", manifest.display());
+            MigrationStatus::Skipped
+        } else if dry_run {
+            MigrationStatus::DryRun
+        } else {
+            let content = format!(
+                "# Generated by `dependency-cascade migrate --from {}`.\n{}",
+                from.to_possible_value().map(|v| v.get_name().to_string()).unwrap_or_default(),
+                node.to_toml_string()?,
+            );
+            fs::write(&manifest, content)?;
+            MigrationStatus::Written
+        };
+
+        results.push(MigratedManifest { name: project.name.clone(), manifest, dependencies, status });
+    }
+
+    Ok(results)
+}
+
+/// Nx projects are directories with a `project.json`, plus any package-based projects
+/// declared through the root `package.json` workspaces.
+fn discover_nx(dir: &Path) -> Result<Vec<Project>, MigrateError> {
+    let root = read_package_json(&dir.join("package.json"))?.unwrap_or_default();
+    let mut projects: BTreeMap<PathBuf, Project> = discover_workspaces(dir, &root.workspace_patterns())?
+        .into_iter()
+        .map(|p| (p.dir.clone(), p))
+        .collect();
+
+    let walker = WalkDir::new(dir).into_iter().filter_entry(|e| {
+        e.depth() == 0 || !matches!(e.file_name().to_str(), Some("node_modules") | Some(".git") | Some(".nx") | Some("dist"))
+    });
+    for entry in walker {
+        let entry = entry.map_err(|e| MigrateError::ReadError(dir.to_path_buf(), e.into()))?;
+        if entry.file_name() != "project.json" {
+            continue;
+        }
+        let project_dir = normalize(entry.path().parent().unwrap_or(dir));
+        let nx: NxProjectJson = read_json(entry.path())?;
+        let package = read_package_json(&project_dir.join("package.json"))?;
+
+        let name = nx.name
+            .or_else(|| package.as_ref().and_then(|p| p.name.clone()))
+            .unwrap_or_else(|| dir_name(&project_dir));
+
+        // Negated implicit dependencies (`!foo`) remove an inferred dependency
+        let excluded: HashSet<&str> = nx.implicit_dependencies.iter()
+            .filter_map(|d| d.strip_prefix('!'))
+            .collect();
+        let dependencies = nx.implicit_dependencies.iter()
+            .filter(|d| !d.starts_with('!'))
+            .chain(package.iter().flat_map(|p| p.all_dependencies()))
+            .filter(|d| !excluded.contains(d.as_str()))
+            .cloned()
+            .collect();
+
+        let project_type = nx.project_type.map(|t| match t.as_str() {
+            "application" => "app".to_string(),
+            "library" => "lib".to_string(),
+            _ => t,
+        });
+
+        projects.insert(project_dir.clone(), Project { name, dir: project_dir, dependencies, project_type });
+    }
+
+    Ok(projects.into_values().collect())
+}
+
+/// Expands workspace globs (e.g. `packages/*`) into the packages they point at.
+fn discover_workspaces(dir: &Path, patterns: &[String]) -> Result<Vec<Project>, MigrateError> {
+    let mut excluded = Vec::new();
+    for pattern in patterns.iter().filter_map(|p| p.strip_prefix('!')) {
+        let full = dir.join(pattern);
+        excluded.push(glob::Pattern::new(&full.to_string_lossy())
+            .map_err(|e| MigrateError::InvalidPattern(pattern.to_string(), e))?);
+    }
+
+    let mut projects = BTreeMap::new();
+    for pattern in patterns.iter().filter(|p| !p.starts_with('!')) {
+        let full = dir.join(pattern).join("package.json");
+        let matches = glob::glob(&full.to_string_lossy())
+            .map_err(|e| MigrateError::InvalidPattern(pattern.clone(), e))?;
+
+        for package_path in matches.flatten() {
+            let project_dir = normalize(package_path.parent().unwrap_or(dir));
+            if excluded.iter().any(|p| p.matches_path(&project_dir))
+                || project_dir.components().any(|c| c.as_os_str() == "node_modules")
+            {
+                continue;
+            }
+            let Some(package) = read_package_json(&package_path)? else { continue };
+            let name = package.name.clone().unwrap_or_else(|| dir_name(&project_dir));
+            let dependencies = package.all_dependencies().cloned().collect();
+            projects.insert(project_dir.clone(), Project { name, dir: project_dir, dependencies, project_type: None });
+        }
+    }
+
+    Ok(projects.into_values().collect())
+}
+
+/// Reads the `packages` list of a `pnpm-workspace.yaml`.
+fn read_pnpm_workspace(dir: &Path) -> Result<Vec<String>, MigrateError> {
+    let path = dir.join("pnpm-workspace.yaml");
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(&path).map_err(|e| MigrateError::ReadError(path.clone(), e))?;
+    let workspace: PnpmWorkspace = serde_norway::from_str(&content).map_err(|e| MigrateError::YamlError(path, e))?;
+    Ok(workspace.packages)
+}
+
+fn read_package_json(path: &Path) -> Result<Option<PackageJson>, MigrateError> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    read_json(path).map(Some)
+}
+
+fn read_json<T: for<'de> Deserialize<'de>>(path: &Path) -> Result<T, MigrateError> {
+    let content = fs::read_to_string(path).map_err(|e| MigrateError::ReadError(path.to_path_buf(), e))?;
+    serde_json::from_str(&content).map_err(|e| MigrateError::ParseError(path.to_path_buf(), e))
+}
+
+/// Drops `.` components so that paths found by walking and by globbing compare equal.
+fn normalize(path: &Path) -> PathBuf {
+    path.components().filter(|c| !matches!(c, std::path::Component::CurDir)).collect()
+}
+
+fn dir_name(dir: &Path) -> String {
+    dir.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testkit::FixtureDir;
+
+    use super::*;
+
+    /// Writes the files of a repository under a fresh temporary directory.
+    fn fixture(files: &[(&str, &str)]) -> FixtureDir {
+        files.iter().fold(FixtureDir::new(), |dir, (path, content)| dir.file(path, content))
+    }
+
+    fn summary(manifests: &[MigratedManifest]) -> Vec<(&str, Vec<&str>)> {
+        manifests.iter().map(|m| (m.name.as_str(), m.dependencies.iter().map(String::as_str).collect())).collect()
+    }
+
+    #[test]
+    fn test_migrate_nx() {
+        let dir = fixture(&[
+            ("package.json", r#"{ "workspaces": ["packages/*", "!packages/legacy"] }"#),
+            ("packages/ui/package.json", r#"{ "name": "ui", "dependencies": { "utils": "*", "react": "*" } }"#),
+            ("packages/utils/package.json", r#"{ "name": "utils" }"#),
+            ("packages/legacy/package.json", r#"{ "name": "legacy" }"#),
+            ("apps/web/project.json", r#"{ "name": "web", "implicitDependencies": ["ui", "!utils"], "projectType": "application" }"#),
+            ("apps/web/package.json", r#"{ "devDependencies": { "utils": "*", "legacy": "*" } }"#),
+            ("node_modules/ui/project.json", r#"{ "name": "vendored" }"#),
+        ]);
+
+        let manifests = migrate(dir.path().to_path_buf(), MigrateSource::Nx, None, true, false).unwrap();
+        // The negated implicit dependency drops utils, and the excluded legacy isn't a project
+        assert_eq!(summary(&manifests), [("web", vec!["ui"]), ("ui", vec!["utils"]), ("utils", vec![])]);
+        assert!(manifests.iter().all(|m| m.status == MigrationStatus::DryRun));
+        assert!(!dir.join("apps/web/dependencies.toml").exists());
+
+        migrate(dir.path().to_path_buf(), MigrateSource::Nx, Some("deps.toml".to_string()), false, false).unwrap();
+        let web = fs::read_to_string(dir.join("apps/web/deps.toml")).unwrap();
+        assert!(web.starts_with("# Generated by `dependency-cascade migrate --from nx`."));
+        assert!(web.contains("type = \"app\""));
+    }
+
+    #[test]
+    fn test_migrate_workspaces() {
+        let packages = [
+            ("packages/a/package.json", r#"{ "name": "a", "peerDependencies": { "b": "*" } }"#),
+            ("packages/b/package.json", r#"{ "name": "b" }"#),
+            ("tools/c/package.json", r#"{ "name": "c", "optionalDependencies": { "a": "*" } }"#),
+        ];
+
+        let pnpm = "# the workspace\npackages:\n  - 'packages/*'\n  - \"tools/*\"\n  - '!tools/c'\ncatalog:\n  react: ^18\n";
+        let dir = fixture(&[&packages[..], &[("package.json", "{}"), ("pnpm-workspace.yaml", pnpm)]].concat());
+        let manifests = migrate(dir.path().to_path_buf(), MigrateSource::Turborepo, None, true, false);
+        assert_eq!(summary(&manifests.unwrap()), [("a", vec!["b"]), ("b", vec![])]);
+
+        // Lerna falls back to packages/* without a packages list nor workspaces
+        let dir = fixture(&[&packages[..], &[("lerna.json", "{}")]].concat());
+        let fallback = migrate(dir.path().to_path_buf(), MigrateSource::Lerna, None, true, false);
+        dir.write("lerna.json", r#"{ "packages": ["packages/*"], "useWorkspaces": true }"#);
+        dir.write("package.json", r#"{ "workspaces": { "packages": ["tools/*", "packages/b"] } }"#);
+        let workspaces = migrate(dir.path().to_path_buf(), MigrateSource::Lerna, None, true, false);
+        assert_eq!(summary(&fallback.unwrap()), [("a", vec!["b"]), ("b", vec![])]);
+        assert_eq!(summary(&workspaces.unwrap()), [("b", vec![]), ("c", vec![])]);
+    }
+
+    #[test]
+    fn test_migrate_existing_manifests() {
+        let dir = fixture(&[
+            ("package.json", r#"{ "workspaces": ["packages/*"] }"#),
+            ("packages/a/package.json", r#"{ "name": "a" }"#),
+            ("packages/a/dependencies.toml", "# hand-written\n"),
+            ("packages/b/package.json", r#"{ "name": "b" }"#),
+        ]);
+        let statuses = |force| -> Vec<MigrationStatus> {
+            migrate(dir.path().to_path_buf(), MigrateSource::Turborepo, None, false, force).unwrap().into_iter().map(|m| m.status).collect()
+        };
+
+        assert_eq!(statuses(false), [MigrationStatus::Skipped, MigrationStatus::Written]);
+        assert_eq!(fs::read_to_string(dir.join("packages/a/dependencies.toml")).unwrap(), "# hand-written\n");
+        assert_eq!(statuses(true), [MigrationStatus::Written, MigrationStatus::Written]);
+        assert!(fs::read_to_string(dir.join("packages/a/dependencies.toml")).unwrap().contains("name = \"a\""));
+
+        let empty = migrate(dir.join("packages"), MigrateSource::Turborepo, None, true, false);
+        assert!(empty.unwrap_err().downcast_ref::<MigrateError>().is_some_and(|e| matches!(e, MigrateError::NoProjects(..))));
+    }
+}
@@ -0,0 +1,1547 @@
+pub mod advise;
+mod amend;
+mod archive;
+pub mod artifact;
+mod badges;
+mod blast_radius;
+mod budget;
+mod changelog;
+mod classify;
+pub mod codeowners;
+mod coverage;
+mod deploy;
+mod encoding;
+pub mod export;
+mod fix;
+pub mod generate;
+mod git;
+mod glob_guard;
+pub mod graph;
+mod hash;
+mod history;
+mod hotspots;
+mod info;
+mod inventory;
+mod languages;
+mod lint;
+mod migrate;
+mod output;
+mod profile;
+mod query;
+mod query_cache;
+pub mod release;
+mod reviewers;
+mod scan;
+#[cfg(feature = "serve")]
+mod serve;
+mod shard;
+mod sidecar;
+mod split_node;
+mod store;
+mod stream;
+mod suggest_deps;
+mod triggers;
+mod validate;
+mod watch;
+mod width;
+
+use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::SystemTime;
+
+use clap::{Args, Subcommand, ValueEnum};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use walkdir::WalkDir;
+
+use crate::testkit::FixtureShape;
+use crate::types::{AffectedReason, ArtifactInfo, CiMetadata, CompatibilityError, DependencyGraph, DependencyGraphCreationError, Node, NodeCreationError, Selector};
+
+pub use advise::AdviseCommands;
+pub use amend::{amend, AmendError};
+pub use artifact::{ArtifactCommands, InspectError};
+pub use badges::{badges, write_badges, BadgeError};
+pub use blast_radius::blast_radius;
+pub use budget::BudgetArgs;
+pub use changelog::{changelog, write_fragments};
+pub use classify::classify;
+pub use codeowners::CodeownersCommands;
+pub use export::ExportCommands;
+pub use generate::GenerateCommands;
+pub use graph::GraphCommands;
+pub use coverage::coverage;
+pub use deploy::{deploy_order, rollout_groups};
+pub use encoding::{encode_graph, ArtifactFormat, EncodeError};
+pub use fix::{fix, FixError, FixRule};
+pub use git::changed_files;
+pub use glob_guard::check_globs;
+pub use hash::{hash, HashError};
+pub use history::{history, HistoryPeriod};
+pub use hotspots::{hotspots, hotspots_markdown};
+pub use info::{info, InfoError, NodeInfo};
+pub use inventory::{content_changes, index_files, moved_files, IndexError};
+pub use languages::{tag_languages, Language};
+pub use lint::{lint, LintError};
+pub use migrate::{migrate, MigrateSource};
+pub use output::{append, wait_for_locks, write_artifact, write_atomic};
+pub use profile::{profile, ProfileError};
+pub use query::{github_output, render_nodes, render_query, QueryDirection, QueryFormat};
+pub use query_cache::cached_affected_reasons;
+pub use release::ReleaseCommands;
+pub use reviewers::reviewers;
+pub use scan::{Scan, ScanError, ScannedManifest};
+#[cfg(feature = "serve")]
+pub use serve::{serve, ServeError};
+pub use shard::{write_shards, ShardError};
+pub use sidecar::{attach_metadata, split_metadata, SidecarError};
+pub use split_node::split_node;
+pub use store::{fetch_artifact, store_artifact};
+pub use stream::{stream_affected, StreamError};
+pub use suggest_deps::{suggest_deps, ScanLanguage};
+pub use triggers::triggers;
+pub use validate::validate;
+pub use watch::{watch, WatchError, WatchReport, WatchedGraph};
+pub use width::{width, Width};
+
+/// Why `prepare` failed.
+#[derive(Debug, thiserror::Error)]
+pub enum PrepareError {
+    #[error(transparent)]
+    Scan(#[from] ScanError),
+    #[error(transparent)]
+    Node(#[from] NodeCreationError),
+    #[error(transparent)]
+    Graph(#[from] DependencyGraphCreationError),
+    #[error(transparent)]
+    Amend(#[from] AmendError),
+}
+
+/// Why an artifact couldn't be loaded.
+#[derive(Debug, thiserror::Error)]
+pub enum LoadError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Parse(#[from] serde_json::Error),
+    #[error("The artifact {0} ends early, it was likely copied or written while being read. Write it with --output to replace it atomically: {1}")]
+    Truncated(PathBuf, serde_json::Error),
+    #[error("Unable to decode the binary artifact {0}: {1}")]
+    Binary(PathBuf, rmp_serde::decode::Error),
+    #[error(transparent)]
+    Incompatible(#[from] CompatibilityError),
+    #[error(transparent)]
+    Graph(#[from] DependencyGraphCreationError),
+}
+
+/// Prepares an artifact of the dependency graph from the given directories.
+/// JSON conversion is done in the CLI.
+/// 
+/// When a previous artifact is given, manifests whose hash didn't change since it was
+/// prepared aren't parsed again: their node is taken from the previous artifact.
+/// 
+/// ### Arguments
+/// * `scan` - The directories to scan and what to leave out
+/// * `previous` - The artifact of a previous `prepare` of the same directory
+/// 
+/// ### Returns
+/// * `DependencyGraph` - The dependency graph artifact
+pub fn prepare(scan: &Scan, allow_cyclical: bool, previous: Option<&DependencyGraph>) -> Result<DependencyGraph, PrepareError> {
+    let previous_nodes: HashMap<&Path, &Node> = previous
+        .map(|graph| graph.get_all_nodes().into_iter().map(|node| (node.path.as_path(), node)).collect())
+        .unwrap_or_default();
+
+    // Recursively walk directory and collect all dependency.toml files as nodes of the graph
+    let mut nodes: Vec<Node> = Vec::new();
+    let mut hashes = BTreeMap::new();
+    let mut parsed = 0;
+    let scanned = scan.read()?;
+    let created = crate::parallel::map(&scanned.manifests, |ScannedManifest { file, path, content }| -> Result<_, PrepareError> {
+        let hash = manifest_hash(content, scan.default_metadata.as_ref());
+
+        // Reuse the previous node if its manifest is unchanged, or create it
+        let unchanged = previous
+            .and_then(|graph| graph.manifest_hashes().get(path))
+            .is_some_and(|previous_hash| *previous_hash == hash);
+        if let Some(node) = previous_nodes.get(path.as_path()).filter(|_| unchanged) {
+            return Ok(((*node).clone(), hash, false));
+        }
+        let mut node = Node::from_toml_str(content, path.clone())?;
+        node.manifest = file.file_name().map(|name| path.join(name));
+        if let Some(defaults) = &scan.default_metadata {
+            node.merge_default_metadata(defaults)?;
+        }
+        Ok((node, hash, true))
+    });
+    for (manifest, created) in scanned.manifests.into_iter().zip(created) {
+        let (node, hash, reparsed) = created?;
+        parsed += usize::from(reparsed);
+        nodes.push(Node { hide_dotfiles: !scan.dotfiles.globs, ..node });
+        hashes.insert(manifest.path, hash);
+    }
+    if previous.is_some() {
+        log::info!("Parsed {} of {} manifests, reused the others", parsed, nodes.len());
+    }
+
+    // Create dependency graph from nodes
+    let mut graph = DependencyGraph::new(nodes, allow_cyclical)?;
+    graph.set_manifest_hashes(hashes);
+    graph.set_generations();
+    graph.set_info(ArtifactInfo {
+        created_at: Some(humantime::format_rfc3339_seconds(SystemTime::now()).to_string()),
+        source_commit: scanned.source_commit,
+        ..ArtifactInfo::current()
+    });
+
+    Ok(graph)
+}
+
+/// The hash of a manifest recorded in the artifact. The default metadata merged into its node
+/// is hashed with it, so that changing the defaults makes the next `prepare` parse it again.
+fn manifest_hash(content: &str, default_metadata: Option<&serde_json::Value>) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(content.as_bytes());
+    if let Some(defaults) = default_metadata {
+        hasher.update(defaults.to_string().as_bytes());
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Recursively walks `dir` and returns the paths of all the dependency toml files found.
+///
+/// ### Arguments
+/// * `dir` - The directory to start the recursive scan from
+/// * `dependency_toml_name` - The name of the dependency toml file. Defaults to `dependencies.toml`
+pub fn find_manifests(dir: &Path, dependency_toml_name: Option<&str>) -> Result<Vec<PathBuf>, walkdir::Error> {
+    let name = dependency_toml_name.unwrap_or("dependencies.toml");
+    let mut manifests = Vec::new();
+    for entry in WalkDir::new(dir) {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy() == name {
+            manifests.push(entry.into_path());
+        }
+    }
+    Ok(manifests)
+}
+
+/// Lists the files of the node, i.e. the files under its directory matching its include
+/// patterns and none of its exclude patterns, sorted.
+pub fn node_files(node: &Node) -> Result<Vec<PathBuf>, walkdir::Error> {
+    let root = if node.path.as_os_str().is_empty() { Path::new(".") } else { node.path.as_path() };
+    let mut files = Vec::new();
+    for entry in WalkDir::new(root).sort_by_file_name() {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let path = entry.into_path();
+        let path = path.strip_prefix("./").map(Path::to_path_buf).unwrap_or(path);
+        if node.includes_path(&path) {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+/// Returns the path of the node declared by the given dependency toml file, i.e. its
+/// parent directory, relative to the root directory.
+pub fn node_path(manifest: &Path) -> PathBuf {
+    normalize(manifest.parent().unwrap())
+}
+
+/// Makes a walked path relative to the current directory, as node paths are.
+fn normalize(path: &Path) -> PathBuf {
+    // Fix the path to be relative to the root directory
+    // NOTE - Surely there is a better way to do this. IDK it's 5:10am
+    let path = path.strip_prefix("./").unwrap_or(path);
+    let path = path.strip_prefix("/").unwrap_or(path);
+    let path = path.strip_prefix(".\\").unwrap_or(path);
+    let path = path.strip_prefix("\\").unwrap_or(path);
+
+    path.to_path_buf()
+}
+
+/// Reads a dependency graph artifact previously written by `prepare`, from standard input
+/// when the path is `-`. A shard index (`prepare --shard-dir`) is loaded with all of its
+/// shards, and metadata stored in a sidecar (`prepare --metadata-file`) is read back.
+/// Their files are looked up relative to the artifact's directory, or the current one.
+pub fn load_graph(graph_artifact_path: &Path) -> Result<DependencyGraph, LoadError> {
+    let mut graph = load_artifact(graph_artifact_path, None)?;
+    attach_metadata(&mut graph, graph_artifact_path, None)?;
+    Ok(graph)
+}
+
+/// Like `load_graph`, but only loads the shards of a shard index that a change to
+/// `changed_files` can affect, and leaves metadata stored in a sidecar unread.
+pub fn load_graph_for(graph_artifact_path: &Path, changed_files: &[PathBuf]) -> Result<DependencyGraph, LoadError> {
+    load_artifact(graph_artifact_path, Some(changed_files))
+}
+
+fn load_artifact(path: &Path, changed_files: Option<&[PathBuf]>) -> Result<DependencyGraph, LoadError> {
+    let content = output::read_artifact_bytes(path)?;
+    if encoding::is_binary(&content) {
+        return match encoding::decode::<DependencyGraph>(&content) {
+            Ok(graph) => {
+                graph.info().check_compatible()?;
+                Ok(graph)
+            }
+            Err(e) => match encoding::decode::<InfoOnly>(&content) {
+                Ok(InfoOnly { info }) => Err(incompatible(&info, &e).unwrap_or(LoadError::Binary(path.to_path_buf(), e))),
+                Err(_) => Err(LoadError::Binary(path.to_path_buf(), e)),
+            },
+        };
+    }
+    // A full artifact fails on its first key, so this is cheap
+    if let Ok(index) = serde_json::from_slice::<shard::ShardIndex>(&content) {
+        index.info.check_compatible()?;
+        return index.load(path.parent().unwrap_or(Path::new("")), changed_files);
+    }
+    match serde_json::from_slice::<DependencyGraph>(&content) {
+        Ok(graph) => {
+            graph.info().check_compatible()?;
+            Ok(graph)
+        }
+        Err(e) => {
+            // An artifact from another version may not parse: explain why when its header says so
+            if let Ok(InfoOnly { info }) = serde_json::from_slice(&content) {
+                if let Some(e) = incompatible(&info, &e) {
+                    return Err(e);
+                }
+            }
+            if e.is_eof() {
+                return Err(LoadError::Truncated(path.to_path_buf(), e));
+            }
+            Err(e.into())
+        }
+    }
+}
+
+/// Why an artifact whose header was read doesn't parse, if another version wrote it.
+fn incompatible(info: &ArtifactInfo, error: &dyn std::fmt::Display) -> Option<LoadError> {
+    match info.check_compatible() {
+        Err(e) => Some(e.into()),
+        Ok(()) => info.unreadable(error).map(LoadError::from),
+    }
+}
+
+/// The header of an artifact, the rest of it is skipped while parsing.
+#[derive(serde::Deserialize)]
+struct InfoOnly {
+    #[serde(default)]
+    info: ArtifactInfo,
+}
+
+/// Queries the dependency graph for the given files.
+/// 
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+/// * `changed_files` - The list of files that have changed
+/// * `filters` - Restrictions on the affected nodes
+/// 
+/// ### Returns
+/// * `Vec<Node>` - The list of nodes that are affected by the changes
+pub fn query(graph: &DependencyGraph, changed_files: &[PathBuf], filters: &FilterArgs) -> Vec<Node> {
+    let affected_nodes = filters.apply(graph, graph.get_affected_nodes(changed_files));
+    affected_nodes.iter()
+        .filter_map(|name| graph.get_node(name))
+        .cloned()
+        .collect()
+}
+
+/// Collects the test commands (`metadata.tests`) of the nodes affected by the given files.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+/// * `changed_files` - The list of files that have changed
+/// * `filters` - Restrictions on the affected nodes
+///
+/// ### Returns
+/// * `Vec<String>` - The deduplicated test commands, dependencies' tests first
+pub fn query_tests(graph: &DependencyGraph, changed_files: &[PathBuf], filters: &FilterArgs) -> Vec<String> {
+    let affected_nodes = filters.apply(graph, graph.get_affected_nodes(changed_files));
+    let mut seen = HashSet::new();
+    graph.toposorted_subset(&affected_nodes)
+        .into_iter()
+        .flat_map(|node| node.tests())
+        .filter(|test| seen.insert(test.clone()))
+        .collect()
+}
+
+/// The `[metadata.ci]` of an affected node, every field present even when empty.
+#[derive(Debug, Clone, Serialize)]
+pub struct CiJob {
+    pub node: String,
+    pub path: PathBuf,
+    #[serde(flatten)]
+    pub ci: CiMetadata,
+}
+
+/// Projects the `metadata.ci` of the nodes affected by the given files.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+/// * `changed_files` - The list of files that have changed
+/// * `filters` - Restrictions on the affected nodes
+///
+/// ### Returns
+/// * `Vec<CiJob>` - A job per affected node, dependencies first
+pub fn query_ci(graph: &DependencyGraph, changed_files: &[PathBuf], filters: &FilterArgs) -> Result<Vec<CiJob>, NodeCreationError> {
+    let affected_nodes = filters.apply(graph, graph.get_affected_nodes(changed_files));
+    graph.toposorted_subset(&affected_nodes)
+        .into_iter()
+        .map(|node| Ok(CiJob {
+            node: node.name.clone(),
+            path: node.path.clone(),
+            ci: node.ci().map_err(|e| NodeCreationError::InvalidCiMetadata(node.name.clone(), e))?,
+        }))
+        .collect()
+}
+
+/// How a change reaches a security-sensitive node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SensitiveHit {
+    /// Some of the node's own files changed.
+    Direct,
+    /// Only dependencies of the node changed.
+    Transitive,
+}
+
+/// An affected node marked `metadata.security_sensitive`.
+#[derive(Debug, Clone, Serialize)]
+pub struct SensitiveNode {
+    pub node: String,
+    pub hit: SensitiveHit,
+    /// The changed node the change comes from, when transitive.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub via: Option<String>,
+}
+
+/// Lists the security-sensitive nodes affected by the given files.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+/// * `changed_files` - The list of files that have changed
+/// * `filters` - Restrictions on the affected nodes
+///
+/// ### Returns
+/// * `Vec<SensitiveNode>` - The affected sensitive nodes, sorted by name
+pub fn query_security(graph: &DependencyGraph, changed_files: &[PathBuf], filters: &FilterArgs) -> Vec<SensitiveNode> {
+    let reasons = graph.get_affected_reasons(changed_files);
+    let affected_nodes = filters.apply(graph, reasons.keys().cloned().collect());
+    affected_nodes.iter()
+        .filter(|name| graph.get_node(name).is_some_and(Node::security_sensitive))
+        .map(|name| match &reasons[name] {
+            AffectedReason::Changed { .. } => SensitiveNode { node: name.clone(), hit: SensitiveHit::Direct, via: None },
+            AffectedReason::Dependency { via } | AffectedReason::Upstream { of: via } => SensitiveNode { node: name.clone(), hit: SensitiveHit::Transitive, via: Some(via.clone()) },
+        })
+        .collect()
+}
+
+/// Measures how many of the nodes affected by the given files can be processed in parallel.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+/// * `changed_files` - The list of files that have changed
+/// * `filters` - Restrictions on the affected nodes
+pub fn query_width(graph: &DependencyGraph, changed_files: &[PathBuf], filters: &FilterArgs) -> Width {
+    width(graph, &filters.apply(graph, graph.get_affected_nodes(changed_files)))
+}
+
+/// The nodes that directly claim a changed file, i.e. whose include patterns match it.
+#[derive(Debug, Clone, Serialize)]
+pub struct FileAttribution {
+    pub file: PathBuf,
+    /// The claiming nodes, sorted by name. Dependents affected through them aren't listed.
+    pub nodes: Vec<String>,
+    /// True when no node claims the file.
+    pub unowned: bool,
+}
+
+/// Attributes each changed file to the nodes that directly claim it.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+/// * `changed_files` - The list of files that have changed
+///
+/// ### Returns
+/// * `Vec<FileAttribution>` - One entry per changed file, in the given order
+pub fn query_files(graph: &DependencyGraph, changed_files: &[PathBuf]) -> Vec<FileAttribution> {
+    changed_files.iter()
+        .map(|file| {
+            let mut claimed_by: Vec<String> = graph.nodes_including(file).into_iter().map(String::from).collect();
+            claimed_by.sort();
+            FileAttribution { file: file.clone(), unowned: claimed_by.is_empty(), nodes: claimed_by }
+        })
+        .collect()
+}
+
+/// Restrictions on the nodes `list` prints, on top of the [`FilterArgs`].
+#[derive(Args, Default)]
+pub struct ListFilters {
+    /// Only list the nodes whose name matches this glob, e.g. `payments-*`.
+    #[arg(long, value_name = "GLOB")]
+    pub name: Option<glob::Pattern>,
+    /// Only list the nodes under this directory, e.g. `services/`.
+    #[arg(long, value_name = "PREFIX")]
+    pub path: Option<PathBuf>,
+    /// Only list the nodes other nodes depend on with `true`, or the ones nothing depends on
+    /// with `false`.
+    #[arg(long, value_name = "BOOL")]
+    pub has_dependents: Option<bool>,
+}
+
+impl ListFilters {
+    fn matches(&self, graph: &DependencyGraph, node: &Node) -> bool {
+        self.name.as_ref().is_none_or(|name| name.matches(&node.name))
+            && self.path.as_ref().is_none_or(|path| node.path.starts_with(path))
+            && self.has_dependents.is_none_or(|has| has != graph.get_dependents(&node.name).is_empty())
+    }
+}
+
+/// Lists the nodes of the graph, sorted by name.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+/// * `list_filters` - Restrictions on the names, paths and dependents of the listed nodes
+/// * `filters` - Restrictions on the listed nodes, with roots and leaves taken among the
+///   nodes matching `list_filters`
+///
+/// ### Returns
+/// * `Vec<Node>` - The nodes matching the filters
+pub fn list(graph: &DependencyGraph, list_filters: &ListFilters, filters: &FilterArgs) -> Vec<Node> {
+    let mut names: Vec<String> = graph.get_all_nodes().into_iter()
+        .filter(|node| list_filters.matches(graph, node))
+        .map(|node| node.name.clone())
+        .collect();
+    names.sort();
+    filters.apply(graph, names).iter()
+        .filter_map(|name| graph.get_node(name))
+        .cloned()
+        .collect()
+}
+
+/// What `query` prints for the affected nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum QueryEmit {
+    /// The affected nodes themselves.
+    Nodes,
+    /// The test commands listed in the `tests` metadata of the affected nodes.
+    Tests,
+    /// Each changed file with the nodes that directly claim it, ignoring the filters.
+    Files,
+    /// The `metadata.ci` of the affected nodes, dependencies first.
+    Ci,
+    /// The affected nodes marked `metadata.security_sensitive`, and how the change reached them.
+    Security,
+    /// The affected nodes tagged `deployable` in deployment waves, by `metadata.rollout.group`,
+    /// ignoring the filters. See `deploy-order`.
+    Rollout,
+    /// The width of the affected subgraph: how many nodes can run in parallel in each layer,
+    /// and at most.
+    Width,
+}
+
+/// Restrictions on the selected nodes, shared by `query`, `list`, `run` and `exec`.
+#[derive(Args, Default)]
+pub struct FilterArgs {
+    /// Only keep the nodes whose metadata matches the expression: `KEY=VALUE`, `KEY == VALUE`
+    /// or `!=` with a JSON value, `KEY < N` or another comparison, or `KEY exists`, where KEY
+    /// is a dotted path, e.g. `metadata.team == "payments"`. Can be repeated, in which case
+    /// every selector must match.
+    #[arg(long = "select", value_name = "EXPR")]
+    pub selectors: Vec<Selector>,
+    /// Only keep the nodes tagged with this language, e.g. by `prepare --detect-languages`.
+    /// Can be repeated, in which case any of the languages matches.
+    #[arg(long = "language", value_enum, value_name = "LANGUAGE")]
+    pub languages: Vec<Language>,
+    /// Only keep the nodes with this tag, from the `tags` of their manifest's `[module]`. Can
+    /// be repeated, in which case every tag must be there.
+    #[arg(long = "filter-tag", value_name = "TAG")]
+    pub tags: Vec<String>,
+    /// Leave out the nodes with this tag. Can be repeated, in which case any of the tags
+    /// leaves a node out.
+    #[arg(long = "exclude-tag", value_name = "TAG")]
+    pub excluded_tags: Vec<String>,
+    /// Only keep the selected nodes that don't depend on another selected node.
+    #[arg(long, default_value_t = false, conflicts_with = "only_leaves")]
+    pub only_roots: bool,
+    /// Only keep the selected nodes that no other selected node depends on.
+    #[arg(long, default_value_t = false)]
+    pub only_leaves: bool,
+}
+
+impl FilterArgs {
+    /// Restricts `names` according to the filters. Roots and leaves are taken among the
+    /// nodes matching the selectors. Trigger-only nodes are always left out.
+    pub fn apply(&self, graph: &DependencyGraph, names: Vec<String>) -> Vec<String> {
+        let names: Vec<String> = names.into_iter()
+            .filter(|name| {
+                let node = graph.get_node(name);
+                node.is_some_and(|node| {
+                    !node.trigger_only
+                        && self.selectors.iter().all(|selector| selector.matches(node))
+                        && (self.languages.is_empty() || self.languages.iter().any(|language| node.has_tag(language.tag())))
+                        && self.tags.iter().all(|tag| node.has_tag(tag))
+                        && !self.excluded_tags.iter().any(|tag| node.has_tag(tag))
+                })
+            })
+            .collect();
+        if self.only_roots {
+            graph.roots_of(&names)
+        } else if self.only_leaves {
+            graph.leaves_of(&names)
+        } else {
+            names
+        }
+    }
+}
+
+/// The options shared by `run` and `exec`.
+#[derive(Args)]
+pub struct RunArgs {
+    /// The JSON artifact file path containing the previously prepared dependency graph
+    /// from the `prepare` command
+    #[arg(short, long, value_name = "FILE")]
+    pub graph_artifact_path: PathBuf,
+    /// The changed files. Only the nodes they affect are run.
+    #[arg(short, long, value_name = "FILE", required_unless_present = "all")]
+    pub files: Vec<PathBuf>,
+    /// Run every node instead of only the affected ones.
+    #[arg(long, default_value_t = false)]
+    pub all: bool,
+    /// The maximum number of nodes running at the same time. Defaults to the number of CPUs.
+    #[arg(short, long, value_name = "N")]
+    pub jobs: Option<usize>,
+    /// Keep running the nodes that don't depend on a failed node instead of stopping
+    /// at the first failure.
+    #[arg(short, long, default_value_t = false)]
+    pub keep_going: bool,
+    /// Run a failing node up to N more times before considering it failed.
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub retries: u32,
+    /// Print the execution plan (nodes, commands, layers and parallelism) instead of running it.
+    #[arg(long, default_value_t = false)]
+    pub plan: bool,
+    /// Print the plan as JSON.
+    #[arg(long, default_value_t = false, requires = "plan")]
+    pub json: bool,
+    /// Write the output of each node to `DIR/<node>.log`, next to the prefixed console output.
+    #[arg(long, value_name = "DIR")]
+    pub log_dir: Option<PathBuf>,
+    /// Write a JSON report of the run: status, duration, exit code, cache hit and log file
+    /// of every node.
+    #[arg(long, value_name = "FILE")]
+    pub report: Option<PathBuf>,
+    /// Write the run as a JUnit XML test suite, one test case per node.
+    #[arg(long, value_name = "FILE")]
+    pub junit: Option<PathBuf>,
+    #[command(flatten)]
+    pub filters: FilterArgs,
+}
+
+/// The commands that can be executed by the Clap-based CLI.
+#[derive(Subcommand)]
+pub enum Commands {
+    /// Prepares a dependency graph using all the `dependency.toml` files, starting 
+    /// recursively from the given directories. Store the resulting JSON in an 
+    /// artifact to use it for other commands.
+    Prepare {
+        /// The directory to start the recursive scan from. Repeat it to scan several
+        /// directories into one graph, e.g. to leave out a directory between them.
+        #[arg(short, long, value_name = "DIR", required_unless_present_any = ["manifest_list", "archive"])]
+        dir: Vec<PathBuf>,
+        /// Make the node paths relative to DIR, which must contain every scanned directory,
+        /// instead of the current directory. Changed files must then be given relative to DIR.
+        /// With `--archive`, a directory inside the archive.
+        #[arg(long, value_name = "DIR")]
+        root: Option<PathBuf>,
+        /// Don't walk the directories and files matching PATTERN, relative to the root, e.g.
+        /// `**/generated` for a large directory only present on some machines. Repeatable.
+        #[arg(long, value_name = "PATTERN")]
+        skip: Vec<glob::Pattern>,
+        /// Abort when the walk goes more than N directories below a scanned directory, e.g.
+        /// when run from the wrong directory.
+        #[arg(long, value_name = "N")]
+        max_depth: Option<usize>,
+        /// Abort when the walk visits more than N files and directories in total.
+        #[arg(long, value_name = "N")]
+        max_files: Option<usize>,
+        /// Read the paths of the manifests from FILE, one per line, instead of walking a
+        /// directory, e.g. the output of `git ls-files '**/dependencies.toml'`. `-` for
+        /// standard input.
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["dir", "skip", "max_depth", "max_files"])]
+        manifest_list: Option<PathBuf>,
+        /// Read the manifests from a tarball, gzipped or not, or a zip file, e.g. a CI source
+        /// archive, instead of a checked-out directory. The commit `git archive` records in
+        /// it becomes the artifact's source commit.
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["dir", "manifest_list", "max_depth", "max_files", "amend", "index_files", "detect_languages"])]
+        archive: Option<PathBuf>,
+        /// The name of the dependency toml file commmon to all the services. 
+        /// Defaults to `dependencies.toml`.
+        #[arg(long, value_name = "NAME")]
+        dependency_toml_name: Option<String>,
+        /// Whether to allow the node dependency graph to be cyclical. Defaults to `false`.
+        #[arg(long, value_name = "ALLOW_CYCLICAL")]
+        allow_cyclical: bool,
+        /// Write one artifact per top-level directory and an `index.json` into DIR instead of
+        /// printing the graph. Pass the index as the artifact of other commands: `query` only
+        /// loads the shards the changed files can affect.
+        #[arg(long, value_name = "DIR")]
+        shard_dir: Option<PathBuf>,
+        /// Store the node metadata in FILE instead of the artifact, so queries that don't
+        /// output it never parse it. Keep the artifact in the same directory as FILE.
+        #[arg(long, value_name = "FILE", conflicts_with = "shard_dir")]
+        metadata_file: Option<PathBuf>,
+        /// The artifact of a previous `prepare` of the same directory. Only the manifests
+        /// that changed since are parsed again.
+        #[arg(long, value_name = "FILE")]
+        previous: Option<PathBuf>,
+        /// Add the nodes under DIR to this artifact instead of preparing a new one, e.g. for
+        /// code generated mid-pipeline. Only the new nodes are checked. Run from the directory
+        /// the artifact was prepared in.
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["shard_dir", "previous", "index_files"])]
+        amend: Option<PathBuf>,
+        /// List every file matched by a node, with its hash, in the artifact. Queries then
+        /// attribute the listed files without matching patterns. Run from DIR.
+        #[arg(long, default_value_t = false, conflicts_with = "shard_dir")]
+        index_files: bool,
+        /// Store the direct and indirect dependencies of every node in the artifact, so
+        /// `graph reaches` answers with a lookup. Grows with the square of the number of nodes.
+        #[arg(long, default_value_t = false, conflicts_with = "shard_dir")]
+        reachability: bool,
+        /// Give the nodes without `metadata.owners` the owners of their directory in this
+        /// CODEOWNERS file.
+        #[arg(long, value_name = "FILE")]
+        codeowners: Option<PathBuf>,
+        /// Tag every node with the languages of the toolchain manifests in its directory:
+        /// `rust` (Cargo.toml), `go` (go.mod), `python` (pyproject.toml, setup.py...),
+        /// `typescript` or `javascript` (package.json, with or without tsconfig.json). Run
+        /// from DIR.
+        #[arg(long, default_value_t = false)]
+        detect_languages: bool,
+        /// The name of the repository, which the manifests of other repositories use in their
+        /// dependencies as `{ repo = "NAME", name = ".." }`. Needed by `artifact merge`.
+        #[arg(long, value_name = "NAME")]
+        repo: Option<String>,
+        /// Report the nodes whose patterns match more than N files, overriding
+        /// `glob_guard.max_files` of the root config. Run from DIR.
+        #[arg(long, value_name = "N", conflicts_with = "archive")]
+        max_node_files: Option<usize>,
+        /// Write the artifact as JSON or as compressed MessagePack, several times smaller and
+        /// faster to load. Every command reads either.
+        #[arg(long, value_enum, default_value_t = ArtifactFormat::Json, conflicts_with = "shard_dir")]
+        artifact_format: ArtifactFormat,
+        /// Read and parse the manifests on N threads, or one per CPU with 0. Worth it for
+        /// repositories of thousands of nodes; the default, 1, is faster for small ones.
+        #[arg(short, long, value_name = "N", default_value_t = 1)]
+        jobs: usize,
+    },
+    /// Queries the dependency graph artifact for all the dependency nodes touched by 
+    /// the given file changes. HINT: Combo it with `git diff --name-only` to know which 
+    /// files have changed, and, consequently, which nodes are affected. Results include 
+    /// all the metadata and file paths of the affected nodes.
+    /// 
+    /// This command only requires the artifact and changed file names, it doesn't need 
+    /// to read any files or directories.
+    Query {
+        /// The JSON artifact file path containing the previously prepared dependency graph 
+        /// from the `prepare` command
+        #[arg(short, long, value_name = "FILE", required_unless_present = "at")]
+        graph_artifact_path: Option<PathBuf>,
+        /// Query the artifact stored in `--store` for COMMIT, a hash, branch or tag, by
+        /// `artifact store`, instead of `--graph-artifact-path`.
+        #[arg(long, value_name = "COMMIT", requires = "store", conflicts_with = "graph_artifact_path")]
+        at: Option<String>,
+        /// The artifact store `--at` reads from: a local directory, `http(s)://...` or
+        /// `s3://bucket/prefix`.
+        #[arg(long, value_name = "LOCATION", requires = "at")]
+        store: Option<String>,
+        /// A list of file paths to query.
+        #[arg(short, long, value_name = "FILE")]
+        files: Vec<PathBuf>,
+        /// Also query the files changed since the merge base with this ref, committed or not,
+        /// e.g. `origin/main`, as listed by git. Run from the directory the artifact was
+        /// prepared in.
+        #[arg(long, value_name = "REF")]
+        since: Option<String>,
+        /// What to output: the affected nodes, the deduplicated and topologically ordered
+        /// test commands from their `metadata.tests`, the nodes claiming each changed file,
+        /// the `[metadata.ci]` of the affected nodes, the affected security-sensitive nodes, the
+        /// affected deployables by rollout group, or the width of the affected subgraph.
+        #[arg(long, value_enum, default_value_t = QueryEmit::Nodes)]
+        emit: QueryEmit,
+        /// How to print it: a JSON document, one node name per line, or one JSON document
+        /// per line.
+        #[arg(long, value_enum, default_value_t = QueryFormat::Json)]
+        format: QueryFormat,
+        /// Print the nodes in dependency order, dependencies before their dependents, instead
+        /// of by name: the order to rebuild and redeploy them in. Test commands and CI jobs
+        /// always come in that order.
+        #[arg(long, default_value_t = false)]
+        toposort: bool,
+        /// Which nodes to report besides the changed ones: their dependents, which the
+        /// change affects, their dependencies, which must be built first, or both. Not with
+        /// `--stream`.
+        #[arg(long, value_enum, default_value_t = QueryDirection::Down, conflicts_with = "stream")]
+        direction: QueryDirection,
+        /// Only report the nodes at most N dependency edges away from a changed node, e.g.
+        /// only the direct dependents with 1. Not with `--stream`.
+        #[arg(long, value_name = "N", conflicts_with = "stream")]
+        max_depth: Option<usize>,
+        #[command(flatten)]
+        filters: FilterArgs,
+        #[command(flatten)]
+        budget: BudgetArgs,
+        /// Read the metadata of the affected nodes from the artifact's metadata file, when
+        /// it has one. Inline metadata is always included.
+        #[arg(long, default_value_t = false)]
+        with_metadata: bool,
+        /// Also append the affected nodes to the file named by `$GITHUB_OUTPUT`, as GitHub
+        /// Actions step outputs: `affected`, a JSON list of names, `matrix`, a job matrix
+        /// including each node's name, path and metadata, and `count`.
+        #[arg(long, default_value_t = false)]
+        github_output: bool,
+        /// Read the artifact as a stream, keeping only the affected nodes in memory, for
+        /// large artifacts on small machines. Slower, and not for sharded artifacts.
+        #[arg(long, default_value_t = false, conflicts_with = "hash_compare")]
+        stream: bool,
+        /// Compare the artifact's file index with the one of an older artifact, both prepared
+        /// with `--index-files`, and only treat the files whose content differs as changed.
+        /// Without `--files`, every such file is.
+        #[arg(long, value_name = "FILE")]
+        hash_compare: Option<PathBuf>,
+        /// Keep the affected nodes of each change in DIR, keyed by the artifact and the
+        /// changed files, so that later queries of the same change, e.g. by other stages of a
+        /// pipeline, read them instead of matching the files again.
+        #[arg(long, value_name = "DIR", conflicts_with = "stream")]
+        cache_dir: Option<PathBuf>,
+        /// Match the changed files against the nodes on N threads, or one per CPU with 0.
+        /// Worth it for thousands of files and nodes.
+        #[arg(short, long, value_name = "N", default_value_t = 1)]
+        jobs: usize,
+    },
+    /// Explains why `query` reports a node as affected by the given files: prints the chain
+    /// of dependencies from every changed node reaching it, e.g. `shared-lib -> payments ->
+    /// checkout`, with the changed files that cascade along it. Exits with a non-zero status
+    /// if the node isn't affected.
+    Why {
+        /// The JSON artifact file path containing the previously prepared dependency graph
+        /// from the `prepare` command
+        #[arg(short, long, value_name = "FILE")]
+        graph_artifact_path: PathBuf,
+        /// The affected node to explain.
+        #[arg(short, long, value_name = "NAME")]
+        node: String,
+        /// A list of file paths to query.
+        #[arg(short, long, value_name = "FILE")]
+        files: Vec<PathBuf>,
+        /// Also query the files changed since the merge base with this ref, as with `query`.
+        #[arg(long, value_name = "REF")]
+        since: Option<String>,
+        /// Print the chains as JSON instead of one per line.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Prepares the dependency graph in memory and queries it at once, without writing an
+    /// artifact, for small repositories and local use. Takes the changed files from git, the
+    /// given ones, or both. Run from the repository root.
+    Affected {
+        /// The directory to start the recursive scan from. Repeatable.
+        #[arg(short, long, value_name = "DIR", default_value = ".")]
+        dir: Vec<PathBuf>,
+        /// Defaults to `dependencies.toml`.
+        #[arg(long, value_name = "NAME")]
+        dependency_toml_name: Option<String>,
+        #[arg(long, default_value_t = false)]
+        allow_cyclical: bool,
+        /// Treat the files changed since the merge base with this ref as changed, committed
+        /// or not, e.g. `origin/main`.
+        #[arg(long, value_name = "REF")]
+        since: Option<String>,
+        /// More changed files.
+        #[arg(short, long, value_name = "FILE")]
+        files: Vec<PathBuf>,
+        /// What to output, as with `query`.
+        #[arg(long, value_enum, default_value_t = QueryEmit::Nodes)]
+        emit: QueryEmit,
+        /// How to print it, as with `query`.
+        #[arg(long, value_enum, default_value_t = QueryFormat::Json)]
+        format: QueryFormat,
+        /// Print the nodes in dependency order, as with `query`.
+        #[arg(long, default_value_t = false)]
+        toposort: bool,
+        /// Which nodes to report besides the changed ones, as with `query`.
+        #[arg(long, value_enum, default_value_t = QueryDirection::Down)]
+        direction: QueryDirection,
+        /// Only report the nodes at most N dependency edges away from a changed node, as
+        /// with `query`.
+        #[arg(long, value_name = "N")]
+        max_depth: Option<usize>,
+        #[command(flatten)]
+        filters: FilterArgs,
+        /// Parse the manifests and match the files on N threads, as with `prepare`.
+        #[arg(short, long, value_name = "N", default_value_t = 1)]
+        jobs: usize,
+    },
+    /// Watches the directories, keeping their graph in memory and preparing it again when a
+    /// manifest changes, and prints a JSON line with the nodes each batch of changed files
+    /// affects. Runs until interrupted. Run from the repository root.
+    Watch {
+        /// The directory to watch and scan for manifests. Repeatable.
+        #[arg(short, long, value_name = "DIR", default_value = ".")]
+        dir: Vec<PathBuf>,
+        /// Defaults to `dependencies.toml`.
+        #[arg(long, value_name = "NAME")]
+        dependency_toml_name: Option<String>,
+        /// Don't walk the directories and files matching PATTERN, nor report their changes.
+        /// Repeatable.
+        #[arg(long, value_name = "PATTERN")]
+        skip: Vec<glob::Pattern>,
+        #[arg(long, default_value_t = false)]
+        allow_cyclical: bool,
+        /// How long to wait for more changes before reporting, e.g. `1s`.
+        #[arg(long, value_name = "DURATION", default_value = "300ms")]
+        debounce: humantime::Duration,
+        /// Also POST each report, as JSON, to this URL.
+        #[arg(long, value_name = "URL")]
+        webhook: Option<String>,
+        #[command(flatten)]
+        filters: FilterArgs,
+    },
+    /// Loads the artifact once and answers queries over HTTP until interrupted: `/affected`,
+    /// `/dependents/{node}`, `/dependencies/{node}` and `/node/{name}`, in JSON.
+    #[cfg(feature = "serve")]
+    Serve {
+        /// The JSON artifact file path containing the previously prepared dependency graph
+        /// from the `prepare` command
+        #[arg(short, long, value_name = "FILE")]
+        graph_artifact_path: PathBuf,
+        /// The address to listen on.
+        #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:8080")]
+        listen: String,
+        /// How many requests to answer at once.
+        #[arg(long, value_name = "N", default_value_t = 4)]
+        threads: usize,
+    },
+    /// Prepares the graph and queries it, printing as JSON the time spent walking, reading
+    /// and parsing manifests, building the graph, writing and reading the artifact, matching
+    /// files and traversing the graph, with the slowest manifests and patterns. Run from the
+    /// repository root.
+    Profile {
+        /// The directory to start the recursive scan from. Repeatable.
+        #[arg(short, long, value_name = "DIR", default_value = ".")]
+        dir: Vec<PathBuf>,
+        /// Defaults to `dependencies.toml`.
+        #[arg(long, value_name = "NAME")]
+        dependency_toml_name: Option<String>,
+        /// Don't walk the directories and files matching PATTERN. Repeatable.
+        #[arg(long, value_name = "PATTERN")]
+        skip: Vec<glob::Pattern>,
+        #[arg(long, default_value_t = false)]
+        allow_cyclical: bool,
+        /// The changed files to query. By default every file under the directories, which
+        /// times the matching of the worst change.
+        #[arg(short, long, value_name = "FILE")]
+        files: Vec<PathBuf>,
+        /// How many of the slowest manifests and patterns to report.
+        #[arg(long, value_name = "N", default_value_t = 10)]
+        top: usize,
+    },
+    /// Writes a repository of generated nodes, to test a config or the scripts wrapping this
+    /// tool against a repository of a given size and shape, and prints them as JSON.
+    #[command(hide = true)]
+    GenerateFixture {
+        /// The directory to write the repository into, created if needed.
+        #[arg(long, value_name = "DIR")]
+        out: PathBuf,
+        #[arg(long, value_name = "N", default_value_t = 10)]
+        nodes: usize,
+        /// How the nodes depend on each other.
+        #[arg(long, value_enum, default_value_t = FixtureShape::Layered)]
+        shape: FixtureShape,
+        /// The number of nodes per layer of the `layered` shape.
+        #[arg(long, value_name = "N", default_value_t = 4)]
+        width: usize,
+        /// The most dependencies a node of the `layered` shape has.
+        #[arg(long, value_name = "N", default_value_t = 2)]
+        fan_out: usize,
+        #[arg(long, value_name = "N", default_value_t = 2)]
+        files_per_node: usize,
+        /// How many directories deep the source files are nested.
+        #[arg(long, value_name = "N", default_value_t = 1)]
+        depth: usize,
+        /// Seeds the choice of dependencies of the `layered` shape.
+        #[arg(long, value_name = "N", default_value_t = 1)]
+        seed: u64,
+        #[arg(long, value_name = "NAME")]
+        dependency_toml_name: Option<String>,
+    },
+    /// Lists the nodes of the dependency graph artifact, with their metadata, sorted by name.
+    List {
+        /// The JSON artifact file path containing the previously prepared dependency graph
+        /// from the `prepare` command
+        #[arg(short, long, value_name = "FILE")]
+        graph_artifact_path: PathBuf,
+        #[command(flatten)]
+        list_filters: ListFilters,
+        #[command(flatten)]
+        filters: FilterArgs,
+        /// How to print the nodes, as with `query`.
+        #[arg(long, value_enum, default_value_t = QueryFormat::Json)]
+        format: QueryFormat,
+    },
+    /// Converts an existing monorepo tool's project configuration (Nx, Turborepo or 
+    /// Lerna) into `dependencies.toml` files written next to each project. Meant as a 
+    /// one-time adoption path; review the generated files before committing them.
+    Migrate {
+        /// The tool whose project configuration should be converted.
+        #[arg(long, value_enum)]
+        from: MigrateSource,
+        /// The root directory of the monorepo.
+        #[arg(short, long, value_name = "DIR")]
+        dir: PathBuf,
+        /// The name of the dependency toml files to write. Defaults to `dependencies.toml`.
+        #[arg(long, value_name = "NAME")]
+        dependency_toml_name: Option<String>,
+        /// Only report which files would be written.
+        #[arg(long)]
+        dry_run: bool,
+        /// Overwrite dependency toml files that already exist.
+        #[arg(long)]
+        force: bool,
+    },
+    /// Checks every node against the lint rules configured in the `[lint]` section of 
+    /// the root config (`dependency-cascade.toml`): maximum dependencies per node, 
+    /// forbidden edges between tagged groups, required tags and naming conventions. 
+    /// Exits with a non-zero status if any finding has `error` severity.
+    Lint {
+        /// The directory to start the recursive scan from.
+        #[arg(short, long, value_name = "DIR")]
+        dir: PathBuf,
+        /// The name of the dependency toml file commmon to all the services. 
+        /// Defaults to `dependencies.toml`.
+        #[arg(long, value_name = "NAME")]
+        dependency_toml_name: Option<String>,
+        /// Print the findings as JSON instead of human-readable text.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Checks the dependency toml files like `prepare` would, but reports every problem at
+    /// once instead of stopping at the first: manifests that don't parse or include no files,
+    /// invalid glob patterns, duplicate names, missing dependencies and cycles, each with its
+    /// file and line when known. Exits with a non-zero status if there are any.
+    Validate {
+        /// The directory to start the recursive scan from. Repeatable.
+        #[arg(short, long, value_name = "DIR", default_value = ".")]
+        dir: Vec<PathBuf>,
+        /// Defaults to `dependencies.toml`.
+        #[arg(long, value_name = "NAME")]
+        dependency_toml_name: Option<String>,
+        /// Don't walk the directories and files matching PATTERN. Repeatable.
+        #[arg(long, value_name = "PATTERN")]
+        skip: Vec<glob::Pattern>,
+        /// Print the problems as JSON instead of human-readable text.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Automatically repairs a subset of issues in the dependency toml files: dangling 
+    /// dependency entries, duplicate declarations, aliased dependency keys and unsorted 
+    /// lists. Each rule has to be opted into with `--rule` (or `--all`).
+    Fix {
+        /// The directory to start the recursive scan from.
+        #[arg(short, long, value_name = "DIR")]
+        dir: PathBuf,
+        /// The name of the dependency toml file commmon to all the services. 
+        /// Defaults to `dependencies.toml`.
+        #[arg(long, value_name = "NAME")]
+        dependency_toml_name: Option<String>,
+        /// A rule to apply. Can be repeated.
+        #[arg(short, long = "rule", value_enum, value_name = "RULE", required_unless_present = "all")]
+        rules: Vec<FixRule>,
+        /// Apply every rule.
+        #[arg(long)]
+        all: bool,
+        /// Print a diff of the changes instead of writing them.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Transforms and describes graph artifacts.
+    Artifact {
+        #[command(subcommand)]
+        command: ArtifactCommands,
+    },
+    /// Exports the graph to the formats of other tools.
+    Export {
+        #[command(subcommand)]
+        command: ExportCommands,
+    },
+    /// Generates CI configuration for the nodes affected by a change.
+    Generate {
+        #[command(subcommand)]
+        command: GenerateCommands,
+    },
+    /// Plans releases from the commits since the last one.
+    Release {
+        #[command(subcommand)]
+        command: ReleaseCommands,
+    },
+    /// Visualizes the dependency graph.
+    Graph {
+        #[command(subcommand)]
+        command: GraphCommands,
+    },
+    /// Analyzes the structure of the graph for refactorings.
+    Advise {
+        #[command(subcommand)]
+        command: AdviseCommands,
+    },
+    /// Derives a CODEOWNERS file from each node's `metadata.owners` and include 
+    /// patterns, keeping review routing in sync with the dependency toml files.
+    Codeowners {
+        #[command(subcommand)]
+        command: CodeownersCommands,
+    },
+    /// Scans the source files of each node for imports resolving into other nodes' 
+    /// paths (Rust `use` and `path` dependencies, relative TS/JS imports, Go imports) and 
+    /// reports declared-but-unused and used-but-undeclared dependencies.
+    SuggestDeps {
+        /// The directory to start the recursive scan from.
+        #[arg(short, long, value_name = "DIR")]
+        dir: PathBuf,
+        /// The name of the dependency toml file commmon to all the services. 
+        /// Defaults to `dependencies.toml`.
+        #[arg(long, value_name = "NAME")]
+        dependency_toml_name: Option<String>,
+        /// Only scan files of this language. Can be repeated. Defaults to all languages.
+        #[arg(short, long = "language", value_enum, value_name = "LANGUAGE")]
+        languages: Vec<ScanLanguage>,
+        /// Only report on this node.
+        #[arg(short, long, value_name = "NAME")]
+        node: Option<String>,
+    },
+    /// Computes the affected nodes of every commit since a git ref and aggregates them, printed
+    /// as JSON: how often each node was affected, the average number of affected nodes per commit
+    /// and its trend over time. Must run from the directory `prepare` ran in.
+    History {
+        /// The JSON artifact file path containing the previously prepared dependency graph
+        /// from the `prepare` command
+        #[arg(short, long, value_name = "FILE")]
+        graph_artifact_path: PathBuf,
+        /// Only commits after this ref are evaluated.
+        #[arg(long, value_name = "REF")]
+        since: String,
+        /// The last commit to evaluate.
+        #[arg(long, value_name = "REF", default_value = "HEAD")]
+        until: String,
+        /// How commits are grouped for the trend.
+        #[arg(long, value_enum, default_value_t = HistoryPeriod::Week)]
+        period: HistoryPeriod,
+    },
+    /// Ranks the nodes changed since a git ref by how often they changed times their number
+    /// of direct and indirect dependents, printed as JSON. High scores are modules that change
+    /// often and affect a lot. Must run from the directory `prepare` ran in.
+    Hotspots {
+        /// The JSON artifact file path containing the previously prepared dependency graph
+        /// from the `prepare` command
+        #[arg(short, long, value_name = "FILE")]
+        graph_artifact_path: PathBuf,
+        /// Only commits after this ref are counted.
+        #[arg(long, value_name = "REF")]
+        since: String,
+        /// The last commit to count.
+        #[arg(long, value_name = "REF", default_value = "HEAD")]
+        until: String,
+        /// Only print the N highest scores.
+        #[arg(long, value_name = "N")]
+        limit: Option<usize>,
+        /// Print a markdown table instead of JSON.
+        #[arg(long, default_value_t = false)]
+        markdown: bool,
+    },
+    /// Lists, for every node, its direct and indirect dependents and the number of include
+    /// patterns they cover, printed as JSON with the largest blast radius first. Exits with a
+    /// non-zero status when a node has more dependents than `[blast_radius] max_dependents`
+    /// of the root config (or `--max-dependents`).
+    BlastRadius {
+        /// The JSON artifact file path containing the previously prepared dependency graph
+        /// from the `prepare` command
+        #[arg(short, long, value_name = "FILE")]
+        graph_artifact_path: PathBuf,
+        /// The most dependents a node may have. Overrides the root config.
+        #[arg(long, value_name = "N")]
+        max_dependents: Option<usize>,
+    },
+    /// Prints shields.io endpoint badges, as a JSON object by badge name: the number of nodes
+    /// (`nodes`), the most dependents of a node (`blast-radius`, red above `[blast_radius]
+    /// max_dependents` of the root config) and the share of nodes with owners (`ownership`).
+    Badges {
+        /// The JSON artifact file path containing the previously prepared dependency graph
+        /// from the `prepare` command
+        #[arg(short, long, value_name = "FILE")]
+        graph_artifact_path: PathBuf,
+        /// Also write each badge to `<DIR>/<name>.json`, to be published and referenced by
+        /// `https://img.shields.io/endpoint?url=...`.
+        #[arg(long, value_name = "DIR")]
+        out_dir: Option<PathBuf>,
+    },
+    /// Prints, as JSON, whether each node affected by the changed files is only affected by
+    /// soft changes: files in one of the `[classify]` categories of the root config (by
+    /// default `docs` and `ci`), so pipelines can run lighter jobs for them.
+    Classify {
+        /// The JSON artifact file path containing the previously prepared dependency graph
+        /// from the `prepare` command
+        #[arg(short, long, value_name = "FILE")]
+        graph_artifact_path: PathBuf,
+        /// A list of file paths to query.
+        #[arg(short, long, value_name = "FILE")]
+        files: Vec<PathBuf>,
+    },
+    /// Prints, as JSON, the deployable nodes affected by the changed files in waves: every
+    /// node is in a later wave than the deployables it depends on, directly or not.
+    DeployOrder {
+        /// The JSON artifact file path containing the previously prepared dependency graph
+        /// from the `prepare` command
+        #[arg(short, long, value_name = "FILE")]
+        graph_artifact_path: PathBuf,
+        /// A list of file paths to query.
+        #[arg(short, long, value_name = "FILE")]
+        files: Vec<PathBuf>,
+        /// The tag marking the deployable nodes.
+        #[arg(long, value_name = "TAG", default_value = "deployable")]
+        tag: String,
+        /// Also deploy the deployable dependencies of the affected deployables, even when the
+        /// change didn't affect them.
+        #[arg(long, default_value_t = false)]
+        with_dependencies: bool,
+        /// Split the waves by the nodes' `metadata.rollout.group`, groups sorted by name and
+        /// nodes without one last, for progressive delivery.
+        #[arg(long, default_value_t = false)]
+        by_group: bool,
+    },
+    /// Prints, as JSON, the reviewers that the nodes affected by the changed files require
+    /// in their `metadata.required_reviewers`, each with the nodes requiring them.
+    Reviewers {
+        /// The JSON artifact file path containing the previously prepared dependency graph
+        /// from the `prepare` command
+        #[arg(short, long, value_name = "FILE")]
+        graph_artifact_path: PathBuf,
+        /// A list of file paths to query.
+        #[arg(short, long, value_name = "FILE")]
+        files: Vec<PathBuf>,
+        /// Only ask for the reviewers of the nodes whose own files changed, not of their
+        /// dependents.
+        #[arg(long, default_value_t = false)]
+        direct: bool,
+    },
+    /// Prints, as JSON, the include patterns of a node and of all of its direct and indirect
+    /// dependencies: every path whose change would mark the node as affected. Meant for
+    /// configuring path filters of external CI triggers.
+    Triggers {
+        /// The JSON artifact file path containing the previously prepared dependency graph
+        /// from the `prepare` command
+        #[arg(short, long, value_name = "FILE")]
+        graph_artifact_path: PathBuf,
+        /// The node to collect the triggers of.
+        node: String,
+        /// Also list the files currently matching, which requires running from the directory
+        /// `prepare` ran in.
+        #[arg(long, default_value_t = false)]
+        list_files: bool,
+    },
+    /// Prints a node's profile: its path, manifest, tags and patterns, its direct dependencies
+    /// and dependents with how many it has in total, and its metadata.
+    Info {
+        /// The JSON artifact file path containing the previously prepared dependency graph
+        /// from the `prepare` command
+        #[arg(short, long, value_name = "FILE")]
+        graph_artifact_path: PathBuf,
+        /// The node to describe.
+        node: String,
+        /// Print the profile as JSON instead of text.
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Walks the working tree and prints, as JSON, the share of files matched by at least one
+    /// node, overall and per top-level directory. Must run from the directory `prepare` ran in.
+    Coverage {
+        /// The JSON artifact file path containing the previously prepared dependency graph
+        /// from the `prepare` command
+        #[arg(short, long, value_name = "FILE")]
+        graph_artifact_path: PathBuf,
+    },
+    /// Computes a content hash per node from its files and the hashes of its dependencies,
+    /// printed as JSON. A node's hash only changes when something in its closure changes,
+    /// which makes it usable as a CI cache key. Must run from the directory `prepare` ran in.
+    Hash {
+        /// The JSON artifact file path containing the previously prepared dependency graph
+        /// from the `prepare` command
+        #[arg(short, long, value_name = "FILE")]
+        graph_artifact_path: PathBuf,
+        /// Only print the hashes of these nodes. Can be repeated. Defaults to all nodes.
+        #[arg(short, long = "node", value_name = "NAME")]
+        nodes: Vec<String>,
+    },
+    /// Runs the commands listed under `metadata.tasks.<task>` for every affected node,
+    /// dependencies first and with up to `--jobs` nodes in parallel. Output lines are
+    /// prefixed with the node name. Exits with a non-zero status if any node fails.
+    Run {
+        /// The task to run, looked up in each node's `metadata.tasks`.
+        task: String,
+        #[command(flatten)]
+        args: RunArgs,
+        /// Cache the outputs declared in `metadata.outputs.<task>`, keyed by the node's content
+        /// hash: a local directory, an `http(s)://` server accepting GET and PUT, or `s3://bucket/prefix`.
+        #[arg(long, value_name = "LOCATION")]
+        cache: Option<String>,
+    },
+    /// Runs an ad-hoc shell command in the directory of every affected node, with the same
+    /// ordering, parallelism and environment as `run`.
+    Exec {
+        #[command(flatten)]
+        args: RunArgs,
+        /// The command to run, after `--`. Its arguments are joined with spaces and run
+        /// through the shell.
+        #[arg(last = true, required = true, value_name = "COMMAND")]
+        command: Vec<String>,
+    },
+    /// Splits a node into several nodes, each owning part of its include patterns.
+    /// Writes the new dependency toml files, points the dependents of the node at the
+    /// right fragments and re-validates the graph before writing anything.
+    SplitNode {
+        /// The directory to start the recursive scan from.
+        #[arg(short, long, value_name = "DIR")]
+        dir: PathBuf,
+        /// The name of the dependency toml file commmon to all the services. 
+        /// Defaults to `dependencies.toml`.
+        #[arg(long, value_name = "NAME")]
+        dependency_toml_name: Option<String>,
+        /// The node to split.
+        #[arg(short, long, value_name = "NAME")]
+        node: String,
+        /// The TOML file listing the `[[fragments]]` (name, include patterns and optionally
+        /// path and dependencies) and the fragments each dependent should use under `[dependents]`.
+        #[arg(short, long, value_name = "FILE")]
+        mapping: PathBuf,
+        /// Ask which fragments to use for dependents missing from the mapping file,
+        /// instead of pointing them at every fragment.
+        #[arg(short, long, default_value_t = false)]
+        interactive: bool,
+        /// Print the changes as a diff instead of writing them.
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testkit::FixtureDir;
+    use crate::types::SCHEMA_VERSION;
+
+    use super::*;
+
+    #[test]
+    fn test_incremental_prepare() {
+        let dir = FixtureDir::new()
+            .manifest("a", "[module]\nname = \"a\"\n\n[file_paths]\ninclude = [\"**\"]\n")
+            .manifest("b", "[module]\nname = \"b\"\n\n[file_paths]\ninclude = [\"**\"]\n");
+        let mut previous = prepare(&Scan::new(dir.path().to_path_buf(), None), false, None).unwrap();
+        assert_eq!(previous.manifest_hashes().len(), 2);
+
+        // Mark both nodes, then only change b's manifest: a is reused, b is parsed again
+        let marker = Some(serde_json::json!({"reused": true}));
+        previous.set_node_metadata("a", marker.clone());
+        previous.set_node_metadata("b", marker.clone());
+        dir.write("b/dependencies.toml", "[module]\nname = \"b\"\n\n[dependencies]\na = { name = \"a\" }\n\n[file_paths]\ninclude = [\"**\"]\n");
+
+        let graph = prepare(&Scan::new(dir.path().to_path_buf(), None), false, Some(&previous)).unwrap();
+        assert_eq!(graph.get_node("a").unwrap().metadata, marker);
+        assert_eq!(graph.get_node("b").unwrap().metadata, None);
+        assert_eq!(graph.get_node("b").unwrap().dependencies, vec!["a"]);
+        assert_ne!(graph.manifest_hashes(), previous.manifest_hashes());
+    }
+
+    #[test]
+    fn test_prepare_default_metadata() {
+        let dir = FixtureDir::new()
+            .manifest("a", "[module]\nname = \"a\"\n\n[metadata.ci]\nrunner = \"large\"\n\n[file_paths]\ninclude = [\"**\"]\n");
+        let scan = |defaults: serde_json::Value| Scan { default_metadata: Some(defaults), ..Scan::new(dir.path().to_path_buf(), None) };
+
+        let previous = prepare(&scan(serde_json::json!({"ci": {"runner": "small", "test": "make test"}})), false, None).unwrap();
+        // The manifest didn't change but the defaults did: the node is merged again
+        let graph = prepare(&scan(serde_json::json!({"team": "core"})), false, Some(&previous));
+
+        let metadata = previous.get_node("a").unwrap().metadata.clone().unwrap();
+        assert_eq!((metadata["ci"]["runner"].as_str(), metadata["ci"]["test"].as_str()), (Some("large"), Some("make test")));
+        let metadata = graph.unwrap().get_node("a").unwrap().metadata.clone().unwrap();
+        assert_eq!(metadata, serde_json::json!({"ci": {"runner": "large"}, "team": "core"}));
+    }
+
+    #[test]
+    fn test_prepare_several_dirs() {
+        let dir = FixtureDir::new();
+        for (name, path) in [("a", "a"), ("c", "vendor/c"), ("d", "experiments/d")] {
+            dir.write(Path::new(path).join("dependencies.toml"), format!("[module]\nname = \"{}\"\n\n[file_paths]\ninclude = [\"**\"]\n", name));
+        }
+
+        // The middle directory is left out, and a directory listed twice is scanned once
+        let scan = Scan {
+            dirs: vec![dir.join("a"), dir.join("vendor"), dir.join("a")],
+            root: Some(dir.path().to_path_buf()),
+            ..Scan::default()
+        };
+        let graph = prepare(&scan, false, None).unwrap();
+        let outside = prepare(&Scan { dirs: vec![std::env::temp_dir()], ..scan }, false, None);
+        let mut paths: Vec<&Path> = graph.get_all_nodes().into_iter().map(|node| node.path.as_path()).collect();
+        paths.sort();
+        assert_eq!(paths, vec![Path::new("a"), Path::new("vendor/c")]);
+        assert_eq!(graph.get_node("c").unwrap().manifest, Some(PathBuf::from("vendor/c/dependencies.toml")));
+        assert!(outside.is_err());
+    }
+
+    #[test]
+    fn test_query_security() {
+        let node = |name: &str, deps: Vec<&str>, sensitive: bool| Node::new(
+            name.to_string(), PathBuf::from(name), vec![PathBuf::from("**")], vec![],
+            deps.into_iter().map(String::from).collect(), Some(serde_json::json!({ "security_sensitive": sensitive })),
+        ).unwrap();
+        let graph = DependencyGraph::new(vec![
+            node("crypto", vec![], true),
+            node("auth", vec!["crypto"], true),
+            node("web", vec!["auth"], false),
+        ], false).unwrap();
+        let filters = FilterArgs::default();
+
+        let sensitive = query_security(&graph, &[PathBuf::from("crypto/src/lib.rs")], &filters);
+        let summary: Vec<_> = sensitive.iter().map(|s| (s.node.as_str(), s.hit, s.via.as_deref())).collect();
+        assert_eq!(summary, vec![("auth", SensitiveHit::Transitive, Some("crypto")), ("crypto", SensitiveHit::Direct, None)]);
+        assert!(query_security(&graph, &[PathBuf::from("web/index.ts")], &filters).is_empty());
+    }
+
+    #[test]
+    fn test_load_incompatible_artifact() {
+        let dir = FixtureDir::new();
+        let load = |name: &str, content: &[u8]| {
+            dir.write(name, content);
+            load_graph(&dir.join(name))
+        };
+        let info = |tool_version: &str, schema_version: u32| serde_json::json!({ "schema_version": schema_version, "tool_version": tool_version });
+
+        // A layout this version can't read: explained when another version wrote it
+        let renamed = serde_json::json!({ "info": info("0.0.1", SCHEMA_VERSION), "nodes": [] }).to_string();
+        let older = load("older.json", renamed.as_bytes());
+        let current = serde_json::json!({ "info": info(env!("CARGO_PKG_VERSION"), SCHEMA_VERSION), "nodes": [] }).to_string();
+        let damaged = load("damaged.json", current.as_bytes());
+        let newer = serde_json::json!({ "info": info("9.0.0", SCHEMA_VERSION + 1), "graph": 1 }).to_string();
+        let newer = load("newer.json", newer.as_bytes());
+        let graph = DependencyGraph::new(vec![], false).unwrap();
+        let json = encode_graph(&graph, ArtifactFormat::Json).unwrap();
+        let truncated = load("truncated.json", &json[..json.len() / 2]);
+        let binary = load("graph.bin", &encode_graph(&graph, ArtifactFormat::Binary).unwrap());
+
+        assert!(matches!(older, Err(LoadError::Incompatible(CompatibilityError::Unreadable { .. }))));
+        assert!(older.unwrap_err().to_string().contains("written by dependency-cascade 0.0.1"));
+        assert!(matches!(damaged, Err(LoadError::Parse(_))));
+        assert!(matches!(newer, Err(LoadError::Incompatible(CompatibilityError::NewerSchema { .. }))));
+        assert!(matches!(truncated, Err(LoadError::Truncated(..))));
+        assert_eq!(binary.unwrap().info().schema_version, SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_trigger_only() {
+        let node = |name: &str, deps: Vec<&str>| Node::new(
+            name.to_string(), PathBuf::from(name), vec![PathBuf::from("**")], vec![],
+            deps.into_iter().map(String::from).collect(), None,
+        ).unwrap();
+        let templates = Node { trigger_only: true, ..node("ci-templates", vec![]) };
+        let graph = DependencyGraph::new(vec![
+            templates,
+            node("lib", vec!["ci-templates"]),
+            node("api", vec!["lib"]),
+        ], false).unwrap();
+        let filters = FilterArgs { only_roots: true, ..FilterArgs::default() };
+
+        // The templates cascade to their dependents without being selected, lib becoming a root
+        let affected: Vec<String> = query(&graph, &[PathBuf::from("ci-templates/build.yml")], &filters).into_iter().map(|n| n.name).collect();
+        assert_eq!(affected, vec!["lib"]);
+        assert!(query(&graph, &[PathBuf::from("ci-templates/build.yml")], &FilterArgs { only_roots: false, ..filters })
+            .iter().all(|node| !node.trigger_only));
+    }
+
+    #[test]
+    fn test_filter_tags() {
+        let node = |name: &str, tags: &[&str]| Node {
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            ..Node::new(name.to_string(), PathBuf::from(name), vec![PathBuf::from("**")], vec![], vec![], None).unwrap()
+        };
+        let graph = DependencyGraph::new(vec![
+            node("api", &["backend", "deployable"]),
+            node("worker", &["backend"]),
+            node("web", &["frontend", "deployable"]),
+            node("docs", &[]),
+        ], false).unwrap();
+        let names: Vec<String> = graph.get_all_nodes().into_iter().map(|node| node.name.clone()).collect();
+        let tags = |tags: &[&str]| tags.iter().map(|tag| tag.to_string()).collect::<Vec<_>>();
+
+        let filters = FilterArgs { tags: tags(&["deployable"]), ..FilterArgs::default() };
+        assert_eq!(filters.apply(&graph, names.clone()), ["api", "web"]);
+        let filters = FilterArgs { tags: tags(&["deployable", "backend"]), ..FilterArgs::default() };
+        assert_eq!(filters.apply(&graph, names.clone()), ["api"]);
+        let filters = FilterArgs { excluded_tags: tags(&["frontend", "backend"]), ..FilterArgs::default() };
+        assert_eq!(filters.apply(&graph, names), ["docs"]);
+    }
+
+    #[test]
+    fn test_list_filters() {
+        let node = |name: &str, path: &str, deps: Vec<&str>| Node::new(
+            name.to_string(), PathBuf::from(path), vec![PathBuf::from("**")], vec![],
+            deps.into_iter().map(String::from).collect(), None,
+        ).unwrap();
+        let graph = DependencyGraph::new(vec![
+            node("lib", "libs/lib", vec![]),
+            node("payments-api", "services/payments-api", vec!["lib"]),
+            node("payments-worker", "services/payments-worker", vec!["lib"]),
+            node("web", "apps/web", vec!["payments-api"]),
+        ], false).unwrap();
+        let list = |list_filters: ListFilters, filters: FilterArgs| -> Vec<String> {
+            list(&graph, &list_filters, &filters).into_iter().map(|node| node.name).collect()
+        };
+
+        let by_name = || ListFilters { name: Some(glob::Pattern::new("payments-*").unwrap()), ..ListFilters::default() };
+        assert_eq!(list(by_name(), FilterArgs::default()), ["payments-api", "payments-worker"]);
+        let by_path = ListFilters { path: Some(PathBuf::from("services")), ..ListFilters::default() };
+        assert_eq!(list(by_path, FilterArgs::default()), ["payments-api", "payments-worker"]);
+        let depended_on = ListFilters { has_dependents: Some(true), ..ListFilters::default() };
+        assert_eq!(list(depended_on, FilterArgs::default()), ["lib", "payments-api"]);
+        let top = ListFilters { has_dependents: Some(false), ..ListFilters::default() };
+        assert_eq!(list(top, FilterArgs::default()), ["payments-worker", "web"]);
+        // Roots are taken among the nodes matching the name
+        assert_eq!(list(by_name(), FilterArgs { only_roots: true, ..FilterArgs::default() }), ["payments-api", "payments-worker"]);
+        assert_eq!(list(ListFilters::default(), FilterArgs { only_roots: true, ..FilterArgs::default() }), ["lib"]);
+    }
+
+    #[test]
+    fn test_query_files() {
+        let node = |name: &str, path: &str| Node::new(
+            name.to_string(), PathBuf::from(path), vec![PathBuf::from("**")], vec![], vec![], None,
+        ).unwrap();
+        let graph = DependencyGraph::new(vec![
+            node("services", "services"),
+            node("api", "services/api"),
+            node("web", "apps/web"),
+        ], false).unwrap();
+        let files = [
+            PathBuf::from("apps/web/index.ts"),
+            PathBuf::from("services/api/main.rs"),
+            PathBuf::from("docs/README.md"),
+        ];
+
+        let attributions = query_files(&graph, &files);
+        let claims: Vec<_> = attributions.iter().map(|a| (a.file.to_str().unwrap(), a.nodes.clone(), a.unowned)).collect();
+        assert_eq!(claims, vec![
+            ("apps/web/index.ts", vec!["web".to_string()], false),
+            // Nested nodes both claim the file, sorted by name
+            ("services/api/main.rs", vec!["api".to_string(), "services".to_string()], false),
+            ("docs/README.md", vec![], true),
+        ]);
+    }
+}
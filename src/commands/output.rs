@@ -0,0 +1,141 @@
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// The artifact path meaning standard input.
+const STDIN: &str = "-";
+
+/// Whether a locked artifact is waited for rather than reported, set by `--wait`.
+static WAIT_FOR_LOCKS: AtomicBool = AtomicBool::new(false);
+
+/// Makes the artifact reads and writes of the process wait for the lock another process holds,
+/// instead of failing.
+pub fn wait_for_locks(wait: bool) {
+    WAIT_FOR_LOCKS.store(wait, Ordering::Relaxed);
+}
+
+/// Writes `contents` to a temporary file next to `path`, then renames it over `path`, so
+/// concurrent readers see either the old file or the complete new one.
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let tmp = sibling(path, |name| format!(".{}.{}.tmp", name, std::process::id()))?;
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, path).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp);
+    })
+}
+
+/// Writes an artifact like `write_atomic`, holding its lock exclusively meanwhile so that
+/// concurrent writers take turns and readers wait for the write in progress.
+pub fn write_artifact(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let _lock = lock(path, true)?;
+    write_atomic(path, contents)
+}
+
+/// Appends `contents` to the file, creating it if needed, e.g. for the `$GITHUB_OUTPUT` file
+/// other steps of a workflow also write to.
+pub fn append(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    OpenOptions::new().create(true).append(true).open(path)?.write_all(contents.as_ref())
+}
+
+/// Whether the artifact path is `-`, for standard input.
+pub fn is_stdin(path: &Path) -> bool {
+    path.as_os_str() == STDIN
+}
+
+/// Reads an artifact, from standard input when `path` is `-`.
+pub fn read_artifact(path: &Path) -> io::Result<String> {
+    String::from_utf8(read_artifact_bytes(path)?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Reads an artifact like `read_artifact`, which may be binary.
+pub fn read_artifact_bytes(path: &Path) -> io::Result<Vec<u8>> {
+    if !is_stdin(path) {
+        let _lock = lock(path, false)?;
+        return fs::read(path);
+    }
+    let mut content = Vec::new();
+    io::stdin().read_to_end(&mut content)?;
+    Ok(content)
+}
+
+/// Takes the advisory lock of an artifact, the file `<name>.lock` next to it: exclusively to
+/// write the artifact, shared to read it. The lock file is left in place, since the artifact
+/// itself is replaced by every write. Readers don't create it, so the artifacts no writer
+/// locked are read as they are.
+///
+/// ### Returns
+/// * `Option<File>` - The lock file, unlocked when dropped, or `None` if there's no lock to take
+fn lock(path: &Path, exclusive: bool) -> io::Result<Option<File>> {
+    let lock_path = sibling(path, |name| format!("{}.lock", name))?;
+    let file = match exclusive {
+        true => OpenOptions::new().create(true).truncate(false).write(true).open(&lock_path)?,
+        false => match File::open(&lock_path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        },
+    };
+
+    if WAIT_FOR_LOCKS.load(Ordering::Relaxed) {
+        match exclusive {
+            true => file.lock()?,
+            false => file.lock_shared()?,
+        }
+        return Ok(Some(file));
+    }
+    match if exclusive { file.try_lock() } else { file.try_lock_shared() } {
+        Ok(()) => Ok(Some(file)),
+        Err(TryLockError::WouldBlock) => Err(io::Error::new(
+            io::ErrorKind::WouldBlock,
+            format!("{} is being written by another process, pass --wait to wait for it", path.display()),
+        )),
+        Err(TryLockError::Error(e)) => Err(e),
+    }
+}
+
+/// The file named after `path`'s file name, in the same directory.
+fn sibling(path: &Path, name: impl FnOnce(&str) -> String) -> io::Result<PathBuf> {
+    let file_name = path.file_name().ok_or_else(|| io::Error::other(format!("{} isn't a file path", path.display())))?;
+    Ok(path.with_file_name(name(&file_name.to_string_lossy())))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testkit::FixtureDir;
+
+    use super::*;
+
+    #[test]
+    fn test_write_atomic() {
+        let dir = FixtureDir::new();
+        let path = dir.join("graph.json");
+        write_atomic(&path, "old").unwrap();
+        write_atomic(&path, "new").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_artifact_lock() {
+        let dir = FixtureDir::new();
+        let path = dir.join("graph.json");
+        write_artifact(&path, "{}").unwrap();
+
+        let writing = lock(&path, true).unwrap();
+        let busy = read_artifact(&path).unwrap_err();
+        let blocked_writer = write_artifact(&path, "[]").unwrap_err();
+        drop(writing);
+        let reading = lock(&path, false).unwrap();
+        let shared = read_artifact(&path).unwrap();
+        drop(reading);
+        let entries = fs::read_dir(dir.path()).unwrap().count();
+
+        assert_eq!(busy.kind(), io::ErrorKind::WouldBlock);
+        assert_eq!(blocked_writer.kind(), io::ErrorKind::WouldBlock);
+        assert_eq!(shared, "{}");
+        // The artifact and its lock file
+        assert_eq!(entries, 2);
+    }
+}
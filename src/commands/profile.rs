@@ -0,0 +1,215 @@
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use walkdir::WalkDir;
+
+use crate::types::{negation, DependencyGraph, DependencyGraphCreationError, Node, NodeCreationError};
+
+use super::{Scan, ScanError};
+
+/// Why `profile` failed.
+#[derive(Debug, thiserror::Error)]
+pub enum ProfileError {
+    #[error(transparent)]
+    Scan(#[from] ScanError),
+    #[error(transparent)]
+    Node(#[from] NodeCreationError),
+    #[error(transparent)]
+    Graph(#[from] DependencyGraphCreationError),
+    #[error("Unable to write or read back the artifact: {0}")]
+    Artifact(#[from] serde_json::Error),
+    #[error(transparent)]
+    Walk(#[from] walkdir::Error),
+}
+
+/// Where the time of preparing and querying the graph of a repository goes.
+#[derive(Debug, Serialize)]
+pub struct Profile {
+    pub manifests: usize,
+    pub nodes: usize,
+    /// The changed files the matching and the traversal were timed with.
+    pub files: usize,
+    /// The phases, in the order they ran.
+    pub phases: Vec<PhaseTime>,
+    /// The manifests that took the longest to parse, slowest first.
+    pub slowest_manifests: Vec<ManifestTime>,
+    /// The patterns that took the longest to match against all the files, slowest first.
+    pub slowest_patterns: Vec<PatternTime>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PhaseTime {
+    pub phase: &'static str,
+    pub duration_ms: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ManifestTime {
+    pub manifest: PathBuf,
+    pub duration_ms: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PatternTime {
+    pub node: String,
+    pub pattern: PathBuf,
+    /// How many of the files it matched.
+    pub matched: usize,
+    pub duration_ms: f64,
+}
+
+/// Prepares the graph and queries it, timing each phase: walking the directories, reading
+/// and parsing the manifests, building the graph, writing and reading the artifact, matching
+/// the files and traversing the graph. Then times every pattern of every node on its own.
+///
+/// ### Arguments
+/// * `scan` - The directories to scan, as with `prepare`
+/// * `files` - The changed files to query, by default every file under the scanned
+///   directories but those in `.git`
+/// * `top` - How many manifests and patterns to report
+///
+/// ### Returns
+/// * `Profile` - The phases and the slowest manifests and patterns
+pub fn profile(scan: &Scan, allow_cyclical: bool, files: Option<&[PathBuf]>, top: usize) -> Result<Profile, ProfileError> {
+    let mut phases = Vec::new();
+    let mut phase = |phase: &'static str, duration: Duration| phases.push(PhaseTime { phase, duration_ms: millis(duration) });
+
+    let start = Instant::now();
+    let found = scan.manifests()?;
+    phase("walk", start.elapsed());
+
+    let start = Instant::now();
+    let mut manifests = Vec::new();
+    for (file, path) in found {
+        manifests.extend(scan.read_manifest(file, path)?);
+    }
+    phase("read", start.elapsed());
+
+    let mut nodes = Vec::new();
+    let mut slowest_manifests = Vec::new();
+    for manifest in &manifests {
+        let start = Instant::now();
+        let mut node = Node::from_toml_str(&manifest.content, manifest.path.clone())?;
+        if let Some(defaults) = &scan.default_metadata {
+            node.merge_default_metadata(defaults)?;
+        }
+        slowest_manifests.push(ManifestTime { manifest: manifest.file.clone(), duration_ms: millis(start.elapsed()) });
+        nodes.push(Node { hide_dotfiles: !scan.dotfiles.globs, ..node });
+    }
+    phase("parse", Duration::from_secs_f64(slowest_manifests.iter().map(|m| m.duration_ms).sum::<f64>() / 1000.0));
+
+    let start = Instant::now();
+    let mut graph = DependencyGraph::new(nodes, allow_cyclical)?;
+    graph.set_generations();
+    phase("graph", start.elapsed());
+
+    let start = Instant::now();
+    let json = serde_json::to_string(&graph)?;
+    phase("serialize", start.elapsed());
+    let start = Instant::now();
+    let graph: DependencyGraph = serde_json::from_str(&json)?;
+    phase("deserialize", start.elapsed());
+
+    let files = match files {
+        Some(files) => files.to_vec(),
+        None => {
+            let start = Instant::now();
+            let files = list_files(scan)?;
+            phase("list_files", start.elapsed());
+            files
+        }
+    };
+
+    let start = Instant::now();
+    for file in &files {
+        graph.nodes_including(file);
+    }
+    let matching = start.elapsed();
+    phase("match", matching);
+    // The query matches the files the same way, the rest of its time is the traversal
+    let start = Instant::now();
+    graph.get_affected_reasons(&files);
+    phase("traverse", start.elapsed().saturating_sub(matching));
+
+    let mut slowest_patterns = Vec::new();
+    for node in graph.get_all_nodes() {
+        for pattern in [&node.included_paths, &node.excluded_paths, &node.cascade_excluded_paths].into_iter().flatten() {
+            let start = Instant::now();
+            let matched = files.iter().filter(|file| node.matches_pattern(negation(pattern).1, file)).count();
+            slowest_patterns.push(PatternTime { node: node.name.clone(), pattern: pattern.clone(), matched, duration_ms: millis(start.elapsed()) });
+        }
+    }
+
+    slowest_manifests.sort_by(|a, b| b.duration_ms.total_cmp(&a.duration_ms));
+    slowest_manifests.truncate(top);
+    slowest_patterns.sort_by(|a, b| b.duration_ms.total_cmp(&a.duration_ms));
+    slowest_patterns.truncate(top);
+    Ok(Profile {
+        manifests: manifests.len(),
+        nodes: graph.get_all_nodes().len(),
+        files: files.len(),
+        phases,
+        slowest_manifests,
+        slowest_patterns,
+    })
+}
+
+/// Lists the files under the scanned directories that `prepare` would walk, relative to the
+/// current directory like node paths.
+fn list_files(scan: &Scan) -> Result<Vec<PathBuf>, walkdir::Error> {
+    let mut files = Vec::new();
+    for dir in &scan.dirs {
+        let walk = WalkDir::new(dir).into_iter().filter_entry(|entry| {
+            let path = super::normalize(entry.path());
+            entry.file_name() != ".git"
+                && (scan.dotfiles.walk || entry.depth() == 0 || !super::scan::hidden(entry.path()))
+                && !scan.skip.iter().any(|pattern| pattern.matches_path(&path))
+        });
+        for entry in walk {
+            let entry = entry?;
+            if entry.file_type().is_file() {
+                files.push(super::normalize(entry.path()));
+            }
+        }
+    }
+    Ok(files)
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::testkit::FixtureDir;
+
+    use super::*;
+
+    #[test]
+    fn test_profile() {
+        let dir = FixtureDir::new();
+        for (node, deps) in [("lib", ""), ("api", "lib = { name = \"lib\" }\n")] {
+            dir.write(Path::new(node).join("src/main.rs"), "");
+            dir.write(
+                Path::new(node).join("dependencies.toml"),
+                format!("[module]\nname = \"{}\"\n\n[file_paths]\ninclude = [\"src/**\"]\nexclude = [\"**/*.md\"]\n\n[dependencies]\n{}", node, deps),
+            );
+        }
+        let scan = Scan::new(dir.path().to_path_buf(), None);
+        let listed = profile(&scan, false, None, 3).unwrap();
+        let given = profile(&scan, false, Some(&[PathBuf::from("elsewhere.rs")]), 10).unwrap();
+
+        let phases: Vec<&str> = listed.phases.iter().map(|phase| phase.phase).collect();
+        assert_eq!(phases, ["walk", "read", "parse", "graph", "serialize", "deserialize", "list_files", "match", "traverse"]);
+        assert_eq!((listed.manifests, listed.nodes, listed.files), (2, 2, 4));
+        assert_eq!(listed.slowest_manifests.len(), 2);
+        assert_eq!(listed.slowest_patterns.len(), 3);
+        assert!(listed.slowest_patterns.iter().all(|pattern| pattern.matched == usize::from(pattern.pattern == Path::new("src/**"))));
+        assert_eq!(given.files, 1);
+        assert!(!given.phases.iter().any(|phase| phase.phase == "list_files"));
+        assert_eq!(given.slowest_patterns.len(), 4);
+    }
+}
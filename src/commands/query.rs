@@ -0,0 +1,252 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::types::{AffectedReason, DependencyGraph, Node, NodeCreationError};
+
+use super::{FilterArgs, QueryEmit};
+
+/// How `query` prints what it emits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum QueryFormat {
+    /// A single JSON document.
+    #[default]
+    Json,
+    /// One node name per line, e.g. for `xargs`. With `--emit tests`, one test command per line.
+    Names,
+    /// One JSON document per line for each node, test, file, job or rollout group.
+    Ndjson,
+}
+
+/// Which way `query` walks the graph from the changed nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum QueryDirection {
+    /// To the nodes depending on them, which the change affects.
+    #[default]
+    Down,
+    /// To the nodes they depend on, which must be built before them.
+    Up,
+    /// Both ways.
+    Both,
+}
+
+impl QueryDirection {
+    /// The changed nodes with the nodes reached from them in this direction, at most
+    /// `max_depth` edges away, and why. Going both ways, a node both depending on a changed
+    /// node and depended on by another is reported as affected.
+    pub fn reasons(self, graph: &DependencyGraph, changed_files: &[PathBuf], max_depth: Option<usize>) -> BTreeMap<String, AffectedReason> {
+        let affected = || match max_depth {
+            Some(_) => graph.get_affected_reasons_within(changed_files, max_depth),
+            // Possibly remembered from a query cache
+            None => graph.get_affected_reasons(changed_files),
+        };
+        match self {
+            QueryDirection::Down => affected(),
+            QueryDirection::Up => graph.get_upstream_reasons(changed_files, max_depth),
+            QueryDirection::Both => {
+                let mut reasons = affected();
+                for (name, reason) in graph.get_upstream_reasons(changed_files, max_depth) {
+                    reasons.entry(name).or_insert(reason);
+                }
+                reasons
+            }
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum QueryOutputError {
+    #[error("--format names doesn't apply to --emit {0}, which emits no nodes")]
+    NoNames(&'static str),
+    #[error("Error serializing: {0}")]
+    Serialize(#[from] serde_json::Error),
+    #[error(transparent)]
+    Node(#[from] NodeCreationError),
+}
+
+/// Queries the graph for the given files and prints what `emit` asks for in `format`.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+/// * `changed_files` - The list of files that have changed
+/// * `emit` - What to print about the affected nodes
+/// * `filters` - Restrictions on the affected nodes
+/// * `format` - How to print it
+/// * `toposort` - Whether to print the nodes in dependency order rather than by name
+///
+/// ### Returns
+/// * `String` - The output, ending with a newline unless empty
+pub fn render_query(
+    graph: &DependencyGraph,
+    changed_files: &[PathBuf],
+    emit: QueryEmit,
+    filters: &FilterArgs,
+    format: QueryFormat,
+    toposort: bool,
+) -> Result<String, QueryOutputError> {
+    match emit {
+        QueryEmit::Nodes => {
+            let mut nodes = super::query(graph, changed_files, filters);
+            if toposort {
+                let names: Vec<String> = nodes.iter().map(|node| node.name.clone()).collect();
+                nodes = graph.toposorted_subset(&names).into_iter().cloned().collect();
+            }
+            render(format, &nodes, || nodes.iter().map(|node| node.name.clone()).collect())
+        }
+        QueryEmit::Tests => {
+            let tests = super::query_tests(graph, changed_files, filters);
+            render(format, &tests, || tests.clone())
+        }
+        QueryEmit::Files => {
+            // The nodes claiming any of the files, once each
+            let files = super::query_files(graph, changed_files);
+            render(format, &files, || files.iter().flat_map(|file| file.nodes.clone()).collect::<BTreeSet<_>>().into_iter().collect())
+        }
+        QueryEmit::Ci => {
+            let jobs = super::query_ci(graph, changed_files, filters)?;
+            render(format, &jobs, || jobs.iter().map(|job| job.node.clone()).collect())
+        }
+        QueryEmit::Security => {
+            let nodes = super::query_security(graph, changed_files, filters);
+            render(format, &nodes, || nodes.iter().map(|node| node.node.clone()).collect())
+        }
+        QueryEmit::Rollout => {
+            let groups = super::rollout_groups(super::deploy_order(graph, changed_files, "deployable", false));
+            render(format, &groups, || groups.iter()
+                .flat_map(|group| &group.waves)
+                .flat_map(|wave| &wave.nodes)
+                .map(|deployment| deployment.node.clone())
+                .collect())
+        }
+        QueryEmit::Width => match format {
+            QueryFormat::Names => Err(QueryOutputError::NoNames("width")),
+            QueryFormat::Json | QueryFormat::Ndjson => Ok(serde_json::to_string(&super::query_width(graph, changed_files, filters))? + "\n"),
+        },
+    }
+}
+
+/// Describes the affected nodes as GitHub Actions step outputs, one `name=value` line each:
+/// `affected`, the JSON list of their names, `matrix`, a matrix with an `include` entry per
+/// node for `strategy.matrix: ${{ fromJSON(...) }}`, and `count`.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+/// * `changed_files` - The list of files that have changed
+/// * `filters` - Restrictions on the affected nodes
+///
+/// ### Returns
+/// * `String` - The lines to append to `$GITHUB_OUTPUT`
+pub fn github_output(graph: &DependencyGraph, changed_files: &[PathBuf], filters: &FilterArgs) -> Result<String, QueryOutputError> {
+    let nodes = super::query(graph, changed_files, filters);
+    let names: Vec<&str> = nodes.iter().map(|node| node.name.as_str()).collect();
+    let include: Vec<serde_json::Value> = nodes.iter()
+        .map(|node| serde_json::json!({ "node": node.name, "path": node.path, "metadata": node.metadata }))
+        .collect();
+    Ok(format!(
+        "affected={}\nmatrix={}\ncount={}\n",
+        serde_json::to_string(&names)?,
+        serde_json::to_string(&serde_json::json!({ "include": include }))?,
+        nodes.len(),
+    ))
+}
+
+/// Prints the nodes in `format`, for `list`.
+pub fn render_nodes(nodes: &[Node], format: QueryFormat) -> Result<String, QueryOutputError> {
+    render(format, nodes, || nodes.iter().map(|node| node.name.clone()).collect())
+}
+
+/// Prints the items as a JSON array, by name, or each as a JSON document.
+fn render<T: Serialize>(format: QueryFormat, items: &[T], names: impl FnOnce() -> Vec<String>) -> Result<String, QueryOutputError> {
+    let lines = match format {
+        QueryFormat::Json => vec![serde_json::to_string(items)?],
+        QueryFormat::Names => names(),
+        QueryFormat::Ndjson => items.iter().map(serde_json::to_string).collect::<Result<_, _>>()?,
+    };
+    Ok(lines.into_iter().map(|line| line + "\n").collect())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_render_query() {
+        let node = |name: &str, deps: Vec<&str>| Node::new(
+            name.to_string(), PathBuf::from(name), vec![PathBuf::from("**")], vec![],
+            deps.into_iter().map(String::from).collect(), None,
+        ).unwrap();
+        let graph = DependencyGraph::new(vec![node("lib", vec![]), node("api", vec!["lib"])], false).unwrap();
+        let files = [PathBuf::from("lib/src/a.rs")];
+        let filters = FilterArgs::default();
+        let render = |emit, format| render_query(&graph, &files, emit, &filters, format, false);
+
+        assert_eq!(render(QueryEmit::Nodes, QueryFormat::Names).unwrap(), "api\nlib\n");
+        let ndjson = render(QueryEmit::Nodes, QueryFormat::Ndjson).unwrap();
+        let documents: Vec<serde_json::Value> = ndjson.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(documents.iter().map(|node| node["name"].as_str().unwrap()).collect::<Vec<_>>(), ["api", "lib"]);
+        let json: serde_json::Value = serde_json::from_str(&render(QueryEmit::Nodes, QueryFormat::Json).unwrap()).unwrap();
+        assert_eq!(json.as_array().map(Vec::len), Some(2));
+        assert_eq!(render(QueryEmit::Files, QueryFormat::Names).unwrap(), "lib\n");
+        assert_eq!(render(QueryEmit::Tests, QueryFormat::Names).unwrap(), "");
+        assert!(matches!(render(QueryEmit::Width, QueryFormat::Names), Err(QueryOutputError::NoNames("width"))));
+    }
+
+    #[test]
+    fn test_github_output() {
+        let node = |name: &str, deps: Vec<&str>| Node::new(
+            name.to_string(), PathBuf::from(format!("libs/{}", name)), vec![PathBuf::from("**")], vec![],
+            deps.into_iter().map(String::from).collect(), Some(serde_json::json!({ "owner": name })),
+        ).unwrap();
+        let graph = DependencyGraph::new(vec![node("lib", vec![]), node("api", vec!["lib"]), node("docs", vec![])], false).unwrap();
+
+        let output = github_output(&graph, &[PathBuf::from("libs/lib/a.rs")], &FilterArgs::default()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], r#"affected=["api","lib"]"#);
+        let matrix: serde_json::Value = serde_json::from_str(lines[1].strip_prefix("matrix=").unwrap()).unwrap();
+        assert_eq!(matrix["include"][1], serde_json::json!({ "node": "lib", "path": "libs/lib", "metadata": { "owner": "lib" } }));
+        assert_eq!(lines[2], "count=2");
+
+        let output = github_output(&graph, &[], &FilterArgs::default()).unwrap();
+        assert_eq!(output, "affected=[]\nmatrix={\"include\":[]}\ncount=0\n");
+    }
+
+    #[test]
+    fn test_query_direction() {
+        let node = |name: &str, deps: Vec<&str>| Node::new(
+            name.to_string(), PathBuf::from(name), vec![PathBuf::from("**")], vec![],
+            deps.into_iter().map(String::from).collect(), None,
+        ).unwrap();
+        let mut graph = DependencyGraph::new(vec![node("base", vec![]), node("lib", vec!["base"]), node("api", vec!["lib"])], false).unwrap();
+        let files = [PathBuf::from("lib/src/a.rs")];
+        let names = |direction: QueryDirection, graph: &DependencyGraph| direction.reasons(graph, &files, None).into_keys().collect::<Vec<_>>();
+
+        assert_eq!(names(QueryDirection::Down, &graph), ["api", "lib"]);
+        assert_eq!(names(QueryDirection::Up, &graph), ["base", "lib"]);
+        assert_eq!(names(QueryDirection::Both, &graph), ["api", "base", "lib"]);
+        let direct = QueryDirection::Down.reasons(&graph, &[PathBuf::from("base/a.rs")], Some(1));
+        assert_eq!(direct.into_keys().collect::<Vec<_>>(), ["base", "lib"]);
+
+        // What main does, so that every emit sees the same nodes
+        let reasons = QueryDirection::Up.reasons(&graph, &files, None);
+        graph.remember_affected(files.to_vec(), reasons);
+        let filters = FilterArgs::default();
+        assert_eq!(render_query(&graph, &files, QueryEmit::Nodes, &filters, QueryFormat::Names, false).unwrap(), "base\nlib\n");
+    }
+
+    #[test]
+    fn test_toposort() {
+        let node = |name: &str, deps: Vec<&str>| Node::new(
+            name.to_string(), PathBuf::from(name), vec![PathBuf::from("**")], vec![],
+            deps.into_iter().map(String::from).collect(), None,
+        ).unwrap();
+        let graph = DependencyGraph::new(vec![node("zlib", vec![]), node("api", vec!["zlib"]), node("app", vec!["api"])], false).unwrap();
+        let files = [PathBuf::from("zlib/src/a.rs")];
+        let render = |toposort| render_query(&graph, &files, QueryEmit::Nodes, &FilterArgs::default(), QueryFormat::Names, toposort).unwrap();
+
+        assert_eq!(render(false), "api\napp\nzlib\n");
+        assert_eq!(render(true), "zlib\napi\napp\n");
+    }
+}
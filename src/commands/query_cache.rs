@@ -0,0 +1,99 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use super::QueryDirection;
+use crate::types::{AffectedReason, DependencyGraph};
+
+/// Finds the nodes affected by a change in a cache directory shared by the stages of a
+/// pipeline, matching the change and storing the result on a miss. The result is keyed by the
+/// content of the artifact, the changed files, in any order, and the direction and depth of
+/// the query, so the first stage to query a change does the matching and the later ones only
+/// read it back.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact, as loaded
+/// * `changed_files` - The list of files that have changed
+/// * `direction` - Which way the query walks the graph from the changed nodes
+/// * `max_depth` - How many edges away from the changed nodes the query goes, if limited
+/// * `cache_dir` - Where the results are kept, created if needed
+///
+/// ### Returns
+/// * `BTreeMap<String, AffectedReason>` - The affected nodes, as `QueryDirection::reasons`
+pub fn cached_affected_reasons(
+    graph: &DependencyGraph,
+    changed_files: &[PathBuf],
+    direction: QueryDirection,
+    max_depth: Option<usize>,
+    cache_dir: &Path,
+) -> Result<BTreeMap<String, AffectedReason>, Box<dyn std::error::Error>> {
+    let path = cache_dir.join(format!("{}.json", cache_key(graph, changed_files, direction, max_depth)?));
+    // An unreadable entry, e.g. written by another version, is computed again
+    if let Ok(reasons) = fs::read_to_string(&path).map(|content| serde_json::from_str(&content)) {
+        match reasons {
+            Ok(reasons) => {
+                log::info!("Read the affected nodes from {}", path.display());
+                return Ok(reasons);
+            }
+            Err(e) => log::warn!("Ignoring the cached result {}: {}", path.display(), e),
+        }
+    }
+
+    let reasons = direction.reasons(graph, changed_files, max_depth);
+    fs::create_dir_all(cache_dir)?;
+    super::write_atomic(&path, serde_json::to_string(&reasons)?)?;
+    Ok(reasons)
+}
+
+/// The SHA-256 of the artifact, the direction and depth of the query, then the sorted and
+/// deduplicated changed files.
+fn cache_key(graph: &DependencyGraph, changed_files: &[PathBuf], direction: QueryDirection, max_depth: Option<usize>) -> serde_json::Result<String> {
+    let mut files: Vec<&PathBuf> = changed_files.iter().collect();
+    files.sort();
+    files.dedup();
+
+    let mut hasher = Sha256::new();
+    hasher.update(serde_json::to_vec(graph)?);
+    hasher.update(format!("{:?}:{:?}", direction, max_depth).as_bytes());
+    for file in files {
+        hasher.update([0]);
+        hasher.update(file.to_string_lossy().as_bytes());
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testkit::FixtureDir;
+    use crate::types::Node;
+
+    use super::*;
+
+    #[test]
+    fn test_cached_affected_reasons() {
+        let fixture = FixtureDir::new();
+        let dir = fixture.join("cache");
+        let node = |name: &str, deps: Vec<&str>| Node::new(
+            name.to_string(), PathBuf::from(name), vec![PathBuf::from("**")], vec![],
+            deps.into_iter().map(String::from).collect(), None,
+        ).unwrap();
+        let graph = DependencyGraph::new(vec![node("lib", vec![]), node("api", vec!["lib"])], false).unwrap();
+        let files = [PathBuf::from("lib/src/a.rs"), PathBuf::from("lib/src/b.rs")];
+
+        let computed = cached_affected_reasons(&graph, &files, QueryDirection::Down, None, &dir).unwrap();
+        // Another order of the same files hits the entry, whatever it holds
+        let key = cache_key(&graph, &[files[1].clone(), files[0].clone()], QueryDirection::Down, None).unwrap();
+        fs::write(dir.join(format!("{}.json", key)), r#"{"api": {"kind": "dependency", "via": "elsewhere"}}"#).unwrap();
+        let cached = cached_affected_reasons(&graph, &[files[1].clone(), files[0].clone()], QueryDirection::Down, None, &dir).unwrap();
+        // Another depth is another entry
+        let limited = cached_affected_reasons(&graph, &files, QueryDirection::Down, Some(0), &dir).unwrap();
+        let entries = fs::read_dir(&dir).unwrap().count();
+
+        assert_eq!(computed, graph.get_affected_reasons(&files));
+        assert_eq!(cached, BTreeMap::from([("api".to_string(), AffectedReason::Dependency { via: "elsewhere".to_string() })]));
+        assert_eq!(limited.keys().collect::<Vec<_>>(), ["lib"]);
+        assert_eq!(entries, 2);
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use clap::Subcommand;
+use serde::Serialize;
+
+use crate::types::{AffectedReason, DependencyGraph};
+
+use super::git::{self, Commit, GitError};
+
+/// The `release` subcommands.
+#[derive(Subcommand)]
+pub enum ReleaseCommands {
+    /// Prints, as JSON and in publish order, the nodes to release for the commits of a range
+    /// and their version bumps. The conventional commit type of the commits changing a node
+    /// decides its bump, its dependents get a patch. Versions are read from
+    /// `metadata.version`. Must run from the directory `prepare` ran in.
+    Plan {
+        /// The JSON artifact file path containing the previously prepared dependency graph
+        /// from the `prepare` command
+        #[arg(short, long, value_name = "FILE")]
+        graph_artifact_path: PathBuf,
+        /// The last release, only commits after it are planned.
+        #[arg(long, value_name = "REF")]
+        since: String,
+        /// The last commit to release.
+        #[arg(long, value_name = "REF", default_value = "HEAD")]
+        until: String,
+    },
+    /// Prints a markdown changelog fragment per node changed by the commits of a range, its
+    /// features, fixes and other changes from the conventional commit subjects. Must run
+    /// from the directory `prepare` ran in.
+    Changelog {
+        /// The JSON artifact file path containing the previously prepared dependency graph
+        /// from the `prepare` command
+        #[arg(short, long, value_name = "FILE")]
+        graph_artifact_path: PathBuf,
+        /// The last release, only commits after it are listed.
+        #[arg(long, value_name = "REF")]
+        since: String,
+        /// The last commit to list.
+        #[arg(long, value_name = "REF", default_value = "HEAD")]
+        until: String,
+        /// Write each fragment to `<DIR>/<node>.md` instead of printing them.
+        #[arg(long, value_name = "DIR")]
+        out_dir: Option<PathBuf>,
+    },
+}
+
+/// A semver bump, from the smallest to the largest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Bump {
+    Patch,
+    Minor,
+    Major,
+}
+
+/// A node to release.
+#[derive(Debug, Clone, Serialize)]
+pub struct Release {
+    pub node: String,
+    pub bump: Bump,
+    /// The node's `metadata.version`.
+    pub current_version: Option<String>,
+    /// The bumped version, when the current one is `MAJOR.MINOR.PATCH`.
+    pub next_version: Option<String>,
+    /// Whether only dependencies of the node changed.
+    pub dependency_only: bool,
+    /// The commits changing the node's own files with a releasable type, oldest first.
+    pub commits: Vec<String>,
+}
+
+/// What `release plan` reports.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReleasePlan {
+    pub since: String,
+    pub until: String,
+    /// Dependencies before their dependents.
+    pub releases: Vec<Release>,
+}
+
+/// Plans the releases of the commits in `since..until`.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+/// * `since` - The commit to start after, usually the last release tag
+/// * `until` - The last commit of the range
+///
+/// ### Returns
+/// * `ReleasePlan` - The nodes to release in publish order
+pub fn plan(graph: &DependencyGraph, since: &str, until: &str) -> Result<ReleasePlan, GitError> {
+    Ok(ReleasePlan {
+        since: since.to_string(),
+        until: until.to_string(),
+        releases: releases(graph, &git::commits(since, until)?),
+    })
+}
+
+fn releases(graph: &DependencyGraph, commits: &[Commit]) -> Vec<Release> {
+    // The bump of each node, whether it was changed itself, and the commits changing it
+    let mut bumps: BTreeMap<String, (Bump, bool, Vec<String>)> = BTreeMap::new();
+    for commit in commits {
+        let Some(bump) = commit_bump(&commit.message) else { continue };
+        for (node, reason) in graph.get_affected_reasons(&commit.files) {
+            let (node_bump, changed, hashes) = bumps.entry(node).or_insert((Bump::Patch, false, Vec::new()));
+            if matches!(reason, AffectedReason::Changed { .. }) {
+                *node_bump = (*node_bump).max(bump);
+                *changed = true;
+                hashes.push(commit.hash.clone());
+            }
+        }
+    }
+
+    let names: Vec<String> = bumps.keys().cloned().collect();
+    graph.toposorted_subset(&names).into_iter()
+        .map(|node| {
+            let (bump, changed, commits) = bumps.remove(&node.name).expect("names come from the bumps");
+            let current_version = node.metadata_value("version").and_then(|v| v.as_str()).map(String::from);
+            Release {
+                node: node.name.clone(),
+                bump,
+                next_version: current_version.as_deref().and_then(|version| bump_version(version, bump)),
+                current_version,
+                dependency_only: !changed,
+                commits,
+            }
+        })
+        .collect()
+}
+
+/// The bump a conventional commit message asks for: major for breaking changes (`feat!:` or a
+/// `BREAKING CHANGE:` footer), minor for `feat`, patch for `fix`, `perf` and `revert`. Other
+/// types, and messages that aren't conventional commits, don't release anything.
+pub fn commit_bump(message: &str) -> Option<Bump> {
+    let (kind, breaking) = conventional_type(message)?;
+    if breaking || message.lines().any(|line| line.starts_with("BREAKING CHANGE:") || line.starts_with("BREAKING-CHANGE:")) {
+        return Some(Bump::Major);
+    }
+    match kind.to_lowercase().as_str() {
+        "feat" => Some(Bump::Minor),
+        "fix" | "perf" | "revert" => Some(Bump::Patch),
+        _ => None,
+    }
+}
+
+/// Splits the subject `type(scope)!: description` into its type and whether it has a `!`.
+pub fn conventional_type(message: &str) -> Option<(&str, bool)> {
+    let (prefix, _) = message.lines().next()?.split_once(": ")?;
+    let (prefix, breaking) = match prefix.strip_suffix('!') {
+        Some(prefix) => (prefix, true),
+        None => (prefix, false),
+    };
+    let kind = match prefix.split_once('(') {
+        Some((kind, scope)) if scope.ends_with(')') => kind,
+        Some(_) => return None,
+        None => prefix,
+    };
+    (!kind.is_empty() && kind.chars().all(|c| c.is_ascii_alphanumeric())).then_some((kind, breaking))
+}
+
+/// Bumps `MAJOR.MINOR.PATCH`, dropping any pre-release or build suffix.
+fn bump_version(version: &str, bump: Bump) -> Option<String> {
+    let core = version.split(['-', '+']).next()?;
+    let parts: Vec<u64> = core.split('.').map(|part| part.parse().ok()).collect::<Option<_>>()?;
+    let [major, minor, patch] = parts[..] else { return None };
+    Some(match bump {
+        Bump::Major => format!("{}.0.0", major + 1),
+        Bump::Minor => format!("{}.{}.0", major, minor + 1),
+        Bump::Patch => format!("{}.{}.{}", major, minor, patch + 1),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::Node;
+
+    use super::*;
+
+    fn node(name: &str, deps: Vec<&str>, version: &str) -> Node {
+        Node::new(
+            name.to_string(),
+            PathBuf::from(name),
+            vec![PathBuf::from("**")],
+            vec![],
+            deps.into_iter().map(String::from).collect(),
+            Some(serde_json::json!({ "version": version })),
+        ).unwrap()
+    }
+
+    #[test]
+    fn test_commit_bump() {
+        assert_eq!(commit_bump("feat(api): add search"), Some(Bump::Minor));
+        assert_eq!(commit_bump("fix: off by one"), Some(Bump::Patch));
+        assert_eq!(commit_bump("refactor!: drop v1 routes"), Some(Bump::Major));
+        assert_eq!(commit_bump("feat: new config\n\nBREAKING CHANGE: renamed keys"), Some(Bump::Major));
+        assert_eq!(commit_bump("docs: typo"), None);
+        assert_eq!(commit_bump("Merge the thing: now"), None);
+        assert_eq!(bump_version("1.4.2-beta.1", Bump::Minor), Some("1.5.0".to_string()));
+        assert_eq!(bump_version("latest", Bump::Patch), None);
+    }
+
+    #[test]
+    fn test_releases() {
+        let graph = DependencyGraph::new(vec![
+            node("web", vec!["api"], "2.0.0"),
+            node("api", vec!["core"], "1.3.0"),
+            node("core", vec![], "0.9.1"),
+            node("cli", vec!["core"], "1.0.0"),
+        ], false).unwrap();
+        let commit = |hash: &str, message: &str, file: &str| Commit {
+            hash: hash.to_string(),
+            timestamp: 0,
+            message: message.to_string(),
+            files: vec![PathBuf::from(file)],
+        };
+        let commits = [
+            commit("a", "fix(core): null check", "core/lib.rs"),
+            commit("b", "feat(api): pagination", "api/main.rs"),
+            commit("c", "docs: cli usage", "cli/README.md"),
+        ];
+
+        let releases = releases(&graph, &commits);
+        let summary: Vec<_> = releases.iter()
+            .map(|r| (r.node.as_str(), r.bump, r.next_version.as_deref().unwrap(), r.dependency_only))
+            .collect();
+        let position = |node: &str| summary.iter().position(|r| r.0 == node).unwrap();
+        assert_eq!(summary[position("core")], ("core", Bump::Patch, "0.9.2", false));
+        assert_eq!(summary[position("api")], ("api", Bump::Minor, "1.4.0", false));
+        assert_eq!(summary[position("web")], ("web", Bump::Patch, "2.0.1", true));
+        assert_eq!(summary[position("cli")], ("cli", Bump::Patch, "1.0.1", true));
+        assert!(position("core") < position("api") && position("api") < position("web"));
+        assert_eq!(releases.iter().find(|r| r.node == "api").unwrap().commits, vec!["b"]);
+    }
+}
@@ -0,0 +1,73 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::types::{AffectedReason, DependencyGraph};
+
+/// A reviewer required by a change, with the nodes requiring them.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Reviewer {
+    pub reviewer: String,
+    /// The nodes listing the reviewer in `metadata.required_reviewers`, sorted.
+    pub nodes: Vec<String>,
+}
+
+/// Collects the `metadata.required_reviewers` of the nodes affected by the changed files.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+/// * `changed_files` - The list of files that have changed
+/// * `direct` - Only consider the nodes whose own files changed, not their dependents
+///
+/// ### Returns
+/// * `Vec<Reviewer>` - Every required reviewer once, sorted
+pub fn reviewers(graph: &DependencyGraph, changed_files: &[PathBuf], direct: bool) -> Vec<Reviewer> {
+    let mut reviewers: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (name, reason) in graph.get_affected_reasons(changed_files) {
+        if direct && !matches!(reason, AffectedReason::Changed { .. }) {
+            continue;
+        }
+        let Some(node) = graph.get_node(&name) else { continue };
+        for reviewer in node.required_reviewers() {
+            reviewers.entry(reviewer).or_default().push(name.clone());
+        }
+    }
+    reviewers.into_iter().map(|(reviewer, nodes)| Reviewer { reviewer, nodes }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::Node;
+
+    use super::*;
+
+    fn node(name: &str, deps: Vec<&str>, reviewers: serde_json::Value) -> Node {
+        Node::new(
+            name.to_string(),
+            PathBuf::from(name),
+            vec![PathBuf::from("**")],
+            vec![],
+            deps.into_iter().map(String::from).collect(),
+            Some(serde_json::json!({ "required_reviewers": reviewers })),
+        ).unwrap()
+    }
+
+    #[test]
+    fn test_reviewers() {
+        let graph = DependencyGraph::new(vec![
+            node("core", vec![], serde_json::json!("@org/platform")),
+            node("billing", vec!["core"], serde_json::json!(["@org/payments", "@org/platform"])),
+            node("docs", vec![], serde_json::json!([])),
+        ], false).unwrap();
+        let files = [PathBuf::from("core/lib.rs"), PathBuf::from("docs/a.md")];
+
+        assert_eq!(reviewers(&graph, &files, false), vec![
+            Reviewer { reviewer: "@org/payments".to_string(), nodes: vec!["billing".to_string()] },
+            Reviewer { reviewer: "@org/platform".to_string(), nodes: vec!["billing".to_string(), "core".to_string()] },
+        ]);
+        assert_eq!(reviewers(&graph, &files, true), vec![
+            Reviewer { reviewer: "@org/platform".to_string(), nodes: vec!["core".to_string()] },
+        ]);
+    }
+}
@@ -0,0 +1,329 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use walkdir::WalkDir;
+
+use crate::types::DotfilesConfig;
+
+/// Where `prepare` looks for manifests, what it leaves out, and how it reads them.
+#[derive(Debug, Clone, Default)]
+pub struct Scan {
+    /// The directories to start the recursive scans from.
+    pub dirs: Vec<PathBuf>,
+    /// The directory node paths are made relative to, containing all of `dirs`. Defaults to
+    /// the current directory.
+    pub root: Option<PathBuf>,
+    /// The name of the dependency toml files. Defaults to `dependencies.toml`.
+    pub dependency_toml_name: Option<String>,
+    /// Patterns of the directories and files not to walk, matched against their paths
+    /// relative to the root like node paths.
+    pub skip: Vec<glob::Pattern>,
+    /// Fail when the walk goes more than this many directories below a scanned directory.
+    pub max_depth: Option<usize>,
+    /// Fail when the walk visits more than this many files and directories in total.
+    pub max_files: Option<usize>,
+    /// A file listing the manifests, one path per line, read instead of walking `dirs`. `-`
+    /// for standard input.
+    pub manifest_list: Option<PathBuf>,
+    /// A tarball or zip file to read the manifests from instead of walking `dirs`, the root
+    /// then being a directory inside it.
+    pub archive: Option<PathBuf>,
+    /// Whether to walk dot-directories, and whether the nodes' globs match dotfiles.
+    pub dotfiles: DotfilesConfig,
+    /// Metadata merged under the metadata of every node.
+    pub default_metadata: Option<serde_json::Value>,
+}
+
+/// The manifests a scan found.
+#[derive(Debug, Clone)]
+pub struct Scanned {
+    pub manifests: Vec<ScannedManifest>,
+    /// The commit the manifests were read at, when known.
+    pub source_commit: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScannedManifest {
+    /// The manifest, as walked, listed or archived.
+    pub file: PathBuf,
+    /// The path of its node.
+    pub path: PathBuf,
+    pub content: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ScanError {
+    #[error("{0} is not inside the root {1}")]
+    OutsideRoot(PathBuf, PathBuf),
+    #[error("The walk reached {0}, deeper than --max-depth {1}: is the directory right, or should --skip leave it out?")]
+    TooDeep(PathBuf, usize),
+    #[error("The walk visited more than --max-files {0} files and directories, stopping at {1}: is the directory right, or should --skip leave some out?")]
+    TooManyFiles(usize, PathBuf),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Walk(#[from] walkdir::Error),
+    #[error(transparent)]
+    Archive(#[from] super::archive::ArchiveError),
+    #[error(transparent)]
+    Plugin(#[from] crate::plugin::PluginError),
+}
+
+impl Scan {
+    /// Scans a single directory, leaving nothing out.
+    pub fn new(dir: PathBuf, dependency_toml_name: Option<String>) -> Self {
+        Scan { dirs: vec![dir], dependency_toml_name, ..Scan::default() }
+    }
+
+    /// The directory the scan is relative to, where the root config and the git repository
+    /// are looked up: the root, or the first directory. The current directory for archives.
+    pub fn base(&self) -> &Path {
+        if self.archive.is_some() {
+            return Path::new(".");
+        }
+        self.root.as_deref().or(self.dirs.first().map(PathBuf::as_path)).unwrap_or(Path::new("."))
+    }
+
+    /// Finds the dependency toml files and reads them, with the paths of their nodes: relative
+    /// to the root when given, otherwise to the current directory as in
+    /// [`node_path`](super::node_path). A manifest found twice is only returned once. The files
+    /// plugins discover nodes in are returned as the manifests the plugins made of them.
+    ///
+    /// ### Returns
+    /// * `Scanned` - The manifests and the commit they were read at
+    pub fn read(&self) -> Result<Scanned, ScanError> {
+        if let Some(archive) = &self.archive {
+            return self.read_archive(archive);
+        }
+        let found = self.manifests()?;
+        let mut manifests = Vec::new();
+        for manifest in crate::parallel::map(&found, |(file, path)| self.read_manifest(file.clone(), path.clone())) {
+            manifests.extend(manifest?);
+        }
+        Ok(Scanned { manifests, source_commit: super::git::head_commit(self.base()) })
+    }
+
+    /// Reads a manifest found by `manifests`.
+    ///
+    /// ### Returns
+    /// * `Option<ScannedManifest>` - The manifest, or `None` if a plugin found no node in the file
+    pub(super) fn read_manifest(&self, file: PathBuf, path: PathBuf) -> Result<Option<ScannedManifest>, ScanError> {
+        let content = fs::read_to_string(&file)?;
+        Ok(self.discover(&file, content)?.map(|content| ScannedManifest { content, file, path }))
+    }
+
+    /// Reads the manifests under the root of the archive, and the commit `git archive`
+    /// recorded in it.
+    fn read_archive(&self, archive: &Path) -> Result<Scanned, ScanError> {
+        let name = self.dependency_toml_name.as_deref().unwrap_or("dependencies.toml");
+        let root = self.root.clone().unwrap_or_default();
+        let relative = |file: &Path| file.strip_prefix(&root).ok().map(super::normalize);
+        let skipped = |path: &Path| path.ancestors()
+            .filter(|ancestor| !ancestor.as_os_str().is_empty())
+            .any(|ancestor| self.skip.iter().any(|pattern| pattern.matches_path(ancestor)) || (!self.dotfiles.walk && hidden(ancestor)));
+
+        let archived = super::archive::read_archive(archive, |file| {
+            file.file_name().is_some_and(|file_name| file_name.to_string_lossy() == name || crate::plugin::discovers(&file_name.to_string_lossy()))
+                && relative(file).is_some_and(|relative| !skipped(&relative))
+        })?;
+        let mut seen = HashSet::new();
+        let mut manifests = Vec::new();
+        for (file, content) in archived.files {
+            let path = relative(&file).and_then(|relative| relative.parent().map(Path::to_path_buf)).unwrap_or_default();
+            let Some(content) = self.discover(&file, content)? else { continue };
+            if seen.insert(path.clone()) {
+                manifests.push(ScannedManifest { file, path, content });
+            }
+        }
+        Ok(Scanned { manifests, source_commit: archived.commit })
+    }
+
+    /// Passes the dependency tomls through, and hands the other files to the plugin discovering
+    /// nodes in them.
+    ///
+    /// ### Returns
+    /// * `Option<String>` - The manifest, or `None` if the file declares no node
+    fn discover(&self, file: &Path, content: String) -> Result<Option<String>, crate::plugin::PluginError> {
+        let name = self.dependency_toml_name.as_deref().unwrap_or("dependencies.toml");
+        if file.file_name().is_some_and(|file_name| file_name.to_string_lossy() == name) {
+            return Ok(Some(content));
+        }
+        crate::plugin::discover(file, &content)
+    }
+
+    /// Finds the dependency toml files under every directory, or reads their paths from the
+    /// manifest list, each with the path of its node.
+    pub(super) fn manifests(&self) -> Result<Vec<(PathBuf, PathBuf)>, ScanError> {
+        let name = self.dependency_toml_name.as_deref().unwrap_or("dependencies.toml");
+        // Both sides are canonicalized so that a relative directory and an absolute root compare
+        let root = self.root.as_deref().map(fs::canonicalize).transpose()?;
+        let relative = |path: &Path| match &root {
+            Some(root) => path.strip_prefix(root).map(Path::to_path_buf).unwrap_or_default(),
+            None => super::normalize(path),
+        };
+
+        let mut seen = HashSet::new();
+        let mut visited = 0;
+        let mut manifests = Vec::new();
+        if let Some(list) = &self.manifest_list {
+            for line in super::output::read_artifact(list)?.lines().map(str::trim).filter(|line| !line.is_empty()) {
+                let manifest = match &root {
+                    Some(root) => {
+                        let manifest = fs::canonicalize(line)?;
+                        if !manifest.starts_with(root) {
+                            return Err(ScanError::OutsideRoot(manifest, root.clone()));
+                        }
+                        manifest
+                    }
+                    None => PathBuf::from(line),
+                };
+                let path = manifest.parent().map(relative).unwrap_or_default();
+                if seen.insert(path.clone()) {
+                    manifests.push((manifest, path));
+                }
+            }
+            return Ok(manifests);
+        }
+        for dir in &self.dirs {
+            let dir = match &root {
+                Some(root) => {
+                    let dir = fs::canonicalize(dir)?;
+                    if !dir.starts_with(root) {
+                        return Err(ScanError::OutsideRoot(dir, root.clone()));
+                    }
+                    dir
+                }
+                None => dir.clone(),
+            };
+            let walk = WalkDir::new(&dir).into_iter()
+                .filter_entry(|entry| {
+                    let walked = self.dotfiles.walk || entry.depth() == 0 || !hidden(entry.path());
+                    walked && !self.skip.iter().any(|pattern| pattern.matches_path(&relative(entry.path())))
+                });
+            for entry in walk {
+                let entry = entry?;
+                visited += 1;
+                if let Some(max) = self.max_depth.filter(|max| entry.depth() > *max) {
+                    return Err(ScanError::TooDeep(entry.into_path(), max));
+                }
+                if let Some(max) = self.max_files.filter(|max| visited > *max) {
+                    return Err(ScanError::TooManyFiles(max, entry.into_path()));
+                }
+                let file_name = entry.file_name().to_string_lossy();
+                if file_name != name && !crate::plugin::discovers(&file_name) {
+                    continue;
+                }
+                let path = entry.path().parent().map(relative).unwrap_or_default();
+                if seen.insert(path.clone()) {
+                    manifests.push((entry.into_path(), path));
+                }
+            }
+        }
+        Ok(manifests)
+    }
+}
+
+/// Whether the file or directory is a dotfile or a dot-directory.
+pub(super) fn hidden(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testkit::FixtureDir;
+
+    use super::*;
+
+    #[test]
+    fn test_skip() {
+        let dir = FixtureDir::new().manifest("a", "").manifest("a/generated/x", "").manifest("b/generated", "");
+
+        let scan = Scan {
+            dirs: vec![dir.path().to_path_buf()],
+            root: Some(dir.path().to_path_buf()),
+            skip: vec![glob::Pattern::new("**/generated").unwrap()],
+            ..Scan::default()
+        };
+        let manifests = scan.manifests();
+        let paths: Vec<PathBuf> = manifests.unwrap().into_iter().map(|(_, path)| path).collect();
+        assert_eq!(paths, vec![PathBuf::from("a")]);
+    }
+
+    #[test]
+    fn test_dotfiles() {
+        let dir = FixtureDir::new().manifest("a", "").manifest(".tools/b", "");
+
+        let scan = Scan { root: Some(dir.path().to_path_buf()), ..Scan::new(dir.path().to_path_buf(), None) };
+        let walked = scan.manifests();
+        let skipped = Scan { dotfiles: DotfilesConfig { walk: false, globs: true }, ..scan }.manifests();
+        let paths = |manifests: Vec<(PathBuf, PathBuf)>| {
+            let mut paths: Vec<PathBuf> = manifests.into_iter().map(|(_, path)| path).collect();
+            paths.sort();
+            paths
+        };
+        assert_eq!(paths(walked.unwrap()), vec![PathBuf::from(".tools/b"), PathBuf::from("a")]);
+        assert_eq!(paths(skipped.unwrap()), vec![PathBuf::from("a")]);
+    }
+
+    #[test]
+    fn test_manifest_list() {
+        let dir = FixtureDir::new().manifest("a", "").manifest("b", "");
+        // b isn't listed, a is listed twice
+        let a = dir.join("a/dependencies.toml");
+        dir.write("manifests.txt", format!("{}\n\n{}\n", a.display(), a.display()));
+
+        let scan = Scan { root: Some(dir.path().to_path_buf()), manifest_list: Some(dir.join("manifests.txt")), ..Scan::default() };
+        let manifests = scan.manifests();
+        let paths: Vec<PathBuf> = manifests.unwrap().into_iter().map(|(_, path)| path).collect();
+        assert_eq!(paths, vec![PathBuf::from("a")]);
+    }
+
+    #[test]
+    fn test_archive() {
+        let dir = FixtureDir::new();
+        let archive = dir.join("source.tar");
+        let mut builder = tar::Builder::new(fs::File::create(&archive).unwrap());
+        for (path, content) in [
+            ("repo-1a2b/a/dependencies.toml", "a"),
+            ("repo-1a2b/a/generated/dependencies.toml", "a-gen"),
+            ("repo-1a2b/b/c/dependencies.toml", "c"),
+            ("repo-1a2b/b/c/README.md", ""),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, path, content.as_bytes()).unwrap();
+        }
+        builder.finish().unwrap();
+        drop(builder);
+
+        let scan = Scan {
+            root: Some(PathBuf::from("repo-1a2b")),
+            skip: vec![glob::Pattern::new("a/generated").unwrap()],
+            archive: Some(archive),
+            ..Scan::default()
+        };
+        let scanned = scan.read();
+        let manifests: Vec<(PathBuf, String)> = scanned.unwrap().manifests.into_iter().map(|m| (m.path, m.content)).collect();
+        assert_eq!(manifests, vec![(PathBuf::from("a"), "a".to_string()), (PathBuf::from("b/c"), "c".to_string())]);
+    }
+
+    #[test]
+    fn test_limits() {
+        let dir = FixtureDir::new().dir("a/b/c");
+
+        let scan = Scan { max_depth: Some(2), ..Scan::new(dir.path().to_path_buf(), None) };
+        let too_deep = scan.manifests();
+        let deep_enough = Scan { max_depth: Some(3), ..scan.clone() }.manifests();
+        let too_many = Scan { max_depth: None, max_files: Some(3), ..scan }.manifests();
+
+        assert!(matches!(
+            too_deep.unwrap_err(),
+            ScanError::TooDeep(path, 2) if path.ends_with("a/b/c"),
+        ));
+        assert!(deep_enough.is_ok());
+        assert!(matches!(too_many.unwrap_err(), ScanError::TooManyFiles(3, _)));
+    }
+}
@@ -0,0 +1,158 @@
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::types::DependencyGraph;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ServeError {
+    #[error("Unable to listen on {0}: {1}")]
+    Bind(String, Box<dyn std::error::Error + Send + Sync>),
+}
+
+/// The body of a `POST /affected`.
+#[derive(Deserialize)]
+struct AffectedRequest {
+    files: Vec<PathBuf>,
+}
+
+/// Answers graph queries over HTTP until interrupted, with the graph loaded once:
+///
+/// * `GET /affected?file=A&file=B`, or `POST /affected` with `{"files": [...]}` - the names of
+///   the nodes the files affect
+/// * `GET /dependents/{node}` and `GET /dependencies/{node}` - the names of the node's
+///   transitive dependents or dependencies
+/// * `GET /node/{name}` - the node, as in the artifact
+///
+/// Responses are JSON, with an `error` field when the status isn't 200.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+/// * `address` - The address to listen on, e.g. `127.0.0.1:8080`
+/// * `threads` - How many requests to answer at once
+pub fn serve(graph: &DependencyGraph, address: &str, threads: usize) -> Result<(), ServeError> {
+    let server = tiny_http::Server::http(address).map_err(|e| ServeError::Bind(address.to_string(), e))?;
+    log::info!("Serving {} nodes on {}", graph.get_all_nodes().len(), address);
+    let content_type = tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).expect("the header is valid");
+
+    std::thread::scope(|scope| {
+        for _ in 0..threads.max(1) {
+            scope.spawn(|| {
+                for mut request in server.incoming_requests() {
+                    let mut body = String::new();
+                    let (status, json) = match request.as_reader().read_to_string(&mut body) {
+                        Ok(_) => respond(graph, request.method().as_str(), request.url(), &body),
+                        Err(e) => (400, json!({ "error": format!("Unable to read the body: {}", e) })),
+                    };
+                    log::info!("{} {} {}", request.method(), request.url(), status);
+                    let response = tiny_http::Response::from_string(format!("{}\n", json))
+                        .with_status_code(status)
+                        .with_header(content_type.clone());
+                    if let Err(e) = request.respond(response) {
+                        log::warn!("Unable to respond: {}", e);
+                    }
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+/// The status and JSON body answering a request.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+/// * `method` - The request method, e.g. `GET`
+/// * `url` - The request path and query string
+/// * `body` - The request body
+fn respond(graph: &DependencyGraph, method: &str, url: &str, body: &str) -> (u16, serde_json::Value) {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let segments: Vec<String> = path.split('/').filter(|segment| !segment.is_empty()).map(|segment| decode(segment, false)).collect();
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+    let error = |status: u16, message: String| (status, json!({ "error": message }));
+
+    match (method, segments.as_slice()) {
+        ("GET", ["affected"]) => {
+            let files: Vec<PathBuf> = query.split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .filter(|(key, _)| *key == "file")
+                .map(|(_, value)| PathBuf::from(decode(value, true)))
+                .collect();
+            (200, json!(graph.get_affected_nodes(&files)))
+        }
+        ("POST", ["affected"]) => match serde_json::from_str::<AffectedRequest>(body) {
+            Ok(request) => (200, json!(graph.get_affected_nodes(&request.files))),
+            Err(e) => error(400, format!("Expected {{\"files\": [...]}}: {}", e)),
+        },
+        ("GET", [kind @ ("dependents" | "dependencies"), name]) => {
+            if graph.get_node(name).is_none() {
+                return error(404, format!("Unknown node '{}'", name));
+            }
+            let nodes = match *kind {
+                "dependents" => graph.get_dependents(name),
+                _ => graph.get_dependencies(name),
+            };
+            let mut names: Vec<String> = nodes.into_iter().map(|node| node.name).collect();
+            names.sort();
+            (200, json!(names))
+        }
+        ("GET", ["node", name]) => match graph.get_node(name) {
+            Some(node) => (200, json!(node)),
+            None => error(404, format!("Unknown node '{}'", name)),
+        },
+        (_, ["affected"] | ["dependents" | "dependencies" | "node", _]) => error(405, format!("{} isn't allowed on {}", method, path)),
+        _ => error(404, format!("No such endpoint {}, use /affected, /dependents/{{node}}, /dependencies/{{node}} or /node/{{name}}", path)),
+    }
+}
+
+/// Decodes the `%XX` escapes of a URL component, and in a query string its `+` spaces.
+fn decode(component: &str, query: bool) -> String {
+    let bytes = component.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => match component.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                Some(byte) => {
+                    decoded.push(byte);
+                    i += 2;
+                }
+                None => decoded.push(b'%'),
+            },
+            b'+' if query => decoded.push(b' '),
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::Node;
+
+    use super::*;
+
+    #[test]
+    fn test_respond() {
+        let node = |name: &str, deps: Vec<&str>| Node::new(
+            name.to_string(), PathBuf::from(name), vec![PathBuf::from("**")], vec![],
+            deps.into_iter().map(String::from).collect(), None,
+        ).unwrap();
+        let graph = DependencyGraph::new(vec![node("lib", vec![]), node("api", vec!["lib"]), node("web", vec!["api"])], false).unwrap();
+
+        assert_eq!(respond(&graph, "GET", "/affected?file=api/src/a%20b.rs&other=x", ""), (200, json!(["api", "web"])));
+        assert_eq!(respond(&graph, "POST", "/affected", r#"{"files": ["lib/a.rs"]}"#), (200, json!(["api", "lib", "web"])));
+        assert_eq!(respond(&graph, "POST", "/affected", "[]").0, 400);
+        assert_eq!(respond(&graph, "GET", "/dependents/lib", ""), (200, json!(["api", "web"])));
+        assert_eq!(respond(&graph, "GET", "/dependencies/web/", ""), (200, json!(["api", "lib"])));
+        assert_eq!(respond(&graph, "GET", "/node/api", "").1["dependencies"], json!(["lib"]));
+        assert_eq!(respond(&graph, "GET", "/node/ghost", "").0, 404);
+        assert_eq!(respond(&graph, "DELETE", "/node/api", "").0, 405);
+        assert_eq!(respond(&graph, "GET", "/", "").0, 404);
+
+        assert_eq!(decode("a%2Fb+c%", true), "a/b c%");
+        assert_eq!(decode("a+b", false), "a+b");
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::{negation, ArtifactInfo, DependencyGraph, Node};
+
+/// The name of the index written next to the shards.
+pub const INDEX_FILE_NAME: &str = "index.json";
+
+/// The shard of nodes living at the root of the repository, or whose patterns reach outside
+/// of their top-level directory. It's loaded by every query.
+const ROOT_SHARD: &str = ".";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ShardError {
+    #[error("Unable to write {0}: {1}")]
+    Write(PathBuf, std::io::Error),
+    #[error("Error serializing: {0}")]
+    Serialize(#[from] serde_json::Error),
+}
+
+/// A thin artifact pointing at one artifact per top-level directory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ShardIndex {
+    /// How the sharded graph was produced.
+    #[serde(default)]
+    pub info: ArtifactInfo,
+    pub shards: Vec<ShardEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ShardEntry {
+    /// The top-level directory the nodes of the shard live in.
+    pub name: String,
+    /// The shard artifact, relative to the index.
+    pub file: PathBuf,
+    pub nodes: usize,
+    /// The shards with nodes depending directly on a node of this shard.
+    pub dependents: Vec<String>,
+}
+
+/// Splits the graph into one artifact per top-level directory, plus an index, inside `dir`.
+///
+/// ### Arguments
+/// * `graph` - The prepared dependency graph
+/// * `dir` - Where to write the shards and the index
+///
+/// ### Returns
+/// * `ShardIndex` - The index, as written to `dir/index.json`
+pub fn write_shards(graph: &DependencyGraph, dir: &Path) -> Result<ShardIndex, ShardError> {
+    let index = build_index(graph);
+    fs::create_dir_all(dir).map_err(|e| ShardError::Write(dir.to_path_buf(), e))?;
+    let write = |path: PathBuf, json: String| super::write_atomic(&path, json).map_err(|e| ShardError::Write(path, e));
+
+    let mut shards: HashMap<String, Vec<&Node>> = HashMap::new();
+    for node in graph.get_all_nodes() {
+        shards.entry(shard_of(node)).or_default().push(node);
+    }
+    for entry in &index.shards {
+        let mut nodes = shards.remove(&entry.name).unwrap_or_default();
+        nodes.sort_by(|a, b| a.name.cmp(&b.name));
+        write(dir.join(&entry.file), serde_json::to_string(&nodes)?)?;
+    }
+    write(dir.join(INDEX_FILE_NAME), serde_json::to_string(&index)?)?;
+    Ok(index)
+}
+
+fn build_index(graph: &DependencyGraph) -> ShardIndex {
+    let shard_by_node: HashMap<&str, String> = graph.get_all_nodes().into_iter()
+        .map(|node| (node.name.as_str(), shard_of(node)))
+        .collect();
+
+    let mut shards: BTreeMap<String, (usize, BTreeSet<String>)> = BTreeMap::new();
+    for node in graph.get_all_nodes() {
+        let shard = &shard_by_node[node.name.as_str()];
+        shards.entry(shard.clone()).or_default().0 += 1;
+        for dep in &node.dependencies {
+            let dep_shard = &shard_by_node[dep.as_str()];
+            if dep_shard != shard {
+                shards.entry(dep_shard.clone()).or_default().1.insert(shard.clone());
+            }
+        }
+    }
+
+    ShardIndex {
+        info: graph.info().clone(),
+        shards: shards.into_iter()
+            .map(|(name, (nodes, dependents))| ShardEntry {
+                file: PathBuf::from(format!("{}.json", file_stem(&name))),
+                name,
+                nodes,
+                dependents: dependents.into_iter().collect(),
+            })
+            .collect(),
+    }
+}
+
+/// The top-level directory of the node, or the root shard if its patterns may match files
+/// elsewhere.
+fn shard_of(node: &Node) -> String {
+    let escapes = node.included_paths.iter()
+        .filter(|p| !negation(p).0)
+        .any(|p| p.is_absolute() || p.components().any(|c| matches!(c, Component::ParentDir)));
+    match node.path.components().next() {
+        Some(Component::Normal(dir)) if !escapes => dir.to_string_lossy().to_string(),
+        _ => ROOT_SHARD.to_string(),
+    }
+}
+
+fn file_stem(shard: &str) -> String {
+    if shard == ROOT_SHARD {
+        return "_root".to_string();
+    }
+    shard.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' { c } else { '_' })
+        .collect()
+}
+
+impl ShardIndex {
+    /// The shards a change to `files` can affect: the root shard, the shards of the files'
+    /// top-level directories, and every shard depending on them. All shards when `files` is `None`.
+    pub fn reachable(&self, files: Option<&[PathBuf]>) -> Vec<&ShardEntry> {
+        let Some(files) = files else { return self.shards.iter().collect() };
+
+        let by_name: HashMap<&str, &ShardEntry> = self.shards.iter().map(|s| (s.name.as_str(), s)).collect();
+        let mut stack: Vec<&str> = files.iter()
+            .filter_map(|file| match file.components().next() {
+                Some(Component::Normal(dir)) if file.components().count() > 1 => dir.to_str(),
+                _ => None,
+            })
+            .chain([ROOT_SHARD])
+            .collect();
+        let mut reached = HashSet::new();
+        while let Some(name) = stack.pop() {
+            let Some(shard) = by_name.get(name) else { continue };
+            if reached.insert(name) {
+                stack.extend(shard.dependents.iter().map(String::as_str));
+            }
+        }
+        self.shards.iter().filter(|s| reached.contains(s.name.as_str())).collect()
+    }
+
+    /// Loads the nodes of the reachable shards (see `reachable`) into a graph. Dependencies
+    /// on nodes of other shards are dropped: a change can't reach the loaded nodes through them.
+    ///
+    /// ### Arguments
+    /// * `dir` - The directory of the index
+    /// * `files` - The changed files, or `None` to load every shard
+    pub fn load(&self, dir: &Path, files: Option<&[PathBuf]>) -> Result<DependencyGraph, super::LoadError> {
+        let mut nodes: Vec<Node> = Vec::new();
+        for shard in self.reachable(files) {
+            let content = fs::read_to_string(dir.join(&shard.file))?;
+            nodes.extend(serde_json::from_str::<Vec<Node>>(&content)?);
+        }
+        log::debug!("Loaded {} nodes from the shards", nodes.len());
+
+        let names: HashSet<String> = nodes.iter().map(|node| node.name.clone()).collect();
+        for node in &mut nodes {
+            node.dependencies.retain(|dep| names.contains(dep));
+        }
+        // Cycles were already checked (or allowed) by `prepare`
+        let mut graph = DependencyGraph::new(nodes, true)?;
+        graph.set_info(self.info.clone());
+        Ok(graph)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testkit::FixtureDir;
+
+    use super::*;
+
+    fn node(name: &str, path: &str, deps: Vec<&str>) -> Node {
+        Node::new(
+            name.to_string(),
+            PathBuf::from(path),
+            vec![PathBuf::from("**")],
+            vec![],
+            deps.into_iter().map(String::from).collect(),
+            None,
+        ).unwrap()
+    }
+
+    #[test]
+    fn test_sharded_query() {
+        let graph = DependencyGraph::new(vec![
+            node("core", "libs/core", vec![]),
+            node("ui", "libs/ui", vec![]),
+            node("api", "services/api", vec!["core"]),
+            node("web", "apps/web", vec!["api", "ui"]),
+            node("tool", "tools/x", vec![]),
+            node("root", "", vec![]),
+        ], false).unwrap();
+
+        let dir = FixtureDir::new();
+        let index = write_shards(&graph, dir.path()).unwrap();
+        let names: Vec<_> = index.shards.iter().map(|s| (s.name.as_str(), s.nodes, s.dependents.clone())).collect();
+        assert_eq!(names, vec![
+            (".", 1, vec![]),
+            ("apps", 1, vec![]),
+            ("libs", 2, vec!["apps".to_string(), "services".to_string()]),
+            ("services", 1, vec!["apps".to_string()]),
+            ("tools", 1, vec![]),
+        ]);
+
+        // A change in services only needs services, apps and the root shard
+        let files = [PathBuf::from("services/api/main.rs")];
+        let reachable: Vec<_> = index.reachable(Some(&files)).iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(reachable, vec![".", "apps", "services"]);
+
+        let sharded = index.load(dir.path(), Some(&files)).unwrap();
+        let mut affected = sharded.get_affected_nodes(&files);
+        affected.sort();
+        assert_eq!(affected, graph.get_affected_nodes(&files));
+        assert!(sharded.get_node("core").is_none());
+
+        assert_eq!(index.load(dir.path(), None).unwrap().get_all_nodes().len(), 6);
+    }
+}
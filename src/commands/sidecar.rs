@@ -0,0 +1,102 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::types::{DependencyGraph, MetadataSidecar, SidecarEntry};
+
+#[derive(Debug, thiserror::Error)]
+pub enum SidecarError {
+    #[error("The metadata file {0} needs a file name")]
+    NoFileName(PathBuf),
+    #[error("Unable to write {0}: {1}")]
+    Write(PathBuf, io::Error),
+    #[error("Error serializing: {0}")]
+    Serialize(#[from] serde_json::Error),
+}
+
+/// Moves the metadata of every node into `path`, leaving offsets in the graph. The artifact
+/// must be stored in the same directory as the sidecar.
+pub fn split_metadata(graph: &mut DependencyGraph, path: &Path) -> Result<(), SidecarError> {
+    let mut nodes: Vec<(String, Option<serde_json::Value>)> = graph.get_all_nodes().into_iter()
+        .map(|node| (node.name.clone(), node.metadata.clone()))
+        .collect();
+    nodes.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut content = Vec::new();
+    let mut entries = BTreeMap::new();
+    for (name, metadata) in nodes {
+        let Some(metadata) = metadata else { continue };
+        let json = serde_json::to_vec(&metadata)?;
+        entries.insert(name.clone(), SidecarEntry { offset: content.len() as u64, len: json.len() as u64 });
+        content.extend(json);
+        content.push(b'\n');
+        graph.set_node_metadata(&name, None);
+    }
+    let file = path.file_name().ok_or_else(|| SidecarError::NoFileName(path.to_path_buf()))?;
+    super::write_atomic(path, content).map_err(|e| SidecarError::Write(path.to_path_buf(), e))?;
+
+    graph.set_metadata_sidecar(Some(MetadataSidecar { file: file.into(), entries }));
+    Ok(())
+}
+
+/// Reads the metadata of the named nodes (all of them when `names` is `None`) back from the
+/// sidecar of an artifact loaded from `artifact_path`. Only the needed parts of the sidecar
+/// are read and parsed. Does nothing when the metadata is inline.
+pub fn attach_metadata(graph: &mut DependencyGraph, artifact_path: &Path, names: Option<&[String]>) -> Result<(), super::LoadError> {
+    let Some(sidecar) = graph.metadata_sidecar().cloned() else { return Ok(()) };
+    let path = artifact_path.parent().unwrap_or(Path::new("")).join(&sidecar.file);
+    let mut file = File::open(&path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+
+    let wanted: Vec<(&String, &SidecarEntry)> = match names {
+        Some(names) => names.iter().filter_map(|name| sidecar.entries.get_key_value(name)).collect(),
+        None => sidecar.entries.iter().collect(),
+    };
+    for (name, entry) in wanted {
+        let mut json = vec![0; entry.len as usize];
+        file.seek(SeekFrom::Start(entry.offset))?;
+        file.read_exact(&mut json)?;
+        graph.set_node_metadata(name, Some(serde_json::from_slice(&json)?));
+    }
+
+    if names.is_none() {
+        graph.set_metadata_sidecar(None);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::testkit::FixtureDir;
+    use crate::types::Node;
+
+    use super::*;
+
+    #[test]
+    fn test_split_and_attach() {
+        let node = |name: &str, metadata| Node::new(
+            name.to_string(), PathBuf::from(name), vec![PathBuf::from("**")], vec![], vec![], metadata,
+        ).unwrap();
+        let mut graph = DependencyGraph::new(vec![
+            node("a", Some(serde_json::json!({"team": "a"}))),
+            node("b", None),
+            node("c", Some(serde_json::json!({"team": "c", "tests": ["x"]}))),
+        ], false).unwrap();
+
+        let dir = FixtureDir::new();
+        split_metadata(&mut graph, &dir.join("meta.jsonl")).unwrap();
+        assert!(graph.get_all_nodes().iter().all(|node| node.metadata.is_none()));
+
+        let artifact = dir.join("graph.json");
+        attach_metadata(&mut graph, &artifact, Some(&["c".to_string()])).unwrap();
+        assert_eq!(graph.get_node("c").unwrap().metadata, Some(serde_json::json!({"team": "c", "tests": ["x"]})));
+        assert_eq!(graph.get_node("a").unwrap().metadata, None);
+        assert!(graph.metadata_sidecar().is_some());
+
+        attach_metadata(&mut graph, &artifact, None).unwrap();
+        assert_eq!(graph.get_node("a").unwrap().metadata, Some(serde_json::json!({"team": "a"})));
+        assert!(graph.metadata_sidecar().is_none());
+    }
+}
@@ -0,0 +1,364 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use toml_edit::{value, DocumentMut, InlineTable};
+
+use crate::types::{DependencyGraph, Node};
+
+/// The mapping file describing how a node is split, e.g.:
+///
+/// ```toml
+/// [[fragments]]
+/// name = "core-api"
+/// include = ["api/**"]
+///
+/// [[fragments]]
+/// name = "core-db"
+/// include = ["db/**", "migrations/**"]
+///
+/// [dependents]
+/// checkout = ["core-api"]
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SplitMapping {
+    pub fragments: Vec<Fragment>,
+    /// Which fragments each dependent of the split node should depend on. Dependents
+    /// that aren't listed depend on every fragment (or are asked for, interactively).
+    #[serde(default)]
+    pub dependents: HashMap<String, Vec<String>>,
+}
+
+/// One of the nodes the split node is broken into.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Fragment {
+    pub name: String,
+    /// The include patterns of the original node assigned to this fragment.
+    pub include: Vec<String>,
+    /// Where the fragment's dependency toml file goes, relative to the original node.
+    /// Defaults to the directory shared by all of the fragment's include patterns.
+    pub path: Option<PathBuf>,
+    /// The fragment's dependencies. Defaults to the dependencies of the original node.
+    pub dependencies: Option<Vec<String>>,
+}
+
+/// A file created, rewritten or removed by the split.
+#[derive(Debug, Clone, Serialize)]
+pub struct FileChange {
+    pub path: PathBuf,
+    #[serde(skip)]
+    pub original: Option<String>,
+    #[serde(skip)]
+    pub updated: Option<String>,
+}
+
+impl FileChange {
+    /// A unified diff of the change.
+    pub fn diff(&self) -> String {
+        let path = self.path.to_string_lossy();
+        similar::TextDiff::from_lines(
+            self.original.as_deref().unwrap_or(""),
+            self.updated.as_deref().unwrap_or(""),
+        )
+            .unified_diff()
+            .header(
+                &if self.original.is_some() { format!("a/{}", path) } else { "/dev/null".to_string() },
+                &if self.updated.is_some() { format!("b/{}", path) } else { "/dev/null".to_string() },
+            )
+            .to_string()
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SplitError {
+    #[error("Node '{0}' not found")]
+    UnknownNode(String),
+    #[error("Include pattern '{0}' of '{1}' isn't assigned to any fragment")]
+    UnassignedPattern(String, String),
+    #[error("Pattern '{0}' isn't an include pattern of '{1}'")]
+    UnknownPattern(String, String),
+    #[error("Pattern '{0}' is assigned to more than one fragment")]
+    DuplicatePattern(String),
+    #[error("Pattern '{0}' of fragment '{1}' is outside of the fragment path {2}")]
+    PatternOutsideFragment(String, String, PathBuf),
+    #[error("Fragments '{0}' and '{1}' would share the directory {2}")]
+    SharedDirectory(String, String, PathBuf),
+    #[error("'{0}' isn't a fragment")]
+    UnknownFragment(String),
+}
+
+/// Splits a node into the fragments described by `mapping`: writes a dependency toml
+/// file per fragment, rewrites the dependents of the node to point at the right fragments
+/// and re-validates the resulting graph before anything is written.
+///
+/// ### Arguments
+/// * `dir` - The directory to start the recursive scan from
+/// * `dependency_toml_name` - The name of the dependency toml file commmon to all the services. Defaults to `dependencies.toml`
+/// * `node_name` - The node to split
+/// * `mapping_path` - The TOML mapping file describing how the node's include patterns and dependents are distributed
+/// * `interactive` - Ask which fragments to use for dependents missing from the mapping
+/// * `dry_run` - Compute the changes without writing them
+///
+/// ### Returns
+/// * `Vec<FileChange>` - Every file created, rewritten or removed
+pub fn split_node(
+    dir: PathBuf,
+    dependency_toml_name: Option<String>,
+    node_name: &str,
+    mapping_path: &Path,
+    interactive: bool,
+    dry_run: bool,
+) -> Result<Vec<FileChange>, Box<dyn std::error::Error>> {
+    let mapping: SplitMapping = toml::from_str(&fs::read_to_string(mapping_path)?)?;
+    let manifest_name = dependency_toml_name.clone().unwrap_or_else(|| "dependencies.toml".to_string());
+    let graph = super::prepare(&super::Scan::new(dir, dependency_toml_name), true, None)?;
+    let original = graph.get_node(node_name)
+        .ok_or_else(|| SplitError::UnknownNode(node_name.to_string()))?;
+
+    let fragments = build_fragments(original, &mapping.fragments)?;
+    let fragment_names: Vec<String> = fragments.iter().map(|f| f.name.clone()).collect();
+
+    // Decide which fragments every dependent points at
+    let mut nodes: Vec<Node> = Vec::new();
+    let mut rewrites: Vec<(PathBuf, Vec<String>)> = Vec::new();
+    for node in graph.get_all_nodes() {
+        if node.name == original.name {
+            continue;
+        }
+        let mut node = node.clone();
+        if node.dependencies.contains(&original.name) {
+            let chosen = match mapping.dependents.get(&node.name) {
+                Some(chosen) => chosen.clone(),
+                None if interactive => prompt_fragments(&node.name, &fragment_names)?,
+                None => fragment_names.clone(),
+            };
+            if let Some(unknown) = chosen.iter().find(|c| !fragment_names.contains(c)) {
+                return Err(Box::new(SplitError::UnknownFragment(unknown.clone())));
+            }
+            node.dependencies.retain(|d| *d != original.name);
+            node.dependencies.extend(chosen.iter().cloned());
+            rewrites.push((node.path.join(&manifest_name), chosen));
+        }
+        nodes.push(node);
+    }
+    nodes.extend(fragments.iter().cloned());
+
+    // Re-validate before touching anything
+    DependencyGraph::new(nodes, false)?;
+
+    let mut changes = Vec::new();
+    let original_manifest = original.path.join(&manifest_name);
+    let original_content = fs::read_to_string(&original_manifest)?;
+    for fragment in &fragments {
+        let path = fragment.path.join(&manifest_name);
+        let existing = (path == original_manifest).then(|| original_content.clone());
+        changes.push(FileChange { path, original: existing, updated: Some(fragment.to_toml_string()?) });
+    }
+    if !changes.iter().any(|c| c.path == original_manifest) {
+        changes.push(FileChange { path: original_manifest, original: Some(original_content), updated: None });
+    }
+    for (path, chosen) in rewrites {
+        let content = fs::read_to_string(&path)?;
+        let updated = rewrite_dependent(&content, &original.name, &chosen)?;
+        changes.push(FileChange { path, original: Some(content), updated: Some(updated) });
+    }
+
+    if !dry_run {
+        for change in &changes {
+            match &change.updated {
+                Some(content) => {
+                    if let Some(parent) = change.path.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    fs::write(&change.path, content)?;
+                }
+                None => fs::remove_file(&change.path)?,
+            }
+        }
+    }
+
+    Ok(changes)
+}
+
+/// Turns the mapping into fragment nodes, checking the include patterns are a partition
+/// of the original node's patterns.
+fn build_fragments(original: &Node, fragments: &[Fragment]) -> Result<Vec<Node>, Box<dyn std::error::Error>> {
+    let original_patterns: BTreeSet<String> = original.included_paths.iter()
+        .map(|p| p.to_string_lossy().to_string())
+        .collect();
+    let mut assigned = BTreeSet::new();
+    let mut directories: HashMap<PathBuf, String> = HashMap::new();
+    let mut nodes = Vec::new();
+
+    for fragment in fragments {
+        for pattern in &fragment.include {
+            if !original_patterns.contains(pattern) {
+                return Err(Box::new(SplitError::UnknownPattern(pattern.clone(), original.name.clone())));
+            }
+            if !assigned.insert(pattern.clone()) {
+                return Err(Box::new(SplitError::DuplicatePattern(pattern.clone())));
+            }
+        }
+
+        let relative = fragment.path.clone().unwrap_or_else(|| common_directory(&fragment.include));
+        let relative: PathBuf = relative.components().filter(|c| !matches!(c, Component::CurDir)).collect();
+        let fragment_path = original.path.join(&relative);
+        if let Some(other) = directories.insert(fragment_path.clone(), fragment.name.clone()) {
+            return Err(Box::new(SplitError::SharedDirectory(other, fragment.name.clone(), fragment_path)));
+        }
+
+        // Patterns are re-rooted at the fragment's directory
+        let mut include = Vec::new();
+        for pattern in &fragment.include {
+            let stripped = Path::new(pattern).strip_prefix(&relative).map_err(|_| {
+                SplitError::PatternOutsideFragment(pattern.clone(), fragment.name.clone(), relative.clone())
+            })?;
+            include.push(stripped.to_path_buf());
+        }
+        let exclude = original.excluded_paths.iter()
+            .filter_map(|p| p.strip_prefix(&relative).ok().map(Path::to_path_buf))
+            .collect();
+
+        let mut node = Node::new(
+            fragment.name.clone(),
+            fragment_path,
+            include,
+            exclude,
+            fragment.dependencies.clone().unwrap_or_else(|| original.dependencies.clone()),
+            original.metadata.clone(),
+        )?;
+        node.tags = original.tags.clone();
+        nodes.push(node);
+    }
+
+    if let Some(missing) = original_patterns.difference(&assigned).next() {
+        return Err(Box::new(SplitError::UnassignedPattern(missing.clone(), original.name.clone())));
+    }
+
+    Ok(nodes)
+}
+
+/// The longest directory prefix without glob characters shared by all the patterns.
+fn common_directory(patterns: &[String]) -> PathBuf {
+    let literal_dirs = patterns.iter().map(|pattern| {
+        let components: Vec<&str> = pattern.split('/').collect();
+        components[..components.len().saturating_sub(1)].iter()
+            .take_while(|c| !c.contains(['*', '?', '[', '{']))
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>()
+    });
+
+    let mut common: Option<Vec<String>> = None;
+    for dirs in literal_dirs {
+        common = Some(match common {
+            None => dirs,
+            Some(prev) => prev.into_iter().zip(dirs).take_while(|(a, b)| a == b).map(|(a, _)| a).collect(),
+        });
+    }
+    common.unwrap_or_default().iter().collect()
+}
+
+/// Replaces the dependency entries pointing at `original` with entries for `fragments`.
+fn rewrite_dependent(content: &str, original: &str, fragments: &[String]) -> Result<String, toml_edit::TomlError> {
+    let mut doc: DocumentMut = content.parse()?;
+    if let Some(dependencies) = doc.get_mut("dependencies").and_then(|d| d.as_table_like_mut()) {
+        let stale: Vec<String> = dependencies.iter()
+            .filter(|(_, item)| item.get("name").and_then(|n| n.as_str()) == Some(original))
+            .map(|(key, _)| key.to_string())
+            .collect();
+        for key in stale {
+            dependencies.remove(&key);
+        }
+        for fragment in fragments {
+            let mut entry = InlineTable::new();
+            entry.insert("name", fragment.as_str().into());
+            dependencies.insert(fragment, value(entry));
+        }
+    }
+    Ok(doc.to_string())
+}
+
+/// Asks on stdin which fragments a dependent should depend on.
+fn prompt_fragments(dependent: &str, fragments: &[String]) -> io::Result<Vec<String>> {
+    eprint!(
+        "{} depends on the split node. Which fragments should it depend on? [{}] (comma separated, empty for all): ",
+        dependent, fragments.join(", ")
+    );
+    io::stderr().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    let chosen: Vec<String> = answer.split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    Ok(if chosen.is_empty() { fragments.to_vec() } else { chosen })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn original() -> Node {
+        Node::new(
+            "core".to_string(),
+            PathBuf::from("libs/core"),
+            vec![PathBuf::from("api/**"), PathBuf::from("db/**"), PathBuf::from("db/migrations/*.sql")],
+            vec![PathBuf::from("db/generated/**")],
+            vec!["base".to_string()],
+            None,
+        ).unwrap()
+    }
+
+    fn fragment(name: &str, include: &[&str]) -> Fragment {
+        Fragment {
+            name: name.to_string(),
+            include: include.iter().map(|s| s.to_string()).collect(),
+            path: None,
+            dependencies: None,
+        }
+    }
+
+    #[test]
+    fn test_build_fragments() {
+        let nodes = build_fragments(&original(), &[
+            fragment("core-api", &["api/**"]),
+            fragment("core-db", &["db/**", "db/migrations/*.sql"]),
+        ]).unwrap();
+
+        assert_eq!(nodes[0].path, PathBuf::from("libs/core/api"));
+        assert_eq!(nodes[0].included_paths, vec![PathBuf::from("**")]);
+        assert_eq!(nodes[1].path, PathBuf::from("libs/core/db"));
+        assert_eq!(nodes[1].included_paths, vec![PathBuf::from("**"), PathBuf::from("migrations/*.sql")]);
+        assert_eq!(nodes[1].excluded_paths, vec![PathBuf::from("generated/**")]);
+        assert_eq!(nodes[1].dependencies, vec!["base"]);
+    }
+
+    #[test]
+    fn test_build_fragments_requires_partition() {
+        let missing = build_fragments(&original(), &[fragment("core-api", &["api/**"])]);
+        assert!(missing.unwrap_err().to_string().contains("isn't assigned"));
+
+        let mut in_place = fragment("b", &["db/migrations/*.sql"]);
+        in_place.path = Some(PathBuf::from("."));
+        let shared = build_fragments(&original(), &[fragment("a", &["api/**", "db/**"]), in_place]);
+        assert!(shared.unwrap_err().to_string().contains("share the directory"));
+    }
+
+    #[test]
+    fn test_rewrite_dependent() {
+        let content = "[module]\nname = \"checkout\"\n\n[dependencies]\ncore = { name = \"core\" } # shared\nother = { name = \"other\" }\n";
+        let rewritten = rewrite_dependent(content, "core", &["core-api".to_string()]).unwrap();
+
+        let node = Node::from_toml_str(&format!("{}\n[file_paths]\ninclude = [\"**\"]\n", rewritten), PathBuf::from("x")).unwrap();
+        let mut dependencies = node.dependencies.clone();
+        dependencies.sort();
+        assert_eq!(dependencies, vec!["core-api", "other"]);
+        assert!(!rewritten.contains("# shared"));
+    }
+}
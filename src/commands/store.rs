@@ -0,0 +1,119 @@
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use tempfile::TempPath;
+
+use crate::runner::cache_backend;
+
+use super::git::{self, GitError};
+
+#[derive(Debug, thiserror::Error)]
+pub enum StoreError {
+    #[error(transparent)]
+    Git(#[from] GitError),
+    #[error("The artifact {0} records no source commit, pass --commit")]
+    NoCommit(PathBuf),
+    #[error("No artifact of commit {0} in {1}")]
+    NotStored(String, String),
+    #[error("Unable to reach the artifact store {0}: {1}")]
+    Backend(String, io::Error),
+    #[error("Unable to write {0}: {1}")]
+    Write(PathBuf, io::Error),
+    #[error(transparent)]
+    Load(#[from] super::LoadError),
+    #[error("Error serializing: {0}")]
+    Serialize(#[from] serde_json::Error),
+}
+
+/// Stores an artifact as `<commit>.json` in a store shared by the pipelines, so that the
+/// graph of any stored commit can be queried later. Sharded artifacts and metadata sidecars
+/// are stored as a single artifact.
+///
+/// ### Arguments
+/// * `graph_artifact_path` - The artifact to store
+/// * `location` - The store: a local directory, `http(s)://...` or `s3://bucket/prefix`,
+///   like the `run --cache` locations
+/// * `revision` - The commit to store it for, by default the one it was prepared at
+///
+/// ### Returns
+/// * `String` - The full hash of the commit the artifact was stored for
+pub fn store_artifact(graph_artifact_path: &Path, location: &str, revision: Option<&str>) -> Result<String, StoreError> {
+    let graph = super::load_graph(graph_artifact_path)?;
+    let commit = match revision {
+        Some(revision) => git::resolve_commit(revision)?,
+        None => graph.info().source_commit.clone().ok_or_else(|| StoreError::NoCommit(graph_artifact_path.to_path_buf()))?,
+    };
+    let json = serde_json::to_vec(&graph)?;
+    cache_backend(location).put(&key(&commit), &json).map_err(|e| StoreError::Backend(location.to_string(), e))?;
+    Ok(commit)
+}
+
+/// Fetches the artifact stored for a commit into a new file of the temporary directory,
+/// readable by the current user only, so that concurrent fetches don't share it.
+///
+/// ### Arguments
+/// * `location` - The store, as given to `store_artifact`
+/// * `revision` - The commit, or a branch, tag or abbreviated hash resolved by git
+///
+/// ### Returns
+/// * `TempPath` - The path of the fetched artifact, deleted when dropped
+pub fn fetch_artifact(location: &str, revision: &str) -> Result<TempPath, StoreError> {
+    let commit = git::resolve_commit(revision)?;
+    let json = cache_backend(location).get(&key(&commit))
+        .map_err(|e| StoreError::Backend(location.to_string(), e))?
+        .ok_or_else(|| StoreError::NotStored(commit.clone(), location.to_string()))?;
+    let mut file = tempfile::Builder::new()
+        .prefix(&format!("dependency-cascade-{}-", commit))
+        .suffix(".json")
+        .tempfile()
+        .map_err(|e| StoreError::Write(std::env::temp_dir(), e))?;
+    file.write_all(&json).map_err(|e| StoreError::Write(file.path().to_path_buf(), e))?;
+    Ok(file.into_temp_path())
+}
+
+/// The name of a commit's artifact in the store.
+fn key(commit: &str) -> String {
+    format!("{}.json", commit)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testkit::FixtureDir;
+    use crate::types::{ArtifactInfo, DependencyGraph, Node};
+
+    use super::*;
+
+    #[test]
+    fn test_store_artifact() {
+        let dir = FixtureDir::new();
+        let store = dir.join("store");
+        let node = Node::new("lib".to_string(), PathBuf::from("lib"), vec![PathBuf::from("**")], vec![], vec![], None).unwrap();
+        let mut graph = DependencyGraph::new(vec![node], false).unwrap();
+        let path = dir.join("graph.json");
+        dir.write("graph.json", serde_json::to_string(&graph).unwrap());
+        let without_commit = store_artifact(&path, store.to_str().unwrap(), None);
+
+        let commit = "0123456789abcdef0123456789abcdef01234567";
+        graph.set_info(ArtifactInfo { source_commit: Some(commit.to_string()), ..ArtifactInfo::current() });
+        dir.write("graph.json", serde_json::to_string(&graph).unwrap());
+        let stored = store_artifact(&path, store.to_str().unwrap(), None).unwrap();
+        let fetched = fetch_artifact(store.to_str().unwrap(), &commit.to_uppercase()).unwrap();
+        let fetched_graph = super::super::load_graph(&fetched).unwrap();
+        let missing = fetch_artifact(store.to_str().unwrap(), &"f".repeat(40));
+
+        // Two fetches of the same commit get files of their own
+        let again = fetch_artifact(store.to_str().unwrap(), commit).unwrap();
+        assert_ne!(fetched.to_path_buf(), again.to_path_buf());
+        drop(fetched);
+        assert!(super::super::load_graph(&again).is_ok());
+        let again_path = again.to_path_buf();
+        drop(again);
+        assert!(!again_path.exists());
+
+        assert!(matches!(without_commit, Err(StoreError::NoCommit(_))));
+        assert_eq!(stored, commit);
+        assert_eq!(fetched_graph.info().source_commit.as_deref(), Some(commit));
+        assert_eq!(fetched_graph.get_all_nodes().len(), 1);
+        assert!(matches!(missing, Err(StoreError::NotStored(_, _))));
+    }
+}
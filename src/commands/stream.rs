@@ -0,0 +1,304 @@
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+
+use crate::types::{ArtifactInfo, DependencyGraph, DependencyGraphCreationError, Edge, MetadataSidecar, Node};
+
+#[derive(Debug, thiserror::Error)]
+pub enum StreamError {
+    #[error("Streaming reads the artifact twice, so it can't read it from standard input")]
+    Stdin,
+    #[error("Streaming parses JSON artifacts, query binary artifacts without --stream")]
+    Binary,
+    #[error("Unable to read {0}: {1}")]
+    Read(PathBuf, std::io::Error),
+    #[error("Failed to parse {0}: {1}")]
+    Parse(PathBuf, serde_json::Error),
+    #[error(transparent)]
+    Graph(#[from] DependencyGraphCreationError),
+}
+
+/// The subgraph of the nodes affected by a change, read from an artifact without loading it.
+pub struct StreamedQuery {
+    /// The affected nodes, with their dependencies restricted to other affected nodes.
+    pub graph: DependencyGraph,
+    /// The number of nodes in the whole artifact.
+    pub total_nodes: usize,
+}
+
+/// Reads the artifact twice as a stream: first to find the nodes claiming the changed files
+/// and the edges, then to keep the affected nodes. Only the edges and the affected nodes are
+/// held in memory. Sharded and binary artifacts and standard input aren't supported.
+///
+/// Since every dependent of an affected node is affected too, the returned subgraph answers
+/// queries for `changed_files` exactly like the whole graph.
+///
+/// ### Arguments
+/// * `path` - The artifact written by `prepare`
+/// * `changed_files` - The list of files that have changed
+///
+/// ### Returns
+/// * `StreamedQuery` - The affected subgraph
+pub fn stream_affected(path: &Path, changed_files: &[PathBuf]) -> Result<StreamedQuery, StreamError> {
+    if super::output::is_stdin(path) {
+        return Err(StreamError::Stdin);
+    }
+    let mut magic = [0; 4];
+    let mut file = File::open(path).map_err(|e| StreamError::Read(path.to_path_buf(), e))?;
+    if file.read_exact(&mut magic).is_ok() && super::encoding::is_binary(&magic) {
+        return Err(StreamError::Binary);
+    }
+
+    // First pass: which nodes claim the files, and which of those cascade from them
+    let mut changed: HashMap<usize, (PathBuf, Vec<&PathBuf>)> = HashMap::new();
+    let mut dependents: HashMap<usize, Vec<(usize, Edge)>> = HashMap::new();
+    let mut total_nodes = 0;
+    let header = read(path, &mut |event| match event {
+        Event::Node(index, node) => {
+            total_nodes += 1;
+            let files: Vec<&PathBuf> = changed_files.iter().filter(|file| node.includes_path(file)).collect();
+            if !files.is_empty() {
+                let cascading = files.into_iter().filter(|file| node.cascades_path(file)).collect();
+                changed.insert(index, (node.path, cascading));
+            }
+        }
+        Event::Edge(dependency, dependent, edge) => dependents.entry(dependency).or_default().push((dependent, edge)),
+    })?;
+
+    // As in `DependencyGraph::get_affected_reasons`, edge paths only filter the first step
+    let mut affected: BTreeSet<usize> = changed.keys().copied().collect();
+    let mut queue: VecDeque<usize> = VecDeque::new();
+    let mut queued: HashSet<usize> = changed.iter().filter(|(_, (_, files))| !files.is_empty()).map(|(index, _)| *index).collect();
+    for (index, (node_path, files)) in &changed {
+        for (dependent, edge) in dependents.get(index).into_iter().flatten() {
+            if files.iter().any(|file| edge.cascades_path(node_path, file)) && queued.insert(*dependent) {
+                affected.insert(*dependent);
+                queue.push_back(*dependent);
+            }
+        }
+    }
+    while let Some(index) = queue.pop_front() {
+        for (dependent, _) in dependents.get(&index).into_iter().flatten() {
+            if queued.insert(*dependent) {
+                affected.insert(*dependent);
+                queue.push_back(*dependent);
+            }
+        }
+    }
+    drop(dependents);
+
+    // Second pass: keep the affected nodes
+    let mut nodes = Vec::with_capacity(affected.len());
+    read(path, &mut |event| {
+        if let Event::Node(index, node) = event {
+            if affected.contains(&index) {
+                nodes.push(*node);
+            }
+        }
+    })?;
+    let names: HashSet<String> = nodes.iter().map(|node| node.name.clone()).collect();
+    for node in &mut nodes {
+        node.dependencies.retain(|dep| names.contains(dep));
+    }
+
+    let mut graph = DependencyGraph::new(nodes, true)?;
+    graph.set_info(header.info);
+    graph.set_metadata_sidecar(header.metadata_sidecar);
+    Ok(StreamedQuery { graph, total_nodes })
+}
+
+enum Event {
+    /// A node and its index in the serialized graph.
+    Node(usize, Box<Node>),
+    /// An edge from a dependency to its dependent, by index, with its data.
+    Edge(usize, usize, Edge),
+}
+
+/// The top-level fields other than the graph itself.
+#[derive(Default)]
+struct Header {
+    info: ArtifactInfo,
+    metadata_sidecar: Option<MetadataSidecar>,
+}
+
+fn read(path: &Path, on_event: &mut dyn FnMut(Event)) -> Result<Header, StreamError> {
+    let file = File::open(path).map_err(|e| StreamError::Read(path.to_path_buf(), e))?;
+    let mut deserializer = serde_json::Deserializer::from_reader(BufReader::new(file));
+    let parse = |e| StreamError::Parse(path.to_path_buf(), e);
+    let header = ArtifactSeed { on_event }.deserialize(&mut deserializer).map_err(parse)?;
+    deserializer.end().map_err(parse)?;
+    Ok(header)
+}
+
+/// Walks `{"info": .., "metadata_sidecar": .., "graph": .., "name_to_index": ..}`.
+struct ArtifactSeed<'a> {
+    on_event: &'a mut dyn FnMut(Event),
+}
+
+impl<'de> DeserializeSeed<'de> for ArtifactSeed<'_> {
+    type Value = Header;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for ArtifactSeed<'_> {
+    type Value = Header;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a dependency graph artifact")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut header = Header::default();
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "info" => {
+                    header.info = map.next_value()?;
+                    header.info.check_compatible().map_err(de::Error::custom)?;
+                }
+                "metadata_sidecar" => header.metadata_sidecar = map.next_value()?,
+                "graph" => map.next_value_seed(GraphSeed { on_event: &mut *self.on_event })?,
+                "shards" => return Err(de::Error::custom("sharded artifacts can't be streamed, query the index instead")),
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok(header)
+    }
+}
+
+/// Walks petgraph's `{"nodes": [..], "edges": [[from, to, weight], ..], ..}`.
+struct GraphSeed<'a> {
+    on_event: &'a mut dyn FnMut(Event),
+}
+
+impl<'de> DeserializeSeed<'de> for GraphSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for GraphSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a serialized graph")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "nodes" => map.next_value_seed(SeqSeed { on_event: &mut *self.on_event, nodes: true })?,
+                "edges" => map.next_value_seed(SeqSeed { on_event: &mut *self.on_event, nodes: false })?,
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Emits an event per element of the node or edge list, one element at a time.
+struct SeqSeed<'a> {
+    on_event: &'a mut dyn FnMut(Event),
+    nodes: bool,
+}
+
+impl<'de> DeserializeSeed<'de> for SeqSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for SeqSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(if self.nodes { "a list of nodes" } else { "a list of edges" })
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        if self.nodes {
+            let mut index = 0;
+            while let Some(node) = seq.next_element::<Node>()? {
+                (self.on_event)(Event::Node(index, Box::new(node)));
+                index += 1;
+            }
+        } else {
+            while let Some((from, to, edge)) = seq.next_element::<(usize, usize, Edge)>()? {
+                (self.on_event)(Event::Edge(from, to, edge));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testkit::FixtureDir;
+
+    use super::*;
+
+    fn node(name: &str, deps: Vec<&str>) -> Node {
+        Node::new(
+            name.to_string(),
+            PathBuf::from(name),
+            vec![PathBuf::from("**")],
+            vec![],
+            deps.into_iter().map(String::from).collect(),
+            None,
+        ).unwrap()
+    }
+
+    #[test]
+    fn test_stream_affected() {
+        let graph = DependencyGraph::new(vec![
+            node("core", vec![]),
+            node("api", vec!["core"]),
+            node("web", vec!["api"]),
+            node("cli", vec!["core"]),
+            node("docs", vec![]),
+        ], false).unwrap();
+        let dir = FixtureDir::new().file("graph.json", serde_json::to_string(&graph).unwrap());
+        let path = dir.join("graph.json");
+
+        let files = [PathBuf::from("api/src/main.rs")];
+        let streamed = stream_affected(&path, &files).unwrap();
+
+        assert_eq!(streamed.total_nodes, 5);
+        assert_eq!(streamed.graph.get_all_nodes().len(), 2);
+        assert_eq!(streamed.graph.get_affected_nodes(&files), graph.get_affected_nodes(&files));
+        assert_eq!(streamed.graph.get_node("web").unwrap().dependencies, vec!["api"]);
+        assert_eq!(streamed.graph.info(), graph.info());
+    }
+
+    #[test]
+    fn test_stream_edge_paths() {
+        // cli only depends on core's protos
+        let mut cli = node("cli", vec!["core"]);
+        cli.dependency_edges.insert("core".to_string(), Edge { paths: vec![PathBuf::from("proto/**")], ..Edge::default() });
+        let graph = DependencyGraph::new(vec![node("core", vec![]), node("api", vec!["core"]), cli], false).unwrap();
+        let dir = FixtureDir::new().file("graph.json", serde_json::to_string(&graph).unwrap());
+        let path = dir.join("graph.json");
+
+        let files = [PathBuf::from("core/src/lib.rs")];
+        let streamed = stream_affected(&path, &files).unwrap();
+        let proto_files = [PathBuf::from("core/proto/user.proto")];
+        let proto_streamed = stream_affected(&path, &proto_files).unwrap();
+
+        assert_eq!(streamed.graph.get_affected_nodes(&files), vec!["api", "core"]);
+        assert_eq!(proto_streamed.graph.get_affected_nodes(&proto_files), vec!["api", "cli", "core"]);
+    }
+}
@@ -0,0 +1,388 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use clap::ValueEnum;
+use regex::Regex;
+use serde::Serialize;
+use walkdir::WalkDir;
+
+use crate::types::{DependencyGraph, Node};
+
+/// Languages `suggest-deps` can scan for imports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScanLanguage {
+    /// `use`/`extern crate` of workspace crates and `path` dependencies in `Cargo.toml`.
+    Rust,
+    /// Relative `import`/`require` specifiers in JavaScript and TypeScript files.
+    Typescript,
+    /// Imports of packages that live in a module of the repository (per `go.mod`).
+    Go,
+}
+
+/// A dependency that's used in the code but not declared in the dependency toml file.
+#[derive(Debug, Clone, Serialize)]
+pub struct UndeclaredDependency {
+    pub dependency: String,
+    /// Where the usage was found, as `file:line`.
+    pub evidence: Vec<String>,
+}
+
+/// The dependency suggestions for a single node.
+#[derive(Debug, Clone, Serialize)]
+pub struct DependencySuggestion {
+    pub node: String,
+    /// The number of source files the scanners understood.
+    pub scanned_files: usize,
+    /// Declared dependencies no scanned file refers to.
+    pub unused: Vec<String>,
+    /// Nodes referred to by scanned files that aren't declared as dependencies.
+    pub undeclared: Vec<UndeclaredDependency>,
+}
+
+/// Repository-wide facts scanners need to resolve an import into a path.
+#[derive(Debug, Default)]
+struct ScanContext {
+    /// Rust crate names (with `-` replaced by `_`) to their directory.
+    rust_crates: HashMap<String, PathBuf>,
+    /// Go module paths to their directory.
+    go_modules: Vec<(String, PathBuf)>,
+}
+
+/// Extracts the repository paths a source file refers to.
+trait ImportScanner {
+    fn language(&self) -> ScanLanguage;
+    fn applies_to(&self, file: &Path) -> bool;
+    /// Returns `(line, referenced path)` pairs.
+    fn scan(&self, ctx: &ScanContext, file: &Path, content: &str) -> Vec<(usize, PathBuf)>;
+}
+
+struct RustScanner {
+    use_re: Regex,
+}
+
+impl ImportScanner for RustScanner {
+    fn language(&self) -> ScanLanguage {
+        ScanLanguage::Rust
+    }
+
+    fn applies_to(&self, file: &Path) -> bool {
+        file.extension().is_some_and(|e| e == "rs") || file.file_name().is_some_and(|n| n == "Cargo.toml")
+    }
+
+    fn scan(&self, ctx: &ScanContext, file: &Path, content: &str) -> Vec<(usize, PathBuf)> {
+        let base = file.parent().unwrap_or(Path::new(""));
+
+        if file.file_name().is_some_and(|n| n == "Cargo.toml") {
+            // `path = "../foo"` dependencies, looked up line by line to keep line numbers
+            return content.lines().enumerate()
+                .filter_map(|(i, line)| {
+                    let (_, rest) = line.split_once("path")?;
+                    let rest = rest.trim_start().strip_prefix('=')?.trim_start();
+                    let value = rest.strip_prefix('"')?.split('"').next()?;
+                    Some((i + 1, normalize(&base.join(value))))
+                })
+                .collect();
+        }
+
+        content.lines().enumerate()
+            .filter_map(|(i, line)| {
+                let name = self.use_re.captures(line)?.get(1)?.as_str();
+                ctx.rust_crates.get(name).map(|dir| (i + 1, dir.clone()))
+            })
+            .collect()
+    }
+}
+
+struct TypescriptScanner {
+    import_re: Regex,
+}
+
+impl ImportScanner for TypescriptScanner {
+    fn language(&self) -> ScanLanguage {
+        ScanLanguage::Typescript
+    }
+
+    fn applies_to(&self, file: &Path) -> bool {
+        file.extension().and_then(|e| e.to_str())
+            .is_some_and(|e| matches!(e, "ts" | "tsx" | "js" | "jsx" | "mjs" | "cjs" | "mts" | "cts"))
+    }
+
+    fn scan(&self, _ctx: &ScanContext, file: &Path, content: &str) -> Vec<(usize, PathBuf)> {
+        let base = file.parent().unwrap_or(Path::new(""));
+        content.lines().enumerate()
+            .flat_map(|(i, line)| {
+                self.import_re.captures_iter(line)
+                    .filter_map(|c| c.get(1))
+                    .map(|m| (i + 1, normalize(&base.join(m.as_str()))))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+}
+
+struct GoScanner;
+
+impl ImportScanner for GoScanner {
+    fn language(&self) -> ScanLanguage {
+        ScanLanguage::Go
+    }
+
+    fn applies_to(&self, file: &Path) -> bool {
+        file.extension().is_some_and(|e| e == "go")
+    }
+
+    fn scan(&self, ctx: &ScanContext, _file: &Path, content: &str) -> Vec<(usize, PathBuf)> {
+        let mut results = Vec::new();
+        let mut in_block = false;
+
+        for (i, line) in content.lines().enumerate() {
+            let trimmed = line.trim();
+            let spec = if in_block {
+                if trimmed.starts_with(')') {
+                    in_block = false;
+                    continue;
+                }
+                trimmed
+            } else if let Some(rest) = trimmed.strip_prefix("import") {
+                let rest = rest.trim_start();
+                if rest.starts_with('(') {
+                    in_block = true;
+                    continue;
+                }
+                rest
+            } else {
+                continue;
+            };
+
+            // Import specs may be aliased: `alias "example.com/mod/pkg"`
+            let Some(import) = spec.split('"').nth(1) else { continue };
+            for (module, dir) in &ctx.go_modules {
+                if let Some(rest) = import.strip_prefix(module.as_str()) {
+                    if rest.is_empty() || rest.starts_with('/') {
+                        results.push((i + 1, dir.join(rest.trim_start_matches('/'))));
+                        break;
+                    }
+                }
+            }
+        }
+        results
+    }
+}
+
+/// Every available scanner.
+fn scanners() -> Vec<Box<dyn ImportScanner>> {
+    vec![
+        Box::new(RustScanner {
+            use_re: Regex::new(r"^\s*(?:pub(?:\([^)]*\))?\s+)?(?:use\s+(?:::)?|extern\s+crate\s+)([A-Za-z_][A-Za-z0-9_]*)").unwrap(),
+        }),
+        Box::new(TypescriptScanner {
+            import_re: Regex::new(r#"(?:\bfrom|\bimport|\brequire\s*\(|\bimport\s*\()\s*['"](\.{1,2}/[^'"]+)['"]"#).unwrap(),
+        }),
+        Box::new(GoScanner),
+    ]
+}
+
+/// Scans the source files of every node for imports that resolve into other nodes and
+/// compares them against the declared dependencies.
+///
+/// ### Arguments
+/// * `dir` - The directory to start the recursive scan from
+/// * `dependency_toml_name` - The name of the dependency toml file commmon to all the services. Defaults to `dependencies.toml`
+/// * `languages` - Restricts scanning to these languages. All languages are scanned when empty
+/// * `node` - Restricts the report to a single node
+///
+/// ### Returns
+/// * `Vec<DependencySuggestion>` - One entry per node with unused or undeclared dependencies
+pub fn suggest_deps(
+    dir: PathBuf,
+    dependency_toml_name: Option<String>,
+    languages: &[ScanLanguage],
+    node: Option<String>,
+) -> Result<Vec<DependencySuggestion>, Box<dyn std::error::Error>> {
+    let graph = super::prepare(&super::Scan::new(dir.clone(), dependency_toml_name), true, None)?;
+    let ctx = scan_context(&dir)?;
+    let scanners: Vec<_> = scanners().into_iter()
+        .filter(|s| languages.is_empty() || languages.contains(&s.language()))
+        .collect();
+
+    let mut suggestions = Vec::new();
+    for current in graph.get_all_nodes() {
+        if node.as_ref().is_some_and(|name| *name != current.name) {
+            continue;
+        }
+
+        let mut scanned_files = 0;
+        let mut used: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for file in source_files(current) {
+            let applicable: Vec<_> = scanners.iter().filter(|s| s.applies_to(&file)).collect();
+            if applicable.is_empty() {
+                continue;
+            }
+            let Ok(content) = fs::read_to_string(&file) else { continue };
+            scanned_files += 1;
+
+            for scanner in applicable {
+                for (line, target) in scanner.scan(&ctx, &file, &content) {
+                    if let Some(owner) = owning_node(&graph, &target).filter(|o| o.name != current.name) {
+                        used.entry(owner.name.clone())
+                            .or_default()
+                            .push(format!("{}:{}", file.display(), line));
+                    }
+                }
+            }
+        }
+
+        // Without any understood file there's no evidence either way
+        let unused: Vec<String> = if scanned_files == 0 {
+            Vec::new()
+        } else {
+            current.dependencies.iter().filter(|d| !used.contains_key(*d)).cloned().collect()
+        };
+        let undeclared: Vec<UndeclaredDependency> = used.into_iter()
+            .filter(|(name, _)| !current.dependencies.contains(name))
+            .map(|(dependency, evidence)| UndeclaredDependency { dependency, evidence })
+            .collect();
+
+        if !unused.is_empty() || !undeclared.is_empty() {
+            suggestions.push(DependencySuggestion { node: current.name.clone(), scanned_files, unused, undeclared });
+        }
+    }
+
+    suggestions.sort_by(|a, b| a.node.cmp(&b.node));
+    Ok(suggestions)
+}
+
+/// Collects the Rust crates and Go modules of the repository.
+fn scan_context(dir: &Path) -> Result<ScanContext, walkdir::Error> {
+    let mut ctx = ScanContext::default();
+    for entry in WalkDir::new(dir).into_iter().filter_entry(|e| !is_ignored_dir(e.path())) {
+        let entry = entry?;
+        let path = normalize(entry.path());
+        let parent = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else { continue };
+
+        match file_name {
+            "Cargo.toml" => {
+                let name = fs::read_to_string(&path).ok()
+                    .and_then(|c| c.parse::<toml::Table>().ok())
+                    .and_then(|t| t.get("package")?.get("name")?.as_str().map(String::from));
+                if let Some(name) = name {
+                    ctx.rust_crates.insert(name.replace('-', "_"), parent);
+                }
+            }
+            "go.mod" => {
+                let module = fs::read_to_string(&path).ok().and_then(|c| {
+                    c.lines().find_map(|l| l.trim().strip_prefix("module ").map(|m| m.trim().trim_matches('"').to_string()))
+                });
+                if let Some(module) = module {
+                    ctx.go_modules.push((module, parent));
+                }
+            }
+            _ => {}
+        }
+    }
+    // Prefer the most specific module when modules are nested
+    ctx.go_modules.sort_by_key(|(module, _)| std::cmp::Reverse(module.len()));
+    Ok(ctx)
+}
+
+/// The files under the node's directory matched by its include/exclude patterns.
+fn source_files(node: &Node) -> Vec<PathBuf> {
+    WalkDir::new(&node.path)
+        .into_iter()
+        .filter_entry(|e| e.depth() == 0 || !is_ignored_dir(e.path()))
+        .filter_map(Result::ok)
+        .filter(|e| e.file_type().is_file())
+        .map(|e| normalize(e.path()))
+        .filter(|p| node.includes_path(p))
+        .collect()
+}
+
+/// The node a referenced path belongs to: the node including it, or else the node with
+/// the most specific directory containing it (imports often point at directories).
+fn owning_node<'a>(graph: &'a DependencyGraph, target: &Path) -> Option<&'a Node> {
+    let nodes = graph.get_all_nodes();
+    nodes.iter()
+        .find(|n| n.includes_path(target))
+        .or_else(|| {
+            nodes.iter()
+                .filter(|n| target.starts_with(&n.path))
+                .max_by_key(|n| n.path.components().count())
+        })
+        .copied()
+}
+
+fn is_ignored_dir(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| matches!(n, "node_modules" | "target" | ".git" | "vendor"))
+}
+
+/// Resolves `.` and `..` components without touching the filesystem.
+fn normalize(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !result.pop() {
+                    result.push("..");
+                }
+            }
+            other => result.push(other.as_os_str()),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scanner(language: ScanLanguage) -> Box<dyn ImportScanner> {
+        scanners().into_iter().find(|s| s.language() == language).unwrap()
+    }
+
+    #[test]
+    fn test_typescript_scanner() {
+        let content = "import { a } from '../lib/src/a';\nconst b = require(\"./b\");\nimport React from 'react';\n";
+        let found = scanner(ScanLanguage::Typescript).scan(&ScanContext::default(), Path::new("apps/web/src/index.ts"), content);
+
+        assert_eq!(found, vec![
+            (1, PathBuf::from("apps/web/lib/src/a")),
+            (2, PathBuf::from("apps/web/src/b")),
+        ]);
+    }
+
+    #[test]
+    fn test_rust_scanner() {
+        let mut ctx = ScanContext::default();
+        ctx.rust_crates.insert("shared_lib".to_string(), PathBuf::from("libs/shared"));
+
+        let content = "use std::fs;\npub use shared_lib::Thing;\nextern crate shared_lib;\n";
+        let found = scanner(ScanLanguage::Rust).scan(&ctx, Path::new("svc/src/main.rs"), content);
+        assert_eq!(found, vec![(2, PathBuf::from("libs/shared")), (3, PathBuf::from("libs/shared"))]);
+
+        let cargo = "[dependencies]\nshared-lib = { path = \"../libs/shared\" }\nserde = \"1\"\n";
+        let found = scanner(ScanLanguage::Rust).scan(&ctx, Path::new("svc/Cargo.toml"), cargo);
+        assert_eq!(found, vec![(2, PathBuf::from("libs/shared"))]);
+    }
+
+    #[test]
+    fn test_go_scanner() {
+        let mut ctx = ScanContext::default();
+        ctx.go_modules.push(("example.com/mono".to_string(), PathBuf::from("")));
+
+        let content = "package main\n\nimport (\n\t\"fmt\"\n\tauth \"example.com/mono/libs/auth\"\n)\nimport \"example.com/mono/libs/db\"\n";
+        let found = scanner(ScanLanguage::Go).scan(&ctx, Path::new("svc/main.go"), content);
+        assert_eq!(found, vec![(5, PathBuf::from("libs/auth")), (7, PathBuf::from("libs/db"))]);
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize(Path::new("./a/b/../c")), PathBuf::from("a/c"));
+        assert_eq!(normalize(Path::new("a/../../b")), PathBuf::from("../b"));
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::types::{negation, DependencyGraph, Node};
+
+#[derive(Debug, thiserror::Error)]
+pub enum TriggersError {
+    #[error("Node '{0}' not found")]
+    UnknownNode(String),
+    #[error("Failed to list the files of '{0}': {1}")]
+    Walk(String, walkdir::Error),
+}
+
+/// The patterns of one of the nodes whose changes affect the queried node, relative to the
+/// directory `prepare` ran in.
+#[derive(Debug, Clone, Serialize)]
+pub struct NodePatterns {
+    pub node: String,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+/// Every path whose change would mark a node as affected.
+#[derive(Debug, Clone, Serialize)]
+pub struct Triggers {
+    pub node: String,
+    /// The union of the include patterns of the node and its direct and indirect dependencies.
+    /// Each node's exclude and negated include patterns only apply to its own includes, see
+    /// `nodes`.
+    pub include: Vec<String>,
+    /// The patterns of the node and of each of its dependencies, sorted by node name.
+    pub nodes: Vec<NodePatterns>,
+    /// The files currently matched by any of the nodes, when listing them was requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub files: Option<Vec<PathBuf>>,
+}
+
+/// Collects the paths that affect `node`: its own and those of all of its dependencies.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+/// * `node` - The name of the node
+/// * `list_files` - Also list the matching files, relative to the current directory
+///
+/// ### Returns
+/// * `Triggers` - The patterns, and the files if requested
+pub fn triggers(graph: &DependencyGraph, node: &str, list_files: bool) -> Result<Triggers, TriggersError> {
+    let target = graph.get_node(node).ok_or_else(|| TriggersError::UnknownNode(node.to_string()))?;
+    let mut nodes: Vec<Node> = graph.get_dependencies(node);
+    nodes.push(target.clone());
+    nodes.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let patterns: Vec<NodePatterns> = nodes.iter()
+        .map(|node| NodePatterns {
+            node: node.name.clone(),
+            include: node.included_paths.iter().map(|p| rooted(&node.path, p)).collect(),
+            exclude: node.excluded_paths.iter().map(|p| rooted(&node.path, p)).collect(),
+        })
+        .collect();
+    let include: BTreeSet<String> = patterns.iter()
+        .flat_map(|p| p.include.iter().filter(|pattern| !pattern.starts_with('!')).cloned())
+        .collect();
+
+    let files = if list_files {
+        let mut files = BTreeSet::new();
+        for node in &nodes {
+            files.extend(super::node_files(node).map_err(|e| TriggersError::Walk(node.name.clone(), e))?);
+        }
+        Some(files.into_iter().collect())
+    } else {
+        None
+    };
+
+    Ok(Triggers { node: node.to_string(), include: include.into_iter().collect(), nodes: patterns, files })
+}
+
+/// Joins a pattern to the node's directory the way `Node::includes_path` does, with `/`,
+/// keeping the `!` of a negated pattern in front.
+fn rooted(dir: &Path, pattern: &Path) -> String {
+    let (negated, pattern) = negation(pattern);
+    let rooted = dir.join(pattern).to_string_lossy().replace('\\', "/");
+    if negated { format!("!{}", rooted) } else { rooted }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(name: &str, deps: Vec<&str>) -> Node {
+        Node::new(
+            name.to_string(),
+            PathBuf::from(format!("libs/{}", name)),
+            vec![PathBuf::from("src/**")],
+            vec![PathBuf::from("src/**/*.md")],
+            deps.into_iter().map(String::from).collect(),
+            None,
+        ).unwrap()
+    }
+
+    #[test]
+    fn test_triggers() {
+        let graph = DependencyGraph::new(vec![
+            node("core", vec![]),
+            node("api", vec!["core"]),
+            node("web", vec!["api"]),
+            node("docs", vec![]),
+        ], false).unwrap();
+
+        let triggers = triggers(&graph, "api", false).unwrap();
+        assert_eq!(triggers.include, vec!["libs/api/src/**", "libs/core/src/**"]);
+        assert_eq!(triggers.nodes.iter().map(|n| n.node.as_str()).collect::<Vec<_>>(), vec!["api", "core"]);
+        assert_eq!(triggers.nodes[1].exclude, vec!["libs/core/src/**/*.md"]);
+        assert!(triggers.files.is_none());
+
+        assert!(matches!(super::triggers(&graph, "nope", false), Err(TriggersError::UnknownNode(_))));
+    }
+}
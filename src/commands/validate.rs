@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::types::{negation, DependencyGraph, DependencyGraphCreationError, Node, NodeCreationError, Severity};
+
+use super::lint::{find_line, LintFinding, Location};
+use super::{Scan, ScanError, ScannedManifest};
+
+/// Checks every manifest found by `scan`, reporting all the problems that would make `prepare`
+/// fail rather than the first one: manifests that don't parse, including no files or with
+/// invalid `[metadata.ci]`, invalid glob patterns, names used twice, dependencies on missing
+/// nodes and cycles. Each is located in the manifest when possible.
+///
+/// ### Arguments
+/// * `scan` - The directories to scan, as with `prepare`
+///
+/// ### Returns
+/// * `Vec<LintFinding>` - Every problem, by manifest and line
+pub fn validate(scan: &Scan) -> Result<Vec<LintFinding>, ScanError> {
+    let mut findings = Vec::new();
+    let mut nodes: Vec<Parsed> = Vec::new();
+    for ScannedManifest { file, path, content } in scan.read()?.manifests {
+        let mut report = |rule, node: String, line, message| findings.push(LintFinding {
+            rule,
+            severity: Severity::Error,
+            node,
+            location: Location { file: file.clone(), line },
+            message,
+        });
+        let node = match Node::from_toml_str(&content, path.clone()) {
+            Ok(node) => node,
+            Err(e) => {
+                let unnamed = path.display().to_string();
+                match e {
+                    NodeCreationError::TomlParseError(e) => {
+                        let line = e.span().map(|span| content[..span.start].matches('\n').count() + 1);
+                        report("parse", unnamed, line, e.message().to_string());
+                    }
+                    NodeCreationError::NoIncludedPaths(name) => {
+                        let line = find_line(&content, "file_paths", "include").or(find_line(&content, "module", "name"));
+                        report("empty-include", name.clone(), line, format!("{} includes no files, add patterns to [file_paths] include", name));
+                    }
+                    NodeCreationError::InvalidCiMetadata(name, e) => {
+                        report("invalid-ci", name.clone(), find_line(&content, "metadata.ci", ""), format!("{} has an invalid [metadata.ci]: {}", name, e));
+                    }
+                    e => report("parse", unnamed, None, e.to_string()),
+                }
+                continue;
+            }
+        };
+
+        for pattern in [&node.included_paths, &node.excluded_paths, &node.cascade_excluded_paths].into_iter().flatten() {
+            let pattern = negation(pattern).1.to_string_lossy();
+            if pattern.starts_with("plugin:") {
+                continue;
+            }
+            if let Err(e) = glob::Pattern::new(&pattern) {
+                report("invalid-glob", node.name.clone(), find_line(&content, "file_paths", &format!("\"{}", pattern)), format!("{} has an invalid pattern {}: {}", node.name, pattern, e.msg));
+            }
+        }
+        nodes.push(Parsed { node, file, content });
+    }
+
+    // The first manifest declaring a name keeps it, the others are reported
+    let mut declared: HashMap<String, PathBuf> = HashMap::new();
+    nodes.retain(|parsed| match declared.get(&parsed.node.name) {
+        Some(first) => {
+            let message = format!("{} is also the name of the node of {}", parsed.node.name, first.display());
+            findings.push(parsed.finding("duplicate-name", "module", "name", message));
+            false
+        }
+        None => {
+            declared.insert(parsed.node.name.clone(), parsed.file.clone());
+            true
+        }
+    });
+    for parsed in &mut nodes {
+        let missing: Vec<String> = parsed.node.dependencies.iter().filter(|dep| !declared.contains_key(*dep)).cloned().collect();
+        for dep in &missing {
+            let message = format!("{} depends on {}, which no manifest declares", parsed.node.name, dep);
+            findings.push(parsed.finding("missing-dependency", "dependencies", &format!("\"{}\"", dep), message));
+        }
+        parsed.node.dependencies.retain(|dep| !missing.contains(dep));
+    }
+
+    // The graph only fails on cycles once the missing dependencies are left out
+    let by_name: HashMap<&str, &Parsed> = nodes.iter().map(|parsed| (parsed.node.name.as_str(), parsed)).collect();
+    if let Err(DependencyGraphCreationError::CircularDependency(report)) = DependencyGraph::new(nodes.iter().map(|parsed| parsed.node.clone()).collect(), false) {
+        for component in report.components {
+            // Reported at one of the dependencies breaking the cycles
+            let Some(dependency) = component.to_break.first().or(component.dependencies.first()) else { continue };
+            let parsed = by_name[dependency.dependent.as_str()];
+            findings.push(parsed.finding("cycle", "dependencies", &format!("\"{}\"", dependency.dependency), format!(
+                "{} form a cycle, e.g. through the dependency of {} on {}",
+                component.nodes.join(", "), dependency.dependent, dependency.dependency,
+            )));
+        }
+    }
+
+    findings.sort_by(|a, b| a.location.file.cmp(&b.location.file).then_with(|| a.location.line.cmp(&b.location.line)));
+    Ok(findings)
+}
+
+/// A manifest that parsed into a node.
+struct Parsed {
+    node: Node,
+    file: PathBuf,
+    content: String,
+}
+
+impl Parsed {
+    /// An error about the node, at the first line of `[section]` containing `needle`.
+    fn finding(&self, rule: &'static str, section: &str, needle: &str, message: String) -> LintFinding {
+        LintFinding {
+            rule,
+            severity: Severity::Error,
+            node: self.node.name.clone(),
+            location: Location { file: self.file.clone(), line: find_line(&self.content, section, needle) },
+            message,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testkit::FixtureDir;
+
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let manifests = [
+            ("a", "[module]\nname = \"a\"\n\n[file_paths]\ninclude = [\"src/[\"]\n\n[dependencies]\nb = { name = \"b\" }\nghost = { name = \"ghost\" }\n"),
+            ("b", "[module]\nname = \"b\"\n\n[file_paths]\ninclude = [\"**\"]\n\n[dependencies]\na = { name = \"a\" }\n"),
+            ("c", "[module]\nname = \"b\"\n\n[file_paths]\ninclude = [\"**\"]\n"),
+            ("d", "[module]\nname = \"d\"\n"),
+            ("e", "[module\nname = \"e\"\n"),
+        ];
+        let dir = manifests.into_iter().fold(FixtureDir::new(), |dir, (node, content)| dir.manifest(node, content));
+        let scan = Scan { root: Some(dir.path().to_path_buf()), ..Scan::new(dir.path().to_path_buf(), None) };
+        let findings = validate(&scan).unwrap();
+
+        let found: Vec<(&str, &str, Option<usize>)> = findings.iter()
+            .map(|finding| (finding.rule, finding.node.as_str(), finding.location.line))
+            .collect();
+        assert_eq!(found, [
+            ("invalid-glob", "a", Some(5)),
+            ("missing-dependency", "a", Some(9)),
+            ("cycle", "b", Some(8)),
+            ("duplicate-name", "b", Some(2)),
+            ("empty-include", "d", Some(2)),
+            ("parse", "e", Some(1)),
+        ]);
+    }
+}
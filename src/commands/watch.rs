@@ -0,0 +1,202 @@
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use notify::event::ModifyKind;
+use notify::{EventKind, RecursiveMode, Watcher};
+use serde::Serialize;
+
+use crate::types::DependencyGraph;
+
+use super::{prepare, FilterArgs, PrepareError, Scan};
+
+#[derive(Debug, thiserror::Error)]
+pub enum WatchError {
+    #[error(transparent)]
+    Prepare(#[from] PrepareError),
+    #[error("Unable to watch the files: {0}")]
+    Notify(#[from] notify::Error),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// What a batch of file changes affects, as `watch` reports it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WatchReport {
+    /// The changed files, relative to the directory `watch` runs in.
+    pub files: Vec<PathBuf>,
+    /// The affected nodes, sorted by name.
+    pub affected: Vec<String>,
+    /// Whether a manifest changed, making the graph prepared again first.
+    pub reprepared: bool,
+}
+
+/// The graph `watch` keeps in memory, prepared again when a manifest changes.
+pub struct WatchedGraph<'a> {
+    scan: &'a Scan,
+    allow_cyclical: bool,
+    graph: DependencyGraph,
+}
+
+impl<'a> WatchedGraph<'a> {
+    /// Prepares the graph of the scan.
+    pub fn new(scan: &'a Scan, allow_cyclical: bool) -> Result<Self, PrepareError> {
+        Ok(WatchedGraph { scan, allow_cyclical, graph: prepare(scan, allow_cyclical, None)? })
+    }
+
+    /// Prepares the graph again if one of the changed files is a manifest, reusing the nodes
+    /// of the unchanged ones, then reports the nodes the files affect. When the manifests no
+    /// longer make a valid graph, the previous one is kept and the error returned.
+    ///
+    /// ### Arguments
+    /// * `files` - The changed files, relative to the current directory
+    /// * `filters` - Restrictions on the affected nodes
+    pub fn changed(&mut self, files: Vec<PathBuf>, filters: &FilterArgs) -> Result<WatchReport, PrepareError> {
+        let manifest_name = self.scan.dependency_toml_name.as_deref().unwrap_or("dependencies.toml");
+        let reprepared = files.iter().any(|file| file.file_name().is_some_and(|name| name == manifest_name));
+        if reprepared {
+            self.graph = prepare(self.scan, self.allow_cyclical, Some(&self.graph))?;
+        }
+        let affected = filters.apply(&self.graph, self.graph.get_affected_nodes(&files));
+        Ok(WatchReport { files, affected, reprepared })
+    }
+}
+
+/// Watches the scanned directories until interrupted, keeping their graph in memory, and
+/// reports what each batch of changes affects: as a JSON line on standard output, and POSTed
+/// to the webhook when given. Changes arriving within `debounce` of each other, e.g. from
+/// saving several files or switching branches, make a single batch. Files under `.git` and
+/// the directories skipped by the scan are ignored. Run from the repository root.
+///
+/// ### Arguments
+/// * `scan` - The directories to watch and prepare the graph of
+/// * `allow_cyclical` - Whether the graph may have cycles
+/// * `filters` - Restrictions on the affected nodes
+/// * `debounce` - How long to wait for more changes before reporting a batch
+/// * `webhook` - A URL to POST each report to, through `curl`
+pub fn watch(scan: &Scan, allow_cyclical: bool, filters: &FilterArgs, debounce: Duration, webhook: Option<&str>) -> Result<(), WatchError> {
+    let mut watched = WatchedGraph::new(scan, allow_cyclical)?;
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender)?;
+    for dir in &scan.dirs {
+        watcher.watch(dir, RecursiveMode::Recursive)?;
+    }
+    let current_dir = std::env::current_dir()?.canonicalize()?;
+    log::info!("Watching {} nodes", watched.graph.get_all_nodes().len());
+
+    while let Ok(event) = receiver.recv() {
+        // Collect the events of the batch, until none come for `debounce`
+        let mut files: BTreeSet<PathBuf> = BTreeSet::new();
+        let mut next = Some(event);
+        while let Some(event) = next {
+            match event {
+                // Reading the manifests to prepare the graph must not start another batch
+                Ok(event) if is_change(&event.kind) => files.extend(event.paths.iter().filter_map(|path| relative(path, &current_dir, scan))),
+                Ok(_) => {}
+                Err(e) => log::warn!("Watch error: {}", e),
+            }
+            next = receiver.recv_timeout(debounce).ok();
+        }
+        if files.is_empty() {
+            continue;
+        }
+
+        let report = match watched.changed(files.into_iter().collect(), filters) {
+            Ok(report) => report,
+            Err(e) => {
+                eprintln!("Error: {}, keeping the previous graph", e);
+                continue;
+            }
+        };
+        let json = serde_json::to_string(&report).map_err(io::Error::other)?;
+        let mut stdout = io::stdout().lock();
+        writeln!(stdout, "{}", json)?;
+        stdout.flush()?;
+        if let Some(webhook) = webhook {
+            if let Err(e) = post(webhook, &json) {
+                eprintln!("Error: Unable to POST to {}: {}", webhook, e);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether the event creates, writes, renames or removes files, rather than reading them or
+/// changing their metadata.
+fn is_change(kind: &EventKind) -> bool {
+    matches!(kind, EventKind::Create(_) | EventKind::Remove(_) | EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Name(_) | ModifyKind::Any))
+}
+
+/// The path of a changed file relative to the current directory, unless it's under `.git`,
+/// skipped by the scan, or a directory.
+fn relative(path: &Path, current_dir: &Path, scan: &Scan) -> Option<PathBuf> {
+    let relative = path.strip_prefix(current_dir).unwrap_or(path).to_path_buf();
+    let ignored = relative.components().any(|component| component == Component::Normal(".git".as_ref()))
+        || scan.skip.iter().any(|pattern| relative.ancestors().any(|ancestor| pattern.matches_path(ancestor)))
+        || path.is_dir();
+    (!ignored).then_some(relative)
+}
+
+/// POSTs the JSON report to the webhook.
+fn post(url: &str, json: &str) -> io::Result<()> {
+    let mut child = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--request", "POST", "--header", "Content-Type: application/json", "--data-binary", "@-", url])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    child.stdin.take().expect("stdin is piped").write_all(json.as_bytes())?;
+    let output = child.wait_with_output()?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(String::from_utf8_lossy(&output.stderr).trim().to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use notify::event::{AccessKind, DataChange};
+
+    use crate::testkit::FixtureDir;
+
+    use super::*;
+
+    #[test]
+    fn test_watched_graph() {
+        let manifest = |name: &str, deps: &str| format!("[module]\nname = \"{}\"\n\n[file_paths]\ninclude = [\"**\"]\n\n[dependencies]\n{}", name, deps);
+        let dir = FixtureDir::new()
+            .manifest("lib", &manifest("lib", ""))
+            .manifest("api", &manifest("api", "lib = { name = \"lib\" }\n"));
+        let scan = Scan { root: Some(dir.path().to_path_buf()), ..Scan::new(dir.path().to_path_buf(), None) };
+        let filters = FilterArgs::default();
+        let mut watched = WatchedGraph::new(&scan, false).unwrap();
+
+        let report = watched.changed(vec![PathBuf::from("lib/src/a.rs")], &filters).unwrap();
+        assert_eq!(report.affected, ["api", "lib"]);
+        assert!(!report.reprepared);
+
+        // A new dependent shows up once its manifest is saved
+        dir.write("web/dependencies.toml", manifest("web", "lib = { name = \"lib\" }\n"));
+        let report = watched.changed(vec![PathBuf::from("web/dependencies.toml"), PathBuf::from("lib/src/a.rs")], &filters).unwrap();
+        assert_eq!(report.affected, ["api", "lib", "web"]);
+        assert!(report.reprepared);
+
+        // A broken manifest keeps the previous graph
+        dir.write("web/dependencies.toml", manifest("web", "ghost = { name = \"ghost\" }\n"));
+        assert!(watched.changed(vec![PathBuf::from("web/dependencies.toml")], &filters).is_err());
+        let report = watched.changed(vec![PathBuf::from("lib/src/a.rs")], &filters).unwrap();
+        assert_eq!(report.affected, ["api", "lib", "web"]);
+
+        let scan = Scan { skip: vec![glob::Pattern::new("target").unwrap()], ..Scan::default() };
+        let root = Path::new("/repo");
+        assert_eq!(relative(Path::new("/repo/lib/a.rs"), root, &scan), Some(PathBuf::from("lib/a.rs")));
+        assert_eq!(relative(Path::new("/repo/.git/index"), root, &scan), None);
+        assert_eq!(relative(Path::new("/repo/target/debug/x"), root, &scan), None);
+        assert!(is_change(&EventKind::Modify(ModifyKind::Data(DataChange::Content))));
+        assert!(!is_change(&EventKind::Access(AccessKind::Read)));
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+
+use crate::types::DependencyGraph;
+
+/// How many of a set of nodes can be processed in parallel, dependencies before dependents.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Width {
+    pub nodes: usize,
+    /// The number of nodes in each layer: a node's layer is one more than the deepest of its
+    /// direct or indirect dependencies among the set, so each layer can run in parallel once
+    /// the previous ones are done.
+    pub layers: Vec<usize>,
+    /// The most nodes of the set that don't depend on each other, i.e. the most runners that
+    /// can ever be busy at once. At least the widest layer.
+    pub max_parallelism: usize,
+}
+
+/// Measures the width of the subgraph of `names`.
+///
+/// ### Arguments
+/// * `graph` - The dependency graph artifact
+/// * `names` - The nodes to measure, e.g. the affected ones
+///
+/// ### Returns
+/// * `Width` - The layer widths and the maximum parallelism
+pub fn width(graph: &DependencyGraph, names: &[String]) -> Width {
+    let wanted: HashSet<&str> = names.iter().map(String::as_str).collect();
+    // The dependencies of each node among the set, direct or not
+    let below: HashMap<&str, Vec<String>> = wanted.iter()
+        .map(|name| {
+            let deps = graph.get_dependencies(name).into_iter()
+                .map(|dep| dep.name)
+                .filter(|dep| dep != name && wanted.contains(dep.as_str()))
+                .collect();
+            (*name, deps)
+        })
+        .collect();
+
+    let mut layer_of: HashMap<&str, usize> = HashMap::new();
+    let mut layers: Vec<usize> = Vec::new();
+    for node in graph.toposorted_subset(names) {
+        let layer = below[node.name.as_str()].iter()
+            .filter_map(|dep| layer_of.get(dep.as_str()).map(|layer| layer + 1))
+            .max()
+            .unwrap_or(0);
+        layer_of.insert(&node.name, layer);
+        if layers.len() <= layer {
+            layers.resize(layer + 1, 0);
+        }
+        layers[layer] += 1;
+    }
+
+    Width { nodes: layer_of.len(), layers, max_parallelism: max_antichain(&below) }
+}
+
+/// The size of the largest set of nodes none of which depends on another. By Dilworth's
+/// theorem, it's the number of nodes minus a maximum matching between each node and the
+/// nodes it depends on.
+fn max_antichain(below: &HashMap<&str, Vec<String>>) -> usize {
+    let mut names: Vec<&str> = below.keys().copied().collect();
+    names.sort();
+    let index: HashMap<&str, usize> = names.iter().enumerate().map(|(i, name)| (*name, i)).collect();
+    let edges: Vec<Vec<usize>> = names.iter()
+        .map(|name| below[name].iter().map(|dep| index[dep.as_str()]).collect())
+        .collect();
+
+    // Kuhn's augmenting paths
+    fn augment(u: usize, edges: &[Vec<usize>], seen: &mut [bool], matched: &mut [Option<usize>]) -> bool {
+        for &v in &edges[u] {
+            if seen[v] {
+                continue;
+            }
+            seen[v] = true;
+            if matched[v].is_none_or(|w| augment(w, edges, seen, matched)) {
+                matched[v] = Some(u);
+                return true;
+            }
+        }
+        false
+    }
+    let mut matched: Vec<Option<usize>> = vec![None; names.len()];
+    let matching = (0..names.len())
+        .filter(|&u| augment(u, &edges, &mut vec![false; names.len()], &mut matched))
+        .count();
+    names.len() - matching
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::types::Node;
+
+    use super::*;
+
+    fn node(name: &str, deps: Vec<&str>) -> Node {
+        Node::new(
+            name.to_string(),
+            PathBuf::from(name),
+            vec![PathBuf::from("**")],
+            vec![],
+            deps.into_iter().map(String::from).collect(),
+            None,
+        ).unwrap()
+    }
+
+    #[test]
+    fn test_width() {
+        let graph = DependencyGraph::new(vec![
+            node("core", vec![]),
+            node("api", vec!["core"]),
+            node("web", vec!["api"]),
+            node("cli", vec!["core"]),
+            node("worker", vec!["cli"]),
+            node("docs", vec![]),
+        ], false).unwrap();
+        let names = graph.get_affected_nodes(&[PathBuf::from("core/lib.rs")]);
+
+        let measured = width(&graph, &names);
+        assert_eq!(measured.nodes, 5);
+        assert_eq!(measured.layers, vec![1, 2, 2]);
+        assert_eq!(measured.max_parallelism, 2);
+
+        // Without core, both chains and docs start at once
+        let measured = width(&graph, &["api", "web", "cli", "worker", "docs"].map(String::from));
+        assert_eq!(measured.layers, vec![3, 2]);
+        assert_eq!(measured.max_parallelism, 3);
+    }
+}
@@ -0,0 +1,29 @@
+//! Builds the dependency graph of a monorepo from the `dependencies.toml` manifests of its
+//! nodes, and finds the nodes a change affects, directly or through their dependencies.
+//!
+//! The `dependency-cascade` binary is a thin CLI over this crate. To embed it, prepare a graph
+//! from the repository, or load an artifact the CLI prepared, then query it:
+//!
+//! ```no_run
+//! use std::path::PathBuf;
+//!
+//! use dependency_cascade::{prepare, query, FilterArgs, Scan};
+//!
+//! let graph = prepare(&Scan::new(PathBuf::from("."), None), false, None)?;
+//! let affected = query(&graph, &[PathBuf::from("libs/auth/src/lib.rs")], &FilterArgs::default());
+//! for node in affected {
+//!     println!("{}", node.name);
+//! }
+//! # Ok::<(), dependency_cascade::PrepareError>(())
+//! ```
+
+pub mod cli;
+pub mod commands;
+pub mod parallel;
+pub mod plugin;
+pub mod runner;
+pub mod testkit;
+pub mod types;
+
+pub use commands::{load_graph, prepare, query, FilterArgs, LoadError, PrepareError, Scan, ScanError};
+pub use types::{AffectedReason, DependencyGraph, DependencyGraphCreationError, Node, NodeCreationError};
@@ -0,0 +1,42 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use rayon::prelude::*;
+
+/// Whether the work below is spread over the threads of the global pool, set by `--jobs`.
+static PARALLEL: AtomicBool = AtomicBool::new(false);
+
+/// Reads and parses the manifests of `prepare`, and matches the changed files of `query`,
+/// on `jobs` threads, or one per CPU when 0. The default, 1, runs them on the calling
+/// thread, which is faster for small repositories. Only the first call has an effect.
+pub fn set_jobs(jobs: usize) -> Result<(), rayon::ThreadPoolBuildError> {
+    if jobs == 1 || PARALLEL.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    rayon::ThreadPoolBuilder::new().num_threads(jobs).build_global()?;
+    PARALLEL.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Maps the items in order, on the global pool when `set_jobs` asked for several threads.
+pub(crate) fn map<T: Sync, R: Send>(items: &[T], f: impl Fn(&T) -> R + Sync + Send) -> Vec<R> {
+    match PARALLEL.load(Ordering::Relaxed) {
+        true => items.par_iter().map(f).collect(),
+        false => items.iter().map(f).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map() {
+        let items: Vec<usize> = (0..1000).collect();
+        let serial = map(&items, |item| item * 2);
+        set_jobs(4).unwrap();
+        assert_eq!(map(&items, |item| item * 2), serial);
+        assert!(PARALLEL.load(Ordering::Relaxed));
+        // Later calls leave the pool alone
+        assert!(set_jobs(2).is_ok());
+    }
+}
@@ -0,0 +1,221 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use serde_json::json;
+use wasmi::{Engine, Linker, Memory, Module, Store, TypedFunc};
+
+use crate::types::{Node, PluginConfig};
+
+/// The plugins declared in the root config, installed once at startup.
+static INSTALLED: OnceLock<Vec<Mutex<Plugin>>> = OnceLock::new();
+
+#[derive(Debug, thiserror::Error)]
+pub enum PluginError {
+    #[error("Unable to read plugin {0}: {1}")]
+    Read(PathBuf, std::io::Error),
+    #[error("Plugin {0} failed: {1}")]
+    Wasm(String, wasmi::Error),
+    #[error("Plugin {0} doesn't export `{1}`")]
+    MissingExport(String, &'static str),
+    #[error("Plugin {0} returned an invalid result: {1}")]
+    InvalidOutput(String, String),
+}
+
+/// A WebAssembly module extending discovery or matching. It exports its `memory` and
+/// `alloc(len: i32) -> i32`, returning where the host may write `len` bytes of input, and
+/// one or both of:
+///
+/// * `discover(ptr: i32, len: i32) -> i64`, given `{"path": .., "content": ..}` for every file
+///   named as one of the plugin's `manifests`, returning `ptr << 32 | len` of the node's
+///   manifest in the `dependencies.toml` format, or 0 when the file declares no node.
+/// * `matches(ptr: i32, len: i32) -> i32`, given `{"node": .., "node_path": .., "argument": ..,
+///   "path": ..}` for the include and exclude patterns `plugin:<name>:<argument>`, returning 1
+///   when the path matches.
+///
+/// Inputs and outputs are UTF-8. Plugins get no imports: they can't reach the filesystem,
+/// the network or the clock.
+pub struct Plugin {
+    pub name: String,
+    /// The file names handed to `discover`.
+    pub manifests: Vec<String>,
+    store: Store<()>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    discover: Option<TypedFunc<(i32, i32), i64>>,
+    matches: Option<TypedFunc<(i32, i32), i32>>,
+}
+
+impl Plugin {
+    /// Instantiates a plugin from its WebAssembly binary.
+    pub fn from_bytes(name: String, manifests: Vec<String>, wasm: &[u8]) -> Result<Self, PluginError> {
+        let fail = |e: wasmi::Error| PluginError::Wasm(name.clone(), e);
+        let engine = Engine::default();
+        let module = Module::new(&engine, wasm).map_err(fail)?;
+        let mut store = Store::new(&engine, ());
+        let instance = Linker::<()>::new(&engine)
+            .instantiate(&mut store, &module)
+            .and_then(|pre| pre.start(&mut store))
+            .map_err(fail)?;
+
+        let memory = instance.get_memory(&store, "memory")
+            .ok_or_else(|| PluginError::MissingExport(name.clone(), "memory"))?;
+        let alloc = instance.get_typed_func(&store, "alloc")
+            .map_err(|_| PluginError::MissingExport(name.clone(), "alloc"))?;
+        let discover = instance.get_typed_func(&store, "discover").ok();
+        let matches = instance.get_typed_func(&store, "matches").ok();
+        if discover.is_none() && matches.is_none() {
+            return Err(PluginError::MissingExport(name, "discover` or `matches"));
+        }
+        Ok(Plugin { name, manifests, store, memory, alloc, discover, matches })
+    }
+
+    /// Hands a file to the plugin's `discover`.
+    ///
+    /// ### Returns
+    /// * `Option<String>` - The node's manifest, or `None` if the file declares no node
+    pub fn discover(&mut self, path: &Path, content: &str) -> Result<Option<String>, PluginError> {
+        let Some(discover) = self.discover else {
+            return Err(PluginError::MissingExport(self.name.clone(), "discover"));
+        };
+        let input = json!({ "path": path, "content": content }).to_string();
+        let (ptr, len) = self.write(&input)?;
+        let result = discover.call(&mut self.store, (ptr, len)).map_err(|e| PluginError::Wasm(self.name.clone(), e))?;
+        if result == 0 {
+            return Ok(None);
+        }
+
+        let (ptr, len) = ((result as u64 >> 32) as usize, (result as u64 & 0xffff_ffff) as usize);
+        let mut output = vec![0; len];
+        self.memory.read(&self.store, ptr, &mut output)
+            .map_err(|e| PluginError::InvalidOutput(self.name.clone(), e.to_string()))?;
+        String::from_utf8(output)
+            .map(Some)
+            .map_err(|e| PluginError::InvalidOutput(self.name.clone(), e.to_string()))
+    }
+
+    /// Asks the plugin's `matches` whether `path` matches the pattern argument for `node`.
+    pub fn matches(&mut self, argument: &str, node: &Node, path: &Path) -> Result<bool, PluginError> {
+        let Some(matches) = self.matches else {
+            return Err(PluginError::MissingExport(self.name.clone(), "matches"));
+        };
+        let input = json!({ "node": node.name, "node_path": node.path, "argument": argument, "path": path }).to_string();
+        let (ptr, len) = self.write(&input)?;
+        let result = matches.call(&mut self.store, (ptr, len)).map_err(|e| PluginError::Wasm(self.name.clone(), e))?;
+        Ok(result == 1)
+    }
+
+    /// Copies the input into memory the plugin allocated.
+    fn write(&mut self, input: &str) -> Result<(i32, i32), PluginError> {
+        let len = input.len() as i32;
+        let ptr = self.alloc.call(&mut self.store, len).map_err(|e| PluginError::Wasm(self.name.clone(), e))?;
+        self.memory.write(&mut self.store, ptr as usize, input.as_bytes())
+            .map_err(|e| PluginError::InvalidOutput(self.name.clone(), e.to_string()))?;
+        Ok((ptr, len))
+    }
+}
+
+/// Loads the plugins declared in the root config.
+///
+/// ### Arguments
+/// * `configs` - The `[[plugins]]` of the root config
+/// * `dir` - The directory their paths are relative to, the root config's
+///
+/// ### Returns
+/// * `Vec<Plugin>` - The instantiated plugins, in the config's order
+pub fn load(configs: &[PluginConfig], dir: &Path) -> Result<Vec<Plugin>, PluginError> {
+    configs.iter()
+        .map(|config| {
+            let path = dir.join(&config.path);
+            let wasm = fs::read(&path).map_err(|e| PluginError::Read(path.clone(), e))?;
+            let name = config.name.clone()
+                .unwrap_or_else(|| path.file_stem().unwrap_or_default().to_string_lossy().to_string());
+            Plugin::from_bytes(name, config.manifests.clone(), &wasm)
+        })
+        .collect()
+}
+
+/// Makes the plugins available to every scan and pattern match of the process. Only the
+/// first call has an effect.
+pub fn install(plugins: Vec<Plugin>) {
+    let _ = INSTALLED.set(plugins.into_iter().map(Mutex::new).collect());
+}
+
+fn installed() -> &'static [Mutex<Plugin>] {
+    INSTALLED.get().map(Vec::as_slice).unwrap_or_default()
+}
+
+/// Whether an installed plugin discovers nodes in the files with this name.
+pub fn discovers(file_name: &str) -> bool {
+    installed().iter().any(|plugin| plugin.lock().unwrap().manifests.iter().any(|name| name == file_name))
+}
+
+/// Hands a file to the first installed plugin discovering nodes in files with its name.
+///
+/// ### Returns
+/// * `Option<String>` - The node's manifest, or `None` if the file declares no node or no
+///   plugin discovers it
+pub fn discover(file: &Path, content: &str) -> Result<Option<String>, PluginError> {
+    let file_name = file.file_name().unwrap_or_default().to_string_lossy();
+    for plugin in installed() {
+        let mut plugin = plugin.lock().unwrap();
+        if plugin.manifests.iter().any(|name| *name == file_name) {
+            return plugin.discover(file, content);
+        }
+    }
+    Ok(None)
+}
+
+/// Evaluates a `<name>:<argument>` plugin pattern, the part following `plugin:`. Unknown
+/// plugins and failing calls match nothing.
+pub fn matches(pattern: &str, node: &Node, path: &Path) -> bool {
+    let (name, argument) = pattern.split_once(':').unwrap_or((pattern, ""));
+    let Some(plugin) = installed().iter().find(|plugin| plugin.lock().unwrap().name == name) else {
+        log::warn!("No plugin named '{}' for the pattern of {}", name, node.name);
+        return false;
+    };
+    plugin.lock().unwrap().matches(argument, node, path)
+        .inspect_err(|e| log::warn!("{}", e))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Discovers a fixed node in any file, and matches the paths whose last character is `s`.
+    const PLUGIN: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (global $next (mut i32) (i32.const 1024))
+            (data (i32.const 0) "[module]\nname = \"legacy\"\n\n[file_paths]\ninclude = [\"**\"]\n")
+            (func (export "alloc") (param $len i32) (result i32)
+                (local $ptr i32)
+                (local.set $ptr (global.get $next))
+                (global.set $next (i32.add (global.get $next) (local.get $len)))
+                (local.get $ptr))
+            (func (export "discover") (param $ptr i32) (param $len i32) (result i64)
+                (i64.const 56))
+            (func (export "matches") (param $ptr i32) (param $len i32) (result i32)
+                ;; The input ends with `<last char of the path>"}`
+                (i32.eq
+                    (i32.load8_u (i32.sub (i32.add (local.get $ptr) (local.get $len)) (i32.const 3)))
+                    (i32.const 115))))
+    "#;
+
+    #[test]
+    fn test_plugin() {
+        let wasm = wat::parse_str(PLUGIN).unwrap();
+        let mut plugin = Plugin::from_bytes("legacy".to_string(), vec!["BUILD".to_string()], &wasm).unwrap();
+
+        let manifest = plugin.discover(Path::new("svc/BUILD"), "target(...)").unwrap().unwrap();
+        let node = Node::from_toml_str(&manifest, PathBuf::from("svc")).unwrap();
+        assert_eq!(node.name, "legacy");
+
+        assert!(plugin.matches("", &node, Path::new("svc/docs")).unwrap());
+        assert!(!plugin.matches("", &node, Path::new("svc/main.c")).unwrap());
+
+        let missing = Plugin::from_bytes("empty".to_string(), vec![], &wat::parse_str(r#"(module (memory (export "memory") 1))"#).unwrap());
+        assert!(matches!(missing, Err(PluginError::MissingExport(_, "alloc"))));
+    }
+}
@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use sha2::{Digest, Sha256};
+use walkdir::WalkDir;
+
+use super::PlannedNode;
+
+const ARCHIVE_MAGIC: &[u8] = b"DCCACHE1";
+
+/// Where cached task outputs are stored.
+pub trait CacheBackend: Send + Sync {
+    /// Fetches the archive stored under `key`, if any.
+    fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>>;
+    /// Stores an archive under `key`.
+    fn put(&self, key: &str, data: &[u8]) -> io::Result<()>;
+}
+
+/// A directory on the local filesystem (or a mounted network share).
+pub struct LocalCache {
+    dir: PathBuf,
+}
+
+impl CacheBackend for LocalCache {
+    fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(self.dir.join(key)) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn put(&self, key: &str, data: &[u8]) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        // Written next to the final path and renamed, so readers never see partial archives
+        let tmp = self.dir.join(format!(".{}.{}", key, std::process::id()));
+        fs::write(&tmp, data)?;
+        fs::rename(tmp, self.dir.join(key))
+    }
+}
+
+/// An HTTP server accepting `GET` and `PUT` on `<base>/<key>`, through `curl`.
+pub struct HttpCache {
+    base: String,
+}
+
+impl CacheBackend for HttpCache {
+    fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        let output = Command::new("curl")
+            .args(["--silent", "--show-error", "--location", "--write-out", "\n%{http_code}"])
+            .arg(format!("{}/{}", self.base, key))
+            .output()?;
+        if !output.status.success() {
+            return Err(io::Error::other(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+        }
+
+        // The status code is appended after the body on its own line
+        let mut body = output.stdout;
+        let split = body.iter().rposition(|b| *b == b'\n').unwrap_or(0);
+        let code = String::from_utf8_lossy(&body[split..]).trim().to_string();
+        body.truncate(split);
+        match code.as_str() {
+            "200" => Ok(Some(body)),
+            "404" => Ok(None),
+            code => Err(io::Error::other(format!("unexpected HTTP status {}", code))),
+        }
+    }
+
+    fn put(&self, key: &str, data: &[u8]) -> io::Result<()> {
+        let args = ["--silent", "--show-error", "--fail", "--request", "PUT", "--data-binary", "@-"];
+        pipe_to(Command::new("curl").args(args).arg(format!("{}/{}", self.base, key)), data)
+    }
+}
+
+/// An S3 bucket (and optional prefix), through the `aws` CLI and its usual credentials.
+pub struct S3Cache {
+    url: String,
+}
+
+impl CacheBackend for S3Cache {
+    fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        let object = format!("{}/{}", self.url, key);
+        let output = Command::new("aws").args(["s3", "cp", "--quiet", &object, "-"]).output()?;
+        if output.status.success() {
+            return Ok(Some(output.stdout));
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("404") || stderr.contains("Not Found") || stderr.contains("NoSuchKey") {
+            Ok(None)
+        } else {
+            Err(io::Error::other(stderr.trim().to_string()))
+        }
+    }
+
+    fn put(&self, key: &str, data: &[u8]) -> io::Result<()> {
+        let object = format!("{}/{}", self.url, key);
+        pipe_to(Command::new("aws").args(["s3", "cp", "--quiet", "-", &object]), data)
+    }
+}
+
+fn pipe_to(command: &mut Command, data: &[u8]) -> io::Result<()> {
+    let mut child = command.stdin(Stdio::piped()).stdout(Stdio::null()).stderr(Stdio::piped()).spawn()?;
+    child.stdin.take().expect("stdin is piped").write_all(data)?;
+    let output = child.wait_with_output()?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(String::from_utf8_lossy(&output.stderr).trim().to_string()))
+    }
+}
+
+/// Picks a backend out of the `--cache` location: `http(s)://...`, `s3://bucket/prefix`,
+/// or a local directory (optionally as a `file://` URL).
+pub fn backend(location: &str) -> Box<dyn CacheBackend> {
+    let location = location.trim_end_matches('/');
+    if location.starts_with("http://") || location.starts_with("https://") {
+        Box::new(HttpCache { base: location.to_string() })
+    } else if location.starts_with("s3://") {
+        Box::new(S3Cache { url: location.to_string() })
+    } else {
+        let dir = location.strip_prefix("file://").unwrap_or(location);
+        Box::new(LocalCache { dir: PathBuf::from(dir) })
+    }
+}
+
+/// The cache shared by every node of a run.
+pub struct Cache {
+    pub backend: Box<dyn CacheBackend>,
+    /// The content hash of each node, see `commands::hash`.
+    pub node_hashes: HashMap<String, String>,
+}
+
+impl Cache {
+    /// The key of a node's task outputs: changes whenever the node's closure, the task or
+    /// its commands change. `None` when the node doesn't declare outputs.
+    pub fn key(&self, task: &str, node: &PlannedNode) -> Option<String> {
+        if node.outputs.is_empty() {
+            return None;
+        }
+        let mut hasher = Sha256::new();
+        hasher.update(self.node_hashes.get(&node.node)?.as_bytes());
+        hasher.update(b"\0");
+        hasher.update(task.as_bytes());
+        for command in node.commands.iter().chain(&node.outputs) {
+            hasher.update(b"\0");
+            hasher.update(command.as_bytes());
+        }
+        Some(format!("{:x}", hasher.finalize()))
+    }
+
+    /// Restores the node's outputs from the cache. Returns false on a miss.
+    pub fn restore(&self, key: &str, node: &PlannedNode) -> io::Result<bool> {
+        let Some(archive) = self.backend.get(key)? else { return Ok(false) };
+        for (path, content) in unpack(&archive)? {
+            let path = node_dir(&node.path).join(path);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, content)?;
+        }
+        Ok(true)
+    }
+
+    /// Stores the node's outputs in the cache.
+    pub fn save(&self, key: &str, node: &PlannedNode) -> io::Result<()> {
+        let dir = node_dir(&node.path);
+        let mut files = Vec::new();
+        for path in output_files(dir, &node.outputs)? {
+            let content = fs::read(dir.join(&path))?;
+            files.push((path, content));
+        }
+        self.backend.put(key, &pack(&files))
+    }
+}
+
+fn node_dir(path: &Path) -> &Path {
+    if path.as_os_str().is_empty() { Path::new(".") } else { path }
+}
+
+/// The files under `dir` matching any of the output patterns, relative to `dir`.
+fn output_files(dir: &Path, patterns: &[String]) -> io::Result<Vec<PathBuf>> {
+    let patterns: Vec<glob::Pattern> = patterns.iter()
+        .map(|p| glob::Pattern::new(p).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e)))
+        .collect::<Result<_, _>>()?;
+
+    let mut files = Vec::new();
+    for entry in WalkDir::new(dir).sort_by_file_name() {
+        let entry = entry.map_err(io::Error::other)?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let relative = entry.path().strip_prefix(dir).unwrap_or(entry.path()).to_path_buf();
+        if patterns.iter().any(|p| p.matches_path(&relative)) {
+            files.push(relative);
+        }
+    }
+    Ok(files)
+}
+
+/// Serializes files as a sequence of length-prefixed paths and contents.
+fn pack(files: &[(PathBuf, Vec<u8>)]) -> Vec<u8> {
+    let mut out = ARCHIVE_MAGIC.to_vec();
+    for (path, content) in files {
+        let path = path.to_string_lossy().replace('\\', "/");
+        out.extend((path.len() as u64).to_le_bytes());
+        out.extend(path.as_bytes());
+        out.extend((content.len() as u64).to_le_bytes());
+        out.extend(content);
+    }
+    out
+}
+
+fn unpack(archive: &[u8]) -> io::Result<Vec<(PathBuf, Vec<u8>)>> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, format!("invalid cache archive: {}", msg));
+    let mut reader = archive.strip_prefix(ARCHIVE_MAGIC).ok_or_else(|| invalid("bad header"))?;
+
+    let read_chunk = |reader: &mut &[u8]| -> io::Result<Vec<u8>> {
+        let mut len = [0u8; 8];
+        reader.read_exact(&mut len)?;
+        let len = u64::from_le_bytes(len) as usize;
+        if len > reader.len() {
+            return Err(invalid("truncated entry"));
+        }
+        let (chunk, rest) = reader.split_at(len);
+        *reader = rest;
+        Ok(chunk.to_vec())
+    };
+
+    let mut files = Vec::new();
+    while !reader.is_empty() {
+        let path = PathBuf::from(String::from_utf8(read_chunk(&mut reader)?).map_err(|_| invalid("bad path"))?);
+        // Never write outside of the node directory
+        if path.is_absolute() || path.components().any(|c| matches!(c, std::path::Component::ParentDir)) {
+            return Err(invalid("path escapes the node directory"));
+        }
+        files.push((path, read_chunk(&mut reader)?));
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack_roundtrip() {
+        let files = vec![
+            (PathBuf::from("dist/app.js"), b"console.log(1)".to_vec()),
+            (PathBuf::from("dist/empty"), Vec::new()),
+        ];
+        assert_eq!(unpack(&pack(&files)).unwrap(), files);
+        assert!(unpack(b"nope").is_err());
+
+        let escaping = pack(&[(PathBuf::from("../etc/passwd"), Vec::new())]);
+        assert!(unpack(&escaping).is_err());
+    }
+
+    #[test]
+    fn test_key() {
+        let cache = Cache {
+            backend: backend("/tmp/unused"),
+            node_hashes: HashMap::from([("web".to_string(), "abc".to_string())]),
+        };
+        let mut node = PlannedNode {
+            node: "web".to_string(),
+            path: PathBuf::from("web"),
+            commands: vec!["npm run build".to_string()],
+            outputs: vec![],
+            env: Default::default(),
+            waits_on: vec![],
+            timeout: None,
+            weight: 1,
+        };
+        assert_eq!(cache.key("build", &node), None);
+
+        node.outputs = vec!["dist/**".to_string()];
+        let key = cache.key("build", &node).unwrap();
+        assert_ne!(Some(key.clone()), cache.key("test", &node));
+        node.commands.push("npm run bundle".to_string());
+        assert_ne!(Some(key), cache.key("build", &node));
+    }
+}
@@ -0,0 +1,352 @@
+mod cache;
+mod plan;
+mod process;
+mod report;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub use cache::{backend as cache_backend, Cache, CacheBackend};
+pub use plan::{Plan, PlanError, PlannedNode};
+pub use report::{junit, Report};
+
+/// How a node's task ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RunStatus {
+    Success,
+    /// The outputs were restored from the cache instead of running the commands.
+    Cached,
+    /// One of the commands failed, with its exit code when the process wasn't killed by a signal.
+    Failed(Option<i32>),
+    /// The task wasn't run because the named dependency didn't succeed.
+    Skipped(String),
+    /// The task wasn't run because another node failed and the run stopped early.
+    Cancelled,
+    /// The commands were killed after running longer than the task's timeout.
+    TimedOut(Duration),
+}
+
+/// The outcome of a task for a single node.
+#[derive(Debug, Clone)]
+pub struct NodeRun {
+    pub node: String,
+    pub status: RunStatus,
+    pub duration: Duration,
+    /// How many times the commands were run, retries included. Zero for nodes that didn't run
+    /// or were restored from the cache.
+    pub attempts: u32,
+    /// The file the commands' output was written to, when logging to a directory.
+    pub log: Option<PathBuf>,
+}
+
+/// The outcome of `run` for every node that defines the task, in the order they finished.
+#[derive(Debug, Clone)]
+pub struct RunSummary {
+    pub task: String,
+    pub runs: Vec<NodeRun>,
+    /// The wall-clock time of the whole run.
+    pub duration: Duration,
+}
+
+impl RunStatus {
+    /// True for nodes whose outputs are up to date, whether they ran or were restored.
+    pub fn is_success(&self) -> bool {
+        matches!(self, RunStatus::Success | RunStatus::Cached)
+    }
+}
+
+impl RunSummary {
+    /// True if the task succeeded for every node.
+    pub fn success(&self) -> bool {
+        self.runs.iter().all(|run| run.status.is_success())
+    }
+}
+
+/// How `run` reacts to failures, and where it caches outputs.
+#[derive(Default)]
+pub struct RunOptions {
+    /// Keep starting nodes that don't depend on a failed node, instead of stopping at the
+    /// first failure.
+    pub keep_going: bool,
+    /// How many more times a failing node is run before it's considered failed.
+    pub retries: u32,
+    /// Restores the outputs of nodes declaring them instead of running their commands,
+    /// and stores them after a successful run.
+    pub cache: Option<Arc<Cache>>,
+    /// Writes the output of each node to `<log_dir>/<node>.log`.
+    pub log_dir: Option<PathBuf>,
+}
+
+/// Executes a plan.
+///
+/// A node only starts once all of the nodes it waits on have succeeded, and is skipped if
+/// any of them failed. The weights of the running nodes never add up to more than
+/// `plan.jobs`. Unless `options.keep_going` is set, no new node is started after a failure.
+///
+/// ### Arguments
+/// * `plan` - The plan built for the task
+/// * `options` - The failure policy
+///
+/// ### Returns
+/// * `RunSummary` - How the task ended for each node
+pub fn run(plan: &Plan, options: &RunOptions) -> RunSummary {
+    let start = Instant::now();
+    let mut pending: Vec<&PlannedNode> = plan.nodes().collect();
+    let weights: HashMap<&str, usize> = pending.iter().map(|n| (n.node.as_str(), n.weight)).collect();
+    let mut finished: HashMap<String, RunStatus> = HashMap::new();
+    let mut runs = Vec::new();
+    let mut running = 0;
+    let mut stopped = false;
+    let (tx, rx) = mpsc::channel::<NodeRun>();
+
+    loop {
+        // Start (or skip) every pending node whose dependencies are settled, in
+        // topological order so that skips cascade within a single pass.
+        let mut i = 0;
+        while i < pending.len() {
+            let node = pending[i];
+            if let Some(failed) = node.waits_on.iter().find(|dep| finished.get(*dep).is_some_and(|s| !s.is_success())) {
+                let status = RunStatus::Skipped(failed.clone());
+                finished.insert(node.node.clone(), status.clone());
+                runs.push(NodeRun { node: node.node.clone(), status, duration: Duration::ZERO, attempts: 0, log: None });
+                pending.remove(i);
+                continue;
+            }
+            if !stopped && running + node.weight <= plan.jobs && node.waits_on.iter().all(|dep| finished.contains_key(dep)) {
+                spawn_node(node.clone(), &plan.task, options, tx.clone());
+                running += node.weight;
+                pending.remove(i);
+                continue;
+            }
+            i += 1;
+        }
+
+        if running == 0 {
+            break;
+        }
+
+        let Ok(run) = rx.recv() else { break };
+        running -= weights[run.node.as_str()];
+        if !run.status.is_success() && !options.keep_going {
+            stopped = true;
+        }
+        finished.insert(run.node.clone(), run.status.clone());
+        runs.push(run);
+    }
+
+    for node in pending {
+        runs.push(NodeRun { node: node.node.clone(), status: RunStatus::Cancelled, duration: Duration::ZERO, attempts: 0, log: None });
+    }
+    // Nodes waiting on each other through a cycle can never start
+    for node in &plan.blocked {
+        let status = RunStatus::Skipped(node.waits_on.first().cloned().unwrap_or_default());
+        runs.push(NodeRun { node: node.node.clone(), status, duration: Duration::ZERO, attempts: 0, log: None });
+    }
+
+    RunSummary { task: plan.task.clone(), runs, duration: start.elapsed() }
+}
+
+/// Runs the node's commands on a new thread, up to `options.retries` more times if they
+/// fail, and reports the outcome on `tx`. Outputs are restored from the cache when possible.
+fn spawn_node(node: PlannedNode, task: &str, options: &RunOptions, tx: mpsc::Sender<NodeRun>) {
+    let retries = options.retries;
+    let cache = options.cache.clone();
+    let key = cache.as_ref().and_then(|cache| cache.key(task, &node));
+    let log_path = options.log_dir.as_ref().map(|dir| dir.join(log_file_name(&node.node)));
+
+    thread::spawn(move || {
+        let start = Instant::now();
+        if let (Some(cache), Some(key)) = (&cache, &key) {
+            match cache.restore(key, &node) {
+                Ok(true) => {
+                    let _ = tx.send(NodeRun { node: node.node, status: RunStatus::Cached, duration: start.elapsed(), attempts: 0, log: None });
+                    return;
+                }
+                Ok(false) => {}
+                Err(e) => eprintln!("[{}] Failed to read the cache: {}", node.node, e),
+            }
+        }
+
+        let log = match log_path.as_ref().map(File::create).transpose() {
+            Ok(log) => log.map(|file| Arc::new(Mutex::new(file))),
+            Err(e) => {
+                eprintln!("[{}] Failed to create the log file: {}", node.node, e);
+                None
+            }
+        };
+
+        let mut attempts = 0;
+        let status = loop {
+            attempts += 1;
+            let status = run_commands(&node, log.as_ref());
+            if status == RunStatus::Success || attempts > retries {
+                break status;
+            }
+            eprintln!("[{}] {}, retrying ({}/{})", node.node, status, attempts, retries);
+        };
+        if let (Some(cache), Some(key), RunStatus::Success) = (&cache, &key, &status) {
+            if let Err(e) = cache.save(key, &node) {
+                eprintln!("[{}] Failed to write the cache: {}", node.node, e);
+            }
+        }
+        let log = log.and(log_path);
+        let _ = tx.send(NodeRun { node: node.node, status, duration: start.elapsed(), attempts, log });
+    });
+}
+
+/// Runs the node's commands one after the other, stopping at the first failure or once
+/// they've run for longer than the node's timeout.
+fn run_commands(node: &PlannedNode, log: Option<&process::Log>) -> RunStatus {
+    let deadline = node.timeout.map(|timeout| Instant::now() + timeout);
+    for command in &node.commands {
+        match process::run_prefixed(command, &node.path, &node.env, &node.node, deadline, log) {
+            Ok(Some(exit)) if exit.success() => {}
+            Ok(Some(exit)) => return RunStatus::Failed(exit.code()),
+            Ok(None) => return RunStatus::TimedOut(node.timeout.unwrap_or_default()),
+            Err(e) => {
+                eprintln!("[{}] Failed to start `{}`: {}", node.node, command, e);
+                return RunStatus::Failed(None);
+            }
+        }
+    }
+    RunStatus::Success
+}
+
+/// A file name for the node's log, as node names may contain `/` or `@`.
+fn log_file_name(node: &str) -> String {
+    let name: String = node.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' { c } else { '_' })
+        .collect();
+    format!("{}.log", name)
+}
+
+impl fmt::Display for RunStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunStatus::Success => write!(f, "success"),
+            RunStatus::Cached => write!(f, "restored from cache"),
+            RunStatus::Failed(Some(code)) => write!(f, "failed (exit code {})", code),
+            RunStatus::Failed(None) => write!(f, "failed"),
+            RunStatus::Skipped(dep) => write!(f, "skipped ({} didn't succeed)", dep),
+            RunStatus::Cancelled => write!(f, "cancelled (an earlier node failed)"),
+            RunStatus::TimedOut(timeout) => write!(f, "timed out after {}", humantime::format_duration(*timeout)),
+        }
+    }
+}
+
+impl fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for run in &self.runs {
+            match run.attempts {
+                0 if run.status == RunStatus::Cached => writeln!(f, "{}: {} in {:.2?}", run.node, run.status, run.duration)?,
+                0 => writeln!(f, "{}: {}", run.node, run.status)?,
+                1 => writeln!(f, "{}: {} in {:.2?}", run.node, run.status, run.duration)?,
+                n => writeln!(f, "{}: {} in {:.2?} after {} attempts", run.node, run.status, run.duration, n)?,
+            }
+        }
+        let count = |matches: fn(&RunStatus) -> bool| self.runs.iter().filter(|r| matches(&r.status)).count();
+        write!(
+            f,
+            "{}: {} succeeded, {} cached, {} failed, {} skipped, {} cancelled",
+            self.task,
+            count(|s| *s == RunStatus::Success),
+            count(|s| *s == RunStatus::Cached),
+            count(|s| matches!(s, RunStatus::Failed(_) | RunStatus::TimedOut(_))),
+            count(|s| matches!(s, RunStatus::Skipped(_))),
+            count(|s| *s == RunStatus::Cancelled),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::testkit::FixtureDir;
+    use crate::types::{DependencyGraph, Node};
+
+    use super::*;
+
+    fn node(name: &str, deps: Vec<&str>, task: Option<&str>) -> Node {
+        Node::new(
+            name.to_string(),
+            PathBuf::new(),
+            vec![PathBuf::from("src/**")],
+            vec![],
+            deps.into_iter().map(String::from).collect(),
+            task.map(|command| serde_json::json!({ "tasks": { "build": command } })),
+        ).unwrap()
+    }
+
+    fn status<'a>(summary: &'a RunSummary, name: &str) -> &'a RunStatus {
+        &summary.runs.iter().find(|r| r.node == name).unwrap().status
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_skips_dependents_of_failures() {
+        let graph = DependencyGraph::new(vec![
+            node("base", vec![], Some("exit 3")),
+            node("lib", vec![], Some("true")),
+            node("app", vec!["base"], Some("true")),
+        ], false).unwrap();
+        let names = ["app", "lib", "base"].map(String::from);
+
+        let options = RunOptions { keep_going: true, ..Default::default() };
+        let summary = run(&Plan::new(&graph, "build", &names, 2).unwrap(), &options);
+        assert!(!summary.success());
+        assert_eq!(status(&summary, "base"), &RunStatus::Failed(Some(3)));
+        assert_eq!(status(&summary, "lib"), &RunStatus::Success);
+        assert_eq!(status(&summary, "app"), &RunStatus::Skipped("base".to_string()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_stops_at_first_failure() {
+        let graph = DependencyGraph::new(vec![
+            node("base", vec![], Some("exit 1")),
+            node("lib", vec!["base"], Some("true")),
+            node("other", vec![], Some("true")),
+        ], false).unwrap();
+        let names = ["base", "lib", "other"].map(String::from);
+
+        // With a single job, `other` comes after `base` and is never started
+        let summary = run(&Plan::new(&graph, "build", &names, 1).unwrap(), &RunOptions::default());
+        assert_eq!(status(&summary, "lib"), &RunStatus::Skipped("base".to_string()));
+        assert_eq!(status(&summary, "other"), &RunStatus::Cancelled);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_retries() {
+        let dir = FixtureDir::new();
+        let marker = dir.join("attempted");
+
+        // Fails the first time, succeeds once the marker exists
+        let command = format!("test -f {0} || (touch {0}; exit 1)", marker.display());
+        let graph = DependencyGraph::new(vec![node("flaky", vec![], Some(&command))], false).unwrap();
+        let names = ["flaky".to_string()];
+
+        let summary = run(&Plan::new(&graph, "build", &names, 1).unwrap(), &RunOptions { retries: 2, ..Default::default() });
+        assert_eq!(status(&summary, "flaky"), &RunStatus::Success);
+        assert_eq!(summary.runs[0].attempts, 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_timeout() {
+        let mut slow = node("slow", vec![], None);
+        slow.metadata = Some(serde_json::json!({ "tasks": { "build": { "run": "sleep 5", "timeout": 0.2 } } }));
+        let graph = DependencyGraph::new(vec![slow], false).unwrap();
+
+        let start = Instant::now();
+        let summary = run(&Plan::new(&graph, "build", &["slow".to_string()], 1).unwrap(), &RunOptions::default());
+        assert_eq!(status(&summary, "slow"), &RunStatus::TimedOut(Duration::from_millis(200)));
+        assert!(start.elapsed() < Duration::from_secs(4));
+    }
+}
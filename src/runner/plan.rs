@@ -0,0 +1,343 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::{Serialize, Serializer};
+
+use crate::types::{AffectedReason, DependencyGraph, Node};
+
+/// A node scheduled by the runner.
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedNode {
+    pub node: String,
+    /// The directory the commands run from.
+    pub path: PathBuf,
+    /// The commands run one after the other.
+    pub commands: Vec<String>,
+    /// The output patterns stored in and restored from the cache.
+    pub outputs: Vec<String>,
+    /// The variables added to the environment of the commands: the node's `metadata.env`
+    /// plus `NODE_NAME`, `NODE_PATH` and `AFFECTED_REASON`.
+    pub env: BTreeMap<String, String>,
+    /// The nodes of the plan (direct or transitive dependencies) that must succeed first.
+    pub waits_on: Vec<String>,
+    /// How long the commands may run, per attempt, before they're killed.
+    #[serde(serialize_with = "serialize_timeout")]
+    pub timeout: Option<Duration>,
+    /// How many of the `jobs` slots the node takes while it runs.
+    pub weight: usize,
+}
+
+/// A task setting that couldn't be understood.
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid `{setting}` for task `{task}` of node '{node}': {message}")]
+pub struct PlanError {
+    node: String,
+    task: String,
+    setting: &'static str,
+    message: String,
+}
+
+/// Everything `run` is going to execute, grouped in layers: a node's layer comes after
+/// the layers of everything it waits on, so each layer can run fully in parallel.
+#[derive(Debug, Clone, Serialize)]
+pub struct Plan {
+    pub task: String,
+    pub jobs: usize,
+    pub layers: Vec<Vec<PlannedNode>>,
+    /// Nodes waiting on each other through a cycle. They're never run.
+    pub blocked: Vec<PlannedNode>,
+    /// The most nodes that can run at the same time, given `jobs` and the widest layer.
+    pub max_parallelism: usize,
+}
+
+impl Plan {
+    /// Plans `task` for the nodes among `names` that define it in `metadata.tasks`.
+    ///
+    /// ### Arguments
+    /// * `graph` - The dependency graph artifact
+    /// * `task` - The key looked up in each node's `metadata.tasks`
+    /// * `names` - The nodes to consider, usually the affected ones
+    /// * `jobs` - The maximum number of nodes running concurrently
+    pub fn new(graph: &DependencyGraph, task: &str, names: &[String], jobs: usize) -> Result<Self, PlanError> {
+        Self::build(graph, task, names, jobs, |node| (node.task_commands(task), node.task_outputs(task)))
+    }
+
+    /// Plans an ad-hoc shell command, run in every node among `names`.
+    pub fn for_command(graph: &DependencyGraph, command: &str, names: &[String], jobs: usize) -> Result<Self, PlanError> {
+        Self::build(graph, command, names, jobs, |_| (vec![command.to_string()], Vec::new()))
+    }
+
+    /// Records why each node is part of the run in its `AFFECTED_REASON` variable. Nodes
+    /// missing from `reasons` keep `requested`.
+    pub fn set_affected_reasons(&mut self, reasons: &BTreeMap<String, AffectedReason>) {
+        for node in self.layers.iter_mut().flatten().chain(self.blocked.iter_mut()) {
+            if let Some(reason) = reasons.get(&node.node) {
+                node.env.insert("AFFECTED_REASON".to_string(), reason.to_string());
+            }
+        }
+    }
+
+    /// Plans the nodes for which `commands_of` returns at least one command, along with the
+    /// output patterns to cache.
+    fn build(
+        graph: &DependencyGraph,
+        task: &str,
+        names: &[String],
+        jobs: usize,
+        commands_of: impl Fn(&Node) -> (Vec<String>, Vec<String>),
+    ) -> Result<Self, PlanError> {
+        let jobs = jobs.max(1);
+        let nodes: Vec<PlannedNode> = graph.toposorted_subset(names)
+            .into_iter()
+            .filter_map(|node| {
+                let (commands, outputs) = commands_of(node);
+                if commands.is_empty() {
+                    return None;
+                }
+                let (timeout, weight) = match task_settings(node, task) {
+                    Ok(settings) => settings,
+                    Err(e) => return Some(Err(e)),
+                };
+                let mut env = node.env();
+                env.insert("NODE_NAME".to_string(), node.name.clone());
+                env.insert("NODE_PATH".to_string(), node.path.to_string_lossy().to_string());
+                env.insert("AFFECTED_REASON".to_string(), "requested".to_string());
+                Some(Ok(PlannedNode {
+                    node: node.name.clone(),
+                    path: node.path.clone(),
+                    commands,
+                    outputs,
+                    env,
+                    waits_on: Vec::new(),
+                    timeout,
+                    // Heavier than the whole pool would never start, so it takes the whole pool
+                    weight: weight.min(jobs),
+                }))
+            })
+            .collect::<Result<_, _>>()?;
+
+        let in_plan: HashSet<String> = nodes.iter().map(|n| n.node.clone()).collect();
+        let mut depth: HashMap<String, usize> = HashMap::new();
+        let mut layers: Vec<Vec<PlannedNode>> = Vec::new();
+        let mut blocked = Vec::new();
+
+        // Nodes come dependencies first, so every settled dependency already has a depth
+        for mut node in nodes {
+            let mut waits_on: Vec<String> = graph.get_dependencies(&node.node)
+                .into_iter()
+                .map(|dep| dep.name)
+                .filter(|dep| in_plan.contains(dep))
+                .collect();
+            waits_on.sort();
+            node.waits_on = waits_on;
+
+            if node.waits_on.iter().any(|dep| !depth.contains_key(dep)) {
+                blocked.push(node);
+                continue;
+            }
+            let layer = node.waits_on.iter().map(|dep| depth[dep] + 1).max().unwrap_or(0);
+            depth.insert(node.node.clone(), layer);
+            if layers.len() <= layer {
+                layers.resize_with(layer + 1, Vec::new);
+            }
+            layers[layer].push(node);
+        }
+
+        // The lightest nodes of a layer fill the pool first
+        let max_parallelism = layers.iter()
+            .map(|layer| {
+                let mut weights: Vec<usize> = layer.iter().map(|n| n.weight).collect();
+                weights.sort();
+                let mut used = 0;
+                weights.into_iter().take_while(|w| { used += w; used <= jobs }).count()
+            })
+            .max()
+            .unwrap_or(0);
+        Ok(Self { task: task.to_string(), jobs, layers, blocked, max_parallelism })
+    }
+
+    /// Every runnable node, layer by layer.
+    pub fn nodes(&self) -> impl Iterator<Item = &PlannedNode> {
+        self.layers.iter().flatten()
+    }
+}
+
+/// Reads the optional `timeout` (seconds, or a duration such as `"10m"`) and `weight`
+/// (defaults to 1) of a task defined as a table.
+fn task_settings(node: &Node, task: &str) -> Result<(Option<Duration>, usize), PlanError> {
+    let invalid = |setting, message: String| PlanError {
+        node: node.name.clone(),
+        task: task.to_string(),
+        setting,
+        message,
+    };
+    let Some(serde_json::Value::Object(table)) = node.task(task) else { return Ok((None, 1)) };
+
+    let timeout = match table.get("timeout") {
+        None => None,
+        Some(serde_json::Value::Number(secs)) => match secs.as_f64() {
+            Some(secs) if secs > 0.0 => Some(Duration::from_secs_f64(secs)),
+            _ => return Err(invalid("timeout", "must be a positive number of seconds".to_string())),
+        },
+        Some(serde_json::Value::String(duration)) => {
+            Some(humantime::parse_duration(duration).map_err(|e| invalid("timeout", e.to_string()))?)
+        }
+        Some(_) => return Err(invalid("timeout", "must be a number of seconds or a duration".to_string())),
+    };
+    let weight = match table.get("weight") {
+        None => 1,
+        Some(weight) => match weight.as_u64() {
+            Some(weight) if weight > 0 => weight as usize,
+            _ => return Err(invalid("weight", "must be a positive integer".to_string())),
+        },
+    };
+
+    Ok((timeout, weight))
+}
+
+fn serialize_timeout<S: Serializer>(timeout: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> {
+    match timeout {
+        Some(timeout) => serializer.serialize_str(&humantime::format_duration(*timeout).to_string()),
+        None => serializer.serialize_none(),
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Plan for `{}`: {} nodes in {} layers, up to {} in parallel (--jobs {})",
+            self.task, self.nodes().count(), self.layers.len(), self.max_parallelism, self.jobs
+        )?;
+        for (i, layer) in self.layers.iter().enumerate() {
+            writeln!(f, "\nLayer {}", i + 1)?;
+            for node in layer {
+                write!(f, "  {} ({})", node.node, node.path.display())?;
+                if !node.waits_on.is_empty() {
+                    write!(f, " after {}", node.waits_on.join(", "))?;
+                }
+                if node.weight > 1 {
+                    write!(f, ", weight {}", node.weight)?;
+                }
+                if let Some(timeout) = node.timeout {
+                    write!(f, ", timeout {}", humantime::format_duration(timeout))?;
+                }
+                writeln!(f)?;
+                for command in &node.commands {
+                    writeln!(f, "    $ {}", command)?;
+                }
+            }
+        }
+        if !self.blocked.is_empty() {
+            let names: Vec<&str> = self.blocked.iter().map(|n| n.node.as_str()).collect();
+            writeln!(f, "\nBlocked by a cycle: {}", names.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(name: &str, deps: Vec<&str>, task: Option<&str>) -> Node {
+        Node::new(
+            name.to_string(),
+            PathBuf::from(name),
+            vec![PathBuf::from("src/**")],
+            vec![],
+            deps.into_iter().map(String::from).collect(),
+            task.map(|command| serde_json::json!({ "tasks": { "build": command } })),
+        ).unwrap()
+    }
+
+    #[test]
+    fn test_plan_layers() {
+        let graph = DependencyGraph::new(vec![
+            node("app", vec!["lib", "other"], Some("make app")),
+            node("lib", vec!["base"], None),
+            node("base", vec![], Some("make base")),
+            node("other", vec![], Some("make other")),
+            node("docs", vec![], None),
+        ], false).unwrap();
+        let names: Vec<String> = ["app", "lib", "base", "other", "docs"].map(String::from).to_vec();
+
+        let plan = Plan::new(&graph, "build", &names, 8).unwrap();
+        let layers: Vec<Vec<&str>> = plan.layers.iter()
+            .map(|layer| layer.iter().map(|n| n.node.as_str()).collect())
+            .collect();
+        assert_eq!(layers, vec![vec!["base", "other"], vec!["app"]]);
+        assert_eq!(plan.layers[1][0].waits_on, vec!["base", "other"]);
+        assert_eq!(plan.max_parallelism, 2);
+        assert_eq!(Plan::new(&graph, "build", &names, 1).unwrap().max_parallelism, 1);
+    }
+
+    #[test]
+    fn test_plan_blocked_by_cycle() {
+        let graph = DependencyGraph::new(vec![
+            node("a", vec!["b"], Some("make a")),
+            node("b", vec!["a"], Some("make b")),
+            node("c", vec![], Some("make c")),
+        ], true).unwrap();
+        let names: Vec<String> = ["a", "b", "c"].map(String::from).to_vec();
+
+        let plan = Plan::new(&graph, "build", &names, 4).unwrap();
+        assert_eq!(plan.nodes().count(), 1);
+        assert_eq!(plan.blocked.len(), 2);
+    }
+
+    #[test]
+    fn test_plan_env() {
+        let mut with_env = node("app", vec![], Some("make app"));
+        with_env.metadata = Some(serde_json::json!({
+            "tasks": { "build": "make app" },
+            "env": { "SERVICE_NAME": "app-svc", "PORT": 8080 },
+        }));
+        let graph = DependencyGraph::new(vec![with_env], false).unwrap();
+        let names = vec!["app".to_string()];
+
+        let mut plan = Plan::new(&graph, "build", &names, 1).unwrap();
+        let env = &plan.layers[0][0].env;
+        assert_eq!(env["SERVICE_NAME"], "app-svc");
+        assert_eq!(env["PORT"], "8080");
+        assert_eq!(env["NODE_NAME"], "app");
+        assert_eq!(env["NODE_PATH"], "app");
+        assert_eq!(env["AFFECTED_REASON"], "requested");
+
+        let reasons = BTreeMap::from([("app".to_string(), AffectedReason::Dependency { via: "lib".to_string() })]);
+        plan.set_affected_reasons(&reasons);
+        assert_eq!(plan.layers[0][0].env["AFFECTED_REASON"], "dependency:lib");
+    }
+
+    #[test]
+    fn test_plan_task_settings() {
+        let mut heavy = node("heavy", vec![], None);
+        heavy.metadata = Some(serde_json::json!({ "tasks": { "build": { "run": "make", "timeout": "90s", "weight": 3 } } }));
+        let mut light = node("light", vec![], None);
+        light.metadata = Some(serde_json::json!({ "tasks": { "build": { "run": "make", "timeout": 5 } } }));
+        let graph = DependencyGraph::new(vec![heavy, light, node("other", vec![], Some("make"))], false).unwrap();
+        let names: Vec<String> = ["heavy", "light", "other"].map(String::from).to_vec();
+
+        let plan = Plan::new(&graph, "build", &names, 4).unwrap();
+        let heavy = plan.nodes().find(|n| n.node == "heavy").unwrap();
+        assert_eq!(heavy.timeout, Some(Duration::from_secs(90)));
+        assert_eq!(heavy.weight, 3);
+        assert_eq!(plan.nodes().find(|n| n.node == "light").unwrap().timeout, Some(Duration::from_secs(5)));
+        // light and other fit next to each other, heavy doesn't fit with both
+        assert_eq!(plan.max_parallelism, 2);
+        // Weights are capped to the pool
+        assert_eq!(Plan::new(&graph, "build", &names, 2).unwrap().nodes().find(|n| n.node == "heavy").unwrap().weight, 2);
+    }
+
+    #[test]
+    fn test_plan_invalid_timeout() {
+        let mut bad = node("bad", vec![], None);
+        bad.metadata = Some(serde_json::json!({ "tasks": { "build": { "run": "make", "timeout": "soon" } } }));
+        let graph = DependencyGraph::new(vec![bad], false).unwrap();
+
+        let err = Plan::new(&graph, "build", &["bad".to_string()], 1).unwrap_err();
+        assert!(err.to_string().contains("Invalid `timeout` for task `build` of node 'bad'"));
+    }
+}
@@ -0,0 +1,107 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A node's log file, shared by the stdout and stderr forwarders.
+pub type Log = Arc<Mutex<File>>;
+
+/// Runs a shell command inside `dir` with `env` added to its environment, streaming every
+/// line of its output prefixed with `[prefix]` so the output of concurrent nodes stays readable.
+/// The output is also copied, without prefix, to `log`.
+///
+/// ### Returns
+/// The exit status, or `None` if the command was still running at `deadline` and was killed.
+pub fn run_prefixed(
+    command: &str,
+    dir: &Path,
+    env: &BTreeMap<String, String>,
+    prefix: &str,
+    deadline: Option<Instant>,
+    log: Option<&Log>,
+) -> io::Result<Option<ExitStatus>> {
+    let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+    let mut cmd = shell(command);
+    cmd.current_dir(dir)
+        .envs(env)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    // In its own process group, so that a timeout also kills whatever the shell started
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut cmd, 0);
+    let mut child = cmd.spawn()?;
+
+    let stdout = child.stdout.take().map(|out| forward(out, prefix.to_string(), false, log.cloned()));
+    let stderr = child.stderr.take().map(|err| forward(err, prefix.to_string(), true, log.cloned()));
+    let status = match deadline {
+        Some(deadline) => wait_until(&mut child, deadline)?,
+        None => Some(child.wait()?),
+    };
+
+    for forwarder in [stdout, stderr].into_iter().flatten() {
+        let _ = forwarder.join();
+    }
+    Ok(status)
+}
+
+/// Waits for the child to exit, killing it (and its process group) once `deadline` passes.
+fn wait_until(child: &mut Child, deadline: Instant) -> io::Result<Option<ExitStatus>> {
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            kill(child);
+            child.wait()?;
+            return Ok(None);
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+fn kill(child: &mut Child) {
+    #[cfg(unix)]
+    {
+        let group = format!("-{}", child.id());
+        let killed = Command::new("kill").args(["-KILL", "--", &group]).status();
+        if killed.is_ok_and(|status| status.success()) {
+            return;
+        }
+    }
+    let _ = child.kill();
+}
+
+fn shell(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    }
+}
+
+/// Copies `source` line by line to our stdout (or stderr), prefixing each line, and to the log.
+fn forward(source: impl Read + Send + 'static, prefix: String, to_stderr: bool, log: Option<Log>) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        for line in BufReader::new(source).lines().map_while(Result::ok) {
+            if let Some(log) = &log {
+                let _ = writeln!(log.lock().unwrap_or_else(|e| e.into_inner()), "{}", line);
+            }
+            // Each line is written with a single call so lines of different nodes don't interleave
+            let line = format!("[{}] {}\n", prefix, line);
+            let _ = if to_stderr {
+                io::stderr().lock().write_all(line.as_bytes())
+            } else {
+                io::stdout().lock().write_all(line.as_bytes())
+            };
+        }
+    })
+}
@@ -0,0 +1,170 @@
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use super::{NodeRun, RunStatus, RunSummary};
+
+/// A machine-readable summary of a run, for CI dashboards.
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub task: String,
+    pub success: bool,
+    pub duration_ms: u128,
+    pub nodes: Vec<NodeReport>,
+}
+
+/// How the task ended for a single node.
+#[derive(Debug, Serialize)]
+pub struct NodeReport {
+    pub node: String,
+    /// One of `success`, `cached`, `failed`, `timed_out`, `skipped` or `cancelled`.
+    pub status: &'static str,
+    pub duration_ms: u128,
+    /// The exit code of the failing command, when it exited on its own.
+    pub exit_code: Option<i32>,
+    pub cache_hit: bool,
+    pub attempts: u32,
+    /// The dependency that didn't succeed, for skipped nodes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skipped_because: Option<String>,
+    pub log: Option<PathBuf>,
+}
+
+impl From<&RunSummary> for Report {
+    fn from(summary: &RunSummary) -> Self {
+        Report {
+            task: summary.task.clone(),
+            success: summary.success(),
+            duration_ms: summary.duration.as_millis(),
+            nodes: summary.runs.iter().map(NodeReport::from).collect(),
+        }
+    }
+}
+
+impl From<&NodeRun> for NodeReport {
+    fn from(run: &NodeRun) -> Self {
+        NodeReport {
+            node: run.node.clone(),
+            status: status_name(&run.status),
+            duration_ms: run.duration.as_millis(),
+            exit_code: match run.status {
+                RunStatus::Success => Some(0),
+                RunStatus::Failed(code) => code,
+                _ => None,
+            },
+            cache_hit: run.status == RunStatus::Cached,
+            attempts: run.attempts,
+            skipped_because: match &run.status {
+                RunStatus::Skipped(dep) => Some(dep.clone()),
+                _ => None,
+            },
+            log: run.log.clone(),
+        }
+    }
+}
+
+fn status_name(status: &RunStatus) -> &'static str {
+    match status {
+        RunStatus::Success => "success",
+        RunStatus::Cached => "cached",
+        RunStatus::Failed(_) => "failed",
+        RunStatus::TimedOut(_) => "timed_out",
+        RunStatus::Skipped(_) => "skipped",
+        RunStatus::Cancelled => "cancelled",
+    }
+}
+
+/// Renders the run as a JUnit XML test suite, one test case per node. Failed and timed out
+/// nodes are failures, skipped and cancelled nodes are skipped.
+pub fn junit(summary: &RunSummary) -> String {
+    let failures = summary.runs.iter()
+        .filter(|run| matches!(run.status, RunStatus::Failed(_) | RunStatus::TimedOut(_)))
+        .count();
+    let skipped = summary.runs.iter()
+        .filter(|run| matches!(run.status, RunStatus::Skipped(_) | RunStatus::Cancelled))
+        .count();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"0\" skipped=\"{}\" time=\"{:.3}\">\n",
+        escape(&summary.task), summary.runs.len(), failures, skipped, summary.duration.as_secs_f64(),
+    ));
+    for run in &summary.runs {
+        let open = format!(
+            "  <testcase classname=\"{}\" name=\"{}\" time=\"{:.3}\"",
+            escape(&summary.task), escape(&run.node), run.duration.as_secs_f64(),
+        );
+        let message = escape(&run.status.to_string());
+        match &run.status {
+            RunStatus::Success | RunStatus::Cached => xml.push_str(&format!("{}/>\n", open)),
+            RunStatus::Failed(_) | RunStatus::TimedOut(_) => {
+                xml.push_str(&format!("{}>\n    <failure message=\"{}\"/>\n", open, message));
+                if let Some(log) = &run.log {
+                    xml.push_str(&format!("    <system-out>{}</system-out>\n", escape(&log.display().to_string())));
+                }
+                xml.push_str("  </testcase>\n");
+            }
+            RunStatus::Skipped(_) | RunStatus::Cancelled => {
+                xml.push_str(&format!("{}>\n    <skipped message=\"{}\"/>\n  </testcase>\n", open, message));
+            }
+        }
+    }
+    xml.push_str("</testsuite>\n");
+    xml
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn summary() -> RunSummary {
+        let run = |node: &str, status, attempts| NodeRun {
+            node: node.to_string(),
+            status,
+            duration: Duration::from_millis(1500),
+            attempts,
+            log: None,
+        };
+        RunSummary {
+            task: "build".to_string(),
+            runs: vec![
+                run("lib", RunStatus::Cached, 0),
+                run("base", RunStatus::Failed(Some(3)), 2),
+                run("app<web>", RunStatus::Skipped("base".to_string()), 0),
+            ],
+            duration: Duration::from_secs(3),
+        }
+    }
+
+    #[test]
+    fn test_report() {
+        let report = Report::from(&summary());
+        assert!(!report.success);
+        assert_eq!(report.duration_ms, 3000);
+
+        let base = &report.nodes[1];
+        assert_eq!((base.status, base.exit_code, base.attempts), ("failed", Some(3), 2));
+        assert!(report.nodes[0].cache_hit);
+        assert_eq!(report.nodes[2].skipped_because.as_deref(), Some("base"));
+    }
+
+    #[test]
+    fn test_junit() {
+        let xml = junit(&summary());
+        assert!(xml.contains("tests=\"3\" failures=\"1\" errors=\"0\" skipped=\"1\" time=\"3.000\""));
+        assert!(xml.contains("<testcase classname=\"build\" name=\"lib\" time=\"1.500\"/>"));
+        assert!(xml.contains("<failure message=\"failed (exit code 3)\"/>"));
+        assert!(xml.contains("name=\"app&lt;web&gt;\""));
+        assert!(xml.contains("<skipped message=\"skipped (base didn&apos;t succeed)\"/>"));
+    }
+}
@@ -0,0 +1,232 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use serde::Serialize;
+use tempfile::TempDir;
+
+/// How the generated nodes depend on each other. Node `i` only ever depends on nodes before
+/// it, so every shape is acyclic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum FixtureShape {
+    /// No dependencies.
+    Independent,
+    /// Every node depends on the previous one.
+    Chain,
+    /// Every node depends on the first one.
+    Star,
+    /// A binary tree: every node depends on its parent.
+    Tree,
+    /// Layers of `width` nodes, each depending on up to `fan_out` nodes of the previous layer.
+    Layered,
+}
+
+/// What to generate.
+#[derive(Debug, Clone)]
+pub struct FixtureSpec {
+    pub nodes: usize,
+    pub shape: FixtureShape,
+    /// The number of nodes per layer of the `layered` shape.
+    pub width: usize,
+    /// The most dependencies a node of the `layered` shape has.
+    pub fan_out: usize,
+    /// The number of source files per node.
+    pub files_per_node: usize,
+    /// How many directories deep the source files are nested.
+    pub depth: usize,
+    /// Seeds the choice of dependencies of the `layered` shape.
+    pub seed: u64,
+    /// The name of the manifests. Defaults to `dependencies.toml`.
+    pub dependency_toml_name: Option<String>,
+}
+
+impl Default for FixtureSpec {
+    fn default() -> Self {
+        FixtureSpec {
+            nodes: 10,
+            shape: FixtureShape::Layered,
+            width: 4,
+            fan_out: 2,
+            files_per_node: 2,
+            depth: 1,
+            seed: 1,
+            dependency_toml_name: None,
+        }
+    }
+}
+
+/// A generated repository.
+#[derive(Debug, Clone, Serialize)]
+pub struct Fixture {
+    pub nodes: Vec<FixtureNode>,
+    /// The number of dependencies declared.
+    pub edges: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FixtureNode {
+    pub name: String,
+    /// The directory of the node, relative to the repository.
+    pub path: PathBuf,
+    pub dependencies: Vec<String>,
+    /// The generated source files, relative to the repository.
+    pub files: Vec<PathBuf>,
+}
+
+/// Writes the nodes described by `spec` under `dir`: `nodes/node-<i>/` each holding a
+/// manifest including `src/**` and its source files.
+///
+/// ### Arguments
+/// * `dir` - The repository to write, created if needed
+/// * `spec` - What to generate
+///
+/// ### Returns
+/// * `Fixture` - The generated nodes, by index
+pub fn generate_fixture(dir: &Path, spec: &FixtureSpec) -> io::Result<Fixture> {
+    let manifest_name = spec.dependency_toml_name.as_deref().unwrap_or("dependencies.toml");
+    let name = |i: usize| format!("node-{}", i);
+    let mut rng = spec.seed;
+
+    let mut nodes = Vec::new();
+    for i in 0..spec.nodes {
+        let mut dependencies: Vec<usize> = match spec.shape {
+            FixtureShape::Independent => vec![],
+            FixtureShape::Chain => (i > 0).then(|| i - 1).into_iter().collect(),
+            FixtureShape::Star => (i > 0).then_some(0).into_iter().collect(),
+            FixtureShape::Tree => (i > 0).then(|| (i - 1) / 2).into_iter().collect(),
+            FixtureShape::Layered => {
+                let width = spec.width.max(1);
+                let layer = i / width;
+                let previous = layer.saturating_sub(1) * width..layer * width;
+                (0..spec.fan_out.min(previous.len()))
+                    .map(|_| previous.start + (next(&mut rng) as usize) % previous.len())
+                    .collect()
+            }
+        };
+        dependencies.sort();
+        dependencies.dedup();
+
+        let path = PathBuf::from("nodes").join(name(i));
+        let mut source_dir = path.join("src");
+        for level in 0..spec.depth {
+            source_dir.push(format!("level-{}", level));
+        }
+        fs::create_dir_all(dir.join(&source_dir))?;
+        let files: Vec<PathBuf> = (0..spec.files_per_node).map(|f| source_dir.join(format!("file-{}.txt", f))).collect();
+        for file in &files {
+            fs::write(dir.join(file), format!("{}\n", file.display()))?;
+        }
+
+        let dependencies: Vec<String> = dependencies.into_iter().map(name).collect();
+        let declared: String = dependencies.iter().map(|dep| format!("\"{0}\" = {{ name = \"{0}\" }}\n", dep)).collect();
+        fs::write(
+            dir.join(&path).join(manifest_name),
+            format!("[module]\nname = \"{}\"\n\n[dependencies]\n{}\n[file_paths]\ninclude = [\"src/**\"]\n", name(i), declared),
+        )?;
+        nodes.push(FixtureNode { name: name(i), path, dependencies, files });
+    }
+
+    let edges = nodes.iter().map(|node| node.dependencies.len()).sum();
+    Ok(Fixture { nodes, edges })
+}
+
+/// A directory of files for a test, unique to it and deleted when dropped. The builder
+/// methods panic when a file can't be written, like the tests they serve.
+///
+/// ```
+/// use dependency_cascade::testkit::FixtureDir;
+///
+/// let dir = FixtureDir::new()
+///     .manifest("api", "[module]\nname = \"api\"\n\n[file_paths]\ninclude = [\"**\"]\n")
+///     .file("api/src/main.rs", "fn main() {}\n");
+/// assert!(dir.join("api/dependencies.toml").exists());
+/// ```
+#[derive(Debug)]
+pub struct FixtureDir(TempDir);
+
+impl FixtureDir {
+    /// Creates an empty directory under the temporary directory.
+    pub fn new() -> Self {
+        FixtureDir(tempfile::Builder::new().prefix("dc-").tempdir().expect("Unable to create a fixture directory"))
+    }
+
+    /// The directory.
+    pub fn path(&self) -> &Path {
+        self.0.path()
+    }
+
+    /// A path inside the directory.
+    pub fn join(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.0.path().join(path)
+    }
+
+    /// Adds a file, creating its parent directories.
+    pub fn file(self, path: impl AsRef<Path>, content: impl AsRef<[u8]>) -> Self {
+        self.write(path, content);
+        self
+    }
+
+    /// Adds an empty directory, with its parents.
+    pub fn dir(self, path: impl AsRef<Path>) -> Self {
+        fs::create_dir_all(self.join(path)).expect("Unable to create a fixture directory");
+        self
+    }
+
+    /// Adds the `dependencies.toml` of the node in `node_dir`.
+    pub fn manifest(self, node_dir: impl AsRef<Path>, content: &str) -> Self {
+        self.file(node_dir.as_ref().join("dependencies.toml"), content)
+    }
+
+    /// Writes a file, creating its parent directories, e.g. to change it during a test.
+    pub fn write(&self, path: impl AsRef<Path>, content: impl AsRef<[u8]>) {
+        let path = self.join(path);
+        fs::create_dir_all(path.parent().unwrap()).expect("Unable to create a fixture directory");
+        fs::write(&path, content).unwrap_or_else(|e| panic!("Unable to write {}: {}", path.display(), e));
+    }
+
+    /// Writes the repository described by `spec` in the directory, see [`generate_fixture`].
+    pub fn generate(&self, spec: &FixtureSpec) -> Fixture {
+        generate_fixture(self.path(), spec).expect("Unable to generate the fixture")
+    }
+}
+
+impl Default for FixtureDir {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A xorshift step, enough to spread dependencies reproducibly.
+fn next(state: &mut u64) -> u64 {
+    let mut x = (*state).max(1);
+    x ^= x << 13;
+    x ^= x >> 7;
+    x ^= x << 17;
+    *state = x;
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::{self, Scan};
+
+    use super::*;
+
+    #[test]
+    fn test_generate_fixture() {
+        let dir = FixtureDir::new();
+        let spec = FixtureSpec { nodes: 12, depth: 2, ..FixtureSpec::default() };
+        let fixture = dir.generate(&spec);
+
+        // The first layer depends on nothing, the others only on the layer before
+        assert!(fixture.nodes[..4].iter().all(|node| node.dependencies.is_empty()));
+        assert!(fixture.nodes[4..].iter().all(|node| !node.dependencies.is_empty() && node.dependencies.len() <= 2));
+        assert_eq!(fixture.nodes[5].files[0], PathBuf::from("nodes/node-5/src/level-0/level-1/file-0.txt"));
+        assert_eq!(dir.generate(&spec).edges, fixture.edges);
+
+        let graph = commands::prepare(&Scan::new(dir.path().to_path_buf(), None), false, None).unwrap();
+        assert_eq!(graph.get_all_nodes().len(), 12);
+        assert_eq!(graph.edges().len(), fixture.edges);
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// The version of the artifact layout written by this build.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The artifact features this build reads, see `ArtifactInfo::features`.
+const KNOWN_FEATURES: &[&str] = &["tags", "cascade_exclude", "negated_patterns", "hide_dotfiles", "trigger_only", "metadata_sidecar", "edge_data", "manifest_hashes", "file_index", "external_dependencies"];
+
+#[derive(Debug, thiserror::Error)]
+pub enum CompatibilityError {
+    #[error("The artifact was written by {writer} and relies on features this \
+             version ({current}) doesn't support: {features}. Upgrade dependency-cascade, or re-run \
+             `prepare` with this version.")]
+    UnsupportedFeatures { writer: String, current: String, features: String },
+    #[error("The artifact was written by {writer} with schema version {schema_version}, \
+             newer than the schema version {supported} of this version ({current}). Upgrade dependency-cascade, \
+             or re-run `prepare` with this version.")]
+    NewerSchema { writer: String, schema_version: u32, current: String, supported: u32 },
+    #[error("The artifact was written by {writer}, whose layout this version ({current}) \
+             can't read: {error}. Re-run `prepare` with this version.")]
+    Unreadable { writer: String, current: String, error: String },
+}
+
+/// Describes how and from what an artifact was produced. Artifacts written before this
+/// header existed load with the default, schema version 0.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArtifactInfo {
+    pub schema_version: u32,
+    /// The `dependency-cascade` version that wrote the artifact.
+    pub tool_version: String,
+    /// When `prepare` ran, in RFC 3339.
+    pub created_at: Option<String>,
+    /// The commit checked out in the prepared directory, if it's a git repository.
+    pub source_commit: Option<String>,
+    /// The name other repositories' manifests refer to this one by, from `prepare --repo`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repo: Option<String>,
+    /// The features the artifact relies on: a reader that doesn't know one of them would
+    /// answer queries differently, so it must refuse the artifact.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<String>,
+    /// The features a reader can ignore and still answer queries the same way.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub optional_features: Vec<String>,
+}
+
+impl ArtifactInfo {
+    /// The header of an artifact produced by this build, without provenance.
+    pub fn current() -> Self {
+        ArtifactInfo {
+            schema_version: SCHEMA_VERSION,
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            created_at: None,
+            source_commit: None,
+            repo: None,
+            features: Vec::new(),
+            optional_features: Vec::new(),
+        }
+    }
+
+    /// Refuses artifacts of a newer schema version or relying on features this build doesn't
+    /// know, and warns about the unknown optional ones, which are ignored.
+    pub fn check_compatible(&self) -> Result<(), CompatibilityError> {
+        if self.schema_version > SCHEMA_VERSION {
+            return Err(CompatibilityError::NewerSchema {
+                writer: self.writer(),
+                schema_version: self.schema_version,
+                current: env!("CARGO_PKG_VERSION").to_string(),
+                supported: SCHEMA_VERSION,
+            });
+        }
+        let unknown = |features: &[String]| -> Vec<String> {
+            features.iter().filter(|f| !KNOWN_FEATURES.contains(&f.as_str())).cloned().collect()
+        };
+        for feature in unknown(&self.optional_features) {
+            log::warn!("Ignoring the artifact's '{}' feature, unknown to this version", feature);
+        }
+        let required = unknown(&self.features);
+        if required.is_empty() {
+            return Ok(());
+        }
+        Err(CompatibilityError::UnsupportedFeatures {
+            writer: self.writer(),
+            current: env!("CARGO_PKG_VERSION").to_string(),
+            features: required.join(", "),
+        })
+    }
+
+    /// Explains why an artifact whose header was read doesn't parse, when another version
+    /// wrote it: its layout likely changed since. `None` if this version wrote it, in which
+    /// case the artifact is damaged rather than outdated.
+    pub fn unreadable(&self, error: impl std::fmt::Display) -> Option<CompatibilityError> {
+        let current = env!("CARGO_PKG_VERSION");
+        if self.schema_version == SCHEMA_VERSION && self.tool_version == current {
+            return None;
+        }
+        Some(CompatibilityError::Unreadable { writer: self.writer(), current: current.to_string(), error: error.to_string() })
+    }
+
+    /// The version that wrote the artifact, for messages. Artifacts of schema version 0
+    /// don't record it.
+    fn writer(&self) -> String {
+        match self.tool_version.is_empty() {
+            true => "an older version of dependency-cascade".to_string(),
+            false => format!("dependency-cascade {}", self.tool_version),
+        }
+    }
+}
+
+/// Points at node metadata stored outside of the artifact, in a file next to it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MetadataSidecar {
+    /// The sidecar file, relative to the artifact's directory.
+    pub file: PathBuf,
+    /// Where each node's metadata JSON lives in the sidecar. Nodes without metadata are absent.
+    pub entries: BTreeMap<String, SidecarEntry>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SidecarEntry {
+    pub offset: u64,
+    pub len: u64,
+}
+
+/// A file matched by at least one node when `prepare --index-files` ran.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexedFile {
+    /// The SHA-256 of the file's content.
+    pub hash: String,
+    /// The nodes whose patterns match the file, sorted.
+    pub nodes: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_compatible() {
+        let info = ArtifactInfo {
+            features: vec!["tags".to_string()],
+            optional_features: vec!["precomputed_closure".to_string()],
+            ..ArtifactInfo::current()
+        };
+        assert!(info.check_compatible().is_ok());
+
+        let info = ArtifactInfo {
+            tool_version: "9.0.0".to_string(),
+            features: vec!["tags".to_string(), "typed_edges".to_string()],
+            ..ArtifactInfo::current()
+        };
+        let message = info.check_compatible().unwrap_err().to_string();
+        assert!(message.contains("dependency-cascade 9.0.0"));
+        assert!(message.contains("support: typed_edges."));
+
+        let info = ArtifactInfo { schema_version: SCHEMA_VERSION + 1, ..ArtifactInfo::current() };
+        assert!(matches!(info.check_compatible(), Err(CompatibilityError::NewerSchema { .. })));
+
+        assert!(ArtifactInfo::current().unreadable("missing field `graph`").is_none());
+        let message = ArtifactInfo::default().unreadable("missing field `graph`").unwrap().to_string();
+        assert!(message.contains("written by an older version of dependency-cascade"));
+        assert!(message.contains("Re-run `prepare`"));
+    }
+}
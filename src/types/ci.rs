@@ -0,0 +1,76 @@
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// The `[metadata.ci]` table of a manifest: what pipeline templates need to build, test and
+/// deploy a node. Validated by `prepare`, projected by `query --emit ci`.
+///
+/// ```toml
+/// [metadata.ci]
+/// test = "cargo test -p api"
+/// build = ["cargo build -p api --release"]
+/// deploy = "./deploy.sh api"
+/// runner = "large"
+/// cache_paths = ["target/"]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CiMetadata {
+    /// The test commands, a single one or a list of them.
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub test: Vec<String>,
+    /// The build commands, a single one or a list of them.
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub build: Vec<String>,
+    /// The deploy commands, a single one or a list of them.
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub deploy: Vec<String>,
+    /// The size of the runner the jobs need.
+    #[serde(default)]
+    pub runner: Option<RunnerSize>,
+    /// Paths to cache between runs, relative to the node.
+    #[serde(default)]
+    pub cache_paths: Vec<String>,
+}
+
+/// The runner sizes of `[metadata.ci] runner`, for pipelines to map to their machines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RunnerSize {
+    Small,
+    Medium,
+    Large,
+    Xlarge,
+}
+
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(command) => vec![command],
+        OneOrMany::Many(commands) => commands,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ci_metadata() {
+        let ci: CiMetadata = serde_json::from_value(serde_json::json!({
+            "test": "cargo test",
+            "build": ["cargo build", "strip target/app"],
+            "runner": "xlarge",
+        })).unwrap();
+        assert_eq!(ci.test, vec!["cargo test"]);
+        assert_eq!(ci.build.len(), 2);
+        assert!(ci.deploy.is_empty());
+        assert_eq!(ci.runner, Some(RunnerSize::Xlarge));
+
+        assert!(serde_json::from_value::<CiMetadata>(serde_json::json!({ "runner": "huge" })).is_err());
+        assert!(serde_json::from_value::<CiMetadata>(serde_json::json!({ "tests": "cargo test" })).is_err());
+    }
+}
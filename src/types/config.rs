@@ -0,0 +1,299 @@
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Repository-wide settings, read from a `dependency-cascade.toml` file at the root
+/// of the monorepo (or from the file given with `--config`). Every section is optional.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Rules applied by the `lint` command.
+    pub lint: LintConfig,
+    /// Thresholds checked by the `blast-radius` command.
+    pub blast_radius: BlastRadiusConfig,
+    /// Categories of changes the `classify` command considers soft.
+    pub classify: ClassifyConfig,
+    /// Checks of the nodes' include patterns run by `prepare`.
+    pub glob_guard: GlobGuardConfig,
+    /// Metadata merged under the metadata of every node by `prepare`.
+    pub default_metadata: toml::Table,
+    /// Whether `prepare` walks and globs match dotfiles and dot-directories.
+    pub dotfiles: DotfilesConfig,
+    /// WebAssembly modules discovering nodes or matching paths, as `[[plugins]]`.
+    pub plugins: Vec<PluginConfig>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("Unable to read config file {0}: {1}")]
+    ReadError(PathBuf, std::io::Error),
+    #[error("Failed to parse config file {0}: {1}")]
+    ParseError(PathBuf, toml::de::Error),
+}
+
+/// How serious a reported finding is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// The `[lint]` section of the root config. Rules that aren't configured don't run.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LintConfig {
+    /// Caps the number of direct dependencies a node may declare.
+    pub max_dependencies: Option<MaxDependenciesRule>,
+    /// Edges that must not exist between groups of nodes, identified by tag.
+    pub forbidden_edges: Vec<ForbiddenEdgeRule>,
+    /// Tags of which every node must carry at least one.
+    pub required_tags: Option<RequiredTagsRule>,
+    /// A regular expression every node name must match.
+    pub naming: Option<NamingRule>,
+}
+
+/// The `[blast_radius]` section of the root config.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BlastRadiusConfig {
+    /// The most direct and indirect dependents a node may have.
+    pub max_dependents: Option<usize>,
+}
+
+/// The `[classify]` section of the root config.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClassifyConfig {
+    /// Glob patterns of changed files, relative to the repository root, by soft category
+    /// name. Replaces the default `docs` and `ci` categories when set.
+    pub categories: BTreeMap<String, Vec<String>>,
+}
+
+impl Default for ClassifyConfig {
+    fn default() -> Self {
+        let patterns = |patterns: &[&str]| patterns.iter().map(|p| p.to_string()).collect();
+        ClassifyConfig {
+            categories: BTreeMap::from([
+                ("docs".to_string(), patterns(&["**/*.md", "**/*.mdx", "**/*.rst", "**/docs/**", "**/LICENSE*"])),
+                ("ci".to_string(), patterns(&[".github/**", ".gitlab-ci.yml", ".gitlab/**", ".circleci/**", "**/Jenkinsfile", ".buildkite/**"])),
+            ]),
+        }
+    }
+}
+
+/// The `[glob_guard]` section of the root config. Patterns escaping their node's directory
+/// are always reported, file counts only when `max_files` is set.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GlobGuardConfig {
+    /// The most files a node's patterns may match.
+    pub max_files: Option<usize>,
+    /// `error` makes `prepare` fail on the reported nodes.
+    pub severity: Severity,
+}
+
+impl Default for GlobGuardConfig {
+    fn default() -> Self {
+        GlobGuardConfig { max_files: None, severity: Severity::Warning }
+    }
+}
+
+/// The `[dotfiles]` section of the root config, for the files and directories whose name
+/// starts with a dot, like `.github/` or `.env`. Both are on by default.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DotfilesConfig {
+    /// Whether `prepare` looks for manifests in dot-directories, `.git` included.
+    pub walk: bool,
+    /// Whether `*`, `?` and `**` match dotfiles and dot-directories. When off, only patterns
+    /// naming them do, like `.github/**`.
+    pub globs: bool,
+}
+
+impl Default for DotfilesConfig {
+    fn default() -> Self {
+        DotfilesConfig { walk: true, globs: true }
+    }
+}
+
+/// A `[[plugins]]` entry of the root config.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PluginConfig {
+    /// The `.wasm` file, relative to the root config.
+    pub path: PathBuf,
+    /// The name patterns use in `plugin:<name>:<argument>`. Defaults to the file's stem.
+    pub name: Option<String>,
+    /// The names of the files the plugin discovers nodes in, besides the dependency tomls.
+    #[serde(default)]
+    pub manifests: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MaxDependenciesRule {
+    pub max: usize,
+    #[serde(default = "warning")]
+    pub severity: Severity,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ForbiddenEdgeRule {
+    /// Tag of the depending nodes.
+    pub from: String,
+    /// Tag of the nodes they must not depend on.
+    pub to: String,
+    #[serde(default = "error")]
+    pub severity: Severity,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RequiredTagsRule {
+    pub one_of: Vec<String>,
+    #[serde(default = "warning")]
+    pub severity: Severity,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NamingRule {
+    pub pattern: String,
+    #[serde(default = "warning")]
+    pub severity: Severity,
+}
+
+fn warning() -> Severity {
+    Severity::Warning
+}
+
+fn error() -> Severity {
+    Severity::Error
+}
+
+impl Config {
+    /// The name of the root config file looked up when `--config` isn't given.
+    pub const FILE_NAME: &'static str = "dependency-cascade.toml";
+
+    /// Parses a config from its TOML representation.
+    pub fn from_toml_str(content: &str, path: &Path) -> Result<Self, ConfigError> {
+        toml::from_str(content).map_err(|e| ConfigError::ParseError(path.to_path_buf(), e))
+    }
+
+    /// Loads the config from the explicit `path` if given, otherwise from
+    /// `dependency-cascade.toml` inside `dir`. A missing default file yields the default config.
+    pub fn load(path: Option<&Path>, dir: &Path) -> Result<Self, ConfigError> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => (dir.join(Self::FILE_NAME), false),
+        };
+
+        if !required && !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(&path).map_err(|e| ConfigError::ReadError(path.clone(), e))?;
+        Self::from_toml_str(&content, &path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_config() {
+        let config = Config::from_toml_str("", Path::new("cfg.toml")).unwrap();
+        assert!(config.lint.max_dependencies.is_none());
+        assert!(config.lint.forbidden_edges.is_empty());
+    }
+
+    #[test]
+    fn test_lint_config() {
+        let toml = r#"
+            [lint.max_dependencies]
+            max = 5
+
+            [[lint.forbidden_edges]]
+            from = "frontend"
+            to = "backend"
+
+            [lint.naming]
+            pattern = "^[a-z-]+$"
+            severity = "error"
+        "#;
+
+        let config = Config::from_toml_str(toml, Path::new("cfg.toml")).unwrap();
+        let max = config.lint.max_dependencies.unwrap();
+        assert_eq!(max.max, 5);
+        assert_eq!(max.severity, Severity::Warning);
+        assert_eq!(config.lint.forbidden_edges[0].severity, Severity::Error);
+        assert_eq!(config.lint.naming.unwrap().severity, Severity::Error);
+        assert_eq!(config.blast_radius.max_dependents, None);
+    }
+
+    #[test]
+    fn test_blast_radius_config() {
+        let config = Config::from_toml_str("[blast_radius]\nmax_dependents = 20", Path::new("cfg.toml")).unwrap();
+        assert_eq!(config.blast_radius.max_dependents, Some(20));
+    }
+
+    #[test]
+    fn test_classify_config() {
+        let config = Config::from_toml_str("", Path::new("cfg.toml")).unwrap();
+        assert_eq!(config.classify.categories.keys().collect::<Vec<_>>(), vec!["ci", "docs"]);
+
+        let config = Config::from_toml_str("[classify.categories]\nconfig = [\"**/*.yaml\"]", Path::new("cfg.toml")).unwrap();
+        assert_eq!(config.classify.categories, BTreeMap::from([("config".to_string(), vec!["**/*.yaml".to_string()])]));
+    }
+
+    #[test]
+    fn test_glob_guard_config() {
+        let config = Config::from_toml_str("", Path::new("cfg.toml")).unwrap();
+        assert_eq!((config.glob_guard.max_files, config.glob_guard.severity), (None, Severity::Warning));
+
+        let config = Config::from_toml_str("[glob_guard]\nmax_files = 5000\nseverity = \"error\"", Path::new("cfg.toml")).unwrap();
+        assert_eq!((config.glob_guard.max_files, config.glob_guard.severity), (Some(5000), Severity::Error));
+    }
+
+    #[test]
+    fn test_default_metadata_config() {
+        let config = Config::from_toml_str("[default_metadata]\nci = { runner = \"small\" }", Path::new("cfg.toml")).unwrap();
+        assert_eq!(config.default_metadata["ci"]["runner"].as_str(), Some("small"));
+    }
+
+    #[test]
+    fn test_dotfiles_config() {
+        let config = Config::from_toml_str("", Path::new("cfg.toml")).unwrap();
+        assert!(config.dotfiles.walk && config.dotfiles.globs);
+
+        let config = Config::from_toml_str("[dotfiles]\nglobs = false", Path::new("cfg.toml")).unwrap();
+        assert!(config.dotfiles.walk && !config.dotfiles.globs);
+    }
+
+    #[test]
+    fn test_plugins_config() {
+        let config = Config::from_toml_str(
+            "[[plugins]]\npath = \"plugins/bazel.wasm\"\nmanifests = [\"BUILD\"]\n\n[[plugins]]\npath = \"owned.wasm\"\nname = \"owned\"",
+            Path::new("cfg.toml"),
+        ).unwrap();
+        assert_eq!(config.plugins.len(), 2);
+        assert_eq!((config.plugins[0].path.as_path(), config.plugins[0].manifests.as_slice()), (Path::new("plugins/bazel.wasm"), &["BUILD".to_string()][..]));
+        assert_eq!(config.plugins[1].name.as_deref(), Some("owned"));
+    }
+
+    #[test]
+    fn test_unknown_key_rejected() {
+        let result = Config::from_toml_str("[lint]\nmax_deps = 3", Path::new("cfg.toml"));
+        assert!(matches!(result, Err(ConfigError::ParseError(_, _))));
+    }
+
+    #[test]
+    fn test_missing_default_file() {
+        let config = Config::load(None, Path::new("/definitely/not/here")).unwrap();
+        assert!(config.lint.required_tags.is_none());
+    }
+}
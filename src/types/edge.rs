@@ -0,0 +1,125 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// What a node depends on another one for, from the `kind` of the dependency in its manifest.
+/// Informational: every kind cascades the same way.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EdgeKind {
+    /// Needed to run the node, the default.
+    #[default]
+    Runtime,
+    /// Only needed to build it, e.g. code generators or build scripts.
+    Build,
+    /// Only needed to test it, e.g. fixtures or test harnesses.
+    Test,
+}
+
+/// The data of a dependency edge, as declared by the dependent:
+///
+/// ```toml
+/// [dependencies]
+/// schema = { name = "schema", kind = "build", paths = ["proto/**"] }
+/// ```
+///
+/// Serialized as `null` when its `kind` and `paths` are the defaults, like the edges of
+/// artifacts written before edges had data. `declared_in` isn't serialized: it's the
+/// `manifest` of the dependent, restored from it when the graph is read.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Edge {
+    pub kind: EdgeKind,
+    /// The manifest the dependency is declared in, when the graph was prepared from manifests.
+    /// Not part of the edge's data, see [`Edge::has_data`].
+    pub declared_in: Option<PathBuf>,
+    /// Patterns, relative to the dependency, of the files whose changes cascade through the
+    /// edge. Empty for all of them.
+    pub paths: Vec<PathBuf>,
+}
+
+/// The serialized fields of an [`Edge`]. `declared_in` is only read, from artifacts written
+/// when it was serialized.
+#[derive(Serialize, Deserialize)]
+struct EdgeFields {
+    #[serde(default)]
+    kind: EdgeKind,
+    #[serde(default, skip_serializing)]
+    declared_in: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    paths: Vec<PathBuf>,
+}
+
+impl EdgeKind {
+    /// The name of the kind in manifests.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EdgeKind::Runtime => "runtime",
+            EdgeKind::Build => "build",
+            EdgeKind::Test => "test",
+        }
+    }
+}
+
+impl Edge {
+    /// Whether the edge has a `kind` or `paths` other than the defaults, which readers of
+    /// data-less edges can't parse.
+    pub fn has_data(&self) -> bool {
+        self.kind != EdgeKind::default() || !self.paths.is_empty()
+    }
+
+    /// Whether a change to `path`, a file of the dependency at `dependency_path`, cascades
+    /// through the edge.
+    pub fn cascades_path(&self, dependency_path: &Path, path: &Path) -> bool {
+        self.paths.is_empty() || self.paths.iter().any(|pattern| {
+            glob::Pattern::new(&dependency_path.join(pattern).to_string_lossy())
+                .is_ok_and(|pattern| pattern.matches_path(path))
+        })
+    }
+}
+
+impl Serialize for Edge {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if !self.has_data() {
+            return serializer.serialize_none();
+        }
+        serializer.serialize_some(&EdgeFields {
+            kind: self.kind,
+            declared_in: None,
+            paths: self.paths.clone(),
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Edge {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match Option::<EdgeFields>::deserialize(deserializer)? {
+            Some(EdgeFields { kind, declared_in, paths }) => Edge { kind, declared_in, paths },
+            None => Edge::default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edge_serialization() {
+        assert_eq!(serde_json::to_string(&Edge::default()).unwrap(), "null");
+        assert_eq!(serde_json::from_str::<Edge>("null").unwrap(), Edge::default());
+
+        let edge = Edge { kind: EdgeKind::Build, declared_in: None, paths: vec![PathBuf::from("proto/**")] };
+        let json = serde_json::to_string(&edge).unwrap();
+        assert_eq!(json, r#"{"kind":"build","paths":["proto/**"]}"#);
+        assert_eq!(serde_json::from_str::<Edge>(&json).unwrap(), edge);
+
+        // Where it's declared is left to the dependent's manifest
+        let declared = Edge { declared_in: Some(PathBuf::from("web/dependencies.toml")), ..Edge::default() };
+        assert_eq!(serde_json::to_string(&declared).unwrap(), "null");
+        let declared = Edge { declared_in: Some(PathBuf::from("web/dependencies.toml")), ..edge.clone() };
+        assert_eq!(serde_json::to_string(&declared).unwrap(), json);
+
+        assert!(edge.cascades_path(Path::new("schema"), Path::new("schema/proto/user.proto")));
+        assert!(!edge.cascades_path(Path::new("schema"), Path::new("schema/README.md")));
+    }
+}
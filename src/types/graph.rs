@@ -1,18 +1,79 @@
-use std::collections::{HashMap, HashSet};
-use std::path::PathBuf;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::path::{Path, PathBuf};
 use serde::{Serialize, Deserialize};
+use petgraph::graph::EdgeReference;
 use petgraph::prelude::*;
 use petgraph::{Directed, Direction};
 use petgraph::algo::toposort;
 
 pub use super::node::Node;
+use super::{ArtifactInfo, Edge, IndexedFile, MetadataSidecar};
 
 /// A directed acyclic graph of dependencies, using petgraph.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DependencyGraph {
-    graph: Graph<Node, (), Directed>,
+    /// How the artifact was produced.
+    #[serde(default)]
+    info: ArtifactInfo,
+    /// Where the node metadata is when it isn't stored inline.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    metadata_sidecar: Option<MetadataSidecar>,
+    /// The SHA-256 of each node's manifest, by node path, so the next `prepare` can reuse
+    /// the nodes whose manifest didn't change.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    manifest_hashes: BTreeMap<PathBuf, String>,
+    /// Every file matched by a node, when `prepare --index-files` listed them. Changed
+    /// files found here are attributed without matching patterns.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    file_index: BTreeMap<PathBuf, IndexedFile>,
+    /// The direct and indirect dependencies of every node, by node name, when `prepare
+    /// --reachability` precomputed them.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    closure: BTreeMap<String, BTreeSet<String>>,
+    #[serde(deserialize_with = "deserialize_graph")]
+    graph: Graph<Node, Edge, Directed>,
     /// Maps a node's name to its petgraph index.
     name_to_index: HashMap<String, NodeIndex>,
+    /// The affected nodes of a change set computed elsewhere, e.g. read from a query cache.
+    #[serde(skip)]
+    remembered: Option<(Vec<PathBuf>, BTreeMap<String, AffectedReason>)>,
+}
+
+/// Reads the petgraph graph, restoring where each edge is declared from its dependent's
+/// manifest, which isn't serialized with the edge.
+fn deserialize_graph<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Graph<Node, Edge, Directed>, D::Error> {
+    let mut graph = Graph::<Node, Edge, Directed>::deserialize(deserializer)?;
+    for edge in graph.edge_indices() {
+        let Some((_, dependent)) = graph.edge_endpoints(edge) else { continue };
+        let manifest = graph[dependent].manifest.clone();
+        let weight = &mut graph[edge];
+        if weight.declared_in.is_none() {
+            weight.declared_in = manifest;
+        }
+    }
+    Ok(graph)
+}
+
+/// Why a node is part of the affected set.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AffectedReason {
+    /// Some of the node's own files changed.
+    Changed { files: Vec<PathBuf> },
+    /// The node depends, directly or not, on the changed node `via`.
+    Dependency { via: String },
+    /// The changed node `of` depends, directly or not, on the node.
+    Upstream { of: String },
+}
+
+/// A chain of dependencies through which a change reaches an affected node.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AffectedChain {
+    /// The changed node first and the affected node last, each depending on the previous one.
+    pub nodes: Vec<String>,
+    /// The changed files of the first node that cascade along the chain.
+    pub files: Vec<PathBuf>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -25,10 +86,95 @@ pub enum DependencyGraphCreationError {
              Existing node names: {2}")]
     MissingDependency(String, String, String),
     /// A circular dependency was detected.
-    #[error("Circular dependency detected: {0} -> {1}. \
-             This means there is a cycle in the dependencies where a node depends on itself \
-             either directly or through other nodes.")]
-    CircularDependency(String, String),
+    #[error("{0}")]
+    CircularDependency(CycleReport),
+}
+
+/// The cycles of a graph, by strongly connected component: the nodes depending on each
+/// other, directly or through other nodes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CycleReport {
+    pub components: Vec<CyclicComponent>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CyclicComponent {
+    /// The nodes of the component, by name.
+    pub nodes: Vec<String>,
+    /// Every dependency between them.
+    pub dependencies: Vec<CyclicDependency>,
+    /// Dependencies whose removal breaks every cycle of the component. A small set, found
+    /// greedily, rather than the smallest.
+    pub to_break: Vec<CyclicDependency>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CyclicDependency {
+    pub dependent: String,
+    pub dependency: String,
+    /// The manifest of the dependent declaring the dependency, when known.
+    pub declared_in: Option<PathBuf>,
+}
+
+impl CycleReport {
+    fn new(graph: &Graph<Node, Edge, Directed>) -> Self {
+        let dependency = |edge: EdgeReference<Edge>| CyclicDependency {
+            dependent: graph[edge.target()].name.clone(),
+            dependency: graph[edge.source()].name.clone(),
+            declared_in: edge.weight().declared_in.clone(),
+        };
+        let to_break: HashSet<EdgeIndex> = petgraph::algo::greedy_feedback_arc_set(graph).map(|edge| edge.id()).collect();
+
+        let mut components: Vec<CyclicComponent> = petgraph::algo::tarjan_scc(graph)
+            .into_iter()
+            .filter(|component| component.len() > 1 || graph.contains_edge(component[0], component[0]))
+            .map(|component| {
+                let members: HashSet<NodeIndex> = component.iter().copied().collect();
+                let edges: Vec<EdgeReference<Edge>> = graph.edge_references()
+                    .filter(|edge| members.contains(&edge.source()) && members.contains(&edge.target()))
+                    .collect();
+                let mut nodes: Vec<String> = component.iter().map(|&idx| graph[idx].name.clone()).collect();
+                let mut dependencies: Vec<CyclicDependency> = edges.iter().map(|&edge| dependency(edge)).collect();
+                let mut to_break: Vec<CyclicDependency> = edges.iter()
+                    .filter(|edge| to_break.contains(&edge.id()))
+                    .map(|&edge| dependency(edge))
+                    .collect();
+                nodes.sort();
+                dependencies.sort();
+                to_break.sort();
+                CyclicComponent { nodes, dependencies, to_break }
+            })
+            .collect();
+        components.sort_by(|a, b| a.nodes.cmp(&b.nodes));
+        CycleReport { components }
+    }
+}
+
+impl fmt::Display for CyclicDependency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} -> {}", self.dependent, self.dependency)?;
+        match &self.declared_in {
+            Some(manifest) => write!(f, ", declared in {} as [dependencies] {}", manifest.display(), self.dependency),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for CycleReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Circular dependencies detected: these nodes depend on themselves, either directly or through each other.")?;
+        for component in &self.components {
+            write!(f, "\n\n{} node(s) in a cycle: {}", component.nodes.len(), component.nodes.join(", "))?;
+            for dependency in &component.dependencies {
+                write!(f, "\n  {}", dependency)?;
+            }
+            write!(f, "\nRemoving these dependencies would break it:")?;
+            for dependency in &component.to_break {
+                write!(f, "\n  {}", dependency)?;
+            }
+        }
+        Ok(())
+    }
 }
 
 impl DependencyGraph {
@@ -39,7 +185,7 @@ impl DependencyGraph {
     ///   - Logs a warning if a dependency does not exist in the graph.
     ///   - Logs an error if a circular dependency is detected.
     pub fn new(nodes: Vec<Node>, allow_cyclical: bool) -> Result<Self, DependencyGraphCreationError> {
-        let mut graph = Graph::<Node, (), Directed>::new();
+        let mut graph = Graph::<Node, Edge, Directed>::new();
         let mut name_to_index = HashMap::new();
         let mut seen_names = HashSet::new();
 
@@ -65,7 +211,11 @@ impl DependencyGraph {
             for dep_name in deps {
                 match name_to_index.get(&dep_name) {
                     Some(&dep_idx) => {
-                        graph.add_edge(dep_idx, idx, ());
+                        let edge = Edge {
+                            declared_in: node.manifest.clone(),
+                            ..node.dependency_edges.get(&dep_name).cloned().unwrap_or_default()
+                        };
+                        graph.add_edge(dep_idx, idx, edge);
                     }
                     None => {
                         return Err(DependencyGraphCreationError::MissingDependency(
@@ -78,58 +228,66 @@ impl DependencyGraph {
             }
         }
 
-        // Check for cycles by trying a toposort.
-        if !allow_cyclical {
-            if let Err(cycle_err) = toposort(&graph, None) {
-                // Find the cycle path by doing a DFS from the problematic node
-                // this is important to help the user understand the cycle.
-                let mut cycle_path = vec![cycle_err.node_id()];
-                let mut current = cycle_err.node_id();
-                let mut visited = HashSet::new();
-            visited.insert(current);
-
-            'outer: while let Some(neighbors) = graph.neighbors_directed(current, Direction::Outgoing).collect::<Vec<_>>().into_iter().next() {
-                current = neighbors;
-                if !visited.insert(current) {
-                    // Found the cycle, trim the path to just the cycle
-                    while cycle_path[0] != current {
-                        cycle_path.remove(0);
-                    }
-                    break 'outer;
-                }
-                cycle_path.push(current);
-            }
-
-            let cycle_names: Vec<_> = cycle_path.iter().map(|&idx| graph[idx].name.as_str()).collect();
-
-            return Err(DependencyGraphCreationError::CircularDependency(
-                cycle_names.join(" -> "),
-                    cycle_names[0].to_string() // Complete the cycle
-                ));
-            }
+        // Check for cycles by trying a toposort, reporting all of them when there are some.
+        if !allow_cyclical && toposort(&graph, None).is_err() {
+            return Err(DependencyGraphCreationError::CircularDependency(CycleReport::new(&graph)));
         }
 
-        Ok(Self { graph, name_to_index })
+        let mut graph = Self {
+            info: ArtifactInfo::current(),
+            metadata_sidecar: None,
+            manifest_hashes: BTreeMap::new(),
+            file_index: BTreeMap::new(),
+            closure: BTreeMap::new(),
+            graph,
+            name_to_index,
+            remembered: None,
+        };
+        graph.stamp_features();
+        Ok(graph)
     }
     
     /// Returns the list of nodes that are direct or indirect dependencies of the given node
     /// (i.e. upstream of `node_name`), using a reverse graph traversal.
-    #[allow(dead_code)]
     pub fn get_dependencies(&self, node_name: &str) -> Vec<Node> {
+        self.get_dependencies_within(node_name, None)
+    }
+
+    /// Returns the dependencies of the given node at most `max_depth` edges away, e.g. only
+    /// the direct ones with 1, or all of them with `None`, closest first.
+    pub fn get_dependencies_within(&self, node_name: &str, max_depth: Option<usize>) -> Vec<Node> {
+        self.reachable(node_name, Direction::Incoming, max_depth)
+    }
+
+    /// Returns the list of nodes that directly or indirectly depend on the given node
+    /// (i.e. downstream of `node_name`), using a forward graph traversal.
+    pub fn get_dependents(&self, node_name: &str) -> Vec<Node> {
+        self.get_dependents_within(node_name, None)
+    }
+
+    /// Returns the dependents of the given node at most `max_depth` edges away, e.g. only
+    /// the direct ones with 1, or all of them with `None`, closest first.
+    pub fn get_dependents_within(&self, node_name: &str, max_depth: Option<usize>) -> Vec<Node> {
+        self.reachable(node_name, Direction::Outgoing, max_depth)
+    }
+
+    /// The nodes reached from the given node by following the edges in `direction`,
+    /// breadth-first, in at most `max_depth` steps.
+    fn reachable(&self, node_name: &str, direction: Direction, max_depth: Option<usize>) -> Vec<Node> {
         let mut results = Vec::new();
         let mut visited = HashSet::new();
 
         if let Some(&start_idx) = self.name_to_index.get(node_name) {
-            let mut stack = vec![start_idx];
+            let mut queue = VecDeque::from([(start_idx, 0)]);
 
-            while let Some(idx) = stack.pop() {
-                for neighbor in self
-                    .graph
-                    .neighbors_directed(idx, Direction::Incoming)
-                {
+            while let Some((idx, depth)) = queue.pop_front() {
+                if max_depth.is_some_and(|max_depth| depth >= max_depth) {
+                    continue;
+                }
+                for neighbor in self.graph.neighbors_directed(idx, direction) {
                     if visited.insert(neighbor) {
                         results.push(self.graph[neighbor].clone());
-                        stack.push(neighbor);
+                        queue.push_back((neighbor, depth + 1));
                     }
                 }
             }
@@ -137,28 +295,192 @@ impl DependencyGraph {
         results
     }
 
-    /// Returns the list of nodes that directly or indirectly depend on the given node
-    /// (i.e. downstream of `node_name`), using a forward graph traversal.
-    pub fn get_dependents(&self, node_name: &str) -> Vec<Node> {
-        let mut results = Vec::new();
-        let mut visited = HashSet::new();
+    /// How the artifact was produced.
+    pub fn info(&self) -> &ArtifactInfo {
+        &self.info
+    }
 
-        if let Some(&start_idx) = self.name_to_index.get(node_name) {
-            let mut stack = vec![start_idx];
+    /// Records how the artifact was produced. The features are those of this graph.
+    pub fn set_info(&mut self, info: ArtifactInfo) {
+        self.info = info;
+        self.stamp_features();
+    }
 
-            while let Some(idx) = stack.pop() {
-                for neighbor in self
-                    .graph
-                    .neighbors_directed(idx, Direction::Outgoing)
-                {
-                    if visited.insert(neighbor) {
-                        results.push(self.graph[neighbor].clone());
-                        stack.push(neighbor);
-                    }
+    /// Records in the info which features the graph uses.
+    fn stamp_features(&mut self) {
+        let nodes = self.get_all_nodes();
+        let required = [
+            ("tags", nodes.iter().any(|node| !node.tags.is_empty())),
+            ("cascade_exclude", nodes.iter().any(|node| !node.cascade_excluded_paths.is_empty())),
+            // Readers without it would select them as work
+            ("trigger_only", nodes.iter().any(|node| node.trigger_only)),
+            // Readers without it would match the hidden files
+            ("hide_dotfiles", nodes.iter().any(|node| node.hide_dotfiles)),
+            // Readers without them would take `!` for part of the pattern
+            ("negated_patterns", nodes.iter().any(|node| {
+                [&node.included_paths, &node.excluded_paths, &node.cascade_excluded_paths].into_iter()
+                    .flatten()
+                    .any(|pattern| super::negation(pattern).0)
+            })),
+            ("metadata_sidecar", self.metadata_sidecar.is_some()),
+            // Readers with data-less edges can't parse them
+            ("edge_data", self.graph.edge_weights().any(Edge::has_data)),
+        ];
+        let optional = [
+            ("generations", nodes.iter().any(|node| node.generation.is_some())),
+            ("manifest_hashes", !self.manifest_hashes.is_empty()),
+            ("file_index", !self.file_index.is_empty()),
+            ("precomputed_closure", !self.closure.is_empty()),
+            // Readers that don't know them only miss edges to other repositories
+            ("external_dependencies", nodes.iter().any(|node| !node.external_dependencies.is_empty())),
+        ];
+        let used = |features: &[(&str, bool)]| -> Vec<String> {
+            features.iter().filter(|(_, used)| *used).map(|(name, _)| name.to_string()).collect()
+        };
+        self.info.features = used(&required);
+        self.info.optional_features = used(&optional);
+    }
+
+    /// Where the node metadata is stored, if not inline.
+    pub fn metadata_sidecar(&self) -> Option<&MetadataSidecar> {
+        self.metadata_sidecar.as_ref()
+    }
+
+    pub fn set_metadata_sidecar(&mut self, sidecar: Option<MetadataSidecar>) {
+        self.metadata_sidecar = sidecar;
+        self.stamp_features();
+    }
+
+    /// The manifest hashes recorded by `prepare`, by node path.
+    pub fn manifest_hashes(&self) -> &BTreeMap<PathBuf, String> {
+        &self.manifest_hashes
+    }
+
+    pub fn set_manifest_hashes(&mut self, hashes: BTreeMap<PathBuf, String>) {
+        self.manifest_hashes = hashes;
+        self.stamp_features();
+    }
+
+    /// The files listed by `prepare --index-files`, empty if they weren't.
+    pub fn file_index(&self) -> &BTreeMap<PathBuf, IndexedFile> {
+        &self.file_index
+    }
+
+    pub fn set_file_index(&mut self, index: BTreeMap<PathBuf, IndexedFile>) {
+        self.file_index = index;
+        self.stamp_features();
+    }
+
+    /// The names of the nodes including `file`, from the file index when it lists the file.
+    pub fn nodes_including(&self, file: &Path) -> Vec<&str> {
+        match self.file_index.get(file) {
+            Some(indexed) => indexed.nodes.iter().map(String::as_str).collect(),
+            None => self.get_all_nodes().into_iter()
+                .filter(|node| node.includes_path(file))
+                .map(|node| node.name.as_str())
+                .collect(),
+        }
+    }
+
+    /// Replaces the metadata of a node. Returns false if there's no such node.
+    pub fn set_node_metadata(&mut self, node_name: &str, metadata: Option<serde_json::Value>) -> bool {
+        match self.name_to_index.get(node_name) {
+            Some(&idx) => {
+                self.graph[idx].metadata = metadata;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Replaces the tags of a node. Returns false if there's no such node.
+    pub fn set_node_tags(&mut self, node_name: &str, tags: Vec<String>) -> bool {
+        let Some(&idx) = self.name_to_index.get(node_name) else { return false };
+        self.graph[idx].tags = tags;
+        self.stamp_features();
+        true
+    }
+
+    /// Whether `dependent` depends on `dependency`, directly or not. Answered from the
+    /// precomputed closure when there's one, by walking the dependencies otherwise.
+    pub fn depends_on(&self, dependent: &str, dependency: &str) -> bool {
+        if let Some(dependencies) = self.closure.get(dependent) {
+            return dependencies.contains(dependency);
+        }
+        let (Some(&start), Some(&target)) = (self.name_to_index.get(dependent), self.name_to_index.get(dependency)) else {
+            return false;
+        };
+        let mut visited = HashSet::new();
+        let mut stack = vec![start];
+        while let Some(idx) = stack.pop() {
+            for dep in self.graph.neighbors_directed(idx, Direction::Incoming) {
+                if dep == target {
+                    return true;
+                }
+                if visited.insert(dep) {
+                    stack.push(dep);
                 }
             }
         }
-        results
+        false
+    }
+
+    /// Precomputes the direct and indirect dependencies of every node, so `depends_on`
+    /// answers with a lookup. The artifact grows with the square of the number of nodes in
+    /// the worst case.
+    pub fn set_closure(&mut self) {
+        let names: Vec<String> = self.get_all_nodes().into_iter().map(|node| node.name.clone()).collect();
+        let mut closure: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        // Dependencies first, so their closure is complete when their dependents are reached,
+        // except within cycles where it's completed by walking the graph
+        for node in self.toposorted_subset(&names) {
+            let idx = self.name_to_index[&node.name];
+            let mut dependencies = BTreeSet::new();
+            for dep in self.graph.neighbors_directed(idx, Direction::Incoming) {
+                let name = &self.graph[dep].name;
+                dependencies.insert(name.clone());
+                match closure.get(name) {
+                    Some(indirect) => dependencies.extend(indirect.iter().cloned()),
+                    None => dependencies.extend(self.get_dependencies(name).into_iter().map(|dep| dep.name)),
+                }
+            }
+            closure.insert(node.name.clone(), dependencies);
+        }
+        self.closure = closure;
+        self.stamp_features();
+    }
+
+    /// Records the generation of every node. Within cycles, dependencies not numbered yet
+    /// count as generation 0.
+    pub fn set_generations(&mut self) {
+        let names: Vec<String> = self.get_all_nodes().into_iter().map(|node| node.name.clone()).collect();
+        let order: Vec<NodeIndex> = self.toposorted_subset(&names).into_iter().map(|node| self.name_to_index[&node.name]).collect();
+        let mut generations: HashMap<NodeIndex, usize> = HashMap::new();
+        for idx in order {
+            let generation = self.graph.neighbors_directed(idx, Direction::Incoming)
+                .filter_map(|dep| generations.get(&dep).map(|generation| generation + 1))
+                .max()
+                .unwrap_or(0);
+            generations.insert(idx, generation);
+        }
+        for (idx, generation) in generations {
+            self.graph[idx].generation = Some(generation);
+        }
+        self.stamp_features();
+    }
+
+    /// The number of dependency edges.
+    pub fn edge_count(&self) -> usize {
+        self.graph.edge_count()
+    }
+
+    /// Every edge as `(dependent, dependency, data)`, sorted by dependent then dependency.
+    pub fn edges(&self) -> Vec<(&Node, &Node, &Edge)> {
+        let mut edges: Vec<(&Node, &Node, &Edge)> = self.graph.edge_references()
+            .map(|edge| (&self.graph[edge.target()], &self.graph[edge.source()], edge.weight()))
+            .collect();
+        edges.sort_by(|a, b| a.0.name.cmp(&b.0.name).then_with(|| a.1.name.cmp(&b.1.name)));
+        edges
     }
 
     /// Retrieves a reference to a node by name.
@@ -173,27 +495,247 @@ impl DependencyGraph {
         self.graph.node_indices().map(|idx| &self.graph[idx]).collect()
     }
 
-    /// Returns a list of all affected nodes by a given file change.
-    pub fn get_affected_nodes(&self, changed_files: &Vec<PathBuf>) -> Vec<String> {
-        let mut affected_nodes = HashSet::new();
-        let nodes = self.get_all_nodes();
+    /// Returns the nodes named in `names` ordered so that every node comes after its
+    /// dependencies. Ties are broken by name so the order is stable across runs, and
+    /// nodes caught in a cycle are appended at the end, also by name.
+    pub fn toposorted_subset(&self, names: &[String]) -> Vec<&Node> {
+        let wanted: HashSet<&str> = names.iter().map(String::as_str).collect();
+
+        // Kahn's algorithm over the whole graph, so that transitive ordering through
+        // nodes outside of the subset is respected.
+        let mut in_degree: HashMap<NodeIndex, usize> = self.graph.node_indices()
+            .map(|idx| (idx, self.graph.neighbors_directed(idx, Direction::Incoming).count()))
+            .collect();
+        let mut ready: BTreeMap<&str, NodeIndex> = in_degree.iter()
+            .filter(|(_, &degree)| degree == 0)
+            .map(|(&idx, _)| (self.graph[idx].name.as_str(), idx))
+            .collect();
+
+        let mut ordered = Vec::new();
+        while let Some((_, idx)) = ready.pop_first() {
+            in_degree.remove(&idx);
+            ordered.push(idx);
+            for dependent in self.graph.neighbors_directed(idx, Direction::Outgoing) {
+                if let Some(degree) = in_degree.get_mut(&dependent) {
+                    *degree -= 1;
+                    if *degree == 0 {
+                        ready.insert(self.graph[dependent].name.as_str(), dependent);
+                    }
+                }
+            }
+        }
+        let mut cyclic: Vec<NodeIndex> = in_degree.into_keys().collect();
+        cyclic.sort_by(|a, b| self.graph[*a].name.cmp(&self.graph[*b].name));
+        ordered.extend(cyclic);
+
+        ordered.into_iter()
+            .map(|idx| &self.graph[idx])
+            .filter(|node| wanted.contains(node.name.as_str()))
+            .collect()
+    }
+
+    /// Keeps the nodes among `names` that don't depend, directly or not, on any other of them.
+    pub fn roots_of(&self, names: &[String]) -> Vec<String> {
+        let set: HashSet<&str> = names.iter().map(String::as_str).collect();
+        names.iter()
+            .filter(|name| !self.get_dependencies(name).iter().any(|dep| set.contains(dep.name.as_str())))
+            .cloned()
+            .collect()
+    }
+
+    /// Keeps the nodes among `names` that no other of them depends on, directly or not.
+    pub fn leaves_of(&self, names: &[String]) -> Vec<String> {
+        let set: HashSet<&str> = names.iter().map(String::as_str).collect();
+        names.iter()
+            .filter(|name| !self.get_dependents(name).iter().any(|dep| set.contains(dep.name.as_str())))
+            .cloned()
+            .collect()
+    }
+
+    /// Returns every affected node along with the reason it's affected. Nodes reached from
+    /// several changed nodes are attributed to the closest one, ties broken by name. Changes
+    /// limited to a node's `cascade_exclude` patterns don't affect its dependents, nor do
+    /// changes outside of the `paths` of the edge to a dependent.
+    pub fn get_affected_reasons(&self, changed_files: &[PathBuf]) -> BTreeMap<String, AffectedReason> {
+        if let Some((files, reasons)) = &self.remembered {
+            if files == changed_files {
+                return reasons.clone();
+            }
+        }
+        self.get_affected_reasons_within(changed_files, None)
+    }
+
+    /// Returns the nodes `get_affected_reasons` does, but only the dependents at most
+    /// `max_depth` edges away from a changed node, e.g. only the direct ones with 1. Changed
+    /// nodes are always affected.
+    pub fn get_affected_reasons_within(&self, changed_files: &[PathBuf], max_depth: Option<usize>) -> BTreeMap<String, AffectedReason> {
+        let mut reasons = BTreeMap::new();
+        let mut cascading = Vec::new();
+        for (name, files) in self.claimed(changed_files) {
+            let Some(node) = self.get_node(name) else { continue };
+            let cascading_files: Vec<PathBuf> = files.iter().filter(|path| node.cascades_path(path)).cloned().collect();
+            if !cascading_files.is_empty() {
+                cascading.push((node.name.clone(), cascading_files));
+            }
+            reasons.insert(node.name.clone(), AffectedReason::Changed { files });
+        }
+
+        // Breadth-first from all cascading changed nodes at once, so dependents get the closest
+        // cause. A changed node that doesn't cascade on its own still does when reached here.
+        // Edge `paths` only filter the changed files, so they only apply to the first step.
+        let mut queued: HashSet<NodeIndex> = HashSet::new();
+        let mut queue: VecDeque<(NodeIndex, String, Vec<PathBuf>, usize)> = cascading.into_iter()
+            .map(|(name, files)| (self.name_to_index[&name], name, files, 0))
+            .collect();
+        queued.extend(queue.iter().map(|(idx, _, _, _)| *idx));
+        while let Some((idx, via, files, depth)) = queue.pop_front() {
+            if max_depth.is_some_and(|max_depth| depth >= max_depth) {
+                continue;
+            }
+            let mut dependents: Vec<(NodeIndex, &Edge)> = self.graph.edges_directed(idx, Direction::Outgoing)
+                .map(|edge| (edge.target(), edge.weight()))
+                .collect();
+            dependents.sort_by(|a, b| self.graph[a.0].name.cmp(&self.graph[b.0].name));
+            for (dependent, edge) in dependents {
+                if !files.is_empty() && !files.iter().any(|file| edge.cascades_path(&self.graph[idx].path, file)) {
+                    continue;
+                }
+                if !queued.insert(dependent) {
+                    continue;
+                }
+                let name = &self.graph[dependent].name;
+                reasons.entry(name.clone()).or_insert_with(|| AffectedReason::Dependency { via: via.clone() });
+                queue.push_back((dependent, via.clone(), Vec::new(), depth + 1));
+            }
+        }
 
-        for node in nodes.iter() {
-            // Check each path individually
-            for path in changed_files {
-                // println!("checking changed file path: {}", path.to_str().unwrap());
-                if node.includes_path(path) {
-                    let dependents = self.get_dependents(&node.name);
-                    affected_nodes.insert(node.name.clone());
-                    for dependent in dependents {
-                        affected_nodes.insert(dependent.name.clone());
+        reasons
+    }
+
+    /// Returns the changed nodes and every node they depend on, directly or not, the other way
+    /// around from `get_affected_reasons`: what must be built before the change. Dependencies
+    /// of several changed nodes are attributed to the closest one, ties broken by name.
+    /// `cascade_exclude` and edge `paths` don't apply, since nothing cascades to dependencies.
+    /// With `max_depth`, only the dependencies at most that many edges away are included.
+    pub fn get_upstream_reasons(&self, changed_files: &[PathBuf], max_depth: Option<usize>) -> BTreeMap<String, AffectedReason> {
+        let mut reasons = BTreeMap::new();
+        let mut queue: VecDeque<(NodeIndex, String, usize)> = VecDeque::new();
+        for (name, files) in self.claimed(changed_files) {
+            let Some(&idx) = self.name_to_index.get(name) else { continue };
+            reasons.insert(name.to_string(), AffectedReason::Changed { files });
+            queue.push_back((idx, name.to_string(), 0));
+        }
+        while let Some((idx, of, depth)) = queue.pop_front() {
+            if max_depth.is_some_and(|max_depth| depth >= max_depth) {
+                continue;
+            }
+            let mut dependencies: Vec<NodeIndex> = self.graph.neighbors_directed(idx, Direction::Incoming).collect();
+            dependencies.sort_by(|a, b| self.graph[*a].name.cmp(&self.graph[*b].name));
+            for dependency in dependencies {
+                let name = &self.graph[dependency].name;
+                if !reasons.contains_key(name) {
+                    reasons.insert(name.clone(), AffectedReason::Upstream { of: of.clone() });
+                    queue.push_back((dependency, of.clone(), depth + 1));
+                }
+            }
+        }
+        reasons
+    }
+
+    /// Explains why a change to `changed_files` affects `node_name`: for every changed node
+    /// reaching it, the shortest chain of dependencies from that node to it, ties broken by
+    /// name. The node's own changed files are a chain of one. Empty if it isn't affected.
+    pub fn affected_chains(&self, changed_files: &[PathBuf], node_name: &str) -> Vec<AffectedChain> {
+        let Some(&target) = self.name_to_index.get(node_name) else { return Vec::new() };
+        let mut chains = Vec::new();
+        for (name, files) in self.claimed(changed_files) {
+            let origin = self.name_to_index[name];
+            if origin == target {
+                chains.push(AffectedChain { nodes: vec![name.to_string()], files });
+                continue;
+            }
+            let node = &self.graph[origin];
+            let files: Vec<PathBuf> = files.into_iter().filter(|path| node.cascades_path(path)).collect();
+            if files.is_empty() {
+                continue;
+            }
+
+            // Breadth-first from the changed node, as in `get_affected_reasons`, remembering
+            // which dependency each node was first reached from
+            let mut reached_from: HashMap<NodeIndex, NodeIndex> = HashMap::new();
+            let mut queue = VecDeque::from([origin]);
+            while let Some(idx) = queue.pop_front() {
+                if idx == target {
+                    break;
+                }
+                let mut dependents: Vec<(NodeIndex, &Edge)> = self.graph.edges_directed(idx, Direction::Outgoing)
+                    .map(|edge| (edge.target(), edge.weight()))
+                    .collect();
+                dependents.sort_by(|a, b| self.graph[a.0].name.cmp(&self.graph[b.0].name));
+                for (dependent, edge) in dependents {
+                    if idx == origin && !files.iter().any(|file| edge.cascades_path(&node.path, file)) {
+                        continue;
+                    }
+                    if dependent != origin && !reached_from.contains_key(&dependent) {
+                        reached_from.insert(dependent, idx);
+                        queue.push_back(dependent);
                     }
-                    break; // No need to check other paths for this node
                 }
             }
+            if !reached_from.contains_key(&target) {
+                continue;
+            }
+            let mut nodes = vec![self.graph[target].name.clone()];
+            let mut idx = target;
+            while let Some(&previous) = reached_from.get(&idx) {
+                nodes.push(self.graph[previous].name.clone());
+                idx = previous;
+            }
+            nodes.reverse();
+            chains.push(AffectedChain { nodes, files });
         }
+        chains.sort_by(|a, b| a.nodes.len().cmp(&b.nodes.len()).then_with(|| a.nodes.cmp(&b.nodes)));
+        chains
+    }
 
-        affected_nodes.into_iter().collect()
+    /// The nodes claiming each of the changed files, with their files.
+    fn claimed(&self, changed_files: &[PathBuf]) -> BTreeMap<&str, Vec<PathBuf>> {
+        let mut claimed: BTreeMap<&str, Vec<PathBuf>> = BTreeMap::new();
+        let including = crate::parallel::map(changed_files, |path| self.nodes_including(path));
+        for (path, names) in changed_files.iter().zip(including) {
+            for name in names {
+                claimed.entry(name).or_default().push(path.clone());
+            }
+        }
+        claimed
+    }
+
+    /// Returns a list of all affected nodes by a given file change, sorted by name.
+    pub fn get_affected_nodes(&self, changed_files: &[PathBuf]) -> Vec<String> {
+        self.get_affected_reasons(changed_files).into_keys().collect()
+    }
+
+    /// Makes `get_affected_reasons` answer `reasons` for `changed_files` without matching
+    /// them again.
+    pub fn remember_affected(&mut self, changed_files: Vec<PathBuf>, reasons: BTreeMap<String, AffectedReason>) {
+        self.remembered = Some((changed_files, reasons));
+    }
+}
+
+impl fmt::Display for AffectedChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let files: Vec<String> = self.files.iter().map(|file| file.display().to_string()).collect();
+        write!(f, "{} ({})", self.nodes.join(" -> "), files.join(", "))
+    }
+}
+
+impl fmt::Display for AffectedReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AffectedReason::Changed { .. } => write!(f, "changed"),
+            AffectedReason::Dependency { via } => write!(f, "dependency:{}", via),
+            AffectedReason::Upstream { of } => write!(f, "upstream:{}", of),
+        }
     }
 }
 
@@ -263,7 +805,41 @@ mod tests {
         ];
 
         let err = DependencyGraph::new(nodes, false).unwrap_err();
-        assert!(matches!(err, DependencyGraphCreationError::CircularDependency(_, _)));
+        assert!(matches!(err, DependencyGraphCreationError::CircularDependency(_)));
+    }
+
+    #[test]
+    fn test_cycle_report() {
+        let mut a = create_test_node("a", vec!["b"]);
+        a.manifest = Some(PathBuf::from("a/dependencies.toml"));
+        let nodes = vec![
+            a,
+            create_test_node("b", vec!["a", "c"]),
+            create_test_node("c", vec!["a"]),
+            create_test_node("d", vec!["d", "a"]),
+            create_test_node("e", vec!["a"]),
+        ];
+
+        let Err(DependencyGraphCreationError::CircularDependency(report)) = DependencyGraph::new(nodes, false) else {
+            panic!("expected a cycle");
+        };
+        // e only depends on the cycle, d depends on itself
+        let nodes: Vec<&[String]> = report.components.iter().map(|c| c.nodes.as_slice()).collect();
+        assert_eq!(nodes, vec![&["a", "b", "c"][..], &["d"][..]]);
+        assert_eq!(report.components[0].dependencies.len(), 4);
+        assert_eq!(report.components[1].to_break.len(), 1);
+
+        // Whatever it suggests, removing it leaves no cycle among a, b and c
+        let to_break = &report.components[0].to_break;
+        assert!(!to_break.is_empty() && to_break.len() < 4);
+        let remaining = |name: &str, deps: Vec<&str>| {
+            let deps = deps.into_iter().filter(|dep| !to_break.iter().any(|d| d.dependent == name && d.dependency == *dep)).collect();
+            create_test_node(name, deps)
+        };
+        let broken = vec![remaining("a", vec!["b"]), remaining("b", vec!["a", "c"]), remaining("c", vec!["a"])];
+        assert!(DependencyGraph::new(broken, false).is_ok());
+
+        assert!(report.to_string().contains("a -> b, declared in a/dependencies.toml as [dependencies] b"));
     }
 
     #[test]
@@ -328,6 +904,12 @@ mod tests {
             .collect();
         
         assert!(c_dependents.is_empty());
+
+        let direct: Vec<String> = graph.get_dependents_within("a", Some(1)).into_iter().map(|n| n.name).collect();
+        assert_eq!(HashSet::<String>::from_iter(direct), HashSet::from_iter(vec!["b".to_string(), "d".to_string()]));
+        let direct: Vec<String> = graph.get_dependencies_within("c", Some(1)).into_iter().map(|n| n.name).collect();
+        assert_eq!(direct, vec!["b".to_string()]);
+        assert!(graph.get_dependents_within("a", Some(0)).is_empty());
     }
 
     #[test]
@@ -367,6 +949,223 @@ mod tests {
         assert_eq!(all_nodes.len(), 2);
     }
 
+    #[test]
+    fn test_toposorted_subset() {
+        let nodes = vec![
+            create_test_node("d", vec!["b", "c"]),
+            create_test_node("c", vec!["a"]),
+            create_test_node("b", vec!["a"]),
+            create_test_node("a", vec![]),
+            create_test_node("e", vec![]),
+        ];
+        let graph = DependencyGraph::new(nodes, false).unwrap();
+
+        let names = |subset: &[&str]| -> Vec<String> {
+            let subset: Vec<String> = subset.iter().map(|s| s.to_string()).collect();
+            graph.toposorted_subset(&subset).into_iter().map(|n| n.name.clone()).collect()
+        };
+        assert_eq!(names(&["d", "c", "b", "a", "e"]), vec!["a", "b", "c", "d", "e"]);
+        assert_eq!(names(&["d", "a"]), vec!["a", "d"]);
+    }
+
+    #[test]
+    fn test_toposorted_subset_with_cycle() {
+        let nodes = vec![
+            create_test_node("a", vec![]),
+            create_test_node("c", vec!["a", "b"]),
+            create_test_node("b", vec!["c"]),
+        ];
+        let graph = DependencyGraph::new(nodes, true).unwrap();
+
+        let all = ["a", "b", "c"].map(String::from);
+        let ordered: Vec<_> = graph.toposorted_subset(&all).into_iter().map(|n| n.name.as_str()).collect();
+        assert_eq!(ordered, vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_get_affected_reasons() {
+        let nodes = vec![
+            create_test_node("a", vec![]),
+            create_test_node("b", vec!["a"]),
+            create_test_node("c", vec!["b"]),
+            create_test_node("d", vec![]),
+        ];
+        let graph = DependencyGraph::new(nodes, false).unwrap();
+
+        let reasons = graph.get_affected_reasons(&[PathBuf::from("test/a/src/file.rs")]);
+        assert_eq!(reasons.len(), 3);
+        assert_eq!(reasons["a"], AffectedReason::Changed { files: vec![PathBuf::from("test/a/src/file.rs")] });
+        assert_eq!(reasons["c"], AffectedReason::Dependency { via: "a".to_string() });
+        assert_eq!(reasons["c"].to_string(), "dependency:a");
+
+        let direct = graph.get_affected_reasons_within(&[PathBuf::from("test/a/src/file.rs")], Some(1));
+        assert_eq!(direct.keys().collect::<Vec<_>>(), ["a", "b"]);
+    }
+
+    #[test]
+    fn test_get_upstream_reasons() {
+        let nodes = vec![
+            create_test_node("a", vec![]),
+            create_test_node("b", vec!["a"]),
+            create_test_node("c", vec!["b", "d"]),
+            create_test_node("d", vec![]),
+            create_test_node("e", vec!["c"]),
+        ];
+        let graph = DependencyGraph::new(nodes, false).unwrap();
+
+        let reasons = graph.get_upstream_reasons(&[PathBuf::from("test/c/src/file.rs")], None);
+        assert_eq!(reasons.keys().collect::<Vec<_>>(), ["a", "b", "c", "d"]);
+        assert!(matches!(reasons["c"], AffectedReason::Changed { .. }));
+        assert_eq!(reasons["a"], AffectedReason::Upstream { of: "c".to_string() });
+        assert_eq!(reasons["a"].to_string(), "upstream:c");
+
+        // The changed dependency stays changed
+        let reasons = graph.get_upstream_reasons(&[PathBuf::from("test/c/src/file.rs"), PathBuf::from("test/b/src/x.rs")], None);
+        assert!(matches!(reasons["b"], AffectedReason::Changed { .. }));
+        assert_eq!(reasons["a"], AffectedReason::Upstream { of: "b".to_string() });
+
+        let reasons = graph.get_upstream_reasons(&[PathBuf::from("test/c/src/file.rs")], Some(1));
+        assert_eq!(reasons.keys().collect::<Vec<_>>(), ["b", "c", "d"]);
+    }
+
+    #[test]
+    fn test_affected_chains() {
+        let mut e = create_test_node("e", vec![]);
+        e.cascade_excluded_paths = vec![PathBuf::from("src/**/*.md")];
+        let nodes = vec![
+            create_test_node("a", vec![]),
+            create_test_node("b", vec!["a"]),
+            create_test_node("c", vec!["a"]),
+            create_test_node("d", vec!["b", "c", "e"]),
+            e,
+        ];
+        let graph = DependencyGraph::new(nodes, false).unwrap();
+        let files = [PathBuf::from("test/a/src/lib.rs"), PathBuf::from("test/d/src/main.rs"), PathBuf::from("test/e/src/README.md")];
+
+        let chains = graph.affected_chains(&files, "d");
+        let nodes: Vec<&[String]> = chains.iter().map(|chain| chain.nodes.as_slice()).collect();
+        assert_eq!(nodes, [vec!["d"], vec!["a", "b", "d"]]);
+        assert_eq!(chains[1].to_string(), "a -> b -> d (test/a/src/lib.rs)");
+        assert!(graph.affected_chains(&files[..1], "e").is_empty());
+        assert!(graph.affected_chains(&files, "unknown").is_empty());
+    }
+
+    #[test]
+    fn test_cascade_exclude() {
+        let mut a = create_test_node("a", vec![]);
+        a.cascade_excluded_paths = vec![PathBuf::from("src/**/*.md")];
+        let mut b = create_test_node("b", vec!["a"]);
+        b.cascade_excluded_paths = vec![PathBuf::from("src/**/*.md")];
+        let graph = DependencyGraph::new(vec![a, b, create_test_node("c", vec!["b"])], false).unwrap();
+
+        // Docs-only changes stay on the node itself
+        assert_eq!(graph.get_affected_nodes(&[PathBuf::from("test/a/src/README.md")]), vec!["a"]);
+
+        let reasons = graph.get_affected_reasons(&[
+            PathBuf::from("test/a/src/README.md"),
+            PathBuf::from("test/a/src/lib.rs"),
+        ]);
+        assert_eq!(reasons.keys().collect::<Vec<_>>(), vec!["a", "b", "c"]);
+
+        // A docs-only change still cascades when the node is reached through a dependency
+        let mut b = create_test_node("b", vec!["z"]);
+        b.cascade_excluded_paths = vec![PathBuf::from("src/**/*.md")];
+        let graph = DependencyGraph::new(vec![create_test_node("z", vec![]), b, create_test_node("c", vec!["b"])], false).unwrap();
+        let reasons = graph.get_affected_reasons(&[PathBuf::from("test/b/src/a.md"), PathBuf::from("test/z/src/a.rs")]);
+        assert!(matches!(reasons["b"], AffectedReason::Changed { .. }));
+        assert_eq!(reasons["c"], AffectedReason::Dependency { via: "z".to_string() });
+    }
+
+    #[test]
+    fn test_features() {
+        let graph = DependencyGraph::new(vec![create_test_node("a", vec![])], false).unwrap();
+        assert!(graph.info().features.is_empty());
+
+        let mut a = create_test_node("a", vec![]);
+        a.cascade_excluded_paths = vec![PathBuf::from("src/**/*.md")];
+        let mut graph = DependencyGraph::new(vec![a], false).unwrap();
+        graph.set_manifest_hashes(BTreeMap::from([(PathBuf::from("test/a"), "0".to_string())]));
+        graph.set_info(ArtifactInfo::current());
+        assert_eq!(graph.info().features, vec!["cascade_exclude"]);
+        assert_eq!(graph.info().optional_features, vec!["manifest_hashes"]);
+    }
+
+    #[test]
+    fn test_features_default_edges() {
+        let mut api = create_test_node("api", vec!["core"]);
+        api.manifest = Some(PathBuf::from("test/api/dependencies.toml"));
+        let graph = DependencyGraph::new(vec![create_test_node("core", vec![]), api], false).unwrap();
+        assert!(graph.info().features.is_empty());
+
+        // Where the edge is declared is read back from the dependent's manifest
+        let json = serde_json::to_string(&graph).unwrap();
+        let read: DependencyGraph = serde_json::from_str(&json).unwrap();
+        let (_, _, edge) = read.edges()[0];
+        assert_eq!(edge.declared_in, Some(PathBuf::from("test/api/dependencies.toml")));
+        assert!(read.info().features.is_empty());
+    }
+
+    #[test]
+    fn test_set_generations() {
+        let nodes = vec![
+            create_test_node("core", vec![]),
+            create_test_node("api", vec!["core"]),
+            create_test_node("web", vec!["api", "core"]),
+            create_test_node("docs", vec![]),
+        ];
+        let mut graph = DependencyGraph::new(nodes, false).unwrap();
+        assert_eq!(graph.get_node("web").unwrap().generation, None);
+
+        graph.set_generations();
+        let generation = |name: &str| graph.get_node(name).unwrap().generation;
+        assert_eq!((generation("core"), generation("api"), generation("web"), generation("docs")), (Some(0), Some(1), Some(2), Some(0)));
+        assert_eq!(graph.info().optional_features, vec!["generations"]);
+    }
+
+    #[test]
+    fn test_depends_on() {
+        let nodes = vec![
+            create_test_node("core", vec![]),
+            create_test_node("api", vec!["core"]),
+            create_test_node("web", vec!["api"]),
+            create_test_node("docs", vec![]),
+        ];
+        let mut graph = DependencyGraph::new(nodes, false).unwrap();
+        let check = |graph: &DependencyGraph| {
+            assert!(graph.depends_on("web", "core"));
+            assert!(graph.depends_on("api", "core"));
+            assert!(!graph.depends_on("core", "web"));
+            assert!(!graph.depends_on("web", "docs"));
+            assert!(!graph.depends_on("web", "web"));
+            assert!(!graph.depends_on("web", "unknown"));
+        };
+        check(&graph);
+        graph.set_closure();
+        check(&graph);
+        assert_eq!(graph.info().optional_features, vec!["precomputed_closure"]);
+    }
+
+    #[test]
+    fn test_roots_and_leaves() {
+        let nodes = vec![
+            create_test_node("a", vec![]),
+            create_test_node("b", vec!["a"]),
+            create_test_node("c", vec!["b"]),
+            create_test_node("d", vec!["a"]),
+            create_test_node("e", vec![]),
+        ];
+        let graph = DependencyGraph::new(nodes, false).unwrap();
+        let all: Vec<String> = ["a", "b", "c", "d", "e"].map(String::from).to_vec();
+
+        assert_eq!(graph.roots_of(&all), vec!["a", "e"]);
+        assert_eq!(graph.leaves_of(&all), vec!["c", "d", "e"]);
+
+        // Only the given nodes count, transitively
+        let subset: Vec<String> = ["a", "c"].map(String::from).to_vec();
+        assert_eq!(graph.roots_of(&subset), vec!["a"]);
+        assert_eq!(graph.leaves_of(&subset), vec!["c"]);
+    }
+
     #[test]
     fn test_get_affected_nodes() {
         let nodes = vec![
@@ -378,19 +1177,19 @@ mod tests {
         let graph = DependencyGraph::new(nodes, false).unwrap();
         
         // Test single file change
-        let affected = graph.get_affected_nodes(&vec![PathBuf::from("test/a/src/file.rs")]);
+        let affected = graph.get_affected_nodes(&[PathBuf::from("test/a/src/file.rs")]);
         assert_eq!(HashSet::<String>::from_iter(affected.clone()), 
             HashSet::from_iter(vec!["a".to_string(), "b".to_string(), "c".to_string()]));
 
         // Test multiple file changes
-        let affected = graph.get_affected_nodes(&vec![
+        let affected = graph.get_affected_nodes(&[
             PathBuf::from("test/a/src/file1.rs"),
         ]);
         assert_eq!(HashSet::<String>::from_iter(affected.clone()),
             HashSet::from_iter(vec!["a".to_string(), "b".to_string(), "c".to_string()]));
 
         // Test file that matches no nodes
-        let affected = graph.get_affected_nodes(&vec![PathBuf::from("test/other/file.rs")]);
+        let affected = graph.get_affected_nodes(&[PathBuf::from("test/other/file.rs")]);
         assert!(affected.is_empty());
     }
 }
@@ -1,5 +1,16 @@
+mod artifact;
+mod ci;
+mod config;
+mod edge;
 mod graph;
 mod node;
+mod patterns;
+mod selector;
 
-pub use graph::DependencyGraph;
-pub use node::Node;
+pub use artifact::{ArtifactInfo, CompatibilityError, IndexedFile, MetadataSidecar, SidecarEntry, SCHEMA_VERSION};
+pub use ci::CiMetadata;
+pub use config::{Config, DotfilesConfig, GlobGuardConfig, LintConfig, PluginConfig, Severity};
+pub use edge::{Edge, EdgeKind};
+pub use graph::{AffectedChain, AffectedReason, DependencyGraph, DependencyGraphCreationError};
+pub use node::{negation, ExternalDependency, Node, NodeCreationError};
+pub use selector::{Comparison, Condition, Selector};
@@ -1,6 +1,10 @@
 use serde::{Serialize, Deserialize};
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use toml_edit::{value, Array, DocumentMut, InlineTable, Item, Table};
+
+use super::patterns::PatternCache;
+use super::{CiMetadata, Edge, EdgeKind};
 
 /// Represents a node in the dependency graph.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,6 +21,47 @@ pub struct Node {
     pub excluded_paths: Vec<PathBuf>,
     /// The names of the nodes this node depends on.
     pub dependencies: Vec<String>,
+    /// Free-form labels used to group nodes (e.g. `frontend`, `deployable`).
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Whether the node only passes changes on to its dependents, e.g. shared CI templates or
+    /// a schema registry: it's never selected as work to do itself.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub trigger_only: bool,
+    /// Patterns of files that mark the node as affected without affecting its dependents,
+    /// e.g. documentation.
+    #[serde(default)]
+    pub cascade_excluded_paths: Vec<PathBuf>,
+    /// Nodes of other repositories this node depends on, resolved by `artifact merge`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub external_dependencies: Vec<ExternalDependency>,
+    /// The `kind` and `paths` of the dependencies declared with them, by dependency name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dependency_edges: BTreeMap<String, Edge>,
+    /// The manifest declaring the node, relative to the prepared directory. Set by `prepare`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manifest: Option<PathBuf>,
+    /// Whether `*`, `?` and `**` in the node's patterns skip dotfiles and dot-directories,
+    /// which only patterns naming them then match. Set by `prepare` from the root config.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub hide_dotfiles: bool,
+    /// The length of the longest chain of dependencies below the node, 0 for the nodes
+    /// without dependencies. Nodes of the same generation can be built in parallel once the
+    /// previous generations are. Set by `prepare`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generation: Option<usize>,
+    /// The patterns, compiled on the first match and again whenever they change.
+    #[serde(skip)]
+    pub(crate) compiled_patterns: PatternCache,
+}
+
+/// A dependency on a node of another repository, declared as
+/// `{ repo = "platform-repo", name = "auth-sdk" }`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ExternalDependency {
+    /// The name the other repository's artifact was prepared with, see `prepare --repo`.
+    pub repo: String,
+    pub name: String,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -29,6 +74,8 @@ pub enum NodeCreationError {
     TomlParseError(#[from] toml::de::Error),
     #[error("Failed to convert metadata to JSON: {0}")]
     MetadataConversionError(#[from] serde_json::Error),
+    #[error("Invalid [metadata.ci] for node {0}: {1}")]
+    InvalidCiMetadata(String, serde_json::Error),
 }
 
 
@@ -47,11 +94,21 @@ struct TomlRoot {
 #[derive(Debug, Deserialize)]
 struct TomlModule {
     name: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    trigger_only: bool,
 }
 
 #[derive(Debug, Deserialize)]
 struct TomlDependency {
     name: String,
+    #[serde(default)]
+    repo: Option<String>,
+    #[serde(default)]
+    kind: EdgeKind,
+    #[serde(default)]
+    paths: Vec<String>,
 }
 
 #[derive(Debug, Deserialize, Default)]
@@ -60,6 +117,8 @@ struct TomlFilePaths {
     include: Vec<String>,
     #[serde(default)]
     exclude: Vec<String>,
+    #[serde(default)]
+    cascade_exclude: Vec<String>,
 }
 
 
@@ -71,7 +130,23 @@ impl Node {
             return Err(NodeCreationError::NoIncludedPaths(name));
         }
 
-        Ok(Self { name, path, included_paths, excluded_paths, dependencies, metadata })
+        Ok(Self {
+            name,
+            path,
+            included_paths,
+            excluded_paths,
+            dependencies,
+            metadata,
+            tags: Vec::new(),
+            trigger_only: false,
+            cascade_excluded_paths: Vec::new(),
+            external_dependencies: Vec::new(),
+            dependency_edges: BTreeMap::new(),
+            manifest: None,
+            hide_dotfiles: false,
+            generation: None,
+            compiled_patterns: PatternCache::default(),
+        })
     }
 
     /// Constructs a `Node` by reading and parsing a TOML file.
@@ -93,55 +168,281 @@ impl Node {
             serde_json::to_value(m).unwrap_or_default()
         });
 
-        // Gather dependency names from the [dependencies] table
+        // Gather dependency names from the [dependencies] table, those of other repositories apart
         let dependencies = parsed
             .dependencies
             .values()
+            .filter(|dep| dep.repo.is_none())
             .map(|dep| dep.name.clone())
             .collect::<Vec<_>>();
+        let mut external_dependencies: Vec<ExternalDependency> = parsed
+            .dependencies
+            .values()
+            .filter_map(|dep| Some(ExternalDependency { repo: dep.repo.clone()?, name: dep.name.clone() }))
+            .collect();
+        external_dependencies.sort();
+        let dependency_edges: BTreeMap<String, Edge> = parsed
+            .dependencies
+            .values()
+            .filter(|dep| dep.repo.is_none() && (dep.kind != EdgeKind::default() || !dep.paths.is_empty()))
+            .map(|dep| (dep.name.clone(), Edge {
+                kind: dep.kind,
+                declared_in: None,
+                paths: dep.paths.iter().map(PathBuf::from).collect(),
+            }))
+            .collect();
 
         // Create the node via the existing ::new method
-        Node::new(
+        let mut node = Node::new(
             parsed.module.name,
             node_path,
-            parsed.file_paths.include.iter().map(|s| PathBuf::from(s)).collect(),
-            parsed.file_paths.exclude.iter().map(|s| PathBuf::from(s)).collect(),
+            parsed.file_paths.include.iter().map(PathBuf::from).collect(),
+            parsed.file_paths.exclude.iter().map(PathBuf::from).collect(),
             dependencies,
             metadata_json,
-        )
+        )?;
+        node.tags = parsed.module.tags;
+        node.trigger_only = parsed.module.trigger_only;
+        node.cascade_excluded_paths = parsed.file_paths.cascade_exclude.iter().map(PathBuf::from).collect();
+        node.external_dependencies = external_dependencies;
+        node.dependency_edges = dependency_edges;
+        node.ci().map_err(|e| NodeCreationError::InvalidCiMetadata(node.name.clone(), e))?;
+
+        Ok(node)
+    }
+
+    /// Merges default metadata under the node's own: tables are merged key by key, any other
+    /// value of the node replaces the default. The result is checked like a manifest's.
+    pub fn merge_default_metadata(&mut self, defaults: &serde_json::Value) -> Result<(), NodeCreationError> {
+        let metadata = match self.metadata.take() {
+            Some(own) => merged(defaults, own),
+            None => defaults.clone(),
+        };
+        self.metadata = Some(metadata);
+        self.ci().map_err(|e| NodeCreationError::InvalidCiMetadata(self.name.clone(), e))?;
+        Ok(())
+    }
+
+    /// The owners recorded in the node's `metadata.owners` (a single owner or a list of them).
+    pub fn owners(&self) -> Vec<String> {
+        self.metadata_strings("owners")
+    }
+
+    /// The reviewers recorded in the node's `metadata.required_reviewers` (a single one or a list of them).
+    pub fn required_reviewers(&self) -> Vec<String> {
+        self.metadata_strings("required_reviewers")
+    }
+
+    /// Whether the node's `metadata.security_sensitive` is `true`, so changes affecting it
+    /// need extra approvals.
+    pub fn security_sensitive(&self) -> bool {
+        self.metadata_value("security_sensitive").and_then(|v| v.as_bool()).unwrap_or(false)
+    }
+
+    /// The progressive delivery group of the node, from its `metadata.rollout.group`.
+    pub fn rollout_group(&self) -> Option<&str> {
+        self.metadata_value("rollout.group").and_then(|v| v.as_str())
+    }
+
+    /// The test commands recorded in the node's `metadata.tests` (a single command or a list of them).
+    pub fn tests(&self) -> Vec<String> {
+        self.metadata_strings("tests")
+    }
+
+    /// The node's `metadata.ci`, empty when it has none.
+    pub fn ci(&self) -> Result<CiMetadata, serde_json::Error> {
+        match self.metadata.as_ref().and_then(|m| m.get("ci")) {
+            Some(ci) => serde_json::from_value(ci.clone()),
+            None => Ok(CiMetadata::default()),
+        }
+    }
+
+    /// The definition of `task` in the node's `metadata.tasks`: a command, a list of
+    /// commands, or a table with the commands under `run` and settings such as `timeout`.
+    pub fn task(&self, task: &str) -> Option<&serde_json::Value> {
+        self.metadata.as_ref().and_then(|m| m.get("tasks")).and_then(|t| t.get(task))
+    }
+
+    /// The commands recorded for `task` in the node's `metadata.tasks`, run one after the other.
+    pub fn task_commands(&self, task: &str) -> Vec<String> {
+        match self.task(task) {
+            Some(serde_json::Value::Object(table)) => strings(table.get("run")),
+            value => strings(value),
+        }
+    }
+
+    /// The output paths of `task` recorded in the node's `metadata.outputs`, as glob patterns
+    /// relative to the node.
+    pub fn task_outputs(&self, task: &str) -> Vec<String> {
+        strings(self.metadata.as_ref().and_then(|m| m.get("outputs")).and_then(|o| o.get(task)))
+    }
+
+    /// The environment variables recorded in the node's `metadata.env`. Numbers and
+    /// booleans are converted to strings, other values are ignored.
+    pub fn env(&self) -> BTreeMap<String, String> {
+        let Some(env) = self.metadata.as_ref().and_then(|m| m.get("env")).and_then(|e| e.as_object()) else {
+            return BTreeMap::new();
+        };
+        env.iter()
+            .filter_map(|(key, value)| match value {
+                serde_json::Value::String(s) => Some((key.clone(), s.clone())),
+                serde_json::Value::Number(n) => Some((key.clone(), n.to_string())),
+                serde_json::Value::Bool(b) => Some((key.clone(), b.to_string())),
+                _ => None,
+            })
+            .collect()
     }
 
-    /// Returns true if the given path matches any of the included paths and none of the excluded paths.
-    /// Paths are checked relative to the node's base path.
+    /// Looks up a metadata value by its dotted path, e.g. `deploy.region`.
+    pub fn metadata_value(&self, path: &str) -> Option<&serde_json::Value> {
+        path.split('.').try_fold(self.metadata.as_ref()?, |value, key| value.get(key))
+    }
+
+    /// Reads a metadata key holding either a single string or a list of strings.
+    fn metadata_strings(&self, key: &str) -> Vec<String> {
+        strings(self.metadata.as_ref().and_then(|m| m.get(key)))
+    }
+
+    /// Returns true if the node carries the given tag.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    /// Renders the node back into the `dependencies.toml` format understood by
+    /// [`Node::from_toml_str`]. Dependencies are keyed by their own name.
+    ///
+    /// # Returns
+    /// The TOML document as a string, or an error if the metadata can't be represented in TOML.
+    pub fn to_toml_string(&self) -> Result<String, toml_edit::ser::Error> {
+        let mut doc = DocumentMut::new();
+
+        let mut module = Table::new();
+        module.insert("name", value(self.name.as_str()));
+        if !self.tags.is_empty() {
+            module.insert("tags", value(self.tags.iter().collect::<Array>()));
+        }
+        if self.trigger_only {
+            module.insert("trigger_only", value(true));
+        }
+        doc.insert("module", Item::Table(module));
+
+        if !self.dependencies.is_empty() || !self.external_dependencies.is_empty() {
+            let mut dependencies = Table::new();
+            for dep in &self.dependencies {
+                let mut entry = InlineTable::new();
+                entry.insert("name", dep.as_str().into());
+                if let Some(edge) = self.dependency_edges.get(dep) {
+                    if edge.kind != EdgeKind::default() {
+                        entry.insert("kind", edge.kind.as_str().into());
+                    }
+                    if !edge.paths.is_empty() {
+                        entry.insert("paths", edge.paths.iter().map(|p| p.to_string_lossy().to_string()).collect::<Array>().into());
+                    }
+                }
+                dependencies.insert(dep, value(entry));
+            }
+            for dep in &self.external_dependencies {
+                let mut entry = InlineTable::new();
+                entry.insert("repo", dep.repo.as_str().into());
+                entry.insert("name", dep.name.as_str().into());
+                dependencies.insert(&format!("{}:{}", dep.repo, dep.name), value(entry));
+            }
+            doc.insert("dependencies", Item::Table(dependencies));
+        }
+
+        if let Some(metadata) = &self.metadata {
+            let metadata = toml_edit::ser::to_document(metadata)?;
+            doc.insert("metadata", Item::Table(metadata.as_table().clone()));
+        }
+
+        let to_array = |paths: &[PathBuf]| {
+            paths.iter().map(|p| p.to_string_lossy().to_string()).collect::<Array>()
+        };
+        let mut file_paths = Table::new();
+        file_paths.insert("include", value(to_array(&self.included_paths)));
+        if !self.excluded_paths.is_empty() {
+            file_paths.insert("exclude", value(to_array(&self.excluded_paths)));
+        }
+        if !self.cascade_excluded_paths.is_empty() {
+            file_paths.insert("cascade_exclude", value(to_array(&self.cascade_excluded_paths)));
+        }
+        doc.insert("file_paths", Item::Table(file_paths));
+
+        Ok(doc.to_string())
+    }
+
+    /// Returns true if the given path matches the included paths and not the excluded paths,
+    /// each list read in order with `!` negations. Paths are checked relative to the node's base path.
     /// 
     /// # Arguments
     /// * `path` - The path to check.
     ///
     /// # Returns
     /// A boolean indicating whether the path is included.
-    pub fn includes_path(&self, path: &PathBuf) -> bool {
-        // First check if path matches any include pattern
-        let matches_include = self.included_paths.iter()
-            .any(|pattern| {
-                let full_pattern = self.path.join(pattern);
-                // println!("full_pattern: {}", full_pattern.to_str().unwrap());
-                glob::Pattern::new(full_pattern.to_str().unwrap())
-                    .map(|p| p.matches_path(path))
-                    .unwrap_or(false)
-            });
-        
-        // println!("matches_include: {}", matches_include);
+    pub fn includes_path(&self, path: &Path) -> bool {
+        let compiled = self.compiled_patterns.get(self);
+        let [included, excluded, _] = &*compiled;
+        included.matches(self, path) && !excluded.matches(self, path)
+    }
+
+    /// Returns true if a change to the given path, which must be included by the node, also
+    /// affects the node's dependents, i.e. if it matches none of the `cascade_exclude` patterns.
+    pub fn cascades_path(&self, path: &Path) -> bool {
+        let compiled = self.compiled_patterns.get(self);
+        let [_, _, cascade_excluded] = &*compiled;
+        !cascade_excluded.matches(self, path)
+    }
+
+    /// Returns true if the path matches a single pattern, without its `!`, relative to the
+    /// node's base path.
+    pub fn matches_pattern(&self, pattern: &Path, path: &Path) -> bool {
+        if let Some(plugin_pattern) = pattern.to_str().and_then(|pattern| pattern.strip_prefix("plugin:")) {
+            return crate::plugin::matches(plugin_pattern, self, path);
+        }
+        let full_pattern = self.path.join(pattern);
+        let options = glob::MatchOptions { require_literal_leading_dot: self.hide_dotfiles, ..Default::default() };
+        glob::Pattern::new(&full_pattern.to_string_lossy())
+            .map(|p| p.matches_path_with(path, options))
+            .unwrap_or(false)
+    }
+}
+
+/// Splits the `!` off a negated pattern.
+///
+/// ### Returns
+/// * `(bool, &Path)` - Whether the pattern is negated, and the pattern without its `!`
+pub fn negation(pattern: &Path) -> (bool, &Path) {
+    match pattern.to_str().and_then(|pattern| pattern.strip_prefix('!')) {
+        Some(negated) => (true, Path::new(negated)),
+        None => (false, pattern),
+    }
+}
 
-        // Then check it's not explicitly excluded
-        let matches_exclude = self.excluded_paths.iter()
-            .any(|pattern| {
-                let full_pattern = self.path.join(pattern);
-                glob::Pattern::new(full_pattern.to_str().unwrap())
-                    .map(|p| p.matches_path(path))
-                    .unwrap_or(false)
-            });
+/// Deep-merges `own` over `defaults`.
+fn merged(defaults: &serde_json::Value, own: serde_json::Value) -> serde_json::Value {
+    match (defaults, own) {
+        (serde_json::Value::Object(defaults), serde_json::Value::Object(mut own)) => {
+            for (key, default) in defaults {
+                let value = match own.remove(key) {
+                    Some(value) => merged(default, value),
+                    None => default.clone(),
+                };
+                own.insert(key.clone(), value);
+            }
+            serde_json::Value::Object(own)
+        }
+        (_, own) => own,
+    }
+}
 
-        matches_include && !matches_exclude
+/// Reads a metadata value holding either a single string or a list of strings.
+fn strings(value: Option<&serde_json::Value>) -> Vec<String> {
+    match value {
+        Some(serde_json::Value::String(value)) => vec![value.clone()],
+        Some(serde_json::Value::Array(values)) => values.iter()
+            .filter_map(|v| v.as_str().map(String::from))
+            .collect(),
+        _ => Vec::new(),
     }
 }
 
@@ -190,6 +491,7 @@ mod tests {
         let toml = r#"
             [module]
             name = "test-module"
+            tags = ["backend", "deployable"]
 
             [dependencies]
             dep1 = { name = "dependency-1" }
@@ -209,6 +511,8 @@ mod tests {
         assert_eq!(node.dependencies.len(), 2);
         assert!(node.dependencies.contains(&"dependency-1".to_string()));
         assert!(node.dependencies.contains(&"dependency-2".to_string()));
+        assert_eq!(node.tags, vec!["backend", "deployable"]);
+        assert!(node.has_tag("backend"));
     }
 
     #[test]
@@ -228,6 +532,7 @@ mod tests {
         assert!(node.excluded_paths.is_empty());
         assert!(node.dependencies.is_empty());
         assert!(node.metadata.is_none());
+        assert!(node.tags.is_empty());
     }
 
     #[test]
@@ -252,6 +557,24 @@ mod tests {
         assert!(matches!(result, Err(NodeCreationError::TomlParseError(_))));
     }
 
+    #[test]
+    fn test_from_toml_invalid_ci() {
+        let toml = r#"
+            [module]
+            name = "api"
+
+            [file_paths]
+            include = ["src/**"]
+
+            [metadata.ci]
+            test = "cargo test"
+            runner = "gigantic"
+        "#;
+
+        let result = Node::from_toml_str(toml, PathBuf::from("/test"));
+        assert!(matches!(result, Err(NodeCreationError::InvalidCiMetadata(name, _)) if name == "api"));
+    }
+
     #[test]
     fn test_from_toml_no_includes() {
         let no_includes = r#"
@@ -292,6 +615,84 @@ mod tests {
         assert_eq!(metadata["bool"], true);
     }
 
+    #[test]
+    fn test_to_toml_roundtrip() {
+        let node = Node::new(
+            "@scope/app".to_string(),
+            PathBuf::from("apps/app"),
+            vec![PathBuf::from("src/**")],
+            vec![PathBuf::from("dist/**")],
+            vec!["lib".to_string()],
+            Some(serde_json::json!({"type": "app", "nested": {"num": 42}}))
+        ).unwrap();
+        let node = Node {
+            tags: vec!["frontend".to_string()],
+            cascade_excluded_paths: vec![PathBuf::from("**/*.md")],
+            external_dependencies: vec![ExternalDependency { repo: "platform".to_string(), name: "auth-sdk".to_string() }],
+            dependency_edges: BTreeMap::from([("lib".to_string(), Edge {
+                kind: EdgeKind::Build,
+                declared_in: None,
+                paths: vec![PathBuf::from("proto/**")],
+            })]),
+            ..node
+        };
+
+        let toml = node.to_toml_string().unwrap();
+        let parsed = Node::from_toml_str(&toml, PathBuf::from("apps/app")).unwrap();
+
+        assert_eq!(parsed.name, node.name);
+        assert_eq!(parsed.dependencies, node.dependencies);
+        assert_eq!(parsed.included_paths, node.included_paths);
+        assert_eq!(parsed.excluded_paths, node.excluded_paths);
+        assert_eq!(parsed.metadata, node.metadata);
+        assert_eq!(parsed.tags, node.tags);
+        assert_eq!(parsed.cascade_excluded_paths, node.cascade_excluded_paths);
+        assert_eq!(parsed.external_dependencies, node.external_dependencies);
+        assert_eq!(parsed.dependency_edges, node.dependency_edges);
+    }
+
+    #[test]
+    fn test_owners() {
+        let mut node = Node::new(
+            "test".to_string(),
+            PathBuf::from("test"),
+            vec![PathBuf::from("src/**")],
+            vec![],
+            vec![],
+            Some(serde_json::json!({"owners": "@org/team"}))
+        ).unwrap();
+        assert_eq!(node.owners(), vec!["@org/team"]);
+
+        node.metadata = Some(serde_json::json!({"owners": ["@org/a", "@org/b"]}));
+        assert_eq!(node.owners(), vec!["@org/a", "@org/b"]);
+
+        node.metadata = None;
+        assert!(node.owners().is_empty());
+    }
+
+    #[test]
+    fn test_tests_metadata() {
+        let toml = r#"
+            [module]
+            name = "test"
+
+            [file_paths]
+            include = ["src/**"]
+
+            [metadata]
+            tests = ["cargo test -p test", "cargo test -p test --doc"]
+
+            [metadata.tasks]
+            build = "cargo build -p test"
+            e2e = { run = ["npm ci", "npm run e2e"], timeout = "10m" }
+        "#;
+        let node = Node::from_toml_str(toml, PathBuf::from("test")).unwrap();
+        assert_eq!(node.tests(), vec!["cargo test -p test", "cargo test -p test --doc"]);
+        assert_eq!(node.task_commands("build"), vec!["cargo build -p test"]);
+        assert_eq!(node.task_commands("e2e"), vec!["npm ci", "npm run e2e"]);
+        assert!(node.task_commands("lint").is_empty());
+    }
+
     #[test]
     fn test_includes_path() {
         let node = Node::new(
@@ -314,6 +715,102 @@ mod tests {
         assert!(!node.includes_path(&PathBuf::from("test/other/file.rs")));
     }
 
+    #[test]
+    fn test_includes_path_negation() {
+        let node = Node::new(
+            "test".to_string(),
+            PathBuf::from("test"),
+            vec![PathBuf::from("**"), PathBuf::from("!generated/**"), PathBuf::from("generated/keep/**")],
+            vec![PathBuf::from("**/*.log"), PathBuf::from("!important.log")],
+            vec![],
+            None
+        ).unwrap();
+
+        // The last matching include wins
+        assert!(node.includes_path(&PathBuf::from("test/src/lib.rs")));
+        assert!(!node.includes_path(&PathBuf::from("test/generated/api.rs")));
+        assert!(node.includes_path(&PathBuf::from("test/generated/keep/api.rs")));
+
+        // And the last matching exclude
+        assert!(!node.includes_path(&PathBuf::from("test/src/debug.log")));
+        assert!(node.includes_path(&PathBuf::from("test/important.log")));
+    }
+
+    #[test]
+    fn test_merge_default_metadata() {
+        let toml = r#"
+            [module]
+            name = "api"
+
+            [metadata]
+            owners = ["@org/api"]
+            ci = { runner = "large" }
+
+            [metadata.tasks]
+            build = "cargo build -p api"
+
+            [file_paths]
+            include = ["**"]
+        "#;
+        let mut node = Node::from_toml_str(toml, PathBuf::from("api")).unwrap();
+        let defaults = serde_json::json!({
+            "owners": ["@org/platform"],
+            "ci": { "runner": "small", "cache_paths": ["target/"] },
+            "tasks": { "lint": "cargo clippy" },
+        });
+        node.merge_default_metadata(&defaults).unwrap();
+
+        assert_eq!(node.metadata, Some(serde_json::json!({
+            "owners": ["@org/api"],
+            "ci": { "runner": "large", "cache_paths": ["target/"] },
+            "tasks": { "build": "cargo build -p api", "lint": "cargo clippy" },
+        })));
+
+        let mut node = Node::from_toml_str(toml, PathBuf::from("api")).unwrap();
+        let invalid = serde_json::json!({ "ci": { "runners": "small" } });
+        assert!(matches!(node.merge_default_metadata(&invalid), Err(NodeCreationError::InvalidCiMetadata(..))));
+    }
+
+    #[test]
+    fn test_includes_path_hidden() {
+        let mut node = Node::new(
+            "test".to_string(),
+            PathBuf::from("test"),
+            vec![PathBuf::from("**"), PathBuf::from(".github/**")],
+            vec![],
+            vec![],
+            None
+        ).unwrap();
+        assert!(node.includes_path(&PathBuf::from("test/.config/settings.toml")));
+
+        node.hide_dotfiles = true;
+        assert!(!node.includes_path(&PathBuf::from("test/.config/settings.toml")));
+        assert!(!node.includes_path(&PathBuf::from("test/src/.env")));
+        assert!(node.includes_path(&PathBuf::from("test/.github/workflows/ci.yml")));
+        assert!(node.includes_path(&PathBuf::from("test/src/lib.rs")));
+    }
+
+    #[test]
+    fn test_includes_path_after_changing_patterns() {
+        let mut node = Node::new(
+            "test".to_string(),
+            PathBuf::from("test"),
+            vec![PathBuf::from("src/**")],
+            vec![],
+            vec![],
+            None
+        ).unwrap();
+        assert!(node.includes_path(&PathBuf::from("test/src/lib.rs")));
+
+        node.excluded_paths.push(PathBuf::from("src/lib.rs"));
+        assert!(!node.includes_path(&PathBuf::from("test/src/lib.rs")));
+
+        node.path = PathBuf::from("moved");
+        node.included_paths = vec![PathBuf::from("docs/**")];
+        assert!(node.includes_path(&PathBuf::from("moved/docs/index.md")));
+        assert!(!node.includes_path(&PathBuf::from("test/src/main.rs")));
+    }
+
     #[test]
     fn test_includes_path_no_excludes() {
         let node = Node::new(
@@ -0,0 +1,186 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, PoisonError, RwLock};
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+
+use super::node::{negation, Node};
+
+/// The include, exclude and `cascade_exclude` patterns of a node, compiled the first time
+/// the node matches a path. They're kept with the path and patterns they were compiled
+/// from, and compiled again when the node's fields no longer match them. Cloning a node
+/// drops them, since the clone's patterns may be changed before it matches anything.
+#[derive(Debug, Default)]
+pub(crate) struct PatternCache(RwLock<Option<(PatternKey, Arc<[CompiledPatterns; 3]>)>>);
+
+/// The fields of a node its compiled patterns depend on.
+#[derive(Debug)]
+struct PatternKey {
+    path: PathBuf,
+    included_paths: Vec<PathBuf>,
+    excluded_paths: Vec<PathBuf>,
+    cascade_excluded_paths: Vec<PathBuf>,
+}
+
+impl PatternKey {
+    fn of(node: &Node) -> Self {
+        PatternKey {
+            path: node.path.clone(),
+            included_paths: node.included_paths.clone(),
+            excluded_paths: node.excluded_paths.clone(),
+            cascade_excluded_paths: node.cascade_excluded_paths.clone(),
+        }
+    }
+
+    fn is_for(&self, node: &Node) -> bool {
+        self.path == node.path
+            && self.included_paths == node.included_paths
+            && self.excluded_paths == node.excluded_paths
+            && self.cascade_excluded_paths == node.cascade_excluded_paths
+    }
+}
+
+impl Clone for PatternCache {
+    fn clone(&self) -> Self {
+        PatternCache::default()
+    }
+}
+
+impl PatternCache {
+    /// The compiled include, exclude and `cascade_exclude` patterns of the node.
+    pub(crate) fn get(&self, node: &Node) -> Arc<[CompiledPatterns; 3]> {
+        if let Some((key, compiled)) = &*self.0.read().unwrap_or_else(PoisonError::into_inner) {
+            if key.is_for(node) {
+                return compiled.clone();
+            }
+        }
+
+        let compiled = Arc::new([
+            CompiledPatterns::new(&node.path, &node.included_paths),
+            CompiledPatterns::new(&node.path, &node.excluded_paths),
+            CompiledPatterns::new(&node.path, &node.cascade_excluded_paths),
+        ]);
+        *self.0.write().unwrap_or_else(PoisonError::into_inner) = Some((PatternKey::of(node), compiled.clone()));
+        compiled
+    }
+}
+
+/// A list of patterns, relative to the node's base path. As in a `.gitignore`, the last
+/// pattern matching a path wins, and a `!` in front of a pattern negates it: `["**",
+/// "!generated/**", "generated/keep/**"]` matches everything but `generated/`, except
+/// `generated/keep/`. Patterns of the form `plugin:<name>:<argument>` are evaluated by the
+/// named plugin.
+///
+/// All the globs are compiled into one `GlobSet`, which finds the candidate patterns of a
+/// path in a single pass. The set matches a superset of what `glob` does, dotfiles aside, so
+/// each candidate is confirmed with its `glob::Pattern`, from the last one, keeping the exact
+/// semantics of the patterns.
+#[derive(Debug)]
+pub(crate) struct CompiledPatterns {
+    /// Every pattern, in order, with whether it's negated.
+    patterns: Vec<(bool, Compiled)>,
+    set: GlobSet,
+    /// The index in `patterns` of each glob of `set`.
+    globs: Vec<usize>,
+    /// The patterns that are candidates for every path: plugin patterns, and globs the set
+    /// doesn't accept.
+    always: Vec<usize>,
+}
+
+#[derive(Debug)]
+enum Compiled {
+    Glob(glob::Pattern),
+    /// A `plugin:<name>:<argument>` pattern, without its `plugin:`.
+    Plugin(String),
+    /// A glob that doesn't compile, which matches nothing.
+    Invalid,
+}
+
+impl CompiledPatterns {
+    fn new(node_path: &Path, patterns: &[PathBuf]) -> Self {
+        let mut compiled = Vec::new();
+        let mut builder = GlobSetBuilder::new();
+        let mut globs = Vec::new();
+        let mut always = Vec::new();
+        for (i, pattern) in patterns.iter().enumerate() {
+            let (negated, pattern) = negation(pattern);
+            if let Some(plugin_pattern) = pattern.to_str().and_then(|pattern| pattern.strip_prefix("plugin:")) {
+                compiled.push((negated, Compiled::Plugin(plugin_pattern.to_string())));
+                always.push(i);
+                continue;
+            }
+            let full_pattern = node_path.join(pattern).to_string_lossy().to_string();
+            let Ok(glob) = glob::Pattern::new(&full_pattern) else {
+                compiled.push((negated, Compiled::Invalid));
+                continue;
+            };
+            compiled.push((negated, Compiled::Glob(glob)));
+            match set_glob(&full_pattern) {
+                Some(set_glob) => {
+                    builder.add(set_glob);
+                    globs.push(i);
+                }
+                None => always.push(i),
+            }
+        }
+        let set = builder.build().unwrap_or_else(|_| {
+            // Every glob was accepted on its own, but if the set still fails, check them all
+            always.append(&mut globs);
+            GlobSet::empty()
+        });
+        CompiledPatterns { patterns: compiled, set, globs, always }
+    }
+
+    /// Whether the last of the patterns matching the path isn't negated.
+    pub(crate) fn matches(&self, node: &Node, path: &Path) -> bool {
+        let mut candidates: Vec<usize> = self.set.matches(path).into_iter().map(|i| self.globs[i]).collect();
+        candidates.extend(&self.always);
+        candidates.sort_unstable_by(|a, b| b.cmp(a));
+
+        let options = glob::MatchOptions { require_literal_leading_dot: node.hide_dotfiles, ..Default::default() };
+        candidates.into_iter()
+            .find(|&i| match &self.patterns[i].1 {
+                Compiled::Glob(glob) => glob.matches_path_with(path, options),
+                Compiled::Plugin(pattern) => crate::plugin::matches(pattern, node, path),
+                Compiled::Invalid => false,
+            })
+            .is_some_and(|i| !self.patterns[i].0)
+    }
+}
+
+/// Translates a `glob` pattern for the set: `*` and `?` cross directories, backslashes and
+/// braces are literal, and a trailing `/` is ignored, as in `glob`.
+fn set_glob(pattern: &str) -> Option<globset::Glob> {
+    let trimmed = pattern.trim_end_matches('/');
+    let pattern = if trimmed.is_empty() { pattern } else { trimmed };
+    GlobBuilder::new(&pattern.replace('{', "[{]").replace('}', "[}]"))
+        .literal_separator(false)
+        .backslash_escape(false)
+        .build()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_matches_like_glob() {
+        let patterns = [
+            "**", "a/**", "a/**/b", "**/*.md", "a/*", "a/*.rs", "*", "a/b", "a/**/*", "**/b/**", "a/[!x]*",
+            "a/?", "a/[a-c]/b", "a/{x,y}", "a\\b", "a/**/", "**/a", "a/.*", "a/*/c", "a/b*/**", "[*]", "a/***",
+        ];
+        let paths = [
+            "a", "a/b", "a/b/c", "a/b/c.md", "x.md", "a/x/y/b", "a/.git/x", "a/.hidden", "b", "a/b.rs", "a/c",
+            "a/{x,y}", "a\\b", "c/a", "a/bb/c", "*", "a/b/c/d.rs", "a/bx/q/r",
+        ];
+        for pattern in patterns {
+            let glob = glob::Pattern::new(pattern).ok();
+            let set = set_glob(pattern).map(|glob| glob.compile_matcher());
+            for path in paths {
+                let matched = glob.as_ref().is_some_and(|glob| glob.matches(path));
+                let candidate = set.as_ref().is_none_or(|set| set.is_match(path));
+                assert!(!matched || candidate, "{} matches {} but isn't a candidate", pattern, path);
+            }
+        }
+    }
+}
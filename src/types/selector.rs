@@ -0,0 +1,279 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde_json::Value;
+
+use super::Node;
+
+/// A condition on node metadata, as given to `--select`. The key is a dotted path into the
+/// metadata, optionally starting with `metadata.`:
+///
+/// * `deploy.region=eu` - the value, compared through its string form
+/// * `metadata.team == "payments"` and `!=` - the value, compared to a JSON literal, or to a
+///   string when the right-hand side isn't one
+/// * `tier < 3`, `<=`, `>` and `>=` - the value, compared to a number
+/// * `metadata.ci.runner exists` and `not exists` - whether there's a non-null value
+///
+/// A list matches if any of its items does, except for `!=`, which matches when none does.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selector {
+    pub key: String,
+    pub condition: Condition,
+}
+
+/// What a [`Selector`] checks the value at its key against.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    /// `KEY=VALUE`
+    Matches(String),
+    /// `KEY == VALUE`
+    Equals(Value),
+    /// `KEY != VALUE`
+    NotEquals(Value),
+    /// `KEY < N` and the like
+    Compare(Comparison, f64),
+    /// `KEY exists`
+    Exists,
+    /// `KEY not exists`
+    Missing,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SelectorParseError {
+    #[error("Invalid selector '{0}', expected KEY=VALUE, KEY == VALUE, KEY != VALUE, KEY < N or KEY exists")]
+    MissingOperator(String),
+    #[error("Invalid selector '{0}', the key is empty")]
+    EmptyKey(String),
+    #[error("Invalid selector '{0}', '{1}' isn't a number")]
+    NotANumber(String, String),
+}
+
+impl FromStr for Selector {
+    type Err = SelectorParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let trimmed = s.trim();
+        let (key, condition) = if let Some(key) = trimmed.strip_suffix(" not exists") {
+            (key, Condition::Missing)
+        } else if let Some(key) = trimmed.strip_suffix(" exists") {
+            (key, Condition::Exists)
+        } else {
+            let start = trimmed.find(['=', '!', '<', '>']).ok_or_else(|| SelectorParseError::MissingOperator(s.to_string()))?;
+            let (key, rest) = trimmed.split_at(start);
+            let operator_len = if rest[1..].starts_with('=') { 2 } else { 1 };
+            let (operator, value) = rest.split_at(operator_len);
+            let value = value.trim();
+            let number = || value.parse::<f64>().map_err(|_| SelectorParseError::NotANumber(s.to_string(), value.to_string()));
+            let condition = match operator {
+                "=" => Condition::Matches(value.to_string()),
+                "==" => Condition::Equals(literal(value)),
+                "!=" => Condition::NotEquals(literal(value)),
+                "<" => Condition::Compare(Comparison::Less, number()?),
+                "<=" => Condition::Compare(Comparison::LessOrEqual, number()?),
+                ">" => Condition::Compare(Comparison::Greater, number()?),
+                ">=" => Condition::Compare(Comparison::GreaterOrEqual, number()?),
+                _ => return Err(SelectorParseError::MissingOperator(s.to_string())),
+            };
+            (key, condition)
+        };
+        let key = key.trim();
+        let key = key.strip_prefix("metadata.").unwrap_or(key);
+        if key.is_empty() {
+            return Err(SelectorParseError::EmptyKey(s.to_string()));
+        }
+        Ok(Selector { key: key.to_string(), condition })
+    }
+}
+
+/// The JSON value of the right-hand side of `==` or `!=`, or the text itself as a string.
+fn literal(value: &str) -> Value {
+    serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()))
+}
+
+impl Selector {
+    /// True if the node's metadata at `key` satisfies the condition.
+    pub fn matches(&self, node: &Node) -> bool {
+        let value = node.metadata_value(&self.key).filter(|value| !value.is_null());
+        match (&self.condition, value) {
+            (Condition::Exists, value) => value.is_some(),
+            (Condition::Missing, value) => value.is_none(),
+            (Condition::NotEquals(expected), value) => !value.is_some_and(|value| self.matches_value(value, &Condition::Equals(expected.clone()))),
+            (condition, Some(value)) => self.matches_value(value, condition),
+            (_, None) => false,
+        }
+    }
+
+    fn matches_value(&self, value: &Value, condition: &Condition) -> bool {
+        if let Value::Array(items) = value {
+            if !matches!(condition, Condition::Equals(Value::Array(_))) {
+                return items.iter().any(|item| self.matches_value(item, condition));
+            }
+        }
+        match condition {
+            Condition::Matches(expected) => match value {
+                Value::String(s) => s == expected,
+                Value::Number(n) => n.to_string() == *expected,
+                Value::Bool(b) => b.to_string() == *expected,
+                Value::Null | Value::Array(_) | Value::Object(_) => false,
+            },
+            Condition::Equals(expected) => match (value.as_f64(), expected.as_f64()) {
+                (Some(a), Some(b)) => a == b,
+                _ => value == expected,
+            },
+            Condition::Compare(comparison, expected) => value.as_f64().is_some_and(|value| match comparison {
+                Comparison::Less => value < *expected,
+                Comparison::LessOrEqual => value <= *expected,
+                Comparison::Greater => value > *expected,
+                Comparison::GreaterOrEqual => value >= *expected,
+            }),
+            Condition::NotEquals(_) | Condition::Exists | Condition::Missing => unreachable!("handled by matches"),
+        }
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.condition {
+            Condition::Matches(value) => write!(f, "{}={}", self.key, value),
+            Condition::Equals(value) => write!(f, "{} == {}", self.key, value),
+            Condition::NotEquals(value) => write!(f, "{} != {}", self.key, value),
+            Condition::Compare(comparison, value) => {
+                let operator = match comparison {
+                    Comparison::Less => "<",
+                    Comparison::LessOrEqual => "<=",
+                    Comparison::Greater => ">",
+                    Comparison::GreaterOrEqual => ">=",
+                };
+                write!(f, "{} {} {}", self.key, operator, value)
+            }
+            Condition::Exists => write!(f, "{} exists", self.key),
+            Condition::Missing => write!(f, "{} not exists", self.key),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn test_selector() {
+        let node = Node::new(
+            "checkout".to_string(),
+            PathBuf::from("checkout"),
+            vec![PathBuf::from("**")],
+            vec![],
+            vec![],
+            Some(serde_json::json!({
+                "team": "payments",
+                "tier": 1,
+                "regions": ["eu", "us"],
+                "deploy": { "canary": true },
+            })),
+        ).unwrap();
+
+        let select = |s: &str| s.parse::<Selector>().unwrap().matches(&node);
+        assert!(select("team=payments"));
+        assert!(!select("team=search"));
+        assert!(select("tier=1"));
+        assert!(select("regions=us"));
+        assert!(select("deploy.canary=true"));
+        assert!(!select("deploy=true"));
+        assert!(!select("owner=payments"));
+
+        assert!(select("metadata.team == \"payments\""));
+        assert!(select("team == payments"));
+        assert!(!select("team != payments"));
+        assert!(select("owner != payments"));
+        assert!(select("tier == 1.0"));
+        assert!(select("tier < 2") && select("tier >= 1") && !select("tier > 1"));
+        assert!(select("regions == \"eu\""));
+        assert!(select("regions == [\"eu\", \"us\"]"));
+        assert!(!select("regions != us"));
+        assert!(select("deploy.canary == true"));
+        assert!(select("metadata.deploy.canary exists"));
+        assert!(select("deploy.region not exists"));
+        assert!(!select("owner exists"));
+
+        assert!("team".parse::<Selector>().is_err());
+        assert!("=payments".parse::<Selector>().is_err());
+        assert!("tier < one".parse::<Selector>().is_err());
+        assert!("team ! x".parse::<Selector>().is_err());
+        assert_eq!("metadata.team == \"payments\"".parse::<Selector>().unwrap().to_string(), "team == \"payments\"");
+    }
+
+    fn node() -> Node {
+        Node::new(
+            "checkout".to_string(),
+            PathBuf::from("checkout"),
+            vec![PathBuf::from("**")],
+            vec![],
+            vec![],
+            Some(serde_json::json!({ "team": "payments", "tier": 2, "canary": false, "owner": null })),
+        ).unwrap()
+    }
+
+    #[test]
+    fn test_selector_operators() {
+        let node = node();
+        let select = |s: &str| s.parse::<Selector>().unwrap().matches(&node);
+        assert!(select("tier=2") && !select("tier=3"));
+        assert!(select("tier == 2") && !select("tier == 3"));
+        assert!(select("tier != 3") && !select("tier != 2"));
+        assert!(select("tier < 3") && !select("tier < 2"));
+        assert!(select("tier <= 2") && !select("tier <= 1"));
+        assert!(select("tier > 1") && !select("tier > 2"));
+        assert!(select("tier >= 2") && !select("tier >= 3"));
+        assert!(select("tier exists") && !select("tier not exists"));
+        // False is a value, unlike null
+        assert!(select("canary exists") && select("canary=false"));
+        assert!(select("owner not exists") && !select("owner exists"));
+    }
+
+    #[test]
+    fn test_selector_missing_key() {
+        let node = node();
+        let select = |s: &str| s.parse::<Selector>().unwrap().matches(&node);
+        for selector in ["region=eu", "region == \"eu\"", "region < 1", "region <= 1", "region > 1", "region >= 1", "region exists", "team.name=payments"] {
+            assert!(!select(selector), "{}", selector);
+        }
+        assert!(select("region != eu"));
+        assert!(select("region not exists"));
+    }
+
+    #[test]
+    fn test_selector_type_mismatch() {
+        let node = node();
+        let select = |s: &str| s.parse::<Selector>().unwrap().matches(&node);
+        // Strings aren't numbers, even when they read like one
+        assert!(!select("team < 10") && !select("team >= 0"));
+        assert!(!select("tier == \"2\""));
+        assert!(select("tier != \"2\""));
+        assert!(!select("team == 1") && !select("canary == \"false\""));
+        assert!(!select("canary < 1"));
+    }
+
+    #[test]
+    fn test_selector_malformed() {
+        let parse = |s: &str| s.parse::<Selector>();
+        for selector in ["", "   ", "team", "team ! x", "team!", "exists"] {
+            assert!(matches!(parse(selector), Err(SelectorParseError::MissingOperator(_))), "{:?}", selector);
+        }
+        for selector in ["=payments", " == 1", "metadata. < 2", "metadata. exists"] {
+            assert!(matches!(parse(selector), Err(SelectorParseError::EmptyKey(_))), "{:?}", selector);
+        }
+        for selector in ["tier <", "tier < one", "tier >= 1x", "tier <=> 2"] {
+            assert!(matches!(parse(selector), Err(SelectorParseError::NotANumber(..))), "{:?}", selector);
+        }
+    }
+}
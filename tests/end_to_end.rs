@@ -0,0 +1,132 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use dependency_cascade::testkit::FixtureDir;
+
+fn run(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_dependency-cascade"))
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?} failed: {}", args, String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Runs a command expected to fail, returning the error it printed.
+fn fail(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_dependency-cascade"))
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1), "{:?}", args);
+    assert!(output.stdout.is_empty(), "{:?} printed {}", args, String::from_utf8_lossy(&output.stdout));
+    String::from_utf8(output.stderr).unwrap()
+}
+
+fn git(dir: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .current_dir(dir)
+        .args(["-c", "user.name=test", "-c", "user.email=test@example.com", "-c", "commit.gpgsign=false"])
+        .args(args)
+        .status()
+        .unwrap();
+    assert!(status.success(), "git {:?} failed", args);
+}
+
+fn names(json: &str) -> Vec<String> {
+    let nodes: Vec<serde_json::Value> = serde_json::from_str(json).unwrap();
+    let mut names: Vec<String> = nodes.iter().map(|node| node["name"].as_str().unwrap().to_string()).collect();
+    names.sort();
+    names
+}
+
+fn fixture(args: &[&str]) -> FixtureDir {
+    let dir = FixtureDir::new();
+    let out = dir.path().to_string_lossy().to_string();
+    run(Path::new("."), &[&["generate-fixture", "--out", &out], args].concat());
+    dir
+}
+
+#[test]
+fn test_prepare_and_query_chain() {
+    let dir = fixture(&["--nodes", "5", "--shape", "chain"]);
+    let graph = run(dir.path(), &["prepare", "--dir", "."]);
+    fs::write(dir.join("graph.json"), graph).unwrap();
+
+    let first = run(dir.path(), &["query", "-g", "graph.json", "--files", "nodes/node-0/src/level-0/file-0.txt"]);
+    let middle = run(dir.path(), &["query", "-g", "graph.json", "--files", "nodes/node-3/src/level-0/file-1.txt"]);
+    let outside = run(dir.path(), &["query", "-g", "graph.json", "--files", "nodes/node-3/README.md"]);
+
+    assert_eq!(names(&first), vec!["node-0", "node-1", "node-2", "node-3", "node-4"]);
+    assert_eq!(names(&middle), vec!["node-3", "node-4"]);
+    assert!(names(&outside).is_empty());
+}
+
+#[test]
+fn test_affected_star() {
+    let dir = fixture(&["--nodes", "4", "--shape", "star", "--depth", "0"]);
+    let hub = run(dir.path(), &["affected", "--files", "nodes/node-0/src/file-0.txt"]);
+    let spoke = run(dir.path(), &["affected", "--files", "nodes/node-2/src/file-0.txt"]);
+
+    assert_eq!(names(&hub), vec!["node-0", "node-1", "node-2", "node-3"]);
+    assert_eq!(names(&spoke), vec!["node-2"]);
+}
+
+#[test]
+fn test_affected_matches_prepare_and_query() {
+    let dir = fixture(&["--nodes", "6", "--shape", "chain", "--depth", "0"]);
+    let graph = run(dir.path(), &["prepare", "--dir", "."]);
+    fs::write(dir.join("graph.json"), graph).unwrap();
+
+    let files = ["--files", "nodes/node-2/src/file-0.txt"];
+    for args in [
+        &[][..],
+        &["--max-depth", "1"],
+        &["--only-leaves"],
+        &["--direction", "both", "--max-depth", "2", "--only-roots"],
+    ] {
+        let affected = run(dir.path(), &[&["affected"], &files[..], args].concat());
+        let queried = run(dir.path(), &[&["query", "-g", "graph.json"], &files[..], args].concat());
+        assert_eq!(names(&affected), names(&queried), "{:?}", args);
+        assert!(!names(&affected).is_empty(), "{:?}", args);
+    }
+    let direct = run(dir.path(), &[&["affected", "--max-depth", "1"], &files[..]].concat());
+    assert_eq!(names(&direct), vec!["node-2", "node-3"]);
+}
+
+#[test]
+fn test_query_since() {
+    let dir = fixture(&["--nodes", "4", "--shape", "chain", "--depth", "0"]);
+    let graph = run(dir.path(), &["prepare", "--dir", "."]);
+    fs::write(dir.join("graph.json"), graph).unwrap();
+    git(dir.path(), &["init", "-q"]);
+    git(dir.path(), &["add", "-A"]);
+    git(dir.path(), &["commit", "-q", "-m", "base"]);
+    git(dir.path(), &["tag", "base"]);
+
+    // Nothing changed since the ref
+    assert!(names(&run(dir.path(), &["query", "-g", "graph.json", "--since", "base"])).is_empty());
+
+    // A commit after the ref, and an uncommitted change on top
+    dir.write("nodes/node-2/src/file-0.txt", "changed");
+    git(dir.path(), &["commit", "-q", "-am", "change node-2"]);
+    assert_eq!(names(&run(dir.path(), &["query", "-g", "graph.json", "--since", "base"])), vec!["node-2", "node-3"]);
+    dir.write("nodes/node-1/src/file-0.txt", "changed");
+    assert_eq!(names(&run(dir.path(), &["query", "-g", "graph.json", "--since", "base"])), vec!["node-1", "node-2", "node-3"]);
+
+    let unknown = fail(dir.path(), &["query", "-g", "graph.json", "--since", "no-such-ref"]);
+    assert!(unknown.starts_with("Error: git diff failed"), "{}", unknown);
+}
+
+#[test]
+fn test_query_since_outside_git() {
+    let dir = fixture(&["--nodes", "2", "--shape", "chain", "--depth", "0"]);
+    let graph = run(dir.path(), &["prepare", "--dir", "."]);
+    fs::write(dir.join("graph.json"), graph).unwrap();
+
+    let outside = fail(dir.path(), &["query", "-g", "graph.json", "--since", "main"]);
+    assert!(outside.starts_with("Error: git diff failed"), "{}", outside);
+}
@@ -0,0 +1,17 @@
+use std::path::PathBuf;
+
+use dependency_cascade::testkit::{FixtureDir, FixtureShape, FixtureSpec};
+use dependency_cascade::{load_graph, prepare, query, FilterArgs, LoadError, Scan};
+
+#[test]
+fn test_prepare_and_query_as_library() {
+    let dir = FixtureDir::new();
+    dir.generate(&FixtureSpec { nodes: 3, shape: FixtureShape::Chain, depth: 0, ..FixtureSpec::default() });
+    let scan = Scan { root: Some(dir.path().to_path_buf()), ..Scan::new(dir.path().to_path_buf(), None) };
+    let graph = prepare(&scan, false, None).unwrap();
+    let missing = load_graph(&dir.join("graph.json"));
+
+    let affected = query(&graph, &[PathBuf::from("nodes/node-1/src/file-0.txt")], &FilterArgs::default());
+    assert_eq!(affected.iter().map(|node| node.name.as_str()).collect::<Vec<_>>(), ["node-1", "node-2"]);
+    assert!(matches!(missing, Err(LoadError::Io(_))));
+}